@@ -55,7 +55,14 @@ pub(crate) struct ProtocolPlugin;
 
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(LeafwingInputPlugin::<CharacterAction>::default());
+        app.add_plugins(LeafwingInputPlugin::<CharacterAction> {
+            config: LeafwingInputConfig::<CharacterAction> {
+                // the server will relay the inputs of each client to the other clients,
+                // so that they can predict the other players' entities
+                rebroadcast_inputs: true,
+                ..default()
+            },
+        });
 
         app.register_component::<ColorComponent>(ChannelDirection::ServerToClient)
             .add_prediction(ComponentSyncMode::Once);
@@ -35,12 +35,6 @@ pub struct ExampleServerPlugin;
 impl Plugin for ExampleServerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, init);
-        app.add_systems(
-            PreUpdate,
-            // This system will replicate the inputs of a client to other
-            // clients so that a client can predict other clients.
-            replicate_inputs.after(MainSet::EmitEvents),
-        );
         app.add_systems(FixedUpdate, handle_character_actions);
         app.add_systems(Update, handle_connections);
     }
@@ -101,22 +95,6 @@ fn init(mut commands: Commands) {
     // ));
 }
 
-pub(crate) fn replicate_inputs(
-    mut connection: ResMut<ConnectionManager>,
-    mut input_events: ResMut<Events<MessageEvent<InputMessage<CharacterAction>>>>,
-) {
-    for mut event in input_events.drain() {
-        let client_id = event.from();
-        // Rebroadcast the input to other clients.
-        connection
-            .send_message_to_target::<InputChannel, _>(
-                &mut event.message,
-                NetworkTarget::AllExceptSingle(client_id),
-            )
-            .unwrap()
-    }
-}
-
 /// Spawn a character whenever a new client has connected.
 pub(crate) fn handle_connections(
     mut connections: EventReader<ConnectEvent>,
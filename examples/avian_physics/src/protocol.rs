@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use crate::shared::color_from_id;
 use lightyear::client::components::{ComponentSyncMode, LerpFn};
 use lightyear::client::interpolation::LinearInterpolator;
-use lightyear::prelude::client;
+use lightyear::prelude::client::{self, LeafwingInputConfig};
 use lightyear::prelude::server::{Replicate, SyncTarget};
 use lightyear::prelude::*;
 use lightyear::utils::avian2d::*;
@@ -161,7 +161,14 @@ impl Plugin for ProtocolPlugin {
         // messages
         app.register_message::<Message1>(ChannelDirection::Bidirectional);
         // inputs
-        app.add_plugins(LeafwingInputPlugin::<PlayerActions>::default());
+        app.add_plugins(LeafwingInputPlugin::<PlayerActions> {
+            config: LeafwingInputConfig::<PlayerActions> {
+                // the server will relay the inputs of each client to the other clients,
+                // so that they can predict the other players' entities
+                rebroadcast_inputs: true,
+                ..default()
+            },
+        });
         app.add_plugins(LeafwingInputPlugin::<AdminActions>::default());
         // components
         app.register_component::<PlayerId>(ChannelDirection::Bidirectional)
@@ -29,12 +29,6 @@ impl Plugin for ExampleServerPlugin {
         });
 
         app.add_systems(Startup, (start_server, init));
-        app.add_systems(
-            PreUpdate,
-            // this system will replicate the inputs of a client to other clients
-            // so that a client can predict other clients
-            replicate_inputs.after(MainSet::EmitEvents),
-        );
         // Re-adding Replicate components to client-replicated entities must be done in this set for proper handling.
         app.add_systems(
             PreUpdate,
@@ -86,27 +80,6 @@ pub(crate) fn movement(
     }
 }
 
-/// When we receive the input of a client, broadcast it to other clients
-/// so that they can predict this client's movements accurately
-pub(crate) fn replicate_inputs(
-    mut connection: ResMut<ConnectionManager>,
-    mut input_events: ResMut<Events<MessageEvent<InputMessage<PlayerActions>>>>,
-) {
-    for mut event in input_events.drain() {
-        let client_id = event.from();
-
-        // Optional: do some validation on the inputs to check that there's no cheating
-
-        // rebroadcast the input to other clients
-        connection
-            .send_message_to_target::<InputChannel, _>(
-                &mut event.message,
-                NetworkTarget::AllExceptSingle(client_id),
-            )
-            .unwrap()
-    }
-}
-
 // Replicate the pre-predicted entities back to the client
 // We have to use `InitialReplicated` instead of `Replicated`, because
 // the server has already assumed authority over the entity so the `Replicated` component
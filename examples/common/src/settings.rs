@@ -114,6 +114,7 @@ impl Conditioner {
             incoming_latency: Duration::from_millis(self.latency_ms as u64),
             incoming_jitter: Duration::from_millis(self.jitter_ms as u64),
             incoming_loss: self.packet_loss,
+            ..Default::default()
         }
     }
 }
@@ -157,7 +158,7 @@ pub struct ClientSettings {
     pub conditioner: Option<Conditioner>,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct SharedSettings {
     /// An id to identify the protocol version
     pub protocol_id: u64,
@@ -186,6 +187,7 @@ pub fn build_server_netcode_config(
         incoming_latency: Duration::from_millis(c.latency_ms as u64),
         incoming_jitter: Duration::from_millis(c.jitter_ms as u64),
         incoming_loss: c.packet_loss,
+        ..Default::default()
     });
     // Use private key from environment variable, if set. Otherwise from settings file.
     let privkey = if let Some(key) = parse_private_key_from_env() {
@@ -204,7 +206,8 @@ pub fn build_server_netcode_config(
     let io_config = server::IoConfig {
         transport: transport_config,
         conditioner,
-        compression: shared.compression,
+        compression: shared.compression.clone(),
+        capture: None,
     };
     server::NetConfig::Netcode {
         config: netcode_config,
@@ -384,7 +387,8 @@ pub fn build_client_netcode_config(
     let io_config = client::IoConfig {
         transport: transport_config,
         conditioner,
-        compression: shared.compression,
+        compression: shared.compression.clone(),
+        capture: None,
     };
     client::NetConfig::Netcode {
         auth,
@@ -237,7 +237,14 @@ pub(crate) struct ProtocolPlugin;
 
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(LeafwingInputPlugin::<PlayerActions>::default());
+        app.add_plugins(LeafwingInputPlugin::<PlayerActions> {
+            config: LeafwingInputConfig::<PlayerActions> {
+                // the server will relay the inputs of each client to the other clients,
+                // so that they can predict the other players' entities
+                rebroadcast_inputs: true,
+                ..default()
+            },
+        });
 
         // Player is synced as Simple, because we periodically update rtt ping stats
         app.register_component::<Player>(ChannelDirection::ServerToClient)
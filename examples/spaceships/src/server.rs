@@ -38,12 +38,6 @@ impl Plugin for ExampleServerPlugin {
             predict_all: self.predict_all,
         });
         app.add_systems(Startup, (start_server, init));
-        app.add_systems(
-            PreUpdate,
-            // this system will replicate the inputs of a client to other clients
-            // so that a client can predict other clients
-            replicate_inputs.after(MainSet::EmitEvents),
-        );
         // the physics/FixedUpdates systems that consume inputs should be run in this set
         app.add_systems(
             FixedUpdate,
@@ -95,26 +89,6 @@ fn init(mut commands: Commands) {
     }
 }
 
-pub(crate) fn replicate_inputs(
-    mut connection: ResMut<ConnectionManager>,
-    mut input_events: ResMut<Events<MessageEvent<InputMessage<PlayerActions>>>>,
-) {
-    for mut event in input_events.drain() {
-        let client_id = event.from();
-
-        // Optional: do some validation on the inputs to check that there's no cheating
-        // Inputs for a specific tick should be write *once*. Don't let players change old inputs.
-
-        // rebroadcast the input to other clients
-        connection
-            .send_message_to_target::<InputChannel, _>(
-                &mut event.message,
-                NetworkTarget::AllExceptSingle(client_id),
-            )
-            .unwrap()
-    }
-}
-
 /// Whenever a new client connects, spawn their spaceship
 pub(crate) fn handle_connections(
     mut connections: EventReader<ConnectEvent>,
@@ -1,5 +1,6 @@
 //! This module contains the [`Channel`] trait
 use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
 
 use lightyear_macros::ChannelInternal;
 
@@ -189,7 +190,7 @@ impl ChannelMode {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 /// [`ChannelDirection`] specifies in which direction the packets can be sent
 pub enum ChannelDirection {
     ClientToServer,
@@ -199,25 +200,70 @@ pub enum ChannelDirection {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ReliableSettings {
-    /// Multiplier of the current RTT estimate, used for delay to wait before resending a packet if it has not been acked.
+    /// Multiplier of the retransmission timeout, used for the delay to wait before resending a packet if it has not
+    /// been acked.
+    ///
+    /// The resend delay used to be `rtt_resend_factor * RTT`; it is now derived from the retransmission timeout of the
+    /// connection, which already accounts for the RTT variance, so the default factor is 1.0.
+    #[deprecated(
+        since = "0.19.0",
+        note = "the resend delay is based on the retransmission timeout; use `rtt_resend_min_delay`, `rtt_resend_max_delay` and `backoff_factor` instead"
+    )]
     pub rtt_resend_factor: f32,
-    /// Minimum duration to wait before resending a packet if it has not been acked
+    /// Minimum duration to wait before resending a packet if it has not been acked.
+    ///
+    /// The delay is otherwise based on the retransmission timeout of the connection, which is estimated from the
+    /// smoothed RTT and the RTT variance.
     pub rtt_resend_min_delay: Duration,
+    /// Maximum duration to wait before resending a packet if it has not been acked
+    pub rtt_resend_max_delay: Duration,
+    /// Every time a message is resent, the delay before the next resend is multiplied by this factor
+    pub backoff_factor: f32,
+    /// If set, messages that are still not acked after this duration stop being resent.
+    ///
+    /// Expired messages are reported through the nacks of the channel.
+    /// This can be overridden for each message.
+    pub message_ttl: Option<Duration>,
 }
 
 impl Default for ReliableSettings {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
-            rtt_resend_factor: 1.5,
+            rtt_resend_factor: 1.0,
             rtt_resend_min_delay: Duration::default(),
+            rtt_resend_max_delay: Duration::from_secs(2),
+            backoff_factor: 2.0,
+            message_ttl: None,
         }
     }
 }
 
 impl ReliableSettings {
-    pub(crate) fn resend_delay(&self, rtt: Duration) -> Duration {
-        let delay = rtt.mul_f32(self.rtt_resend_factor);
-        std::cmp::max(delay, self.rtt_resend_min_delay)
+    pub fn with_message_ttl(mut self, message_ttl: Duration) -> Self {
+        self.message_ttl = Some(message_ttl);
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff_factor: f32, rtt_resend_max_delay: Duration) -> Self {
+        self.backoff_factor = backoff_factor;
+        self.rtt_resend_max_delay = rtt_resend_max_delay;
+        self
+    }
+
+    /// Delay to wait before resending a message that has already been resent `num_resends` times
+    #[allow(deprecated)]
+    pub(crate) fn resend_delay(&self, rto: Duration, num_resends: u32) -> Duration {
+        // cap the exponent, the delay is clamped to the max delay anyway
+        let backoff = self
+            .backoff_factor
+            .max(1.0)
+            .powi(num_resends.min(32) as i32);
+        let delay = rto.mul_f32((self.rtt_resend_factor.max(0.0) * backoff).min(u32::MAX as f32));
+        delay.clamp(
+            self.rtt_resend_min_delay,
+            std::cmp::max(self.rtt_resend_min_delay, self.rtt_resend_max_delay),
+        )
     }
 }
 
@@ -250,3 +296,8 @@ pub struct InputChannel;
 /// Channel to send messages related to Authority transfers
 /// This is an Ordered Reliable channel
 pub struct AuthorityChannel;
+
+#[derive(ChannelInternal)]
+/// Channel to send the requests and responses of RPCs
+/// This is an Unordered Reliable channel
+pub struct RpcChannel;
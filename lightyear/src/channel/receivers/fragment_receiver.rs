@@ -1,17 +1,16 @@
 use std::collections::HashMap;
 
 use bytes::Bytes;
-use tracing::trace;
+use tracing::{error, trace};
 
 use crate::packet::message::{FragmentData, MessageId};
-use crate::packet::packet::FRAGMENT_SIZE;
 use crate::prelude::Tick;
 use crate::shared::time_manager::WrappedTime;
 
 /// `FragmentReceiver` is used to reconstruct fragmented messages
 #[derive(Debug)]
 pub struct FragmentReceiver {
-    fragment_messages: HashMap<MessageId, FragmentConstructor>,
+    pub(crate) fragment_messages: HashMap<MessageId, FragmentConstructor>,
 }
 
 impl FragmentReceiver {
@@ -33,6 +32,12 @@ impl FragmentReceiver {
         })
     }
 
+    /// Discard the fragments received for a message that will never be completed
+    /// (for example because the message expired on the sender side)
+    pub fn discard(&mut self, message_id: MessageId) {
+        self.fragment_messages.remove(&message_id);
+    }
+
     /// Receive a fragment of a FragmentData message.
     ///
     /// When we complete the final message by aggregating all fragments, we will return the
@@ -43,6 +48,10 @@ impl FragmentReceiver {
         remote_sent_tick: Tick,
         current_time: Option<WrappedTime>,
     ) -> Option<(Tick, Bytes)> {
+        if fragment.num_fragments == 0 {
+            error!(message_id = ?fragment.message_id, "received a fragment of a message with no fragments");
+            return None;
+        }
         let fragment_message = self
             .fragment_messages
             .entry(fragment.message_id)
@@ -50,6 +59,19 @@ impl FragmentReceiver {
                 FragmentConstructor::new(remote_sent_tick, fragment.num_fragments as usize)
             });
 
+        // the fragment does not match the other fragments of the message: the message cannot be reconstructed
+        if !fragment_message.is_valid_fragment(fragment.fragment_id as usize, fragment.bytes.len())
+        {
+            error!(
+                message_id = ?fragment.message_id,
+                fragment_id = ?fragment.fragment_id,
+                num_bytes = fragment.bytes.len(),
+                "received an invalid fragment, dropping the message"
+            );
+            self.fragment_messages.remove(&fragment.message_id);
+            return None;
+        }
+
         // completed the fragmented message!
         if let Some(payload) = fragment_message.receive_fragment(
             fragment.fragment_id as usize,
@@ -70,6 +92,12 @@ pub struct FragmentConstructor {
     num_fragments: usize,
     num_received_fragments: usize,
     received: Vec<bool>,
+    /// Size of the fragments of this message. All fragments except the last one have the same size,
+    /// but that size depends on the packet size used by the sender, so it is only known once we receive
+    /// a fragment that is not the last one.
+    fragment_size: Option<usize>,
+    /// The last fragment, if it was received before the fragment size was known
+    last_fragment: Option<Vec<u8>>,
     // bytes: Bytes,
     bytes: Vec<u8>,
 
@@ -83,12 +111,34 @@ impl FragmentConstructor {
             num_fragments,
             num_received_fragments: 0,
             received: vec![false; num_fragments],
-            bytes: vec![0; num_fragments * FRAGMENT_SIZE],
+            fragment_size: None,
+            last_fragment: None,
+            bytes: vec![],
             tick,
             last_received: None,
         }
     }
 
+    /// Check that a fragment can be part of this message: the index must be in range, and all fragments
+    /// except the last one must have the same size
+    pub fn is_valid_fragment(&self, fragment_index: usize, num_bytes: usize) -> bool {
+        if fragment_index >= self.num_fragments {
+            return false;
+        }
+        let is_last_fragment = fragment_index == self.num_fragments - 1;
+        match self.fragment_size {
+            Some(fragment_size) if is_last_fragment => num_bytes <= fragment_size,
+            Some(fragment_size) => num_bytes == fragment_size,
+            None if is_last_fragment => true,
+            // the first non-last fragment gives the fragment size, which can't be smaller than the last fragment
+            None => self
+                .last_fragment
+                .as_ref()
+                .map_or(true, |last_fragment| last_fragment.len() <= num_bytes),
+        }
+    }
+
+    /// Add a fragment to the message. The fragment must have been checked with [`Self::is_valid_fragment`]
     pub fn receive_fragment(
         &mut self,
         fragment_index: usize,
@@ -99,25 +149,33 @@ impl FragmentConstructor {
 
         let is_last_fragment = fragment_index == self.num_fragments - 1;
 
-        // TODO: check sizes?
-
         if !self.received[fragment_index] {
             self.received[fragment_index] = true;
             self.num_received_fragments += 1;
 
             if is_last_fragment {
-                let len = (self.num_fragments - 1) * FRAGMENT_SIZE + bytes.len();
-                self.bytes.resize(len, 0);
+                self.last_fragment = Some(bytes.to_vec());
+            } else {
+                let fragment_size = match self.fragment_size {
+                    Some(fragment_size) => fragment_size,
+                    None => {
+                        self.bytes = vec![0; (self.num_fragments - 1) * bytes.len()];
+                        self.fragment_size = Some(bytes.len());
+                        bytes.len()
+                    }
+                };
+                let start = fragment_index * fragment_size;
+                let end = start + bytes.len();
+                self.bytes[start..end].copy_from_slice(bytes);
             }
-
-            let start = fragment_index * FRAGMENT_SIZE;
-            let end = start + bytes.len();
-            self.bytes[start..end].copy_from_slice(bytes);
         }
 
         if self.num_received_fragments == self.num_fragments {
             trace!("Received all fragments!");
-            let payload = std::mem::take(&mut self.bytes);
+            let mut payload = std::mem::take(&mut self.bytes);
+            if let Some(last_fragment) = self.last_fragment.take() {
+                payload.extend_from_slice(&last_fragment);
+            }
             return Some((self.tick, payload.into()));
         }
 
@@ -128,6 +186,7 @@ impl FragmentConstructor {
 #[cfg(test)]
 mod tests {
     use crate::channel::senders::fragment_sender::FragmentSender;
+    use crate::packet::packet::FRAGMENT_SIZE;
 
     use super::*;
 
@@ -149,4 +208,69 @@ mod tests {
             Some((Tick(0), message_bytes.clone()))
         );
     }
+
+    /// The sender can use a smaller fragment size (because of path MTU discovery)
+    #[test]
+    fn test_receiver_smaller_fragments() {
+        let mut receiver = FragmentReceiver::new();
+        let mut sender = FragmentSender::new();
+        sender.fragment_size = 300;
+        let message_bytes = Bytes::from((0..1000).map(|i| i as u8).collect::<Vec<u8>>());
+        let fragments = sender
+            .build_fragments(MessageId(0), None, message_bytes.clone())
+            .unwrap();
+        assert_eq!(fragments.len(), 4);
+
+        // the last fragment is received first
+        assert_eq!(
+            receiver.receive_fragment(fragments[3].clone(), Tick(0), None),
+            None
+        );
+        assert_eq!(
+            receiver.receive_fragment(fragments[1].clone(), Tick(1), None),
+            None
+        );
+        assert_eq!(
+            receiver.receive_fragment(fragments[0].clone(), Tick(2), None),
+            None
+        );
+        assert_eq!(
+            receiver.receive_fragment(fragments[2].clone(), Tick(3), None),
+            Some((Tick(0), message_bytes))
+        );
+    }
+
+    /// Fragments that don't match the other fragments of the message make us drop the message
+    /// instead of panicking
+    #[test]
+    fn test_receiver_invalid_fragments() {
+        let mut receiver = FragmentReceiver::new();
+        let mut sender = FragmentSender::new();
+        sender.fragment_size = 300;
+        let message_bytes = Bytes::from(vec![1u8; 1000]);
+        let fragments = sender
+            .build_fragments(MessageId(0), None, message_bytes.clone())
+            .unwrap();
+        sender.fragment_size = 400;
+        let bigger_fragments = sender
+            .build_fragments(MessageId(0), None, message_bytes.clone())
+            .unwrap();
+
+        assert_eq!(
+            receiver.receive_fragment(fragments[0].clone(), Tick(0), None),
+            None
+        );
+        // a fragment bigger than the fragment size of the message
+        assert_eq!(
+            receiver.receive_fragment(bigger_fragments[1].clone(), Tick(1), None),
+            None
+        );
+        assert!(receiver.fragment_messages.is_empty());
+
+        // a fragment index out of range
+        let mut fragment = fragments[0].clone();
+        fragment.fragment_id = 10;
+        assert_eq!(receiver.receive_fragment(fragment, Tick(2), None), None);
+        assert!(receiver.fragment_messages.is_empty());
+    }
 }
@@ -18,8 +18,9 @@ pub struct OrderedReliableReceiver {
     /// The channel is reliable so we should see all message ids sequentially.
     pending_recv_message_id: MessageId,
     // TODO: optimize via ring buffer?
-    /// Buffer of the messages that we received, but haven't processed yet
-    recv_message_buffer: BTreeMap<MessageId, (Tick, Bytes)>,
+    /// Buffer of the messages that we received, but haven't processed yet.
+    /// The bytes are None for the tombstones of messages that expired on the sender side.
+    recv_message_buffer: BTreeMap<MessageId, (Tick, Option<Bytes>)>,
     fragment_receiver: FragmentReceiver,
 }
 
@@ -52,15 +53,20 @@ impl ChannelReceive for OrderedReliableReceiver {
         if let btree_map::Entry::Vacant(entry) = self.recv_message_buffer.entry(message_id) {
             match message.data {
                 MessageData::Single(single) => {
-                    entry.insert((message.remote_sent_tick, single.bytes));
+                    if single.tombstone {
+                        // the message expired on the sender side, so its missing fragments won't be sent
+                        self.fragment_receiver.discard(message_id);
+                    }
+                    let bytes = (!single.tombstone).then_some(single.bytes);
+                    entry.insert((message.remote_sent_tick, bytes));
                 }
                 MessageData::Fragment(fragment) => {
-                    if let Some(res) = self.fragment_receiver.receive_fragment(
+                    if let Some((tick, bytes)) = self.fragment_receiver.receive_fragment(
                         fragment,
                         message.remote_sent_tick,
                         None,
                     ) {
-                        entry.insert(res);
+                        entry.insert((tick, Some(bytes)));
                     }
                 }
             }
@@ -73,15 +79,21 @@ impl ChannelReceive for OrderedReliableReceiver {
     /// until we have received the message we are waiting for (the next expected MessageId)
     /// This assumes that the sender sends all message ids sequentially.
     fn read_message(&mut self) -> Option<(Tick, Bytes)> {
-        // Check if we have received the message we are waiting for
-        let message = self
-            .recv_message_buffer
-            .remove(&self.pending_recv_message_id)?;
-
-        // if we have finally received the message we are waiting for, return it and
-        // wait for the next one
-        self.pending_recv_message_id += 1;
-        Some(message)
+        loop {
+            // Check if we have received the message we are waiting for
+            let (tick, bytes) = self
+                .recv_message_buffer
+                .remove(&self.pending_recv_message_id)?;
+
+            // if we have finally received the message we are waiting for, return it and
+            // wait for the next one
+            self.pending_recv_message_id += 1;
+            // tombstones are sent in place of messages that expired on the sender side:
+            // skip them
+            if let Some(bytes) = bytes {
+                return Some((tick, bytes));
+            }
+        }
     }
 }
 
@@ -91,7 +103,9 @@ mod tests {
 
     use crate::channel::receivers::ordered_reliable::OrderedReliableReceiver;
     use crate::channel::receivers::ChannelReceive;
+    use crate::channel::senders::fragment_sender::FragmentSender;
     use crate::packet::message::{MessageId, ReceiveMessage, SingleData};
+    use crate::packet::packet::FRAGMENT_SIZE;
     use crate::prelude::{PacketError, Tick};
 
     #[test]
@@ -143,4 +157,61 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_ordered_reliable_receiver_skips_expired_messages() -> Result<(), PacketError> {
+        let mut receiver = OrderedReliableReceiver::new();
+
+        // message 0 expired on the sender side, so we receive a tombstone instead
+        let tombstone = SingleData::tombstone(MessageId(0));
+        // an empty message is still a valid message
+        let mut single1 = SingleData::new(None, Bytes::new());
+        single1.id = Some(MessageId(1));
+        receiver.buffer_recv(ReceiveMessage {
+            data: single1.clone().into(),
+            remote_sent_tick: Tick(1),
+        })?;
+        assert_eq!(receiver.read_message(), None);
+        receiver.buffer_recv(ReceiveMessage {
+            data: tombstone.into(),
+            remote_sent_tick: Tick(2),
+        })?;
+        assert_eq!(receiver.read_message(), Some((Tick(1), Bytes::new())));
+        assert_eq!(receiver.pending_recv_message_id, MessageId(2));
+        Ok(())
+    }
+
+    /// The fragments received for a message that expired on the sender side are discarded
+    #[test]
+    fn test_ordered_reliable_receiver_discards_expired_fragments() -> Result<(), PacketError> {
+        let mut receiver = OrderedReliableReceiver::new();
+        let fragments = FragmentSender::new()
+            .build_fragments(
+                MessageId(0),
+                None,
+                Bytes::from(vec![1u8; FRAGMENT_SIZE * 2]),
+            )
+            .unwrap();
+        receiver.buffer_recv(ReceiveMessage {
+            data: fragments[0].clone().into(),
+            remote_sent_tick: Tick(1),
+        })?;
+        assert_eq!(receiver.fragment_receiver.fragment_messages.len(), 1);
+
+        receiver.buffer_recv(ReceiveMessage {
+            data: SingleData::tombstone(MessageId(0)).into(),
+            remote_sent_tick: Tick(2),
+        })?;
+        assert!(receiver.fragment_receiver.fragment_messages.is_empty());
+        assert_eq!(receiver.read_message(), None);
+        assert_eq!(receiver.pending_recv_message_id, MessageId(1));
+
+        // a fragment that was resent before the message expired is ignored
+        receiver.buffer_recv(ReceiveMessage {
+            data: fragments[1].clone().into(),
+            remote_sent_tick: Tick(3),
+        })?;
+        assert!(receiver.fragment_receiver.fragment_messages.is_empty());
+        Ok(())
+    }
 }
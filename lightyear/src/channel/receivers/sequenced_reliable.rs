@@ -17,8 +17,9 @@ use crate::shared::time_manager::TimeManager;
 pub struct SequencedReliableReceiver {
     // TODO: optimize via ring buffer?
     // TODO: actually do we even need a buffer? we might just need a buffer of 1
-    /// Buffer of the messages that we received, but haven't processed yet
-    recv_message_buffer: BTreeMap<MessageId, (Tick, Bytes)>,
+    /// Buffer of the messages that we received, but haven't processed yet.
+    /// The bytes are None for the tombstones of messages that expired on the sender side.
+    recv_message_buffer: BTreeMap<MessageId, (Tick, Option<Bytes>)>,
     /// Highest message id received so far
     most_recent_message_id: MessageId,
     fragment_receiver: FragmentReceiver,
@@ -58,15 +59,20 @@ impl ChannelReceive for SequencedReliableReceiver {
         if let btree_map::Entry::Vacant(entry) = self.recv_message_buffer.entry(message_id) {
             match message.data {
                 MessageData::Single(single) => {
-                    entry.insert((message.remote_sent_tick, single.bytes));
+                    if single.tombstone {
+                        // the message expired on the sender side, so its missing fragments won't be sent
+                        self.fragment_receiver.discard(message_id);
+                    }
+                    let bytes = (!single.tombstone).then_some(single.bytes);
+                    entry.insert((message.remote_sent_tick, bytes));
                 }
                 MessageData::Fragment(fragment) => {
-                    if let Some(res) = self.fragment_receiver.receive_fragment(
+                    if let Some((tick, bytes)) = self.fragment_receiver.receive_fragment(
                         fragment,
                         message.remote_sent_tick,
                         None,
                     ) {
-                        entry.insert(res);
+                        entry.insert((tick, Some(bytes)));
                     }
                 }
             }
@@ -76,9 +82,13 @@ impl ChannelReceive for SequencedReliableReceiver {
     fn read_message(&mut self) -> Option<(Tick, Bytes)> {
         // keep popping messages until we get one that is more recent than the last one we processed
         loop {
-            let (message_id, message) = self.recv_message_buffer.pop_first()?;
-            if message_id >= self.most_recent_message_id {
-                return Some(message);
+            let (message_id, (tick, bytes)) = self.recv_message_buffer.pop_first()?;
+            if message_id < self.most_recent_message_id {
+                continue;
+            }
+            // tombstones are sent in place of messages that expired on the sender side
+            if let Some(bytes) = bytes {
+                return Some((tick, bytes));
             }
         }
     }
@@ -20,8 +20,9 @@ pub struct UnorderedReliableReceiver {
     pending_recv_message_id: MessageId,
     // TODO: optimize via ring buffer?
     // TODO: actually we could just use a VecDeque here?
-    /// Buffer of the messages that we received, but haven't processed yet
-    recv_message_buffer: BTreeMap<MessageId, (Tick, Bytes)>,
+    /// Buffer of the messages that we received, but haven't processed yet.
+    /// The bytes are None for the tombstones of messages that expired on the sender side.
+    recv_message_buffer: BTreeMap<MessageId, (Tick, Option<Bytes>)>,
     fragment_receiver: FragmentReceiver,
     /// Keep tracking of the message ids we have received, so we can update the oldest_pending_message_id
     received_message_ids: HashSet<MessageId>,
@@ -61,46 +62,58 @@ impl ChannelReceive for UnorderedReliableReceiver {
                     // receive the message if we haven't received it already
                     if !self.received_message_ids.contains(&message_id) {
                         self.received_message_ids.insert(message_id);
-                        entry.insert((message.remote_sent_tick, single.bytes));
+                        if single.tombstone {
+                            // the message expired on the sender side, so its missing fragments won't be sent
+                            self.fragment_receiver.discard(message_id);
+                        }
+                        let bytes = (!single.tombstone).then_some(single.bytes);
+                        entry.insert((message.remote_sent_tick, bytes));
                     }
                 }
-                MessageData::Fragment(fragment) => {
-                    if let Some(res) = self.fragment_receiver.receive_fragment(
+                // ignore the fragments of a message that was already received, or that expired on the sender side
+                MessageData::Fragment(fragment)
+                    if !self.received_message_ids.contains(&message_id) =>
+                {
+                    if let Some((tick, bytes)) = self.fragment_receiver.receive_fragment(
                         fragment,
                         message.remote_sent_tick,
                         None,
                     ) {
-                        // receive the message if we haven't received it already
-                        if !self.received_message_ids.contains(&message_id) {
-                            self.received_message_ids.insert(message_id);
-                            entry.insert(res);
-                        }
+                        self.received_message_ids.insert(message_id);
+                        entry.insert((tick, Some(bytes)));
                     }
                 }
+                MessageData::Fragment(_) => {}
             }
         }
         Ok(())
     }
 
     fn read_message(&mut self) -> Option<(Tick, Bytes)> {
-        // return if there are no messages in the buffer
-        let (message_id, data) = self.recv_message_buffer.pop_first()?;
-
-        // this was the message we were waiting for (as a reliable receiver)
-        if self.pending_recv_message_id == message_id {
-            // update the pending message id (skip through all message ids we have already received out of order)
-            while self
-                .received_message_ids
-                .contains(&self.pending_recv_message_id)
-            {
-                self.received_message_ids
-                    .remove(&self.pending_recv_message_id);
-                self.pending_recv_message_id += 1;
+        loop {
+            // return if there are no messages in the buffer
+            let (message_id, (tick, bytes)) = self.recv_message_buffer.pop_first()?;
+
+            // this was the message we were waiting for (as a reliable receiver)
+            if self.pending_recv_message_id == message_id {
+                // update the pending message id (skip through all message ids we have already received out of order)
+                while self
+                    .received_message_ids
+                    .contains(&self.pending_recv_message_id)
+                {
+                    self.received_message_ids
+                        .remove(&self.pending_recv_message_id);
+                    self.pending_recv_message_id += 1;
+                }
             }
-        }
 
-        // receive oldest message in the buffer
-        Some(data)
+            // tombstones are sent in place of messages that expired on the sender side:
+            // skip them
+            if let Some(bytes) = bytes {
+                // receive oldest message in the buffer
+                return Some((tick, bytes));
+            }
+        }
     }
 }
 
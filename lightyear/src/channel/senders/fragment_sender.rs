@@ -14,7 +14,7 @@ pub(crate) struct FragmentSender {
 impl FragmentSender {
     pub fn new() -> Self {
         Self {
-            // TODO: make this overridable?
+            // this is updated if the packet size changes (with path MTU discovery)
             fragment_size: FRAGMENT_SIZE,
         }
     }
@@ -24,10 +24,10 @@ impl FragmentSender {
         tick: Option<Tick>,
         fragment_bytes: Bytes,
     ) -> Result<Vec<FragmentData>, SerializationError> {
-        if fragment_bytes.len() <= FRAGMENT_SIZE {
+        if fragment_bytes.len() <= self.fragment_size {
             unreachable!(
                 "Message size must be at least {} to need to be fragmented",
-                FRAGMENT_SIZE
+                self.fragment_size
             );
         }
         let chunks = fragment_bytes.chunks(self.fragment_size);
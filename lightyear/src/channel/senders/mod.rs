@@ -1,5 +1,6 @@
 use std::collections::VecDeque;
 
+use bevy::utils::Duration;
 use bytes::Bytes;
 use crossbeam_channel::Receiver;
 use enum_dispatch::enum_dispatch;
@@ -42,6 +43,34 @@ pub trait ChannelSend {
         priority: f32,
     ) -> Result<Option<MessageId>, SerializationError>;
 
+    /// Queues a message to be transmitted, with a time-to-live that overrides the one of the channel
+    /// (`None` means that the message never expires).
+    ///
+    /// Only reliable channels resend messages, so the other channels ignore the TTL.
+    fn buffer_send_with_ttl(
+        &mut self,
+        message: Bytes,
+        priority: f32,
+        _ttl: Option<Duration>,
+    ) -> Result<Option<MessageId>, SerializationError> {
+        self.buffer_send(message, priority)
+    }
+
+    /// Stop trying to send a message that hasn't been acked yet.
+    ///
+    /// Returns true if the message was cancelled. Only reliable channels keep track of the messages
+    /// after sending them.
+    fn cancel(&mut self, _message_id: MessageId) -> bool {
+        false
+    }
+
+    /// Returns the number of messages that were resent since the last call.
+    ///
+    /// Only reliable channels resend messages.
+    fn take_num_retransmissions(&mut self) -> usize {
+        0
+    }
+
     /// Reads from the buffer of messages to send to prepare a list of Packets
     /// that can be sent over the network for this channel
     fn send_packet(&mut self) -> (VecDeque<SendMessage>, VecDeque<SendMessage>);
@@ -58,6 +87,10 @@ pub trait ChannelSend {
 
     /// Send nacks to the subscribers of nacks
     fn send_nacks(&mut self, nack: MessageId);
+
+    /// Set the maximum size of a message before it gets fragmented
+    /// (the packet size can change because of path MTU discovery)
+    fn set_fragment_size(&mut self, fragment_size: usize);
 }
 
 /// Enum dispatch lets us derive ChannelSend on each enum variant
@@ -33,6 +33,13 @@ pub enum UnackedMessage {
         last_sent: Option<WrappedTime>,
     },
     Fragmented(Vec<FragmentAck>),
+    /// The message expired or was cancelled before being acked.
+    ///
+    /// We keep sending a [`SingleData::tombstone`] with the same id until it is acked, so that
+    /// the receiver does not wait for the original message forever.
+    Tombstone {
+        last_sent: Option<WrappedTime>,
+    },
 }
 
 #[derive(Debug)]
@@ -40,6 +47,10 @@ pub struct UnackedMessageWithPriority {
     pub unacked_message: UnackedMessage,
     pub base_priority: f32,
     pub accumulated_priority: f32,
+    /// Time after which the message stops being resent
+    pub expires_at: Option<WrappedTime>,
+    /// Number of times the message has been resent, used to back off the resend delay
+    pub num_resends: u32,
 }
 
 /// A sender that makes sure to resend messages until it receives an ack
@@ -69,7 +80,10 @@ pub struct ReliableSender {
     ack_senders: Vec<Sender<MessageId>>,
     /// List of senders that want to be notified when a message is lost
     nack_senders: Vec<Sender<MessageId>>,
-    current_rtt: Duration,
+    /// Retransmission timeout of the connection
+    current_rto: Duration,
+    /// Number of messages that were resent since the stats were last collected
+    num_retransmissions: usize,
     current_time: WrappedTime,
     /// Internal timer to determine if the channel is ready to send messages
     timer: Option<Timer>,
@@ -95,18 +109,38 @@ impl ReliableSender {
             fragment_sender: FragmentSender::new(),
             ack_senders: vec![],
             nack_senders: vec![],
-            current_rtt: Duration::default(),
+            current_rto: Duration::default(),
+            num_retransmissions: 0,
             current_time: WrappedTime::default(),
             timer,
             priority_multiplier: 1.0,
         }
     }
+
+    /// Stop resending the messages whose TTL has expired, and notify the nack subscribers
+    fn expire_messages(&mut self) {
+        for (message_id, message) in self.unacked_messages.iter_mut() {
+            if matches!(message.unacked_message, UnackedMessage::Tombstone { .. })
+                || message
+                    .expires_at
+                    .map_or(true, |expires_at| self.current_time < expires_at)
+            {
+                continue;
+            }
+            trace!(?message_id, "reliable message expired");
+            message.unacked_message = UnackedMessage::Tombstone { last_sent: None };
+            message.num_resends = 0;
+            self.nack_senders
+                .retain(|sender| sender.send(*message_id).is_ok());
+        }
+    }
 }
 
 impl ChannelSend for ReliableSender {
     fn update(&mut self, time_manager: &TimeManager, ping_manager: &PingManager, _: &TickManager) {
         self.current_time = time_manager.current_time();
-        self.current_rtt = ping_manager.rtt();
+        self.current_rto = ping_manager.rto();
+        self.expire_messages();
         if let Some(timer) = &mut self.timer {
             timer.tick(time_manager.delta());
             self.priority_multiplier =
@@ -125,6 +159,16 @@ impl ChannelSend for ReliableSender {
         &mut self,
         message: Bytes,
         priority: f32,
+    ) -> Result<Option<MessageId>, SerializationError> {
+        self.buffer_send_with_ttl(message, priority, self.reliable_settings.message_ttl)
+    }
+
+    /// Add a new message to the buffer of messages to be sent, overriding the TTL of the channel
+    fn buffer_send_with_ttl(
+        &mut self,
+        message: Bytes,
+        priority: f32,
+        ttl: Option<Duration>,
     ) -> Result<Option<MessageId>, SerializationError> {
         let message_id = self.next_send_message_id;
         let unacked_message = if message.len() > self.fragment_sender.fragment_size {
@@ -153,6 +197,8 @@ impl ChannelSend for ReliableSender {
             // store with 0.0 accumulated priority because priority gets accumulated when we collect the messages
             // for sending (even the first time the message is sent)
             accumulated_priority: 0.0,
+            expires_at: ttl.map(|ttl| self.current_time + ttl),
+            num_resends: 0,
         };
         self.unacked_messages
             .insert(message_id, unacked_message_with_priority);
@@ -171,21 +217,25 @@ impl ChannelSend for ReliableSender {
         // Collect the list of messages that need to be sent
         // Either because they have never been sent, or because they need to be resent
 
-        // resend delay is based on the rtt
-        let resend_delay =
-            chrono::Duration::from_std(self.reliable_settings.resend_delay(self.current_rtt))
-                .unwrap();
-        let should_send = |last_sent: &Option<WrappedTime>| -> bool {
-            match last_sent {
-                // send if the message has never been sent
-                None => true,
-                // or if we sent it a while back but didn't get an ack
-                Some(last_sent) => self.current_time - *last_sent > resend_delay,
-            }
-        };
-
         // Iterate through all unacked messages, oldest message ids first
         for (message_id, unacked_message_with_priority) in self.unacked_messages.iter_mut() {
+            // resend delay is based on the retransmission timeout, with an exponential backoff
+            // for messages that have already been resent
+            let resend_delay = chrono::Duration::from_std(
+                self.reliable_settings
+                    .resend_delay(self.current_rto, unacked_message_with_priority.num_resends),
+            )
+            .unwrap_or(chrono::Duration::MAX);
+            let should_send = |last_sent: &Option<WrappedTime>| -> bool {
+                match last_sent {
+                    // send if the message has never been sent
+                    None => true,
+                    // or if we sent it a while back but didn't get an ack
+                    Some(last_sent) => self.current_time - *last_sent > resend_delay,
+                }
+            };
+            let mut resent = false;
+
             // accumulate the priority for all messages (including the ones that were just added, since we set the accumulated priority to 0.0)
             unacked_message_with_priority.accumulated_priority +=
                 unacked_message_with_priority.base_priority * self.priority_multiplier;
@@ -215,6 +265,25 @@ impl ChannelSend for ReliableSender {
                                 priority: unacked_message_with_priority.accumulated_priority,
                             });
                             self.message_ids_to_send.insert(message_info);
+                            resent |= last_sent.is_some();
+                            *last_sent = Some(self.current_time);
+                        }
+                    }
+                }
+                UnackedMessage::Tombstone { ref mut last_sent } => {
+                    if should_send(last_sent) {
+                        let message_info = MessageAck {
+                            message_id: *message_id,
+                            fragment_id: None,
+                        };
+                        if !self.message_ids_to_send.contains(&message_info) {
+                            let message = SingleData::tombstone(*message_id);
+                            self.single_messages_to_send.push_back(SendMessage {
+                                data: message.into(),
+                                priority: unacked_message_with_priority.accumulated_priority,
+                            });
+                            self.message_ids_to_send.insert(message_info);
+                            resent |= last_sent.is_some();
                             *last_sent = Some(self.current_time);
                         }
                     }
@@ -236,11 +305,16 @@ impl ChannelSend for ReliableSender {
                                     priority: unacked_message_with_priority.accumulated_priority,
                                 });
                                 self.message_ids_to_send.insert(message_info);
+                                resent |= f.last_sent.is_some();
                                 f.last_sent = Some(self.current_time);
                             }
                         })
                 }
             }
+            if resent {
+                unacked_message_with_priority.num_resends += 1;
+                self.num_retransmissions += 1;
+            }
         }
 
         // TODO: is this message_ids_to_send even useful? in which situation would we send the same message twice?
@@ -281,6 +355,13 @@ impl ChannelSend for ReliableSender {
                 message_ack.message_id
             );
             match &mut unacked_message.unacked_message {
+                // the ack can be for a fragment of the original message; only the ack of the tombstone
+                // itself means that the receiver knows that the message won't be delivered
+                UnackedMessage::Tombstone { .. } => {
+                    if message_ack.fragment_id.is_none() {
+                        self.unacked_messages.remove(&message_ack.message_id);
+                    }
+                }
                 UnackedMessage::Single { .. } => {
                     if message_ack.fragment_id.is_some() {
                         panic!(
@@ -329,9 +410,37 @@ impl ChannelSend for ReliableSender {
 
     /// Send nacks to the subscribers of nacks
     fn send_nacks(&mut self, nack: MessageId) {
-        for sender in &self.nack_senders {
-            sender.send(nack).unwrap();
+        // - the message might have been acked in a packet that was sent later
+        // - the message was already reported as lost when it expired
+        if self.unacked_messages.get(&nack).map_or(true, |message| {
+            matches!(message.unacked_message, UnackedMessage::Tombstone { .. })
+        }) {
+            return;
+        }
+        // the subscriber might have dropped its receiver
+        self.nack_senders.retain(|sender| sender.send(nack).is_ok());
+    }
+
+    /// Stop sending a message that hasn't been acked yet
+    fn cancel(&mut self, message_id: MessageId) -> bool {
+        let Some(message) = self.unacked_messages.get_mut(&message_id) else {
+            return false;
+        };
+        if matches!(message.unacked_message, UnackedMessage::Tombstone { .. }) {
+            return false;
         }
+        trace!(?message_id, "cancelling reliable message");
+        message.unacked_message = UnackedMessage::Tombstone { last_sent: None };
+        message.num_resends = 0;
+        true
+    }
+
+    fn take_num_retransmissions(&mut self) -> usize {
+        std::mem::take(&mut self.num_retransmissions)
+    }
+
+    fn set_fragment_size(&mut self, fragment_size: usize) {
+        self.fragment_sender.fragment_size = fragment_size;
     }
 }
 
@@ -341,7 +450,7 @@ mod tests {
     use bytes::Bytes;
 
     use crate::channel::builder::ReliableSettings;
-    use crate::packet::message::SingleData;
+    use crate::packet::message::{MessageData, SingleData};
 
     use super::*;
 
@@ -349,12 +458,15 @@ mod tests {
     fn test_reliable_sender_internals() {
         let mut sender = ReliableSender::new(
             ReliableSettings {
-                rtt_resend_factor: 1.5,
                 rtt_resend_min_delay: Duration::from_millis(100),
+                rtt_resend_max_delay: Duration::from_secs(1),
+                backoff_factor: 2.0,
+                message_ttl: None,
+                ..Default::default()
             },
             Duration::default(),
         );
-        sender.current_rtt = Duration::from_millis(100);
+        sender.current_rto = Duration::from_millis(100);
         sender.current_time = WrappedTime::new(0);
 
         // Buffer a new message
@@ -396,4 +508,133 @@ mod tests {
         let (single, _) = sender.send_packet();
         assert_eq!(single.len(), 0);
     }
+
+    #[test]
+    fn test_reliable_sender_backoff() {
+        let mut sender = ReliableSender::new(
+            ReliableSettings::default().with_backoff(2.0, Duration::from_millis(300)),
+            Duration::default(),
+        );
+        sender.current_rto = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+
+        sender.buffer_send(Bytes::from("hello"), 1.0).unwrap();
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 1);
+        assert_eq!(sender.take_num_retransmissions(), 0);
+
+        // first resend after the rto
+        sender.current_time += Duration::from_millis(150);
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 1);
+        assert_eq!(sender.take_num_retransmissions(), 1);
+
+        // then the resend delay is doubled
+        sender.current_time += Duration::from_millis(150);
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 0);
+        sender.current_time += Duration::from_millis(100);
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 1);
+
+        // and capped by the max delay
+        sender.current_time += Duration::from_millis(350);
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 1);
+        assert_eq!(sender.take_num_retransmissions(), 2);
+    }
+
+    #[test]
+    fn test_reliable_sender_ttl() {
+        let mut sender = ReliableSender::new(
+            ReliableSettings::default().with_message_ttl(Duration::from_millis(500)),
+            Duration::default(),
+        );
+        let nacks = sender.subscribe_nacks();
+        let acks = sender.subscribe_acks();
+        sender.current_rto = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+
+        let message = Bytes::from("hello");
+        sender.buffer_send(message.clone(), 1.0).unwrap();
+        // the TTL can be overridden for a single message
+        sender
+            .buffer_send_with_ttl(message.clone(), 1.0, Some(Duration::from_secs(10)))
+            .unwrap();
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 2);
+
+        // the first message expires: it is reported as lost and replaced with a tombstone
+        sender.current_time += Duration::from_millis(600);
+        sender.expire_messages();
+        assert_eq!(nacks.try_recv(), Ok(MessageId(0)));
+        assert!(nacks.try_recv().is_err());
+        let (single, _) = sender.send_packet();
+        assert_eq!(single.len(), 2);
+        assert_eq!(
+            single.front().unwrap().data,
+            MessageData::from(SingleData::tombstone(MessageId(0)))
+        );
+
+        // cancel the second message
+        assert!(sender.cancel(MessageId(1)));
+        assert!(!sender.cancel(MessageId(1)));
+        // losing the packets of a tombstone is not reported again
+        sender.send_nacks(MessageId(0));
+        assert!(nacks.try_recv().is_err());
+
+        // once the tombstones are acked, the messages are removed, without being reported as acked
+        sender.receive_ack(&MessageAck {
+            message_id: MessageId(0),
+            fragment_id: None,
+        });
+        sender.receive_ack(&MessageAck {
+            message_id: MessageId(1),
+            fragment_id: None,
+        });
+        assert!(sender.unacked_messages.is_empty());
+        assert!(acks.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_reliable_sender_no_nack_after_ack() {
+        let mut sender = ReliableSender::new(
+            ReliableSettings::default().with_message_ttl(Duration::from_millis(500)),
+            Duration::default(),
+        );
+        let nacks = sender.subscribe_nacks();
+        sender.current_rto = Duration::from_millis(100);
+        sender.current_time = WrappedTime::new(0);
+
+        sender.buffer_send(Bytes::from("hello"), 1.0).unwrap();
+        sender.send_packet();
+        // the message is resent, and the second packet is acked
+        sender.current_time += Duration::from_millis(200);
+        sender.send_packet();
+        sender.receive_ack(&MessageAck {
+            message_id: MessageId(0),
+            fragment_id: None,
+        });
+
+        // the loss of the first packet is not reported since the message was acked
+        sender.send_nacks(MessageId(0));
+        // the message does not expire since it was acked
+        sender.current_time += Duration::from_millis(600);
+        sender.expire_messages();
+        assert!(nacks.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_reliable_sender_dropped_nack_receiver() {
+        let mut sender = ReliableSender::new(ReliableSettings::default(), Duration::default());
+        let nacks = sender.subscribe_nacks();
+        let dropped_nacks = sender.subscribe_nacks();
+        drop(dropped_nacks);
+        sender.buffer_send(Bytes::from("hello"), 1.0).unwrap();
+
+        // the subscribers that dropped their receiver are removed instead of panicking
+        sender.send_nacks(MessageId(0));
+        assert_eq!(nacks.try_recv(), Ok(MessageId(0)));
+        assert_eq!(sender.nack_senders.len(), 1);
+    }
 }
@@ -159,9 +159,12 @@ impl ChannelSend for UnorderedUnreliableWithAcksSender {
 
     /// Send nacks to the subscribers of nacks
     fn send_nacks(&mut self, nack: MessageId) {
-        for sender in &self.nack_senders {
-            sender.send(nack).unwrap();
-        }
+        // the subscriber might have dropped its receiver
+        self.nack_senders.retain(|sender| sender.send(nack).is_ok());
+    }
+
+    fn set_fragment_size(&mut self, fragment_size: usize) {
+        self.fragment_sender.fragment_size = fragment_size;
     }
 }
 
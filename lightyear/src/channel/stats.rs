@@ -5,6 +5,7 @@ pub(crate) mod send {
         num_single_messages_sent: usize,
         num_fragment_messages_sent: usize,
         num_bytes_sent: usize,
+        num_retransmissions: usize,
     }
 
     impl ChannelSendStats {
@@ -24,8 +25,17 @@ pub(crate) mod send {
             self.num_bytes_sent = self.num_bytes_sent.saturating_add(num_bytes);
         }
 
+        pub fn add_retransmissions(&mut self, num: usize) {
+            self.num_retransmissions = self.num_retransmissions.saturating_add(num);
+        }
+
         pub fn messages_sent(&self) -> usize {
             self.num_single_messages_sent + self.num_fragment_messages_sent
         }
+
+        /// Number of messages that had to be resent because they were not acked in time
+        pub fn retransmissions(&self) -> usize {
+            self.num_retransmissions
+        }
     }
 }
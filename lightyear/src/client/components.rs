@@ -5,6 +5,7 @@ use std::fmt::Debug;
 
 use bevy::prelude::{Component, Entity, ReflectComponent};
 use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::{Message, Tick};
 
@@ -42,7 +43,7 @@ pub trait SyncMetadata<C> {
     fn mode() -> ComponentSyncMode;
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 /// Defines how a predicted or interpolated component will be replicated from confirmed to predicted/interpolated
 ///
 /// We use a single enum instead of 2 separate enums because we want to be able to use the same enum for both predicted and interpolated components
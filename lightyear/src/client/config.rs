@@ -9,6 +9,8 @@ use crate::client::interpolation::plugin::InterpolationConfig;
 use crate::client::prediction::plugin::PredictionConfig;
 use crate::client::sync::SyncConfig;
 use crate::connection::client::NetConfig;
+use crate::packet::congestion::CongestionControlConfig;
+use crate::packet::mtu::MtuDiscoveryConfig;
 use crate::shared::config::SharedConfig;
 use crate::shared::ping::manager::PingConfig;
 use crate::shared::replication::plugin::ReplicationConfig;
@@ -60,6 +62,12 @@ pub struct PacketConfig {
     pub send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// If set, the bandwidth cap is replaced with a send budget that adapts to the RTT and packet loss
+    /// of the connection
+    pub congestion_control: Option<CongestionControlConfig>,
+    /// If set, the packet size starts small and grows up to the largest size that can reach the remote peer.
+    /// Otherwise packets can be up to [`MAX_PACKET_SIZE`](crate::connection::netcode::MAX_PACKET_SIZE) bytes.
+    pub mtu_discovery: Option<MtuDiscoveryConfig>,
 }
 
 impl Default for PacketConfig {
@@ -69,6 +77,8 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            congestion_control: None,
+            mtu_discovery: None,
         }
     }
 }
@@ -89,6 +99,16 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn with_congestion_control(mut self, congestion_control: CongestionControlConfig) -> Self {
+        self.congestion_control = Some(congestion_control);
+        self
+    }
+
+    pub fn with_mtu_discovery(mut self, mtu_discovery: MtuDiscoveryConfig) -> Self {
+        self.mtu_discovery = Some(mtu_discovery);
+        self
+    }
 }
 
 /// The configuration object that lets you create a `ClientPlugin` with the desired settings.
@@ -4,10 +4,11 @@ use bevy::ecs::entity::MapEntities;
 use bevy::prelude::{Event, Resource, World};
 use bevy::utils::{Duration, HashMap};
 use bytes::Bytes;
+use crossbeam_channel::Receiver;
 use tracing::{debug, trace, trace_span};
 
 use crate::channel::builder::{
-    EntityActionsChannel, EntityUpdatesChannel, PingChannel, PongChannel,
+    EntityActionsChannel, EntityUpdatesChannel, PingChannel, PongChannel, RpcChannel,
 };
 
 use crate::channel::receivers::ChannelReceive;
@@ -16,7 +17,8 @@ use crate::client::config::ClientConfig;
 use crate::client::error::ClientError;
 use crate::client::sync::SyncConfig;
 use crate::connection::netcode::MAX_PACKET_SIZE;
-use crate::packet::message_manager::MessageManager;
+use crate::packet::message::MessageId;
+use crate::packet::message_manager::{MessageManager, DEFAULT_MESSAGE_PRIORITY};
 use crate::packet::packet_builder::{Payload, RecvPayload};
 use crate::packet::priority_manager::PriorityConfig;
 use crate::prelude::client::PredictionConfig;
@@ -24,12 +26,13 @@ use crate::prelude::{Channel, ChannelKind, ClientId, Message, ReplicationConfig}
 use crate::protocol::channel::ChannelRegistry;
 use crate::protocol::component::ComponentRegistry;
 use crate::protocol::event::EventReplicationMode;
-use crate::protocol::message::{MessageRegistry, MessageType};
+use crate::protocol::message::{MessageKind, MessageRegistry, MessageType};
 use crate::protocol::registry::NetId;
 use crate::serialize::reader::Reader;
 use crate::serialize::writer::Writer;
 use crate::serialize::{SerializationError, ToBytes};
 use crate::server::error::ServerError;
+use crate::shared::config::Mode;
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::events::private::InternalEventSend;
 use crate::shared::events::EventSend;
@@ -42,6 +45,7 @@ use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::{EntityActionsMessage, EntityUpdatesMessage, ReplicationSend};
 use crate::shared::replication::{ReplicationPeer, ReplicationReceive};
+use crate::shared::rpc::{RpcId, RpcManager, RpcRequest, RpcResponse};
 use crate::shared::sets::ClientMarker;
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
@@ -90,6 +94,10 @@ pub struct ConnectionManager {
     /// - in host server mode, we deserialize the bytes and push them to the server's Message Events queue directly
     /// - in non-host server mode, we buffer the bytes to the message manager as usual
     pub(crate) messages_to_send: Vec<(Bytes, ChannelKind)>,
+    /// True if the client is running in host-server mode, in which case messages are not sent through io
+    pub(crate) host_server: bool,
+    /// Requests sent to the server that are waiting for a response
+    pub(crate) rpc_manager: RpcManager,
 }
 
 // NOTE: useful when we sometimes need to create a temporary fake ConnectionManager
@@ -109,6 +117,7 @@ impl Default for ConnectionManager {
                 &ChannelRegistry::default(),
                 0.0,
                 PriorityConfig::default(),
+                None,
             ),
             delta_manager: DeltaManager::default(),
             replication_sender,
@@ -121,6 +130,8 @@ impl Default for ConnectionManager {
             received_messages: HashMap::default(),
             writer: Writer::with_capacity(0),
             messages_to_send: Vec::default(),
+            host_server: false,
+            rpc_manager: RpcManager::default(),
         }
     }
 }
@@ -132,12 +143,14 @@ impl ConnectionManager {
         channel_registry: &ChannelRegistry,
         client_config: &ClientConfig,
     ) -> Self {
-        let bandwidth_cap_enabled = client_config.packet.bandwidth_cap_enabled;
+        let bandwidth_cap_enabled = client_config.packet.bandwidth_cap_enabled
+            || client_config.packet.congestion_control.is_some();
         // create the message manager and the channels
         let mut message_manager = MessageManager::new(
             channel_registry,
             client_config.packet.nack_rtt_multiple,
             client_config.packet.into(),
+            client_config.packet.mtu_discovery,
         );
         // get notified when a replication-update message gets acked/nacked
         let entity_updates_sender = &mut message_manager
@@ -158,6 +171,7 @@ impl ConnectionManager {
             bandwidth_cap_enabled,
         );
         let replication_receiver = ReplicationReceiver::new();
+        let rpc_manager = RpcManager::new(&mut message_manager);
         Self {
             message_registry: message_registry.clone(),
             message_manager,
@@ -172,6 +186,8 @@ impl ConnectionManager {
             received_messages: HashMap::default(),
             writer: Writer::with_capacity(MAX_PACKET_SIZE),
             messages_to_send: Vec::default(),
+            host_server: matches!(client_config.shared.mode, Mode::HostServer),
+            rpc_manager,
         }
     }
 
@@ -208,6 +224,7 @@ impl ConnectionManager {
             .update(time_manager, &self.ping_manager, tick_manager);
         self.replication_sender.update(world_tick);
         self.ping_manager.update(time_manager);
+        self.rpc_manager.update(time_manager.delta());
 
         // (we update the sync manager in POST_UPDATE)
     }
@@ -243,6 +260,140 @@ impl ConnectionManager {
         self.send_message_to_target::<C, M>(message, NetworkTarget::None)
     }
 
+    /// Send a [`Message`] to the server using a specific [`Channel`], with a time-to-live that overrides the one
+    /// set in the [`ReliableSettings`](crate::prelude::ReliableSettings) of the channel.
+    /// If `ttl` is `None`, the time-to-live of the channel is used.
+    ///
+    /// If the message is not acked before the ttl elapses, the sender stops resending it.
+    /// Returns the [`MessageId`] of the message, which can be used to cancel it or to match it with the
+    /// nacks obtained via [`subscribe_nacks`](Self::subscribe_nacks). Messages are not assigned a [`MessageId`]
+    /// in host-server mode.
+    pub fn send_message_with_ttl<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        ttl: Option<Duration>,
+    ) -> Result<Option<MessageId>, ClientError> {
+        self.buffer_message_with_ttl::<C, M>(message, ttl.map(Some))
+    }
+
+    /// Send a [`Message`] to the server using a specific [`Channel`], and keep resending it until it is acked
+    /// even if the channel has a time-to-live.
+    ///
+    /// Returns the [`MessageId`] of the message, which can be used to cancel it. Messages are not assigned
+    /// a [`MessageId`] in host-server mode.
+    pub fn send_message_without_ttl<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+    ) -> Result<Option<MessageId>, ClientError> {
+        self.buffer_message_with_ttl::<C, M>(message, Some(None))
+    }
+
+    /// Buffer a message with the time-to-live of the channel if `ttl` is `None`, or with the
+    /// time-to-live `ttl` otherwise (`Some(None)` means that the message never expires)
+    fn buffer_message_with_ttl<C: Channel, M: Message>(
+        &mut self,
+        message: &M,
+        ttl: Option<Option<Duration>>,
+    ) -> Result<Option<MessageId>, ClientError> {
+        if self.host_server {
+            self.send_message::<C, M>(message)?;
+            return Ok(None);
+        }
+        let message_bytes = self.serialize_message(message, NetworkTarget::None)?;
+        // buffer the messages that were sent before this one first, to preserve the ordering
+        self.messages_to_send
+            .drain(..)
+            .try_for_each(|(message_bytes, channel_kind)| {
+                self.message_manager
+                    .buffer_send(message_bytes, channel_kind)?;
+                Ok::<(), ClientError>(())
+            })?;
+        let channel_kind = ChannelKind::of::<C>();
+        Ok(match ttl {
+            None => self.message_manager.buffer_send_with_priority(
+                message_bytes,
+                channel_kind,
+                DEFAULT_MESSAGE_PRIORITY,
+            )?,
+            Some(ttl) => self.message_manager.buffer_send_with_ttl(
+                message_bytes,
+                channel_kind,
+                DEFAULT_MESSAGE_PRIORITY,
+                ttl,
+            )?,
+        })
+    }
+
+    /// Serialize the [`NetworkTarget`] followed by the message, mapping the entities of the message
+    /// to the remote world
+    fn serialize_message<M: Message>(
+        &mut self,
+        message: &M,
+        target: NetworkTarget,
+    ) -> Result<Bytes, ClientError> {
+        // write the target first
+        // NOTE: this is ok to do because most of the time (without rebroadcast, this just adds 1 byte)
+        target.to_bytes(&mut self.writer)?;
+        // then write the message
+        self.message_registry.serialize(
+            message,
+            &mut self.writer,
+            Some(&mut self.replication_receiver.remote_entity_map.local_to_remote),
+        )?;
+        Ok(self.writer.split())
+    }
+
+    /// Stop sending a message that was sent on a reliable channel and is not acked yet.
+    ///
+    /// Returns true if the message was cancelled.
+    pub fn cancel_message<C: Channel>(
+        &mut self,
+        message_id: MessageId,
+    ) -> Result<bool, ClientError> {
+        Ok(self
+            .message_manager
+            .cancel(message_id, ChannelKind::of::<C>())?)
+    }
+
+    /// Get notified of the [`MessageId`]s of the messages sent on channel `C` that were lost or expired
+    pub fn subscribe_nacks<C: Channel>(&mut self) -> Result<Receiver<MessageId>, ClientError> {
+        Ok(self
+            .message_manager
+            .subscribe_nacks(ChannelKind::of::<C>())?)
+    }
+
+    /// Send a request to the server on the [`RpcChannel`].
+    ///
+    /// The request/response pair must have been registered with [`register_rpc`](crate::prelude::AppRpcExt::register_rpc).
+    /// Returns the [`RpcId`] of the request; a [`RpcResponseEvent<Res>`](crate::prelude::RpcResponseEvent) with the same id
+    /// will be emitted when the response is received, or when no response was received before the `timeout`.
+    pub fn send_request<Req: Message, Res: Message>(
+        &mut self,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<RpcId, ClientError> {
+        let id = self.rpc_manager.next_id();
+        // stop resending the request once nobody is waiting for the response
+        let message_id = self
+            .send_message_with_ttl::<RpcChannel, _>(&RpcRequest { id, request }, Some(timeout))?;
+        self.rpc_manager.add_pending(
+            id,
+            MessageKind::of::<RpcResponse<Res>>(),
+            message_id,
+            timeout,
+        );
+        Ok(id)
+    }
+
+    /// Answer a request that was received from the server
+    pub fn send_response<Res: Message>(
+        &mut self,
+        id: RpcId,
+        response: Res,
+    ) -> Result<(), ClientError> {
+        self.send_message::<RpcChannel, _>(&RpcResponse { id, response })
+    }
+
     /// Send a [`Event`] to the server using a specific [`Channel`].
     /// The event will be buffered via EventWriter.
     pub fn send_event<C: Channel, E: Event + Message>(
@@ -510,6 +661,14 @@ impl ConnectionManager {
             .map_or(true, |server_tick| tick >= server_tick)
         {
             trace!("new last recv server tick: {:?}", tick);
+            if self
+                .sync_manager
+                .latest_received_server_tick
+                .is_some_and(|server_tick| tick > server_tick)
+            {
+                self.sync_manager
+                    .update_snapshot_jitter(tick, tick_manager.config.tick_duration);
+            }
             self.sync_manager.latest_received_server_tick = Some(tick);
             // TODO: add 'received_new_server_tick' ?
             // we probably actually physically received the packet some time between our last `receive` and now.
@@ -596,16 +755,7 @@ impl InternalMessageSend for ConnectionManager {
         channel_kind: ChannelKind,
         target: NetworkTarget,
     ) -> Result<(), ClientError> {
-        // write the target first
-        // NOTE: this is ok to do because most of the time (without rebroadcast, this just adds 1 byte)
-        target.to_bytes(&mut self.writer)?;
-        // then write the message
-        self.message_registry.serialize(
-            message,
-            &mut self.writer,
-            Some(&mut self.replication_receiver.remote_entity_map.local_to_remote),
-        )?;
-        let message_bytes = self.writer.split();
+        let message_bytes = self.serialize_message(message, target)?;
 
         // TODO: emit logs/metrics about the message being buffered?
         self.messages_to_send.push((message_bytes, channel_kind));
@@ -1,4 +1,5 @@
 use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::diagnostics::InterpolationDiagnosticsPlugin;
 use crate::client::prediction::diagnostics::PredictionDiagnosticsPlugin;
 use bevy::app::{App, Plugin, PostUpdate};
 use bevy::diagnostic::Diagnostics;
@@ -7,6 +8,7 @@ use bevy::time::common_conditions::on_timer;
 use bevy::utils::Duration;
 
 use crate::connection::client::{ClientConnection, NetClient};
+use crate::packet::congestion::CongestionDiagnosticsPlugin;
 use crate::prelude::{client::is_disconnected, is_host_server};
 use crate::shared::ping::diagnostics::PingDiagnosticsPlugin;
 use crate::transport::io::IoDiagnosticsPlugin;
@@ -39,6 +41,13 @@ fn ping_diagnostics_system(connection: Res<ConnectionManager>, diagnostics: Diag
     PingDiagnosticsPlugin::add_measurements(&connection.ping_manager, diagnostics);
 }
 
+fn congestion_diagnostics_system(connection: Res<ConnectionManager>, diagnostics: Diagnostics) {
+    CongestionDiagnosticsPlugin::add_measurements(
+        connection.message_manager.send_budget(),
+        diagnostics,
+    );
+}
+
 impl Plugin for ClientDiagnosticsPlugin {
     fn build(&self, app: &mut App) {
         {
@@ -51,7 +60,18 @@ impl Plugin for ClientDiagnosticsPlugin {
                     .run_if(on_timer(flush_interval).and(not(is_host_server.or(is_disconnected)))),
             );
         }
+        {
+            let congestion_plugin = CongestionDiagnosticsPlugin::default();
+            let flush_interval = congestion_plugin.flush_interval;
+            app.add_plugins(congestion_plugin);
+            app.add_systems(
+                PostUpdate,
+                congestion_diagnostics_system
+                    .run_if(on_timer(flush_interval).and(not(is_host_server.or(is_disconnected)))),
+            );
+        }
         app.add_plugins(PredictionDiagnosticsPlugin::default());
+        app.add_plugins(InterpolationDiagnosticsPlugin::default());
 
         {
             app.add_plugins(IoDiagnosticsPlugin);
@@ -82,6 +82,12 @@ pub struct LeafwingInputConfig<A> {
     ///  for the 3 last packets.
     // TODO: this seems unused now
     pub packet_redundancy: u16,
+    /// If enabled, the server will relay the inputs of each client to the other clients, so that they can
+    /// predict the remote players' entities with their real inputs.
+    ///
+    /// See [`ReceivedInputMessages`](crate::server::input::leafwing::ReceivedInputMessages) to validate the inputs
+    /// before they are relayed.
+    pub rebroadcast_inputs: bool,
 
     // TODO: add an option where we send all diffs vs send only just-pressed diffs
     pub marker: PhantomData<A>,
@@ -92,6 +98,7 @@ impl<A> Default for LeafwingInputConfig<A> {
         LeafwingInputConfig {
             lag_compensation: false,
             packet_redundancy: 4,
+            rebroadcast_inputs: false,
             marker: PhantomData,
         }
     }
@@ -40,12 +40,22 @@
 //! - handle inputs in your game logic in systems that run in the `FixedUpdate` schedule. These systems
 //!   will read the inputs using the [`InputEvent`] event.
 //!
+//! ### Inputs for multiple entities
+//!
+//! By default the inputs are global to the client. If a client controls several entities (for example
+//! in a local multiplayer game where two players share the same client), you can buffer inputs for a specific
+//! entity with [`add_input_for_entity`](InputManager::add_input_for_entity).
+//! The entity can be a predicted, confirmed or pre-predicted entity: it will be converted to the corresponding
+//! server entity, and the [`InputEvent`]s emitted on the client and on the server will contain the
+//! entity that the input is for.
+//!
 //! NOTE: I would advise to activate the `leafwing` feature to handle inputs via the `input_leafwing` module, instead.
 //! That module is more up-to-date and has more features.
 //! This module is kept for simplicity but might get removed in the future.
+use bevy::ecs::entity::Entities;
 use bevy::prelude::*;
 use bevy::reflect::Reflect;
-use bevy::utils::Duration;
+use bevy::utils::{Duration, HashMap};
 use tracing::{error, trace};
 
 use crate::client::config::ClientConfig;
@@ -53,13 +63,16 @@ use crate::client::connection::ConnectionManager;
 use crate::client::events::InputEvent;
 use crate::client::prediction::plugin::is_in_rollback;
 use crate::client::prediction::rollback::Rollback;
+use crate::client::prediction::Predicted;
 use crate::client::run_conditions::is_synced;
 use crate::client::sync::SyncSet;
 use crate::connection::client::NetClient;
 use crate::connection::client::NetClientDispatch;
 use crate::inputs::native::input_buffer::InputBuffer;
-use crate::inputs::native::UserAction;
+use crate::inputs::native::{InputMessage, UserAction};
+use crate::inputs::InputTarget;
 use crate::prelude::{is_host_server, ChannelKind, ChannelRegistry, Tick, TickManager};
+use crate::shared::replication::components::PrePredicted;
 use crate::shared::sets::{ClientMarker, InternalMainSet};
 use crate::shared::tick_manager::TickEvent;
 use crate::{channel::builder::InputChannel, prelude::client::ClientConnection};
@@ -83,12 +96,15 @@ pub struct InputConfig {
 #[derive(Debug, Resource)]
 pub struct InputManager<A> {
     pub(crate) input_buffer: InputBuffer<A>,
+    /// Inputs for the entities controlled by this client
+    pub(crate) entity_buffers: HashMap<Entity, InputBuffer<A>>,
 }
 
 impl<A> Default for InputManager<A> {
     fn default() -> Self {
         Self {
             input_buffer: InputBuffer::default(),
+            entity_buffers: HashMap::default(),
         }
     }
 }
@@ -100,10 +116,32 @@ impl<A: UserAction> InputManager<A> {
         self.input_buffer.get(tick).cloned()
     }
 
+    /// Get a cloned version of the input for the given entity
+    pub(crate) fn get_entity_input(&self, entity: Entity, tick: Tick) -> Option<A> {
+        self.entity_buffers
+            .get(&entity)
+            .and_then(|buffer| buffer.get(tick).cloned())
+    }
+
     /// Buffer a user action for the given tick
     pub fn add_input(&mut self, input: A, tick: Tick) {
         self.input_buffer.set(tick, Some(input));
     }
+
+    /// Buffer a user action that controls `entity` for the given tick
+    ///
+    /// The entity is the client's local entity (predicted, confirmed or pre-predicted)
+    pub fn add_input_for_entity(&mut self, input: A, entity: Entity, tick: Tick) {
+        self.entity_buffers
+            .entry(entity)
+            .or_default()
+            .set(tick, Some(input));
+    }
+
+    /// Iterate through the global buffer and the buffers of each entity
+    fn buffers_mut(&mut self) -> impl Iterator<Item = &mut InputBuffer<A>> {
+        std::iter::once(&mut self.input_buffer).chain(self.entity_buffers.values_mut())
+    }
 }
 
 impl Default for InputConfig {
@@ -241,8 +279,14 @@ fn write_input_event<A: UserAction>(
     let tick = rollback.map_or(tick_manager.tick(), |r| {
         tick_manager.tick_or_rollback_tick(r.as_ref())
     });
-    let input = input_manager.get_input(tick);
     client_input_events.send(InputEvent::new(input_manager.get_input(tick), ()));
+    for entity in input_manager.entity_buffers.keys() {
+        client_input_events.send(InputEvent::new_for_entity(
+            input_manager.get_entity_input(*entity, tick),
+            (),
+            *entity,
+        ));
+    }
 }
 
 /// Receive an [`TickEvent`] signifying that the local tick has been updated,
@@ -254,13 +298,15 @@ fn receive_tick_events<A: UserAction>(
     match trigger.event() {
         TickEvent::TickSnap { old_tick, new_tick } => {
             // if the tick got updated, update our inputs to match our new ticks
-            if let Some(start_tick) = input_manager.input_buffer.start_tick {
-                trace!(
-                    "Receive tick snap event {:?}. Updating input buffer start_tick!",
-                    trigger.event()
-                );
-                input_manager.input_buffer.start_tick = Some(start_tick + (*new_tick - *old_tick));
-            };
+            for input_buffer in input_manager.buffers_mut() {
+                if let Some(start_tick) = input_buffer.start_tick {
+                    trace!(
+                        "Receive tick snap event {:?}. Updating input buffer start_tick!",
+                        trigger.event()
+                    );
+                    input_buffer.start_tick = Some(start_tick + (*new_tick - *old_tick));
+                };
+            }
         }
     }
 }
@@ -272,10 +318,16 @@ fn prepare_input_message<A: UserAction>(
     mut input_manager: ResMut<InputManager<A>>,
     config: Res<ClientConfig>,
     tick_manager: Res<TickManager>,
+    entities: &Entities,
+    entity_query: Query<(Option<&Predicted>, Option<&PrePredicted>)>,
 ) {
     let Some(mut connection) = connection else {
         return;
     };
+    // stop sending inputs for entities that were despawned
+    input_manager
+        .entity_buffers
+        .retain(|entity, _| entities.contains(*entity));
 
     let current_tick = tick_manager.tick();
     // TODO: the number of messages should be in SharedConfig
@@ -302,9 +354,52 @@ fn prepare_input_message<A: UserAction>(
     //  - buffer an input every frame; and require some redundancy (number of tick per frame)
     //  - or buffer an input only when we are sending, and require more redundancy
     // let message_len = 20 as u16;
-    let message = input_manager
-        .input_buffer
-        .create_message(tick_manager.tick(), message_len);
+    let mut message = InputMessage::new(tick_manager.tick());
+    message.add_inputs(
+        message_len,
+        InputTarget::Global,
+        &input_manager.input_buffer,
+    );
+    for (entity, input_buffer) in input_manager.entity_buffers.iter() {
+        let Ok((predicted, pre_predicted)) = entity_query.get(*entity) else {
+            continue;
+        };
+        if pre_predicted.is_some() {
+            // wait until the client receives the PrePredicted entity confirmation to send inputs,
+            // the server will map the entity to its own entity when receiving the message
+            if predicted.is_none() {
+                continue;
+            }
+            message.add_inputs(
+                message_len,
+                InputTarget::PrePredictedEntity(*entity),
+                input_buffer,
+            );
+        } else {
+            // 1. if the entity is confirmed, we need to convert the entity to the server's entity
+            // 2. if the entity is predicted, we need to first convert the entity to confirmed, and then from confirmed to remote
+            let Some(server_entity) = predicted
+                .map_or(Some(*entity), |p| p.confirmed_entity)
+                .and_then(|confirmed| {
+                    connection
+                        .replication_receiver
+                        .remote_entity_map
+                        .get_remote(confirmed)
+                })
+            else {
+                trace!(
+                    ?entity,
+                    "not sending inputs because couldnt find server entity"
+                );
+                continue;
+            };
+            message.add_inputs(
+                message_len,
+                InputTarget::Entity(server_entity),
+                input_buffer,
+            );
+        }
+    }
     // all inputs are absent
     if !message.is_empty() {
         // TODO: should we provide variants of each user-facing function, so that it pushes the error
@@ -326,7 +421,9 @@ fn prepare_input_message<A: UserAction>(
 
     // delete old input values
     let interpolation_tick = connection.sync_manager.interpolation_tick(&tick_manager);
-    input_manager.input_buffer.pop(interpolation_tick);
+    for input_buffer in input_manager.buffers_mut() {
+        input_buffer.pop(interpolation_tick);
+    }
     // .pop(current_tick - (message_len + 1));
 }
 
@@ -335,6 +432,7 @@ fn prepare_input_message<A: UserAction>(
 fn send_input_directly_to_client_events<A: UserAction>(
     tick_manager: Res<TickManager>,
     client: Res<ClientConnection>,
+    entities: &Entities,
     mut input_manager: ResMut<InputManager<A>>,
     mut server_input_events: EventWriter<crate::server::events::InputEvent<A>>,
 ) {
@@ -343,16 +441,30 @@ fn send_input_directly_to_client_events<A: UserAction>(
         let input = input_manager.input_buffer.pop(tick);
         let event = crate::server::events::InputEvent::new(input, client.id());
         server_input_events.send(event);
+        // the client and server share the same World, so there is no entity mapping to do
+        input_manager
+            .entity_buffers
+            .retain(|entity, _| entities.contains(*entity));
+        for (entity, input_buffer) in input_manager.entity_buffers.iter_mut() {
+            let input = input_buffer.pop(tick);
+            server_input_events.send(crate::server::events::InputEvent::new_for_entity(
+                input,
+                client.id(),
+                *entity,
+            ));
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::client::input::native::InputSystemSet;
-    use crate::prelude::client::InputManager;
-    use crate::prelude::{server, TickManager};
+    use crate::prelude::client::{Confirmed, InputManager, Predicted};
+    use crate::prelude::server::{Replicate, SyncTarget};
+    use crate::prelude::{client, server, ClientId, NetworkTarget, PrePredicted, TickManager};
     use crate::tests::host_server_stepper::HostServerStepper;
     use crate::tests::protocol::MyInput;
+    use crate::tests::stepper::{BevyStepper, TEST_CLIENT_ID};
     use bevy::prelude::*;
 
     fn press_input(
@@ -391,4 +503,200 @@ mod tests {
         stepper.frame_step();
         assert!(stepper.server_app.world().resource::<Counter>().0 > 0);
     }
+
+    /// The client entities that are controlled by the local players
+    #[derive(Resource)]
+    struct LocalPlayers(Entity, Entity);
+
+    #[derive(Resource, Default)]
+    struct ReceivedInputs(Vec<(Entity, MyInput)>);
+
+    fn press_entity_inputs(
+        mut input_manager: ResMut<InputManager<MyInput>>,
+        tick_manager: Res<TickManager>,
+        players: Res<LocalPlayers>,
+    ) {
+        input_manager.add_input_for_entity(MyInput(1), players.0, tick_manager.tick());
+        input_manager.add_input_for_entity(MyInput(2), players.1, tick_manager.tick());
+    }
+
+    fn receive_entity_inputs(
+        mut received: ResMut<ReceivedInputs>,
+        mut input: EventReader<server::InputEvent<MyInput>>,
+    ) {
+        for input in input.read() {
+            if let (Some(entity), Some(action)) = (input.entity(), input.input()) {
+                received.0.push((entity, action.clone()));
+            }
+        }
+    }
+
+    /// Check that a client can send inputs for several entities, and that the server
+    /// receives them for the corresponding server entities
+    #[test]
+    fn test_entity_inputs() {
+        let mut stepper = BevyStepper::default();
+        let server_entity_1 = stepper
+            .server_app
+            .world_mut()
+            .spawn(Replicate::default())
+            .id();
+        let server_entity_2 = stepper
+            .server_app
+            .world_mut()
+            .spawn(Replicate::default())
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let remote_entity_map = &stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map;
+        let players = LocalPlayers(
+            remote_entity_map.get_local(server_entity_1).unwrap(),
+            remote_entity_map.get_local(server_entity_2).unwrap(),
+        );
+        stepper.client_app.world_mut().insert_resource(players);
+        stepper.client_app.add_systems(
+            FixedPreUpdate,
+            press_entity_inputs.in_set(InputSystemSet::BufferInputs),
+        );
+        stepper
+            .server_app
+            .init_resource::<ReceivedInputs>()
+            .add_systems(FixedUpdate, receive_entity_inputs);
+
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let received = &stepper.server_app.world().resource::<ReceivedInputs>().0;
+        assert!(received.contains(&(server_entity_1, MyInput(1))));
+        assert!(received.contains(&(server_entity_2, MyInput(2))));
+        assert!(received
+            .iter()
+            .all(|(entity, _)| *entity == server_entity_1 || *entity == server_entity_2));
+    }
+
+    /// The client entity that is controlled by the local player
+    #[derive(Resource)]
+    struct LocalPlayer(Entity);
+
+    fn press_player_input(
+        mut input_manager: ResMut<InputManager<MyInput>>,
+        tick_manager: Res<TickManager>,
+        player: Res<LocalPlayer>,
+    ) {
+        input_manager.add_input_for_entity(MyInput(1), player.0, tick_manager.tick());
+    }
+
+    /// Send inputs for the client entity `player`, and check that the server receives them
+    /// for `server_entity`
+    fn check_player_inputs(stepper: &mut BevyStepper, player: Entity, server_entity: Entity) {
+        stepper
+            .client_app
+            .world_mut()
+            .insert_resource(LocalPlayer(player));
+        stepper.client_app.add_systems(
+            FixedPreUpdate,
+            press_player_input.in_set(InputSystemSet::BufferInputs),
+        );
+        stepper
+            .server_app
+            .init_resource::<ReceivedInputs>()
+            .add_systems(FixedUpdate, receive_entity_inputs);
+
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let received = &stepper.server_app.world().resource::<ReceivedInputs>().0;
+        assert!(received.contains(&(server_entity, MyInput(1))));
+        assert!(received.iter().all(|(entity, _)| *entity == server_entity));
+    }
+
+    /// Check that the inputs for a predicted entity are received by the server
+    /// for the server entity (the client maps from predicted to confirmed to server entity)
+    #[test]
+    fn test_predicted_entity_inputs() {
+        let mut stepper = BevyStepper::default();
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn(Replicate {
+                sync: SyncTarget {
+                    prediction: NetworkTarget::All,
+                    ..default()
+                },
+                ..default()
+            })
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let confirmed = stepper
+            .client_app
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .expect("entity was not replicated to client");
+        let predicted = stepper
+            .client_app
+            .world()
+            .get::<Confirmed>(confirmed)
+            .unwrap()
+            .predicted
+            .expect("predicted entity was not spawned");
+        check_player_inputs(&mut stepper, predicted, server_entity);
+    }
+
+    /// Check that the inputs for a pre-predicted entity are received by the server
+    /// for the server entity (the server maps the pre-predicted entity upon reception)
+    #[test]
+    fn test_pre_predicted_entity_inputs() {
+        let mut stepper = BevyStepper::default();
+        let pre_predicted = stepper
+            .client_app
+            .world_mut()
+            .spawn((client::Replicate::default(), PrePredicted::default()))
+            .id();
+        stepper.flush();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        let confirmed = stepper
+            .client_app
+            .world()
+            .get::<PrePredicted>(pre_predicted)
+            .unwrap()
+            .confirmed_entity
+            .unwrap();
+        let server_entity = stepper
+            .server_app
+            .world()
+            .resource::<server::ConnectionManager>()
+            .connection(ClientId::Netcode(TEST_CLIENT_ID))
+            .unwrap()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(confirmed)
+            .expect("entity was not replicated to server");
+
+        // the inputs are only sent once the server has confirmed the pre-predicted entity
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_entity)
+            .insert(server::Replicate::default());
+        stepper.flush();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world()
+            .get::<Predicted>(pre_predicted)
+            .is_some());
+        check_player_inputs(&mut stepper, pre_predicted, server_entity);
+    }
 }
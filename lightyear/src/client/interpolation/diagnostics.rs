@@ -0,0 +1,65 @@
+//! Collect diagnostics for the interpolation timeline.
+
+use crate::client::connection::ConnectionManager;
+use crate::prelude::{client::is_disconnected, is_host_server};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+
+/// Plugin in charge of collecting diagnostics for the interpolation timeline.
+pub struct InterpolationDiagnosticsPlugin {
+    /// Number of diagnostics to keep in history
+    history_length: usize,
+    /// How often to flush the stored data into the Diagnostics
+    flush_interval: Duration,
+}
+
+impl Default for InterpolationDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_length: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl InterpolationDiagnosticsPlugin {
+    /// How far behind the server time the interpolation timeline is aiming to be
+    pub const DELAY: DiagnosticPath = DiagnosticPath::const_new("interpolation.delay.ms");
+
+    /// Jitter of the arrival times of server packets
+    pub const SNAPSHOT_JITTER: DiagnosticPath =
+        DiagnosticPath::const_new("interpolation.snapshot_jitter.ms");
+
+    fn flush_measurements(connection: Res<ConnectionManager>, mut diagnostics: Diagnostics) {
+        diagnostics.add_measurement(&Self::DELAY, || {
+            connection
+                .sync_manager
+                .interpolation_objective_delay()
+                .as_secs_f64()
+                * 1000.0
+        });
+        diagnostics.add_measurement(&Self::SNAPSHOT_JITTER, || {
+            connection.sync_manager.snapshot_jitter().as_secs_f64() * 1000.0
+        });
+    }
+}
+
+impl Plugin for InterpolationDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let should_run = on_timer(self.flush_interval).and(not(is_host_server.or(is_disconnected)));
+
+        app.add_systems(PostUpdate, Self::flush_measurements.run_if(should_run));
+        app.register_diagnostic(
+            Diagnostic::new(Self::DELAY)
+                .with_suffix("ms")
+                .with_max_history_length(self.history_length),
+        );
+        app.register_diagnostic(
+            Diagnostic::new(Self::SNAPSHOT_JITTER)
+                .with_suffix("ms")
+                .with_max_history_length(self.history_length),
+        );
+    }
+}
@@ -23,6 +23,9 @@ const SEND_INTERVAL_TICK_FACTOR: f32 = 1.3;
 /// This is provided so that you can easily compute your own interpolation if you want to.
 #[derive(Component, PartialEq, Debug)]
 pub struct InterpolateStatus<C: Component> {
+    /// confirmed state that was received before `start`, along with value.
+    /// Used to extrapolate the component when `end` is not available
+    pub previous: Option<(Tick, C)>,
     /// start tick to interpolate from, along with value
     pub start: Option<(Tick, C)>,
     /// end tick to interpolate to, along with value
@@ -31,9 +34,52 @@ pub struct InterpolateStatus<C: Component> {
     pub current_tick: Tick,
     /// for more accurate interpolation, this is the fraction between [current_tick, current_tick + 1[
     pub current_overstep: f32,
+    /// whether the current value of the component was interpolated or extrapolated
+    pub mode: InterpolationMode,
+    /// extrapolated value that we are blending from after receiving a new confirmed state,
+    /// along with the tick and overstep at which the blend started
+    pub blend: Option<(C, Tick, f32)>,
+}
+
+/// Indicates how the value of an interpolated component was computed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// The value is interpolated between two confirmed states
+    #[default]
+    Interpolated,
+    /// We ran out of confirmed states to interpolate towards, so the value is extrapolated
+    /// past the last confirmed state
+    Extrapolated,
 }
 
 impl<C: Component> InterpolateStatus<C> {
+    pub(crate) fn new(start: Option<(Tick, C)>, current_tick: Tick, current_overstep: f32) -> Self {
+        Self {
+            previous: None,
+            start,
+            end: None,
+            current_tick,
+            current_overstep,
+            mode: InterpolationMode::Interpolated,
+            blend: None,
+        }
+    }
+
+    /// Returns the most recent known value of the component at the given tick,
+    /// among the confirmed states stored in the status
+    pub fn value_at(&self, tick: Tick) -> Option<&C> {
+        [&self.end, &self.start, &self.previous]
+            .into_iter()
+            .flatten()
+            .find(|(value_tick, _)| *value_tick <= tick)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns true if the current value of the component is extrapolated
+    pub fn is_extrapolated(&self) -> bool {
+        self.mode == InterpolationMode::Extrapolated
+    }
+
     pub fn interpolation_fraction(&self) -> Option<f32> {
         self.start.as_ref().and_then(|(start_tick, _)| {
             self.end.as_ref().map(|(end_tick, _)| {
@@ -51,6 +97,7 @@ impl<C: Component> InterpolateStatus<C> {
 /// At the end of each frame, interpolate the components between the last 2 confirmed server states
 /// Invariant: start_tick <= current_interpolate_tick + overstep < end_tick
 pub(crate) fn update_interpolate_status<C: SyncComponent>(
+    component_registry: Res<ComponentRegistry>,
     config: Res<ClientConfig>,
     connection: Res<ConnectionManager>,
     tick_manager: Res<TickManager>,
@@ -68,6 +115,16 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
         * config.shared.server_replication_send_interval.as_secs_f32()
         / config.shared.tick.tick_duration.as_secs_f32()) as i16
         + 1;
+    // if the component is extrapolated, we keep the start tick around for longer, since we extrapolate from it
+    let reset_delta_tick =
+        component_registry
+            .extrapolation::<C>()
+            .map_or(send_interval_delta_tick, |extrapolation| {
+                send_interval_delta_tick
+                    + (extrapolation.max_duration.as_secs_f32()
+                        / config.shared.tick.tick_duration.as_secs_f32())
+                    .ceil() as i16
+            });
 
     let current_interpolate_tick = connection
         .sync_manager
@@ -76,6 +133,7 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
         .sync_manager
         .interpolation_overstep(tick_manager.as_ref());
     for (entity, component, mut status, mut history) in query.iter_mut() {
+        let mut previous = status.previous.take();
         let mut start = status.start.take();
         let mut end = status.end.take();
 
@@ -89,12 +147,14 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
                     ?current_interpolate_tick,
                     "interpolation is beyond previous end tick"
                 );
-                start.clone_from(&end);
                 // TODO: this clone should be avoidable
+                // (if we were extrapolating, the `interpolate` system will blend back to the confirmed value)
                 if let Some(mut component) = component {
-                    *component = end_value.clone();
+                    if !status.is_extrapolated() {
+                        *component = end_value.clone();
+                    }
                 }
-                end = None;
+                previous = std::mem::replace(&mut start, end.take());
             }
         }
 
@@ -113,7 +173,11 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
                     old_start = ?start.as_ref().map(|(tick, _)| tick),
                     new_start = ?new_tick,
                     "found more recent tick between start and interpolation tick");
-                start = new_start;
+                if start.as_ref().is_some_and(|(tick, _)| *tick < new_tick) {
+                    previous = std::mem::replace(&mut start, new_start);
+                } else {
+                    start = new_start;
+                }
             }
         }
 
@@ -172,10 +236,12 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
         if end.is_none() {
             let temp_start = std::mem::take(&mut start);
             if let Some((start_tick, _)) = temp_start {
-                if current_interpolate_tick - start_tick < send_interval_delta_tick {
+                if current_interpolate_tick - start_tick < reset_delta_tick {
                     start = temp_start;
+                } else {
+                    // else (if it's been too long), reset the server tick to None
+                    previous = None;
                 }
-                // else (if it's been too long), reset the server tick to None
             }
         }
 
@@ -188,6 +254,7 @@ pub(crate) fn update_interpolate_status<C: SyncComponent>(
             start_tick = ?start.as_ref().map(|(tick, _)| tick),
             end_tick = ?end.as_ref().map(|(tick, _) | tick),
             "update_interpolate_status");
+        status.previous = previous;
         status.start = start;
         status.end = end;
         status.current_tick = current_interpolate_tick;
@@ -253,24 +320,274 @@ pub(crate) fn insert_interpolated_component<C: SyncComponent>(
 /// Update the component value on the Interpolate entity
 pub(crate) fn interpolate<C: Component + Clone>(
     component_registry: Res<ComponentRegistry>,
-    mut query: Query<(&mut C, &InterpolateStatus<C>)>,
+    config: Res<ClientConfig>,
+    mut query: Query<(&mut C, &mut InterpolateStatus<C>)>,
 ) {
-    for (mut component, status) in query.iter_mut() {
+    let tick_duration = config.shared.tick.tick_duration.as_secs_f32();
+    let extrapolation = component_registry.extrapolation::<C>();
+    for (mut component, mut status) in query.iter_mut() {
         debug!("checking if we do interpolation");
         // NOTE: it is possible that we reach start_tick when end_tick is not set
-        if let Some((start_tick, start_value)) = &status.start {
-            if let Some((end_tick, end_value)) = &status.end {
-                debug!(?start_tick, interpolate_tick=?status.current_tick, ?end_tick, "doing interpolation!");
-                assert!(status.current_tick < *end_tick);
-                if start_tick != end_tick {
-                    let t = status.interpolation_fraction().unwrap();
-                    let value = component_registry.interpolate(start_value, end_value, t);
-                    *component = value;
+        let Some((start_tick, start_value)) = &status.start else {
+            // the start was reset because we haven't received updates in a while: we are not
+            // extrapolating anymore
+            status
+                .reborrow()
+                .map_unchanged(|status| &mut status.mode)
+                .set_if_neq(InterpolationMode::Interpolated);
+            if status.blend.is_some() {
+                status.blend = None;
+            }
+            continue;
+        };
+        let (mut value, mode) = if let Some((end_tick, end_value)) = &status.end {
+            debug!(?start_tick, interpolate_tick=?status.current_tick, ?end_tick, "doing interpolation!");
+            assert!(status.current_tick < *end_tick);
+            if start_tick != end_tick {
+                let t = status.interpolation_fraction().unwrap();
+                (
+                    component_registry.interpolate(start_value, end_value, t),
+                    InterpolationMode::Interpolated,
+                )
+            } else {
+                (start_value.clone(), InterpolationMode::Interpolated)
+            }
+        } else if let (Some(extrapolation), Some((previous_tick, previous_value))) =
+            (extrapolation, &status.previous)
+        {
+            // we ran out of confirmed states: extrapolate from the last two confirmed states
+            let interval = (*start_tick - *previous_tick) as f32 * tick_duration;
+            let elapsed = (((status.current_tick - *start_tick) as f32 + status.current_overstep)
+                * tick_duration)
+                .min(extrapolation.max_duration.as_secs_f32());
+            if elapsed <= 0.0 {
+                (start_value.clone(), InterpolationMode::Interpolated)
+            } else {
+                trace!(?start_tick, interpolate_tick=?status.current_tick, ?elapsed, "doing extrapolation!");
+                (
+                    component_registry.extrapolate(previous_value, start_value, interval, elapsed),
+                    InterpolationMode::Extrapolated,
+                )
+            }
+        } else if status.is_extrapolated() {
+            // we cannot extrapolate anymore, go back to the last confirmed value
+            (start_value.clone(), InterpolationMode::Interpolated)
+        } else {
+            continue;
+        };
+
+        if mode == InterpolationMode::Interpolated {
+            if status.is_extrapolated() {
+                // we received a new confirmed state after extrapolating: blend from the extrapolated value
+                status.blend = Some((
+                    (*component).clone(),
+                    status.current_tick,
+                    status.current_overstep,
+                ));
+            }
+            if let Some((blend_value, blend_tick, blend_overstep)) = &status.blend {
+                let blend_duration = extrapolation.map_or(0.0, |e| e.blend_duration.as_secs_f32());
+                let elapsed = ((status.current_tick - *blend_tick) as f32
+                    + status.current_overstep
+                    - *blend_overstep)
+                    * tick_duration;
+                if elapsed < blend_duration {
+                    value = component_registry.interpolate(
+                        blend_value,
+                        &value,
+                        elapsed / blend_duration,
+                    );
                 } else {
-                    *component = start_value.clone();
+                    status.blend = None;
                 }
             }
+        } else if status.blend.is_some() {
+            status.blend = None;
         }
+        // avoid triggering change detection on the status every frame
+        status
+            .reborrow()
+            .map_unchanged(|status| &mut status.mode)
+            .set_if_neq(mode);
+        *component = value;
+    }
+}
+
+/// Update the component value on the Interpolate entity with cubic Hermite interpolation,
+/// using the velocity component `V` at the start and end states as tangents.
+///
+/// This runs after [`interpolate`] and only overrides the value when we are interpolating between two states.
+pub(crate) fn hermite_interpolate<C: Component + Clone, V: Component>(
+    component_registry: Res<ComponentRegistry>,
+    config: Res<ClientConfig>,
+    mut query: Query<(&mut C, &InterpolateStatus<C>, &InterpolateStatus<V>)>,
+) {
+    let Some(hermite_fn) = component_registry.hermite_interpolation::<C, V>() else {
+        return;
+    };
+    let tick_duration = config.shared.tick.tick_duration.as_secs_f32();
+    for (mut component, status, velocity_status) in query.iter_mut() {
+        // we are extrapolating, or blending back from an extrapolated value
+        if status.blend.is_some() {
+            continue;
+        }
+        let (Some((start_tick, start_value)), Some((end_tick, end_value))) =
+            (&status.start, &status.end)
+        else {
+            continue;
+        };
+        if start_tick == end_tick {
+            continue;
+        }
+        let (Some(start_velocity), Some(end_velocity)) = (
+            velocity_status.value_at(*start_tick),
+            velocity_status.value_at(*end_tick),
+        ) else {
+            continue;
+        };
+        let t = status.interpolation_fraction().unwrap();
+        let duration = (*end_tick - *start_tick) as f32 * tick_duration;
+        trace!(?start_tick, interpolate_tick=?status.current_tick, ?end_tick, "doing hermite interpolation!");
+        *component = hermite_fn(
+            start_value,
+            start_velocity,
+            end_value,
+            end_velocity,
+            t,
+            duration,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::client::interpolation::plugin::ExtrapolationConfig;
+    use crate::tests::protocol::ComponentSyncModeFull;
+    use crate::tests::stepper::BevyStepper;
+
+    fn run_interpolate(stepper: &mut BevyStepper, entity: Entity) -> (f32, InterpolationMode) {
+        let _ = stepper
+            .client_app
+            .world_mut()
+            .run_system_once(interpolate::<ComponentSyncModeFull>);
+        let world = stepper.client_app.world();
+        (
+            world.get::<ComponentSyncModeFull>(entity).unwrap().0,
+            world
+                .get::<InterpolateStatus<ComponentSyncModeFull>>(entity)
+                .unwrap()
+                .mode,
+        )
+    }
+
+    fn status_mut(
+        stepper: &mut BevyStepper,
+        entity: Entity,
+    ) -> Mut<'_, InterpolateStatus<ComponentSyncModeFull>> {
+        stepper
+            .client_app
+            .world_mut()
+            .get_mut::<InterpolateStatus<ComponentSyncModeFull>>(entity)
+            .unwrap()
+    }
+
+    /// Check that we extrapolate when there is no end state, blend back to the interpolated value
+    /// when a new confirmed state arrives, and stop extrapolating when the start state is reset
+    #[test]
+    fn test_extrapolate_blend_reset() {
+        let mut stepper = BevyStepper::default();
+        let tick_duration = stepper.tick_duration;
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ComponentRegistry>()
+            .set_extrapolation::<ComponentSyncModeFull>(
+                ExtrapolationConfig::default()
+                    .with_max_duration(tick_duration * 5)
+                    .with_blend_duration(tick_duration * 2),
+            );
+        let entity = stepper
+            .client_app
+            .world_mut()
+            .spawn((
+                ComponentSyncModeFull(10.0),
+                InterpolateStatus {
+                    previous: Some((Tick(0), ComponentSyncModeFull(0.0))),
+                    start: Some((Tick(10), ComponentSyncModeFull(10.0))),
+                    end: None,
+                    current_tick: Tick(12),
+                    current_overstep: 0.0,
+                    mode: InterpolationMode::Interpolated,
+                    blend: None,
+                },
+            ))
+            .id();
+
+        // 1. no end state: extrapolate along the line from previous to start
+        let (value, mode) = run_interpolate(&mut stepper, entity);
+        assert_eq!(mode, InterpolationMode::Extrapolated);
+        assert!((value - 12.0).abs() < 1e-3);
+
+        // 2. a new confirmed state arrives: blend from the extrapolated value
+        {
+            let mut status = status_mut(&mut stepper, entity);
+            status.previous = Some((Tick(10), ComponentSyncModeFull(10.0)));
+            status.start = Some((Tick(12), ComponentSyncModeFull(11.0)));
+            status.end = Some((Tick(20), ComponentSyncModeFull(19.0)));
+        }
+        let (value, mode) = run_interpolate(&mut stepper, entity);
+        assert_eq!(mode, InterpolationMode::Interpolated);
+        assert!((value - 12.0).abs() < 1e-3);
+        assert!(status_mut(&mut stepper, entity).blend.is_some());
+
+        // after the blend duration, we are back to the interpolated value
+        status_mut(&mut stepper, entity).current_tick = Tick(15);
+        let (value, mode) = run_interpolate(&mut stepper, entity);
+        assert_eq!(mode, InterpolationMode::Interpolated);
+        assert!((value - 14.0).abs() < 1e-3);
+        assert!(status_mut(&mut stepper, entity).blend.is_none());
+
+        // 3. extrapolate again, then the start state is reset because no update was received
+        {
+            let mut status = status_mut(&mut stepper, entity);
+            status.previous = Some((Tick(12), ComponentSyncModeFull(11.0)));
+            status.start = Some((Tick(20), ComponentSyncModeFull(19.0)));
+            status.end = None;
+            status.current_tick = Tick(21);
+        }
+        let (_, mode) = run_interpolate(&mut stepper, entity);
+        assert_eq!(mode, InterpolationMode::Extrapolated);
+        {
+            let mut status = status_mut(&mut stepper, entity);
+            status.previous = None;
+            status.start = None;
+        }
+        let (_, mode) = run_interpolate(&mut stepper, entity);
+        assert_eq!(mode, InterpolationMode::Interpolated);
+
+        // the status is not marked as changed when nothing changes
+        let last_changed = stepper
+            .client_app
+            .world()
+            .entity(entity)
+            .get_ref::<InterpolateStatus<ComponentSyncModeFull>>()
+            .unwrap()
+            .last_changed();
+        run_interpolate(&mut stepper, entity);
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .entity(entity)
+                .get_ref::<InterpolateStatus<ComponentSyncModeFull>>()
+                .unwrap()
+                .last_changed(),
+            last_changed
+        );
     }
 }
 
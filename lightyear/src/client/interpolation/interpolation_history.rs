@@ -117,12 +117,11 @@ pub(crate) fn add_component_history<C: SyncComponent>(
                             //  stay fixed until we get the next update, then it will start moving)
                             // new_component,
                             history,
-                            InterpolateStatus::<C> {
-                                start: Some((current_tick, new_component)),
-                                end: None,
+                            InterpolateStatus::<C>::new(
+                                Some((current_tick, new_component)),
                                 current_tick,
                                 current_overstep,
-                            },
+                            ),
                         ));
                     }
                     ComponentSyncMode::Once | ComponentSyncMode::Simple => {
@@ -6,13 +6,16 @@ use std::ops::{Add, Mul};
 
 pub use interpolate::InterpolateStatus;
 pub use interpolation_history::ConfirmedHistory;
-pub use plugin::{add_interpolation_systems, add_prepare_interpolation_systems};
+pub use plugin::{
+    add_hermite_interpolation_systems, add_interpolation_systems, add_prepare_interpolation_systems,
+};
 pub use visual_interpolation::{VisualInterpolateStatus, VisualInterpolationPlugin};
 
 use crate::client::components::LerpFn;
 use crate::client::interpolation::resource::InterpolationManager;
 
 mod despawn;
+pub mod diagnostics;
 pub mod interpolate;
 pub mod interpolation_history;
 pub mod plugin;
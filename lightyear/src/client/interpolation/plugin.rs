@@ -7,7 +7,7 @@ use super::interpolation_history::{
 use crate::client::components::{ComponentSyncMode, SyncComponent};
 use crate::client::interpolation::despawn::{despawn_interpolated, removed_components};
 use crate::client::interpolation::interpolate::{
-    insert_interpolated_component, interpolate, update_interpolate_status,
+    hermite_interpolate, insert_interpolated_component, interpolate, update_interpolate_status,
 };
 use crate::client::interpolation::resource::InterpolationManager;
 use crate::client::interpolation::spawn::spawn_interpolated_entity;
@@ -67,6 +67,9 @@ pub struct InterpolationConfig {
     /// The higher the server update_rate (i.e. smaller send_interval), the smaller the interpolation delay
     /// Set to 0.0 if you want to only use the Delay
     pub send_interval_ratio: f32,
+    /// If set, the interpolation delay is not fixed but adapts to the measured network conditions
+    /// (jitter and packet loss). `min_delay` and `send_interval_ratio` are then ignored.
+    pub adaptive_delay: Option<AdaptiveDelayConfig>,
 }
 
 impl Default for InterpolationConfig {
@@ -74,6 +77,7 @@ impl Default for InterpolationConfig {
         Self {
             min_delay: Duration::from_millis(0),
             send_interval_ratio: 2.0,
+            adaptive_delay: None,
         }
     }
 }
@@ -89,6 +93,11 @@ impl InterpolationConfig {
         self
     }
 
+    pub fn with_adaptive_delay(mut self, adaptive_delay: AdaptiveDelayConfig) -> Self {
+        self.adaptive_delay = Some(adaptive_delay);
+        self
+    }
+
     /// How much behind the latest server update we want the interpolation time to be
     pub(crate) fn to_duration(self, server_send_interval: Duration) -> Duration {
         // TODO: deal with server_send_interval = 0 (set to frame rate)
@@ -97,6 +106,122 @@ impl InterpolationConfig {
     }
 }
 
+/// Config for the adaptive interpolation delay.
+///
+/// The interpolation delay must be big enough that we always have a server snapshot to interpolate towards,
+/// but every extra millisecond of delay makes remote entities lag further behind.
+/// The adaptive delay is computed from the server send interval, the jitter of the snapshot arrival times and the
+/// loss of the packets sent by the server, and is smoothed over time so that the interpolation timeline doesn't jump around.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct AdaptiveDelayConfig {
+    /// The interpolation delay will never be smaller than this
+    pub min_delay: Duration,
+    /// The interpolation delay will never be bigger than this
+    pub max_delay: Duration,
+    /// How many multiples of the measured jitter do we add to the delay
+    pub jitter_multiple_margin: f32,
+    /// Acceptable probability that we run out of snapshots to interpolate towards because of consecutive
+    /// packet losses. Lower values will add more send intervals to the delay when there is packet loss.
+    pub max_starvation_probability: f32,
+    /// Time constant of the exponential smoothing applied to the delay.
+    /// Higher values make the delay change more slowly.
+    pub smoothing: Duration,
+}
+
+impl Default for AdaptiveDelayConfig {
+    fn default() -> Self {
+        Self {
+            min_delay: Duration::from_millis(0),
+            max_delay: Duration::from_millis(500),
+            jitter_multiple_margin: 3.0,
+            max_starvation_probability: 0.01,
+            smoothing: Duration::from_secs(1),
+        }
+    }
+}
+
+impl AdaptiveDelayConfig {
+    pub fn with_bounds(mut self, min_delay: Duration, max_delay: Duration) -> Self {
+        self.min_delay = min_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter_multiple_margin(mut self, jitter_multiple_margin: f32) -> Self {
+        self.jitter_multiple_margin = jitter_multiple_margin;
+        self
+    }
+
+    pub fn with_max_starvation_probability(mut self, max_starvation_probability: f32) -> Self {
+        self.max_starvation_probability = max_starvation_probability;
+        self
+    }
+
+    pub fn with_smoothing(mut self, smoothing: Duration) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// The interpolation delay that we want to reach, given the current network conditions
+    pub(crate) fn target_delay(
+        &self,
+        server_send_interval: Duration,
+        jitter: Duration,
+        packet_loss: f32,
+    ) -> Duration {
+        // we need to wait for `n` consecutive snapshots to be lost before starving, which happens with
+        // probability `packet_loss ^ n`, so we need n >= ln(max_starvation_probability) / ln(packet_loss).
+        // We always need to wait for at least one send interval.
+        let packet_loss = packet_loss.clamp(0.0, 0.99);
+        let send_intervals = if packet_loss > 0.0 {
+            (self.max_starvation_probability.ln() / packet_loss.ln())
+                .ceil()
+                .max(1.0) as u32
+        } else {
+            1
+        };
+        let delay =
+            server_send_interval * send_intervals + jitter.mul_f32(self.jitter_multiple_margin);
+        delay.clamp(self.min_delay, self.max_delay)
+    }
+}
+
+/// Config to specify how a component should be extrapolated when there are no more confirmed
+/// states to interpolate towards (for example because of packet loss or jitter).
+///
+/// Extrapolation is opt-in, and is enabled per component with
+/// [`ComponentRegistration::add_extrapolation`](crate::protocol::component::ComponentRegistration::add_extrapolation)
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct ExtrapolationConfig {
+    /// Maximum amount of time that we will extrapolate past the last confirmed state.
+    /// After that, the component stays at the last extrapolated value until a new confirmed state arrives.
+    pub max_duration: Duration,
+    /// When a new confirmed state arrives after we were extrapolating, we blend from the extrapolated value
+    /// back to the interpolated value during this amount of time, to avoid a visible snap.
+    pub blend_duration: Duration,
+}
+
+impl Default for ExtrapolationConfig {
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_millis(250),
+            blend_duration: Duration::from_millis(100),
+        }
+    }
+}
+
+impl ExtrapolationConfig {
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    pub fn with_blend_duration(mut self, blend_duration: Duration) -> Self {
+        self.blend_duration = blend_duration;
+        self
+    }
+}
+
 #[derive(Default)]
 pub struct InterpolationPlugin {
     config: InterpolationConfig,
@@ -181,12 +306,25 @@ pub fn add_interpolation_systems<C: SyncComponent>(app: &mut App) {
     );
 }
 
+/// Add the system that performs cubic Hermite interpolation of `C`, using the velocity component `V`.
+/// It overrides the value computed by the regular interpolation system.
+pub fn add_hermite_interpolation_systems<C: SyncComponent, V: SyncComponent>(app: &mut App) {
+    app.add_systems(
+        Update,
+        hermite_interpolate::<C, V>
+            .after(interpolate::<C>)
+            .in_set(InterpolationSet::Interpolate),
+    );
+}
+
 impl Plugin for InterpolationPlugin {
     fn build(&self, app: &mut App) {
         let should_run_interpolation = not(is_host_server).and(is_synced);
 
         // REFLECT
         app.register_type::<InterpolationConfig>()
+            .register_type::<AdaptiveDelayConfig>()
+            .register_type::<ExtrapolationConfig>()
             .register_type::<Interpolated>();
 
         // RESOURCES
@@ -235,4 +373,30 @@ mod tests {
             (Tick(2), 0.0)
         );
     }
+
+    #[test]
+    fn test_adaptive_target_delay() {
+        let config = AdaptiveDelayConfig::default();
+        let send_interval = Duration::from_millis(50);
+        // no jitter or packet loss: we only need to wait for the next snapshot
+        assert_eq!(
+            config.target_delay(send_interval, Duration::ZERO, 0.0),
+            send_interval
+        );
+        // jitter
+        assert_eq!(
+            config.target_delay(send_interval, Duration::from_millis(125), 0.0),
+            Duration::from_millis(425)
+        );
+        // 20% packet loss: we wait for 3 send intervals, so that we starve only if 3 consecutive snapshots are lost
+        assert_eq!(
+            config.target_delay(send_interval, Duration::ZERO, 0.2),
+            Duration::from_millis(150)
+        );
+        // the delay is bounded
+        assert_eq!(
+            config.target_delay(send_interval, Duration::from_millis(200), 0.5),
+            config.max_delay
+        );
+    }
 }
@@ -6,12 +6,16 @@ use crate::transport::dummy::DummyIo;
 use crate::transport::error::Result;
 use crate::transport::io::{BaseIo, IoStats};
 use crate::transport::local::LocalChannelBuilder;
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::middleware::capture::PacketCapture;
 #[cfg(feature = "zstd")]
 use crate::transport::middleware::compression::zstd::compression::ZstdCompressor;
 #[cfg(feature = "zstd")]
 use crate::transport::middleware::compression::zstd::decompression::ZstdDecompressor;
 use crate::transport::middleware::conditioner::LinkConditioner;
-use crate::transport::middleware::PacketReceiverWrapper;
+use crate::transport::middleware::{PacketReceiverWrapper, PacketSenderWrapper};
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::replay::{ReplayBuilder, ReplayClock};
 #[cfg(not(target_family = "wasm"))]
 use crate::transport::udp::UdpSocketBuilder;
 #[cfg(feature = "websocket")]
@@ -22,6 +26,7 @@ use crate::transport::{BoxedReceiver, Transport};
 use bevy::prelude::TypePath;
 use crossbeam_channel::{Receiver, Sender};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 /// Use this to configure the [`Transport`] that will be used to establish a connection with the
 /// server.
@@ -48,6 +53,12 @@ pub enum ClientTransport {
         recv: Receiver<Vec<u8>>,
         send: Sender<Vec<u8>>,
     },
+    /// Replay the packets received during a capture (see [`CaptureConfig`](crate::prelude::CaptureConfig)).
+    ///
+    /// Packets sent by the client are dropped. The client must use the same protocol id and connect token
+    /// as in the captured session.
+    #[cfg(not(target_family = "wasm"))]
+    Replay { path: PathBuf, clock: ReplayClock },
     /// Dummy transport if the connection handles its own io (for example steam sockets)
     Dummy,
 }
@@ -86,6 +97,10 @@ impl ClientTransport {
             ClientTransport::LocalChannel { recv, send } => {
                 ClientTransportBuilderEnum::LocalChannel(LocalChannelBuilder { recv, send })
             }
+            #[cfg(not(target_family = "wasm"))]
+            ClientTransport::Replay { path, clock } => {
+                ClientTransportBuilderEnum::Replay(ReplayBuilder { path, clock })
+            }
             ClientTransport::Dummy => ClientTransportBuilderEnum::Dummy(DummyIo),
         }
     }
@@ -112,8 +127,12 @@ impl SharedIoConfig<ClientTransport> {
         let (mut sender, receiver) = transport.split();
         #[allow(unused_mut)]
         let mut receiver: BoxedReceiver = if let Some(conditioner_config) = self.conditioner {
-            let conditioner = LinkConditioner::new(conditioner_config);
-            Box::new(conditioner.wrap(receiver))
+            if conditioner_config.conditions_outgoing() {
+                let conditioner = LinkConditioner::outgoing(&conditioner_config);
+                sender = Box::new(PacketSenderWrapper::wrap(conditioner, sender));
+            }
+            let conditioner = LinkConditioner::incoming(&conditioner_config);
+            Box::new(PacketReceiverWrapper::wrap(conditioner, receiver))
         } else {
             Box::new(receiver)
         };
@@ -121,15 +140,20 @@ impl SharedIoConfig<ClientTransport> {
             CompressionConfig::None => {}
             #[cfg(feature = "zstd")]
             CompressionConfig::Zstd { level } => {
-                use crate::transport::middleware::PacketSenderWrapper;
                 let compressor = ZstdCompressor::new(level);
                 sender = Box::new(compressor.wrap(sender));
                 let decompressor = ZstdDecompressor::new();
                 receiver = Box::new(decompressor.wrap(receiver));
             }
+            #[cfg(feature = "zstd")]
+            CompressionConfig::ZstdDictionary { level, dictionary } => {
+                let compressor = ZstdCompressor::with_dictionary(level, &dictionary)?;
+                sender = Box::new(compressor.wrap(sender));
+                let decompressor = ZstdDecompressor::with_dictionary(&dictionary)?;
+                receiver = Box::new(decompressor.wrap(receiver));
+            }
             #[cfg(feature = "lz4")]
             CompressionConfig::Lz4 => {
-                use crate::transport::middleware::PacketSenderWrapper;
                 let compressor =
                     crate::transport::middleware::compression::lz4::Compressor::default();
                 sender = Box::new(compressor.wrap(sender));
@@ -138,6 +162,16 @@ impl SharedIoConfig<ClientTransport> {
                 receiver = Box::new(decompressor.wrap(receiver));
             }
         }
+        if let Some(capture_config) = self.capture {
+            #[cfg(not(target_family = "wasm"))]
+            {
+                let capture = PacketCapture::new(&capture_config)?;
+                sender = Box::new(PacketSenderWrapper::wrap(capture.clone(), sender));
+                receiver = Box::new(PacketReceiverWrapper::wrap(capture, receiver));
+            }
+            #[cfg(target_family = "wasm")]
+            bevy::log::warn!(?capture_config, "Packet capture is not supported on wasm");
+        }
         Ok(BaseIo {
             local_addr,
             sender,
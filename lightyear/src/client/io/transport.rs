@@ -4,6 +4,8 @@ use crate::transport::error::Error as TransportError;
 use crate::transport::io::IoState;
 use crate::transport::local::{LocalChannel, LocalChannelBuilder};
 #[cfg(not(target_family = "wasm"))]
+use crate::transport::replay::{ReplayBuilder, ReplayTransport};
+#[cfg(not(target_family = "wasm"))]
 use crate::transport::udp::{UdpSocket, UdpSocketBuilder};
 #[cfg(feature = "websocket")]
 use crate::transport::websocket::client::{WebSocketClientSocket, WebSocketClientSocketBuilder};
@@ -42,6 +44,8 @@ pub(crate) enum ClientTransportBuilderEnum {
     #[cfg(feature = "websocket")]
     WebSocketClient(WebSocketClientSocketBuilder),
     LocalChannel(LocalChannelBuilder),
+    #[cfg(not(target_family = "wasm"))]
+    Replay(ReplayBuilder),
     Dummy(DummyIo),
 }
 
@@ -55,5 +59,7 @@ pub(crate) enum ClientTransportEnum {
     #[cfg(feature = "websocket")]
     WebSocketClient(WebSocketClientSocket),
     LocalChannel(LocalChannel),
+    #[cfg(not(target_family = "wasm"))]
+    Replay(ReplayTransport),
     Dummy(DummyIo),
 }
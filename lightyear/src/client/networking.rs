@@ -5,6 +5,8 @@ use async_channel::TryRecvError;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick};
 use bevy::prelude::ResMut;
 use bevy::prelude::*;
+#[cfg(not(target_family = "wasm"))]
+use bevy::tasks::{block_on, futures_lite::future, IoTaskPool, Task};
 use tracing::{error, trace};
 
 use crate::client::config::ClientConfig;
@@ -15,17 +17,25 @@ use crate::client::networking::utils::AppStateExt;
 use crate::client::replication::send::ReplicateToServer;
 use crate::client::run_conditions::is_disconnected;
 use crate::client::sync::SyncSet;
-use crate::connection::client::{ClientConnection, ConnectionError, ConnectionState, NetClient};
+#[cfg(not(target_family = "wasm"))]
+use crate::connection::client::Authentication;
+use crate::connection::client::{
+    ClientConnection, ConnectionError, ConnectionState, NetClient, NetConfig,
+};
+#[cfg(not(target_family = "wasm"))]
+use crate::connection::netcode::{request_token, ConnectToken, TokenServiceError};
 use crate::connection::server::IoConfig;
 use crate::prelude::{
     is_host_server, ChannelRegistry, MainSet, MessageRegistry, TickManager, TimeManager,
 };
 use crate::protocol::component::ComponentRegistry;
+use crate::protocol::fingerprint::ProtocolFingerprint;
 use crate::server::clients::ControlledEntities;
 use crate::shared::config::Mode;
 use crate::shared::replication::components::Replicated;
 use crate::shared::sets::{ClientMarker, InternalMainSet};
 use crate::transport::io::IoState;
+use crate::transport::PacketSender;
 
 #[derive(Default)]
 pub(crate) struct ClientNetworkingPlugin;
@@ -87,6 +97,13 @@ impl Plugin for ClientNetworkingPlugin {
 
         // CONNECTING
         app.add_systems(OnEnter(NetworkingState::Connecting), connect);
+        #[cfg(not(target_family = "wasm"))]
+        app.add_systems(
+            PreUpdate,
+            poll_connect_token_request
+                .run_if(resource_exists::<ConnectTokenRequest>)
+                .before(InternalMainSet::<ClientMarker>::Receive),
+        );
 
         // CONNECTED
         app.add_systems(
@@ -214,6 +231,12 @@ pub(crate) fn send(
             error!("Error sending packet: {}", e);
         });
     }
+    // release the packets that were delayed by the io middleware (e.g. the link conditioner)
+    if let Some(io) = netcode.io_mut() {
+        let _ = io
+            .flush()
+            .inspect_err(|e| error!("Error flushing io: {}", e));
+    }
 
     // no need to clear the connection, because we already std::mem::take it
     // client.connection.clear();
@@ -259,6 +282,8 @@ pub(crate) fn sync_update(
         time_manager.deref_mut(),
         tick_manager.deref_mut(),
         &connection.ping_manager,
+        // the interpolation delay depends on the loss of the packets sent by the server
+        connection.message_manager.received_packet_loss(),
         &config.prediction,
         &config.interpolation,
         // TODO: how to adjust this for replication groups that have a custom send_interval?
@@ -411,8 +436,13 @@ fn on_disconnect(
         }
     });
 
+    // stop waiting for a connect token
+    #[cfg(not(target_family = "wasm"))]
+    commands.remove_resource::<ConnectTokenRequest>();
     // set synced to false
     connection_manager.sync_manager.synced = false;
+    // the pending requests will never get a response
+    connection_manager.rpc_manager.disconnect();
     // try to disconnect again to close io tasks (in case the disconnection is from the io)
     let _ = netclient.disconnect();
 
@@ -462,13 +492,66 @@ fn rebuild_client_connection(world: &mut World) {
     );
     world.insert_resource(connection_manager);
 
+    build_client_connection(world, client_config.net);
+}
+
+/// Replace the [`ClientConnection`] with a new one built from `net_config`
+fn build_client_connection(world: &mut World, net_config: NetConfig) {
     // drop the previous client connection to make sure we release any resources before creating the new one
     world.remove_resource::<ClientConnection>();
     // insert the new client connection
-    let client_connection = client_config.net.build_client();
+    let protocol_fingerprint = ProtocolFingerprint::from_world(world);
+    let client_connection = net_config.build_client(protocol_fingerprint);
     world.insert_resource(client_connection);
 }
 
+/// Task that requests a [`ConnectToken`] from a [`TokenService`](crate::connection::netcode::TokenService),
+/// when connecting with [`Authentication::RequestToken`]
+#[cfg(not(target_family = "wasm"))]
+#[derive(Resource)]
+struct ConnectTokenRequest(Task<Result<ConnectToken, TokenServiceError>>);
+
+#[cfg(not(target_family = "wasm"))]
+impl ConnectTokenRequest {
+    fn new(addr: std::net::SocketAddr, credentials: Vec<u8>, timeout: std::time::Duration) -> Self {
+        let task = IoTaskPool::get().spawn(async_compat::Compat::new(async move {
+            match tokio::time::timeout(timeout, request_token(addr, &credentials)).await {
+                Ok(result) => result,
+                Err(_) => Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into()),
+            }
+        }));
+        Self(task)
+    }
+}
+
+/// Start the connection once the [`ConnectToken`] has been received from the token service,
+/// or disconnect if the request failed
+#[cfg(not(target_family = "wasm"))]
+fn poll_connect_token_request(world: &mut World) {
+    let mut request = world.resource_mut::<ConnectTokenRequest>();
+    let Some(result) = block_on(future::poll_once(&mut request.0)) else {
+        return;
+    };
+    world.remove_resource::<ConnectTokenRequest>();
+    match result {
+        Ok(token) => {
+            let mut net_config = world.resource::<ClientConfig>().net.clone();
+            if let NetConfig::Netcode { auth, .. } = &mut net_config {
+                *auth = Authentication::Token(token);
+            }
+            build_client_connection(world, net_config);
+            start_connection(world);
+        }
+        Err(e) => {
+            error!("Could not get a ConnectToken from the token service: {e}");
+            world.resource_mut::<ClientConnection>().disconnect_reason = Some(e.into());
+            world
+                .resource_mut::<NextState<NetworkingState>>()
+                .set(NetworkingState::Disconnected);
+        }
+    }
+}
+
 // TODO: the design where the user has to call world.connect_client() is better because the user can handle the Error however they want!
 
 /// Connect the client
@@ -488,6 +571,25 @@ fn connect(world: &mut World) {
     // new client connection and connection manager, which want to do because we need to reset
     // the internal time, sync, priority, message numbers, etc.)
     rebuild_client_connection(world);
+    // requesting the token can take a while, so we do it in a task instead of blocking the main thread.
+    // The connection will be started by `poll_connect_token_request` once the token is received
+    #[cfg(not(target_family = "wasm"))]
+    if let NetConfig::Netcode {
+        auth: Authentication::RequestToken { addr, credentials },
+        config,
+        ..
+    } = &world.resource::<ClientConfig>().net
+    {
+        let timeout = std::time::Duration::from_secs(config.client_timeout_secs.max(1) as u64);
+        let request = ConnectTokenRequest::new(*addr, credentials.clone(), timeout);
+        world.insert_resource(request);
+        return;
+    }
+    start_connection(world);
+}
+
+/// Start the connection process of the [`ClientConnection`]
+fn start_connection(world: &mut World) {
     if let Err(e) = world.resource_mut::<ClientConnection>().connect() {
         error!("Error connecting client: {}", e);
         world.resource_mut::<ClientConnection>().disconnect_reason = Some(e);
@@ -623,4 +725,117 @@ mod tests {
         stepper.frame_step();
         assert_eq!(stepper.server_app.world().resource::<CheckCounter>().0, 2); // 2 because local client as well as external client disconnect
     }
+
+    /// Start a [`TokenService`] for the server of the stepper, and make the client request its token from it
+    #[cfg(not(target_family = "wasm"))]
+    fn setup_token_service(
+        stepper: &mut crate::tests::stepper::BevyStepper,
+        validator: impl crate::connection::netcode::CredentialValidator,
+    ) {
+        use crate::connection::client::{Authentication, NetConfig};
+        use crate::connection::netcode::TokenService;
+        use bevy::tasks::{IoTaskPool, TaskPool};
+
+        let server_config = stepper.server_app.world().resource::<ServerConfig>();
+        let crate::server::config::NetConfig::Netcode { config, .. } = &server_config.net[0] else {
+            unreachable!()
+        };
+        let service = TokenService::new(
+            crate::transport::LOCAL_SOCKET,
+            config.protocol_id,
+            config.private_key,
+        )
+        .with_validator(validator);
+        IoTaskPool::get_or_init(TaskPool::new);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        service.spawn_with_listener(listener).detach();
+
+        let mut client_config = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConfig>();
+        let NetConfig::Netcode { auth, .. } = &mut client_config.net else {
+            unreachable!()
+        };
+        *auth = Authentication::RequestToken {
+            addr,
+            credentials: b"password".to_vec(),
+        };
+    }
+
+    /// Step the stepper until the client leaves the `Connecting` state.
+    /// The token service runs on real time, so we also need to wait between frames.
+    #[cfg(not(target_family = "wasm"))]
+    fn wait_while_connecting(stepper: &mut crate::tests::stepper::BevyStepper) -> NetworkingState {
+        for _ in 0..200 {
+            stepper.frame_step();
+            let state = *stepper
+                .client_app
+                .world()
+                .resource::<State<NetworkingState>>()
+                .get();
+            if state != NetworkingState::Connecting {
+                return state;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        NetworkingState::Connecting
+    }
+
+    #[test]
+    #[cfg(not(target_family = "wasm"))]
+    fn test_connect_with_requested_token() {
+        let mut stepper = crate::tests::stepper::BevyStepper::default_no_init();
+        setup_token_service(
+            &mut stepper,
+            |credentials: Vec<u8>, _: std::net::SocketAddr| async move {
+                (credentials == b"password").then(crate::connection::netcode::TokenGrant::default)
+            },
+        );
+        stepper.build();
+        assert_eq!(
+            wait_while_connecting(&mut stepper),
+            NetworkingState::Connected
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_family = "wasm"))]
+    fn test_connect_with_denied_token_request() {
+        let mut stepper = crate::tests::stepper::BevyStepper::default_no_init();
+        setup_token_service(
+            &mut stepper,
+            |_: Vec<u8>, _: std::net::SocketAddr| async move { None },
+        );
+        stepper
+            .client_app
+            .init_resource::<CheckCounter>()
+            .add_systems(Update, receive_client_disconnect_event);
+        stepper.build();
+        assert_eq!(
+            wait_while_connecting(&mut stepper),
+            NetworkingState::Disconnected
+        );
+        stepper.frame_step();
+        // the client is notified that the token request failed
+        assert_eq!(stepper.client_app.world().resource::<CheckCounter>().0, 1);
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn receive_client_disconnect_event(
+        mut reader: EventReader<crate::client::events::DisconnectEvent>,
+        mut res: ResMut<CheckCounter>,
+    ) {
+        for event in reader.read() {
+            if matches!(
+                event.reason,
+                Some(crate::connection::client::ConnectionError::TokenRequest(
+                    crate::connection::netcode::TokenServiceError::Denied
+                ))
+            ) {
+                res.0 += 1;
+            }
+        }
+    }
 }
@@ -8,6 +8,7 @@ use tracing::{debug, error, trace};
 
 use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent};
 use crate::client::config::ClientConfig;
+use crate::client::prediction::rollback::Rollback;
 use crate::client::prediction::Predicted;
 use crate::prelude::{
     ComponentRegistry, Mode, PreSpawnedPlayerObject, ShouldBePredicted, TickManager,
@@ -135,8 +136,12 @@ pub(crate) fn remove_component_for_despawn_predicted<C: SyncComponent>(
 pub(crate) fn restore_components_if_despawn_rolled_back<C: SyncComponent>(
     mut commands: Commands,
     mut query: Query<(Entity, &mut RemovedCache<C>), Without<C>>,
+    rollback: Res<Rollback>,
 ) {
     for (entity, mut cache) in query.iter_mut() {
+        if !rollback.is_entity_rollback(entity) {
+            continue;
+        }
         debug!("restoring component after rollback");
         let Some(component) = std::mem::take(&mut cache.0) else {
             debug!("could not find component");
@@ -32,8 +32,9 @@ use super::resource_history::{
     handle_tick_event_resource_history, update_resource_history, ResourceHistory,
 };
 use super::rollback::{
-    check_rollback, increment_rollback_tick, prepare_rollback, prepare_rollback_non_networked,
-    prepare_rollback_prespawn, prepare_rollback_resource, run_rollback, Rollback, RollbackState,
+    check_rollback, increment_rollback_tick, prepare_rollback, prepare_rollback_entities,
+    prepare_rollback_non_networked, prepare_rollback_prespawn, prepare_rollback_resource,
+    run_rollback, Rollback, RollbackState,
 };
 use super::spawn::spawn_predicted_entity;
 
@@ -79,6 +80,29 @@ pub struct PredictionConfig {
     /// (i.e. if the client is 10 ticks head and correction_ticks is 1.0, then the correction will be done over 10 ticks)
     // Number of ticks it will take to visually update the Predicted state to the new Corrected state
     pub correction_ticks_factor: f32,
+    /// Defines which predicted entities are rolled back when a mismatch is detected
+    pub rollback_mode: RollbackMode,
+}
+
+/// Defines which predicted entities are rolled back when a mismatch between the predicted and the confirmed state is detected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum RollbackMode {
+    /// Any mismatch rolls back every predicted entity
+    #[default]
+    Global,
+    /// Only the predicted entities of the [`ReplicationGroup`](crate::prelude::ReplicationGroup)s that have a mismatch are rolled back.
+    ///
+    /// Entities of different groups often interact with each other (for example two players colliding); you can declare
+    /// that two groups need to be rolled back together with [`Rollback::link_groups`].
+    ///
+    /// **The whole `FixedMain` schedule is still re-run during the rollback**: lightyear only skips the entities that
+    /// are not rolled back in its own systems (prediction history, prediction despawns). Your systems that simulate
+    /// predicted entities must skip them as well, otherwise they would be simulated more than once for the same tick:
+    /// query them with a [`RollbackQuery`](crate::prelude::client::RollbackQuery) instead of a `Query`, or check
+    /// [`Rollback::is_disabled_during_rollback`].
+    ///
+    /// Pre-spawned entities and resources are always rolled back.
+    PerGroup,
 }
 
 impl Default for PredictionConfig {
@@ -103,6 +127,7 @@ impl PredictionConfig {
             maximum_input_delay_before_prediction: 3,
             maximum_predicted_ticks: 7,
             correction_ticks_factor: 1.0,
+            rollback_mode: RollbackMode::Global,
         }
     }
 
@@ -114,6 +139,7 @@ impl PredictionConfig {
             maximum_input_delay_before_prediction: 0,
             maximum_predicted_ticks: 100,
             correction_ticks_factor: 1.0,
+            rollback_mode: RollbackMode::Global,
         }
     }
 
@@ -125,6 +151,7 @@ impl PredictionConfig {
             maximum_input_delay_before_prediction: 0,
             maximum_predicted_ticks: 0,
             correction_ticks_factor: 0.0,
+            rollback_mode: RollbackMode::Global,
         }
     }
 
@@ -152,6 +179,12 @@ impl PredictionConfig {
         self
     }
 
+    /// Update the [`RollbackMode`]
+    pub fn with_rollback_mode(mut self, rollback_mode: RollbackMode) -> Self {
+        self.rollback_mode = rollback_mode;
+        self
+    }
+
     /// Compute the amount of input delay that should be applied, considering the current RTT
     pub fn input_delay_ticks(&self, rtt: Duration, tick_interval: Duration) -> u16 {
         assert!(self.minimum_input_delay_ticks <= self.maximum_input_delay_before_prediction,
@@ -191,6 +224,8 @@ pub enum PredictionSet {
     RestoreVisualCorrection,
     /// Check if rollback is needed
     CheckRollback,
+    /// Compute which predicted entities need to be rolled back
+    PrepareRollbackEntities,
     /// Prepare rollback by snapping the current state to the confirmed state and clearing histories
     /// For pre-spawned entities, we just roll them back to their historical state.
     /// If they didn't exist in the rollback tick, despawn them
@@ -395,6 +430,7 @@ impl Plugin for PredictionPlugin {
                     PredictionSet::SpawnHistory,
                     PredictionSet::RestoreVisualCorrection,
                     PredictionSet::CheckRollback,
+                    PredictionSet::PrepareRollbackEntities.run_if(is_in_rollback),
                     PredictionSet::PrepareRollback.run_if(is_in_rollback),
                     PredictionSet::Rollback.run_if(is_in_rollback),
                 )
@@ -416,6 +452,7 @@ impl Plugin for PredictionPlugin {
                 spawn_predicted_entity
                     .after(PreSpawnedPlayerObjectSet::Spawn)
                     .in_set(PredictionSet::SpawnPrediction),
+                prepare_rollback_entities.in_set(PredictionSet::PrepareRollbackEntities),
                 run_rollback.in_set(PredictionSet::Rollback),
                 #[cfg(feature = "metrics")]
                 super::rollback::no_rollback
@@ -483,6 +520,7 @@ mod tests {
             maximum_input_delay_before_prediction: 3,
             maximum_predicted_ticks: 7,
             correction_ticks_factor: 0.0,
+            rollback_mode: RollbackMode::Global,
         };
         // 1. Test the minimum input delay
         assert_eq!(
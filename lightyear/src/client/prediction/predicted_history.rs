@@ -195,7 +195,7 @@ fn add_history<C: SyncComponent>(
 ///
 /// This system only handles changes, removals are handled in `apply_component_removal`
 pub(crate) fn update_prediction_history<T: Component + PartialEq + Clone>(
-    mut query: Query<(Ref<T>, &mut PredictionHistory<T>)>,
+    mut query: Query<(Entity, Ref<T>, &mut PredictionHistory<T>)>,
     tick_manager: Res<TickManager>,
     rollback: Res<Rollback>,
 ) {
@@ -203,7 +203,11 @@ pub(crate) fn update_prediction_history<T: Component + PartialEq + Clone>(
     let tick = tick_manager.tick_or_rollback_tick(rollback.as_ref());
 
     // update history if the predicted component changed
-    for (component, mut history) in query.iter_mut() {
+    for (entity, component, mut history) in query.iter_mut() {
+        // entities that are not part of a partial rollback keep their history
+        if rollback.is_disabled_during_rollback(entity) {
+            continue;
+        }
         // change detection works even when running the schedule for rollback
         if component.is_changed() {
             history.add_update(tick, component.deref().clone());
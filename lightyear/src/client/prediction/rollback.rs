@@ -3,13 +3,16 @@ use std::ops::{Deref, DerefMut};
 
 use bevy::app::FixedMain;
 use bevy::ecs::entity::EntityHashSet;
+use bevy::ecs::query::{QueryData, QueryFilter, QueryItem, ROQueryItem};
 use bevy::ecs::reflect::ReflectResource;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::{
     Commands, Component, DespawnRecursiveExt, DetectChanges, Entity, Query, Ref, Res, ResMut,
     Resource, With, Without, World,
 };
 use bevy::reflect::Reflect;
 use bevy::time::{Fixed, Time};
+use bevy::utils::{HashMap, HashSet};
 use parking_lot::RwLock;
 use tracing::{debug, error, trace, trace_span};
 
@@ -18,8 +21,11 @@ use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
 use crate::client::prediction::correction::Correction;
 use crate::client::prediction::diagnostics::PredictionMetrics;
+use crate::client::prediction::plugin::RollbackMode;
 use crate::client::prediction::resource::PredictionManager;
-use crate::prelude::{ComponentRegistry, HistoryState, PreSpawnedPlayerObject, Tick, TickManager};
+use crate::prelude::{
+    ComponentRegistry, HistoryState, PreSpawnedPlayerObject, ReplicationGroupId, Tick, TickManager,
+};
 
 use super::predicted_history::PredictionHistory;
 use super::resource_history::ResourceHistory;
@@ -34,7 +40,18 @@ pub struct Rollback {
     /// We use a RwLock because we want to be able to update this value from multiple systems
     /// in parallel.
     pub state: RwLock<RollbackState>,
-    // pub rollback_groups: EntityHashMap<ReplicationGroupId, RollbackState>,
+    /// With [`RollbackMode::PerGroup`], the replication groups that had a mismatch and need to be rolled back
+    #[reflect(ignore)]
+    rollback_groups: RwLock<HashSet<ReplicationGroupId>>,
+    /// The predicted entities that are rolled back during the current rollback.
+    ///
+    /// If None, all predicted entities are rolled back.
+    #[reflect(ignore)]
+    rollback_entities: Option<EntityHashSet>,
+    /// Replication groups whose entities interact with each other: if one of them is rolled back,
+    /// the other ones must be rolled back as well.
+    #[reflect(ignore)]
+    group_links: HashMap<ReplicationGroupId, HashSet<ReplicationGroupId>>,
 }
 
 /// Resource that will track whether we should do rollback or not
@@ -57,6 +74,7 @@ impl Rollback {
     pub(crate) fn new(state: RollbackState) -> Self {
         Self {
             state: RwLock::new(state),
+            ..Default::default()
         }
     }
 
@@ -89,12 +107,196 @@ impl Rollback {
     /// Set the rollback state back to non-rollback
     pub(crate) fn set_non_rollback(&self) {
         *self.state.write().deref_mut() = RollbackState::Default;
+        self.rollback_groups.write().clear();
     }
 
     /// Set the rollback state to `ShouldRollback` with the given tick
     pub(crate) fn set_rollback_tick(&self, tick: Tick) {
         *self.state.write().deref_mut() = RollbackState::ShouldRollback { current_tick: tick };
     }
+
+    /// Mark the replication group as needing a rollback starting from `tick`.
+    ///
+    /// If multiple groups need a rollback, we start the rollback from the earliest tick.
+    pub(crate) fn add_rollback_group(&self, group: ReplicationGroupId, tick: Tick) {
+        self.rollback_groups.write().insert(group);
+        let mut state = self.state.write();
+        match state.deref_mut() {
+            RollbackState::ShouldRollback { current_tick } => {
+                if tick < *current_tick {
+                    *current_tick = tick;
+                }
+            }
+            RollbackState::Default => {
+                *state = RollbackState::ShouldRollback { current_tick: tick };
+            }
+        }
+    }
+
+    /// Returns true if the replication group is already marked for rollback
+    pub fn is_group_rollback(&self, group: ReplicationGroupId) -> bool {
+        self.rollback_groups.read().contains(&group)
+    }
+
+    /// Returns true if the predicted entity is rolled back during the current rollback
+    pub fn is_entity_rollback(&self, entity: Entity) -> bool {
+        match &self.rollback_entities {
+            Some(entities) => entities.contains(&entity),
+            None => true,
+        }
+    }
+
+    /// Returns true if we are running a partial rollback (see [`RollbackMode::PerGroup`]) and the predicted
+    /// entity is not part of it.
+    ///
+    /// The `FixedMain` schedule is re-run during rollback; your systems that simulate predicted entities should
+    /// skip these entities, otherwise they would be simulated more than once for the same tick.
+    pub fn is_disabled_during_rollback(&self, entity: Entity) -> bool {
+        self.is_rollback() && !self.is_entity_rollback(entity)
+    }
+
+    /// Declare that the entities of the replication groups `a` and `b` interact with each other.
+    ///
+    /// With [`RollbackMode::PerGroup`], a rollback of one of the groups will also roll back the other group
+    /// (and transitively all the groups linked to it).
+    pub fn link_groups(&mut self, a: ReplicationGroupId, b: ReplicationGroupId) {
+        self.group_links.entry(a).or_default().insert(b);
+        self.group_links.entry(b).or_default().insert(a);
+    }
+
+    /// Remove the link between the replication groups `a` and `b`
+    pub fn unlink_groups(&mut self, a: ReplicationGroupId, b: ReplicationGroupId) {
+        for (group, other) in [(a, b), (b, a)] {
+            if let Some(links) = self.group_links.get_mut(&group) {
+                links.remove(&other);
+                if links.is_empty() {
+                    self.group_links.remove(&group);
+                }
+            }
+        }
+    }
+
+    /// Remove all the links of the replication group (for example when the group is despawned)
+    pub fn clear_group_links(&mut self, group: ReplicationGroupId) {
+        if let Some(links) = self.group_links.remove(&group) {
+            for other in links {
+                self.unlink_groups(group, other);
+            }
+        }
+    }
+
+    /// Iterate through the groups that were directly linked to `group` with [`Rollback::link_groups`]
+    pub fn linked_groups(
+        &self,
+        group: ReplicationGroupId,
+    ) -> impl Iterator<Item = ReplicationGroupId> + '_ {
+        self.group_links.get(&group).into_iter().flatten().copied()
+    }
+
+    /// Add all the groups that are transitively linked to the groups that need a rollback
+    fn expand_rollback_groups(&mut self) {
+        let rollback_groups = self.rollback_groups.get_mut();
+        let mut stack: Vec<ReplicationGroupId> = rollback_groups.iter().copied().collect();
+        while let Some(group) = stack.pop() {
+            for other in self.group_links.get(&group).into_iter().flatten() {
+                if rollback_groups.insert(*other) {
+                    stack.push(*other);
+                }
+            }
+        }
+    }
+}
+
+/// A [`SystemParam`] that behaves like a [`Query`], but skips the predicted entities that are not part of the
+/// current rollback (see [`RollbackMode::PerGroup`]).
+///
+/// Use it instead of a [`Query`] in the `FixedMain` systems that simulate predicted entities, so that the entities
+/// that are not rolled back are not simulated twice for the same tick. Outside of rollback, no entity is skipped.
+#[derive(SystemParam)]
+pub struct RollbackQuery<'w, 's, D: QueryData + 'static, F: QueryFilter + 'static> {
+    rollback: Res<'w, Rollback>,
+    query: Query<'w, 's, (Entity, D), F>,
+}
+
+impl<'s, D: QueryData, F: QueryFilter> RollbackQuery<'_, 's, D, F> {
+    /// Returns the read-only query item for `entity`, or None if the entity doesn't match the query
+    /// or is not part of the current rollback
+    pub fn get(&self, entity: Entity) -> Option<ROQueryItem<'_, D>> {
+        if self.rollback.is_disabled_during_rollback(entity) {
+            return None;
+        }
+        self.query.get(entity).ok().map(|(_, item)| item)
+    }
+
+    /// Returns the query item for `entity`, or None if the entity doesn't match the query
+    /// or is not part of the current rollback
+    pub fn get_mut(&mut self, entity: Entity) -> Option<QueryItem<'_, D>> {
+        if self.rollback.is_disabled_during_rollback(entity) {
+            return None;
+        }
+        self.query.get_mut(entity).ok().map(|(_, item)| item)
+    }
+
+    /// Iterate through the read-only query items of the entities that are part of the current rollback
+    pub fn iter(&self) -> impl Iterator<Item = ROQueryItem<'_, D>> + use<'_, 's, D, F> {
+        let rollback: &Rollback = &self.rollback;
+        self.query
+            .iter()
+            .filter(move |(entity, _)| !rollback.is_disabled_during_rollback(*entity))
+            .map(|(_, item)| item)
+    }
+
+    /// Iterate through the query items of the entities that are part of the current rollback
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = QueryItem<'_, D>> + use<'_, 's, D, F> {
+        let rollback: &Rollback = &self.rollback;
+        self.query
+            .iter_mut()
+            .filter(move |(entity, _)| !rollback.is_disabled_during_rollback(*entity))
+            .map(|(_, item)| item)
+    }
+}
+
+/// Returns the replication group of a confirmed entity.
+///
+/// Entities for which we don't know the group are considered to be in their own group.
+fn confirmed_group(connection: &ConnectionManager, confirmed: Entity) -> ReplicationGroupId {
+    connection
+        .replication_receiver
+        .local_entity_to_group
+        .get(&confirmed)
+        .copied()
+        .unwrap_or(ReplicationGroupId(confirmed.to_bits()))
+}
+
+/// With [`RollbackMode::PerGroup`], compute the list of predicted entities that will be rolled back:
+/// - the predicted entities of the groups that had a mismatch, or that are linked to those groups
+/// - all the pre-spawned entities, since they are not part of any replication group yet
+pub(crate) fn prepare_rollback_entities(
+    config: Res<ClientConfig>,
+    connection: Res<ConnectionManager>,
+    mut rollback: ResMut<Rollback>,
+    confirmed_query: Query<(Entity, &Confirmed)>,
+    prespawned_query: Query<Entity, With<PreSpawnedPlayerObject>>,
+) {
+    if config.prediction.rollback_mode == RollbackMode::Global {
+        rollback.rollback_entities = None;
+        return;
+    }
+    rollback.expand_rollback_groups();
+    let mut entities: EntityHashSet = prespawned_query.iter().collect();
+    for (confirmed_entity, confirmed) in confirmed_query.iter() {
+        let Some(predicted) = confirmed.predicted else {
+            continue;
+        };
+        if rollback.is_group_rollback(confirmed_group(&connection, confirmed_entity)) {
+            entities.insert(predicted);
+        }
+    }
+    debug!(
+        num_entities = entities.len(),
+        "Preparing rollback for a subset of the predicted entities"
+    );
+    rollback.rollback_entities = Some(entities);
 }
 
 /// Check if we need to do a rollback.
@@ -112,6 +314,7 @@ pub(crate) fn check_rollback<C: SyncComponent>(
     // We use Option<> because the predicted component could have been removed while it still exists in Confirmed
     confirmed_query: Query<(Entity, Option<&C>, Ref<Confirmed>)>,
     rollback: Res<Rollback>,
+    config: Res<ClientConfig>,
 ) {
     // TODO: can just enable bevy spans?
     let _span = trace_span!("client rollback check");
@@ -126,6 +329,7 @@ pub(crate) fn check_rollback<C: SyncComponent>(
     }
 
     let current_tick = tick_manager.tick();
+    let per_group = config.prediction.rollback_mode == RollbackMode::PerGroup;
     for (confirmed_entity, confirmed_component, confirmed) in confirmed_query.iter() {
         // NOTE: it is not enough to check if we received any ComponentRemoveEvent<C>, ComponentUpdateEvent<C> and ComponentInsertEvent<C>
         //  because we could have entity A and B in the same ReplicationGroup.
@@ -178,9 +382,16 @@ pub(crate) fn check_rollback<C: SyncComponent>(
             continue;
         }
 
+        // with per-group rollbacks, we only need to know if this entity's group should be rolled back
+        let group = per_group.then(|| confirmed_group(&connection, confirmed_entity));
+        let already_rollback = match group {
+            Some(group) => rollback.is_group_rollback(group),
+            None => rollback.is_rollback(),
+        };
+
         // 3.a We are still not sure if we should do rollback. Compare history against confirmed
         // We rollback if there's no history (newly added predicted entity, or if there is a mismatch)
-        if !rollback.is_rollback() {
+        if !already_rollback {
             // info!(
             //     ?tick,
             //     ?current_tick,
@@ -252,7 +463,10 @@ pub(crate) fn check_rollback<C: SyncComponent>(
                 // in `prepare_rollback`, we will reset the state to what the server sends us for the confirmed.tick
                 // The server sends the packet in PostUpdate after the confirmed.tick is done.
                 // When we do rollback we need to start reading inputs from the tick after that!
-                rollback.set_rollback_tick(tick + 1);
+                match group {
+                    Some(group) => rollback.add_rollback_group(group, tick + 1),
+                    None => rollback.set_rollback_tick(tick + 1),
+                }
             }
         } else {
             // 3.b We already know we should do rollback (because of another entity/component), start the rollback
@@ -312,6 +526,9 @@ pub(crate) fn prepare_rollback<C: SyncComponent>(
         let Some(predicted_entity) = confirmed.predicted else {
             continue;
         };
+        if !rollback.is_entity_rollback(predicted_entity) {
+            continue;
+        }
 
         // 1. Get the predicted entity, and its history
         let Ok((predicted_component, mut predicted_history, mut correction)) =
@@ -540,6 +757,9 @@ pub(crate) fn prepare_rollback_non_networked<C: Component + PartialEq + Clone>(
     // 0. If the entity didn't exist at the rollback tick, despawn it
     // TODO? or is it handled for us?
     for (entity, component, mut history) in predicted_query.iter_mut() {
+        if !rollback.is_entity_rollback(entity) {
+            continue;
+        }
         // 1. restore the component to the historical value
         match history.pop_until_tick(rollback_tick) {
             None | Some(HistoryState::Removed) => {
@@ -735,8 +955,9 @@ pub(crate) fn run_rollback(world: &mut World) {
     metrics.rollback_ticks += num_rollback_ticks as u32;
 
     // revert the state of Rollback for the next frame
-    let rollback = world.get_resource_mut::<Rollback>().unwrap();
+    let mut rollback = world.get_resource_mut::<Rollback>().unwrap();
     rollback.set_non_rollback();
+    rollback.rollback_entities = None;
 }
 
 #[cfg(feature = "metrics")]
@@ -941,6 +1162,142 @@ mod unit_tests {
             .resource::<Rollback>()
             .is_rollback());
     }
+
+    #[test]
+    fn test_link_groups() {
+        let mut rollback = Rollback::default();
+        let (a, b, c, d) = (
+            ReplicationGroupId(0),
+            ReplicationGroupId(1),
+            ReplicationGroupId(2),
+            ReplicationGroupId(3),
+        );
+        rollback.link_groups(a, b);
+        rollback.link_groups(b, c);
+        rollback.add_rollback_group(a, Tick(5));
+        rollback.add_rollback_group(d, Tick(3));
+        assert_eq!(rollback.get_rollback_tick(), Some(Tick(3)));
+
+        // groups are transitively linked
+        rollback.expand_rollback_groups();
+        assert!([a, b, c, d]
+            .iter()
+            .all(|group| rollback.is_group_rollback(*group)));
+
+        rollback.set_non_rollback();
+        rollback.clear_group_links(b);
+        assert_eq!(rollback.linked_groups(a).count(), 0);
+        assert_eq!(rollback.linked_groups(c).count(), 0);
+        rollback.add_rollback_group(a, Tick(5));
+        rollback.expand_rollback_groups();
+        assert!(!rollback.is_group_rollback(b));
+    }
+
+    /// Check that with RollbackMode::PerGroup, only the entities of the mismatching group
+    /// (and of the groups linked to it) are rolled back
+    #[test]
+    fn test_rollback_per_group() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConfig>()
+            .prediction
+            .rollback_mode = RollbackMode::PerGroup;
+
+        // add two pairs of predicted/confirmed entities, in different replication groups
+        let tick = stepper.client_tick();
+        let spawn_pair = |stepper: &mut BevyStepper| {
+            let confirmed = stepper
+                .client_app
+                .world_mut()
+                .spawn((
+                    Confirmed {
+                        tick,
+                        ..Default::default()
+                    },
+                    ComponentSyncModeFull(1.0),
+                ))
+                .id();
+            let predicted = stepper
+                .client_app
+                .world_mut()
+                .spawn(Predicted {
+                    confirmed_entity: Some(confirmed),
+                })
+                .id();
+            stepper
+                .client_app
+                .world_mut()
+                .entity_mut(confirmed)
+                .get_mut::<Confirmed>()
+                .unwrap()
+                .predicted = Some(predicted);
+            (confirmed, predicted)
+        };
+        let (confirmed_a, predicted_a) = spawn_pair(&mut stepper);
+        let (confirmed_b, predicted_b) = spawn_pair(&mut stepper);
+        stepper.frame_step();
+
+        let check = |stepper: &mut BevyStepper| {
+            let tick = stepper.client_tick();
+            stepper
+                .client_app
+                .world_mut()
+                .entity_mut(confirmed_a)
+                .get_mut::<ComponentSyncModeFull>()
+                .unwrap()
+                .0 += 1.0;
+            received_confirmed_update(stepper, confirmed_a, tick);
+            received_confirmed_update(stepper, confirmed_b, tick);
+            let _ = stepper
+                .client_app
+                .world_mut()
+                .run_system_once(check_rollback::<ComponentSyncModeFull>);
+            let _ = stepper
+                .client_app
+                .world_mut()
+                .run_system_once(prepare_rollback_entities);
+            let rollback = stepper.client_app.world().resource::<Rollback>();
+            assert_eq!(rollback.get_rollback_tick(), Some(tick + 1));
+            (
+                rollback.is_entity_rollback(predicted_a),
+                rollback.is_entity_rollback(predicted_b),
+            )
+        };
+
+        // 1. only the group of entity A is rolled back
+        assert_eq!(check(&mut stepper), (true, false));
+        let rollback = stepper.client_app.world().resource::<Rollback>();
+        assert!(!rollback.is_disabled_during_rollback(predicted_a));
+        assert!(rollback.is_disabled_during_rollback(predicted_b));
+        // systems using a RollbackQuery don't re-simulate the entities outside the rolled back group
+        let simulated = stepper
+            .client_app
+            .world_mut()
+            .run_system_once(|query: RollbackQuery<Entity, With<Predicted>>| {
+                query.iter().collect::<Vec<_>>()
+            })
+            .unwrap();
+        assert_eq!(simulated, vec![predicted_a]);
+
+        // 2. the groups are linked: both are rolled back
+        stepper
+            .client_app
+            .world()
+            .resource::<Rollback>()
+            .set_non_rollback();
+        // entities without a known replication group are in their own group
+        stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<Rollback>()
+            .link_groups(
+                ReplicationGroupId(confirmed_a.to_bits()),
+                ReplicationGroupId(confirmed_b.to_bits()),
+            );
+        assert_eq!(check(&mut stepper), (true, true));
+    }
 }
 
 /// More general integration tests for rollback
@@ -597,12 +597,7 @@ pub(crate) mod send {
                     .prepare_component_insert(entity, group_id, raw_data);
             } else {
                 trace!(?entity, "send update");
-                let send_tick = sender
-                    .replication_sender
-                    .group_channels
-                    .entry(group_id)
-                    .or_default()
-                    .send_tick;
+                let send_tick = sender.replication_sender.get_changes_since_tick(group_id);
 
                 // send the update for all changes newer than the last send bevy tick for the group
                 if send_tick.map_or(true, |c| {
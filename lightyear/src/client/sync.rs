@@ -98,6 +98,10 @@ pub struct SyncManager {
     server_time_estimate: WrappedTime,
     pub(crate) interpolation_time: WrappedTime,
     interpolation_speed_ratio: f32,
+    /// How far behind the server time estimate the interpolation time should be
+    interpolation_objective_delay: Duration,
+    /// Estimate of the jitter of the arrival times of server packets (RFC 3550 interarrival jitter)
+    snapshot_jitter: Duration,
 
     // ticks
     /// Number of input delay ticks to apply.
@@ -133,6 +137,8 @@ impl SyncManager {
             server_time_estimate: WrappedTime::default(),
             interpolation_time: WrappedTime::default(),
             interpolation_speed_ratio: 1.0,
+            interpolation_objective_delay: Duration::default(),
+            snapshot_jitter: Duration::default(),
             // server tick
             current_input_delay: 0,
             latest_received_server_tick: None,
@@ -151,6 +157,7 @@ impl SyncManager {
         time_manager: &mut TimeManager,
         tick_manager: &mut TickManager,
         ping_manager: &PingManager,
+        packet_loss: f32,
         prediction_config: &PredictionConfig,
         interpolation_delay: &InterpolationConfig,
         server_send_interval: Duration,
@@ -164,11 +171,13 @@ impl SyncManager {
         // check if we are ready to finalize the handshake
         if !self.synced && ping_manager.sync_stats.len() >= self.config.handshake_pings as usize {
             self.synced = true;
-            self.interpolation_time = self.interpolation_objective(
+            self.interpolation_objective_delay = self.interpolation_target_delay(
                 interpolation_delay,
                 server_send_interval,
-                tick_manager,
+                ping_manager,
+                packet_loss,
             );
+            self.interpolation_time = self.interpolation_objective();
             debug!(
                 interpolation_tick = ?self.interpolation_tick(tick_manager),
                 "Client is synced!"
@@ -177,7 +186,14 @@ impl SyncManager {
         }
 
         if self.synced {
-            self.update_interpolation_time(interpolation_delay, server_send_interval, tick_manager);
+            self.update_interpolation_objective_delay(
+                interpolation_delay,
+                server_send_interval,
+                ping_manager,
+                packet_loss,
+                time_manager.delta(),
+            );
+            self.update_interpolation_time(tick_manager);
         }
         None
     }
@@ -333,26 +349,93 @@ impl SyncManager {
         )
     }
 
-    pub(crate) fn interpolation_objective(
+    /// Every time we receive a new server tick, update our estimate of the jitter of the snapshot arrival times.
+    ///
+    /// This must be called before `latest_received_server_tick` and `duration_since_latest_received_server_tick`
+    /// are updated.
+    pub(crate) fn update_snapshot_jitter(&mut self, new_tick: Tick, tick_duration: Duration) {
+        let Some(latest_tick) = self.latest_received_server_tick else {
+            return;
+        };
+        // difference between the time elapsed between the two arrivals, and the time elapsed between
+        // the two server ticks
+        let expected = (new_tick - latest_tick) as f32 * tick_duration.as_secs_f32();
+        let deviation = (self
+            .duration_since_latest_received_server_tick
+            .as_secs_f32()
+            - expected)
+            .abs();
+        let jitter = self.snapshot_jitter.as_secs_f32();
+        self.snapshot_jitter = Duration::from_secs_f32(jitter + (deviation - jitter) / 16.0);
+    }
+
+    /// Jitter of the arrival times of server packets
+    pub fn snapshot_jitter(&self) -> Duration {
+        self.snapshot_jitter
+    }
+
+    /// How far behind the server time estimate the interpolation time is aiming to be
+    pub fn interpolation_objective_delay(&self) -> Duration {
+        self.interpolation_objective_delay
+    }
+
+    /// The interpolation delay that we want to reach, given the current network conditions
+    fn interpolation_target_delay(
         &self,
-        // TODO: make interpolation delay part of SyncConfig?
         interpolation_delay: &InterpolationConfig,
-        // TODO: should we get this via an estimate?
         server_send_interval: Duration,
-        tick_manager: &TickManager,
-    ) -> WrappedTime {
-        // // TODO: maybe integrate because of jitter?
-        // let objective_time = WrappedTime::from_duration(
-        //     self.latest_received_server_tick.0 as u32 * tick_manager.config.tick_duration
-        //         + self.duration_since_latest_received_server_tick,
-        // );
-        // let objective_time = self.server_time_estimate();
+        ping_manager: &PingManager,
+        packet_loss: f32,
+    ) -> Duration {
+        match interpolation_delay.adaptive_delay {
+            Some(adaptive) => {
+                // the ping jitter accounts for the variance in latency, the snapshot jitter also accounts
+                // for the variance in when the server sends packets
+                let jitter = std::cmp::max(self.snapshot_jitter, ping_manager.jitter());
+                adaptive.target_delay(server_send_interval, jitter, packet_loss)
+            }
+            None => interpolation_delay.to_duration(server_send_interval),
+        }
+    }
+
+    /// Move the interpolation delay towards the target delay
+    fn update_interpolation_objective_delay(
+        &mut self,
+        interpolation_delay: &InterpolationConfig,
+        server_send_interval: Duration,
+        ping_manager: &PingManager,
+        packet_loss: f32,
+        delta: Duration,
+    ) {
+        let target = self.interpolation_target_delay(
+            interpolation_delay,
+            server_send_interval,
+            ping_manager,
+            packet_loss,
+        );
+        let smoothing = interpolation_delay
+            .adaptive_delay
+            .map_or(Duration::ZERO, |adaptive| adaptive.smoothing);
+        if smoothing.is_zero() {
+            self.interpolation_objective_delay = target;
+            return;
+        }
+        // exponential smoothing that doesn't depend on the frame rate
+        let alpha = 1.0 - (-delta.as_secs_f32() / smoothing.as_secs_f32()).exp();
+        let current = self.interpolation_objective_delay.as_secs_f32();
+        self.interpolation_objective_delay =
+            Duration::from_secs_f32(current + (target.as_secs_f32() - current) * alpha);
+        trace!(
+            ?target,
+            delay = ?self.interpolation_objective_delay,
+            "updated interpolation delay"
+        );
+    }
+
+    pub(crate) fn interpolation_objective(&self) -> WrappedTime {
         // how much we want interpolation time to be behind the latest received server tick?
-        // TODO: use a specified config margin + add std of time_between_server_updates?
         let objective_delta =
-            chrono::Duration::from_std(interpolation_delay.to_duration(server_send_interval))
-                .unwrap();
-        // info!("objective_delta: {:?}", objective_delta);
+            chrono::Duration::from_std(self.interpolation_objective_delay).unwrap();
         self.server_time_estimate() - objective_delta
     }
 
@@ -385,18 +468,10 @@ impl SyncManager {
 
     // TODO: only run when there's a change? (new server tick received or new ping received)
     // TODO: change name to make it clear that we might modify speed
-    pub(crate) fn update_interpolation_time(
-        &mut self,
-        // TODO: make interpolation delay part of SyncConfig?
-        interpolation_delay: &InterpolationConfig,
-        // TODO: should we get this via an estimate?
-        server_update_rate: Duration,
-        tick_manager: &TickManager,
-    ) {
+    pub(crate) fn update_interpolation_time(&mut self, tick_manager: &TickManager) {
         // for interpolation time, we don't need to use ticks (because we only need interpolation at the end
         // of the frame, not during the FixedUpdate schedule)
-        let objective_time =
-            self.interpolation_objective(interpolation_delay, server_update_rate, tick_manager);
+        let objective_time = self.interpolation_objective();
         let delta = objective_time - self.interpolation_time;
         trace!(
             ?objective_time,
@@ -21,6 +21,7 @@ use crate::prelude::client::ClientTransport;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::prelude::LinkConditionerConfig;
 use crate::prelude::{generate_key, Key};
+use crate::protocol::fingerprint::ProtocolFingerprint;
 use crate::transport::config::SharedIoConfig;
 
 #[derive(Debug)]
@@ -124,7 +125,11 @@ impl Default for NetConfig {
 }
 
 impl NetConfig {
-    pub fn build_client(self) -> ClientConnection {
+    /// Build the [`ClientConnection`].
+    ///
+    /// The `protocol_fingerprint` is sent to the server during the handshake (only for netcode connections),
+    /// so that the server can deny clients that use a different protocol.
+    pub fn build_client(self, protocol_fingerprint: ProtocolFingerprint) -> ClientConnection {
         match self {
             NetConfig::Netcode {
                 auth,
@@ -135,8 +140,21 @@ impl NetConfig {
                     .get_token(config.client_timeout_secs, config.token_expire_secs)
                     .expect("could not generate token");
                 let token_bytes = token.try_into_bytes().unwrap();
+                let protocol_fingerprint =
+                    protocol_fingerprint.with_compression(&io_config.compression);
+                let netcode_config = config.build().protocol_fingerprint(protocol_fingerprint.0);
+                // the payloads are compressed with the dictionary before being encrypted
+                #[cfg(feature = "zstd")]
+                let mut io_config = io_config;
+                #[cfg(feature = "zstd")]
+                let netcode_config = match io_config.compression.take_dictionary() {
+                    Some((level, dictionary)) => {
+                        netcode_config.payload_dictionary(level, dictionary)
+                    }
+                    None => netcode_config,
+                };
                 let netcode =
-                    super::netcode::NetcodeClient::with_config(&token_bytes, config.build())
+                    super::netcode::NetcodeClient::with_config(&token_bytes, netcode_config)
                         .expect("could not create netcode client");
                 let client = super::netcode::Client {
                     client: netcode,
@@ -236,6 +254,8 @@ impl NetClient for ClientConnection {
 ///   The server must use the same `protocol_id` and `private_key` as the game servers.
 ///   The backend server could be a dedicated webserver; or the game server itself, if it has a way to
 ///   establish secure connection.
+/// - the client can request a `ConnectToken` from a [`TokenService`](crate::connection::netcode::TokenService),
+///   with `Authentication::RequestToken`.
 /// - when testing, it can be convenient for the client to create its own `ConnectToken` manually.
 ///   You can use `Authentication::Manual` for those cases.
 pub enum Authentication {
@@ -255,6 +275,17 @@ pub enum Authentication {
         private_key: Key,
         protocol_id: u64,
     },
+    /// The client requests a `ConnectToken` from a [`TokenService`](crate::connection::netcode::TokenService)
+    /// listening on `addr` every time it connects, by sending its `credentials`.
+    ///
+    /// The request runs in a task on the [`IoTaskPool`](bevy::tasks::IoTaskPool); the client stays in the
+    /// [`Connecting`](crate::prelude::client::NetworkingState::Connecting) state until the token is received.
+    /// If the request fails, the client is disconnected with [`ConnectionError::TokenRequest`].
+    #[cfg(not(target_family = "wasm"))]
+    RequestToken {
+        addr: SocketAddr,
+        credentials: Vec<u8>,
+    },
     #[default]
     /// The client has no `ConnectToken`, so it cannot connect to the game server yet.
     ///
@@ -287,20 +318,25 @@ impl Authentication {
                 .expire_seconds(token_expire_secs)
                 .generate()
                 .ok(),
-            Authentication::None => {
-                // create a fake connect token so that we can build a NetcodeClient
-                ConnectToken::build(
-                    SocketAddr::from_str("0.0.0.0:0").unwrap(),
-                    0,
-                    0,
-                    generate_key(),
-                )
-                .timeout_seconds(client_timeout_secs)
-                .generate()
-                .ok()
-            }
+            // the token is only requested when the client connects
+            #[cfg(not(target_family = "wasm"))]
+            Authentication::RequestToken { .. } => Self::placeholder_token(client_timeout_secs),
+            Authentication::None => Self::placeholder_token(client_timeout_secs),
         }
     }
+
+    /// Create a fake connect token so that we can build a NetcodeClient
+    fn placeholder_token(client_timeout_secs: i32) -> Option<ConnectToken> {
+        ConnectToken::build(
+            SocketAddr::from_str("0.0.0.0:0").unwrap(),
+            0,
+            0,
+            generate_key(),
+        )
+        .timeout_seconds(client_timeout_secs)
+        .generate()
+        .ok()
+    }
 }
 
 impl std::fmt::Debug for Authentication {
@@ -319,6 +355,12 @@ impl std::fmt::Debug for Authentication {
                 .field("private_key", private_key)
                 .field("protocol_id", protocol_id)
                 .finish(),
+            #[cfg(not(target_family = "wasm"))]
+            Authentication::RequestToken { addr, .. } => f
+                .debug_struct("RequestToken")
+                .field("addr", addr)
+                .field("credentials", &"<credentials>")
+                .finish(),
             Authentication::None => write!(f, "None"),
         }
     }
@@ -339,6 +381,11 @@ pub enum ConnectionError {
     Netcode(#[from] super::netcode::error::Error),
     #[error("netcode state: {0:?}")]
     NetcodeState(super::netcode::ClientState),
+    #[error("connection denied by the server: {0:?}")]
+    Denied(super::server::DeniedReason),
+    #[error("could not get a connect token: {0}")]
+    #[cfg(not(target_family = "wasm"))]
+    TokenRequest(#[from] super::netcode::TokenServiceError),
     #[error(transparent)]
     #[cfg(all(feature = "steam", not(target_family = "wasm")))]
     SteamInvalidHandle(#[from] steamworks::networking_sockets::InvalidHandle),
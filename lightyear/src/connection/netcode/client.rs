@@ -6,8 +6,11 @@ use tracing::{debug, error, info, trace};
 use crate::client::io::Io;
 use crate::connection::client::{ConnectionError, ConnectionState, IoConfig, NetClient};
 use crate::connection::id;
+use crate::connection::server::DeniedReason;
 use crate::packet::packet_builder::RecvPayload;
 use crate::transport::io::IoState;
+#[cfg(feature = "zstd")]
+use crate::transport::middleware::compression::zstd::{ZstdDictionary, ZstdPayloadCompression};
 use crate::transport::{PacketReceiver, PacketSender, LOCAL_SOCKET};
 use crate::utils::pool::Pool;
 
@@ -56,6 +59,9 @@ pub struct ClientConfig<Ctx> {
     packet_send_rate: f64,
     context: Ctx,
     on_state_change: Option<Callback<Ctx>>,
+    protocol_fingerprint: u64,
+    #[cfg(feature = "zstd")]
+    payload_dictionary: Option<(i32, ZstdDictionary)>,
 }
 
 impl Default for ClientConfig<()> {
@@ -65,6 +71,9 @@ impl Default for ClientConfig<()> {
             packet_send_rate: PACKET_SEND_RATE_SEC,
             context: (),
             on_state_change: None,
+            protocol_fingerprint: 0,
+            #[cfg(feature = "zstd")]
+            payload_dictionary: None,
         }
     }
 }
@@ -81,6 +90,9 @@ impl<Ctx> ClientConfig<Ctx> {
             packet_send_rate: PACKET_SEND_RATE_SEC,
             context: ctx,
             on_state_change: None,
+            protocol_fingerprint: 0,
+            #[cfg(feature = "zstd")]
+            payload_dictionary: None,
         }
     }
     /// Set the number of redundant disconnect packets that will be sent to a server when the clients wants to disconnect.
@@ -95,6 +107,18 @@ impl<Ctx> ClientConfig<Ctx> {
         self.packet_send_rate = rate_seconds;
         self
     }
+    /// Set the fingerprint of the client's protocol, which is sent to the server in the connection request.
+    pub fn protocol_fingerprint(mut self, protocol_fingerprint: u64) -> Self {
+        self.protocol_fingerprint = protocol_fingerprint;
+        self
+    }
+    /// Compress the payload of the packets with a zstd dictionary before encrypting them.
+    /// The server must use the same dictionary.
+    #[cfg(feature = "zstd")]
+    pub fn payload_dictionary(mut self, level: i32, dictionary: ZstdDictionary) -> Self {
+        self.payload_dictionary = Some((level, dictionary));
+        self
+    }
     /// Set a callback that will be called when the client changes states.
     pub fn on_state_change<F>(mut self, cb: F) -> Self
     where
@@ -189,8 +213,12 @@ pub struct NetcodeClient<Ctx = ()> {
     replay_protection: ReplayProtection,
     should_disconnect: bool,
     should_disconnect_state: ClientState,
+    /// Reason sent by the server when it denied the connection request
+    denied_reason: Option<DeniedReason>,
     packet_queue: VecDeque<RecvPayload>,
     buffer_pool: Pool<Vec<u8>>,
+    #[cfg(feature = "zstd")]
+    payload_compression: Option<ZstdPayloadCompression>,
     cfg: ClientConfig<Ctx>,
 }
 
@@ -209,6 +237,12 @@ impl<Ctx> NetcodeClient<Ctx> {
                 return Err(Error::InvalidToken(err));
             }
         };
+        #[cfg(feature = "zstd")]
+        let payload_compression = cfg
+            .payload_dictionary
+            .as_ref()
+            .map(|(level, dictionary)| ZstdPayloadCompression::new(*level, dictionary))
+            .transpose()?;
         Ok(Self {
             id: 0,
             state: ClientState::Disconnected,
@@ -224,8 +258,11 @@ impl<Ctx> NetcodeClient<Ctx> {
             replay_protection: ReplayProtection::new(),
             should_disconnect: false,
             should_disconnect_state: ClientState::Disconnected,
+            denied_reason: None,
             packet_queue: VecDeque::new(),
             buffer_pool: Pool::new(10, || vec![0u8; MAX_PKT_BUF_SIZE]),
+            #[cfg(feature = "zstd")]
+            payload_compression,
             cfg,
         })
     }
@@ -325,6 +362,7 @@ impl<Ctx> NetcodeClient<Ctx> {
                     self.token.expire_timestamp,
                     self.token.nonce,
                     self.token.private_data,
+                    self.cfg.protocol_fingerprint,
                 )
             }
             ClientState::SendingChallengeResponse => {
@@ -363,6 +401,14 @@ impl<Ctx> NetcodeClient<Ctx> {
         Ok(())
     }
 
+    fn read_payload(&mut self, payload: &[u8]) -> Result<RecvPayload> {
+        #[cfg(feature = "zstd")]
+        if let Some(compression) = self.payload_compression.as_mut() {
+            return Ok(compression.decompress(payload)?);
+        }
+        Ok(bytes::Bytes::copy_from_slice(payload))
+    }
+
     pub fn server_addr(&self) -> SocketAddr {
         self.token.server_addresses[self.server_addr_idx]
     }
@@ -382,6 +428,7 @@ impl<Ctx> NetcodeClient<Ctx> {
                 );
                 self.should_disconnect = true;
                 self.should_disconnect_state = ClientState::ConnectionDenied;
+                self.denied_reason = Some(pkt.reason);
             }
             (Packet::Challenge(pkt), ClientState::SendingConnectionRequest) => {
                 debug!("client received connection challenge packet from server");
@@ -417,7 +464,7 @@ impl<Ctx> NetcodeClient<Ctx> {
                 // let buf = self.buffer_pool.pull(|| vec![0u8; pkt.buf.len()]);
                 // TODO: COPY THE PAYLOAD INTO A BUFFER FROM THE POOL! and we allocate buffers to the pool
                 //  outside of the hotpath? we could have a static pool of buffers?
-                let buf = bytes::Bytes::copy_from_slice(pkt.buf);
+                let buf = self.read_payload(pkt.buf)?;
                 // TODO: control the size/memory of the packet queue?
                 self.packet_queue.push_back(buf);
             }
@@ -524,6 +571,7 @@ impl<Ctx> NetcodeClient<Ctx> {
     /// This function does not perform any IO, it only readies the client to send/receive packets on the next call to [`update`](NetcodeClient::update). <br>
     pub fn connect(&mut self) {
         self.reset_connection();
+        self.denied_reason = None;
         self.set_state(ClientState::SendingConnectionRequest);
         info!(
             "client connecting to server {} [{}/{}]",
@@ -608,6 +656,12 @@ impl<Ctx> NetcodeClient<Ctx> {
         if buf.len() > MAX_PACKET_SIZE {
             return Err(Error::SizeMismatch(MAX_PACKET_SIZE, buf.len()));
         }
+        #[cfg(feature = "zstd")]
+        if let Some(compression) = self.payload_compression.as_mut() {
+            let mut compressed = [0u8; MAX_PKT_BUF_SIZE];
+            let size = compression.compress(buf, &mut compressed)?;
+            return self.send_packet(PayloadPacket::create(&compressed[..size]), io);
+        }
         self.send_packet(PayloadPacket::create(buf), io)?;
         Ok(())
     }
@@ -632,6 +686,10 @@ impl<Ctx> NetcodeClient<Ctx> {
     pub fn state(&self) -> ClientState {
         self.state
     }
+    /// The reason sent by the server if it denied our last connection request
+    pub fn denied_reason(&self) -> Option<&DeniedReason> {
+        self.denied_reason.as_ref()
+    }
     /// Returns true if the client is in an error state.
     pub fn is_error(&self) -> bool {
         self.state < ClientState::Disconnected
@@ -691,6 +749,12 @@ pub(crate) mod connection {
                     ConnectionState::Connecting
                 }
                 ClientState::Connected => ConnectionState::Connected,
+                ClientState::ConnectionDenied => ConnectionState::Disconnected {
+                    reason: Some(match self.client.denied_reason() {
+                        Some(reason) => ConnectionError::Denied(reason.clone()),
+                        None => ConnectionError::NetcodeState(self.client.state),
+                    }),
+                },
                 _ => ConnectionState::Disconnected {
                     reason: Some(ConnectionError::NetcodeState(self.client.state)),
                 },
@@ -39,6 +39,8 @@ pub enum Error {
     ServerIsFull(ClientId),
     #[error("client_id {0} handle_connection_request_fn returned false")]
     Denied(ClientId),
+    #[error("client_id {0} tried to connect with a protocol that is different from the server's")]
+    ProtocolMismatch(ClientId),
     #[error("client_id {0} server ignored non-connection-request packet")]
     Ignored(SocketAddr),
     #[error("clock went backwards (did you invent a time machine?): {0}")]
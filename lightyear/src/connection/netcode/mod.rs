@@ -126,15 +126,22 @@ pub use crypto::{generate_key, try_generate_key, Key};
 pub use error::{Error, Result};
 pub use server::{connection::Server, Callback, ClientId, NetcodeServer, ServerConfig};
 pub use token::{ConnectToken, ConnectTokenBuilder, InvalidTokenError};
+#[cfg(not(target_family = "wasm"))]
+pub use token_service::{
+    request_token, request_token_blocking, AcceptAll, CredentialValidator, IssuedClientIds,
+    TokenGrant, TokenService, TokenServiceError,
+};
 
 mod bytes;
 mod client;
 mod crypto;
 pub(crate) mod error;
-mod packet;
+pub(crate) mod packet;
 mod replay;
 mod server;
 mod token;
+#[cfg(not(target_family = "wasm"))]
+mod token_service;
 mod utils;
 
 pub(crate) const MAC_BYTES: usize = 16;
@@ -81,6 +81,11 @@ pub struct RequestPacket {
     pub expire_timestamp: u64,
     pub token_nonce: XNonce,
     pub token_data: Box<[u8; ConnectTokenPrivate::SIZE]>,
+    /// Fingerprint of the client's protocol, used by the server to check that the client is compatible
+    ///
+    /// This field is not part of the netcode.io standard: it makes the request packet incompatible
+    /// with standard netcode.io clients and servers.
+    pub protocol_fingerprint: u64,
 }
 
 impl RequestPacket {
@@ -89,6 +94,7 @@ impl RequestPacket {
         expire_timestamp: u64,
         token_nonce: XNonce,
         token_data: [u8; ConnectTokenPrivate::SIZE],
+        protocol_fingerprint: u64,
     ) -> Packet<'static> {
         Packet::Request(RequestPacket {
             version_info: *NETCODE_VERSION,
@@ -96,6 +102,7 @@ impl RequestPacket {
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
+            protocol_fingerprint,
         })
     }
     pub fn validate(&self, protocol_id: u64, current_timestamp: u64) -> Result<(), Error> {
@@ -136,6 +143,7 @@ impl Bytes for RequestPacket {
         writer.write_u64::<LittleEndian>(self.expire_timestamp)?;
         writer.write_all(&self.token_nonce)?;
         writer.write_all(&self.token_data[..])?;
+        writer.write_u64::<LittleEndian>(self.protocol_fingerprint)?;
         Ok(())
     }
 
@@ -149,12 +157,14 @@ impl Bytes for RequestPacket {
         let token_nonce = XNonce::from_slice(&nonce).to_owned();
         let mut token_data = [0; ConnectTokenPrivate::SIZE];
         reader.read_exact(&mut token_data)?;
+        let protocol_fingerprint = reader.read_u64::<LittleEndian>()?;
         Ok(Self {
             version_info,
             protocol_id,
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
+            protocol_fingerprint,
         })
     }
 }
@@ -210,6 +220,20 @@ impl Bytes for DeniedReason {
                     ));
                 }
             }
+            DeniedReason::CustomPayload(payload) => {
+                writer.write_u8(7)?;
+                if payload.len() > DeniedReason::MAX_CUSTOM_PAYLOAD_BYTES {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "custom denied payload too long",
+                    ));
+                }
+                writer.write_u16::<LittleEndian>(payload.len() as u16)?;
+                writer.write_all(payload)?;
+            }
+            DeniedReason::ProtocolMismatch => {
+                writer.write_u8(8)?;
+            }
         }
         Ok(())
     }
@@ -235,6 +259,19 @@ impl Bytes for DeniedReason {
             let reason_str = String::from_utf8(string_buf)
                 .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid denied reason"))?;
             Ok(DeniedReason::Custom(reason_str))
+        } else if variant == 7 {
+            let len = reader.read_u16::<LittleEndian>()? as usize;
+            if len > DeniedReason::MAX_CUSTOM_PAYLOAD_BYTES {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "custom denied payload too long",
+                ));
+            }
+            let mut payload = vec![0; len];
+            reader.read_exact(&mut payload)?;
+            Ok(DeniedReason::CustomPayload(payload))
+        } else if variant == 8 {
+            Ok(DeniedReason::ProtocolMismatch)
         } else {
             Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -618,6 +655,7 @@ mod tests {
             expire_timestamp,
             token_nonce: nonce,
             token_data: Box::new(token_data),
+            protocol_fingerprint: 0x0123_4567_89ab_cdef,
         });
 
         let mut buf = [0u8; MAX_PACKET_SIZE];
@@ -643,6 +681,7 @@ mod tests {
         assert_eq!(req_pkt.protocol_id, protocol_id);
         assert_eq!(req_pkt.expire_timestamp, expire_timestamp);
         assert_eq!(req_pkt.token_nonce, nonce);
+        assert_eq!(req_pkt.protocol_fingerprint, 0x0123_4567_89ab_cdef);
 
         let mut reader = std::io::Cursor::new(&req_pkt.token_data[..]);
         let connect_token_private = ConnectTokenPrivate::read_from(&mut reader).unwrap();
@@ -690,6 +729,51 @@ mod tests {
         assert_eq!(denied_pkt.reason, DeniedReason::Custom(String::from("a")));
     }
 
+    #[test]
+    fn denied_packet_custom_payload() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct BanInfo {
+            reason: String,
+            remaining_secs: u32,
+        }
+
+        let packet_key = generate_key();
+        let protocol_id = 0x1234_5678_9abc_def0;
+        let sequence = 0u64;
+        let mut replay_protection = ReplayProtection::new();
+
+        let ban = BanInfo {
+            reason: String::from("cheating"),
+            remaining_secs: 3600,
+        };
+        let packet = Packet::Denied(DeniedPacket {
+            reason: DeniedReason::custom_payload(&ban).unwrap(),
+        });
+
+        let mut buf = [0u8; MAX_PKT_BUF_SIZE];
+        let size = packet
+            .write(&mut buf, sequence, &packet_key, protocol_id)
+            .unwrap();
+
+        let packet = Packet::read(
+            &mut buf[..size],
+            protocol_id,
+            0,
+            packet_key,
+            Some(&mut replay_protection),
+            0xff,
+        )
+        .unwrap();
+
+        let Packet::Denied(denied_pkt) = packet else {
+            panic!("wrong packet type");
+        };
+        assert_eq!(
+            denied_pkt.reason.decode_custom_payload::<BanInfo>(),
+            Some(ban)
+        );
+    }
+
     #[test]
     fn denied_packet() {
         let packet_key = generate_key();
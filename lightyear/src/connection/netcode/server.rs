@@ -12,12 +12,14 @@ use tracing::{instrument, Level};
 use crate::connection::id;
 use crate::connection::netcode::token::TOKEN_EXPIRE_SEC;
 use crate::connection::server::{
-    ConnectionError, ConnectionRequestHandler, DefaultConnectionRequestHandler, DeniedReason,
-    IoConfig, NetServer,
+    ConnectionError, ConnectionRequest, ConnectionRequestDecision, ConnectionRequestHandler,
+    DefaultConnectionRequestHandler, DeniedReason, IoConfig, NetServer, PendingDecision,
 };
 use crate::packet::packet_builder::RecvPayload;
 use crate::server::config::NetcodeConfig;
 use crate::server::io::{Io, ServerIoEvent, ServerNetworkEventSender};
+#[cfg(feature = "zstd")]
+use crate::transport::middleware::compression::zstd::{ZstdDictionary, ZstdPayloadCompression};
 use crate::transport::{PacketReceiver, PacketSender};
 
 use super::{
@@ -30,13 +32,21 @@ use super::{
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken, ConnectTokenBuilder, ConnectTokenPrivate},
-    MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
+    CONNECTION_TIMEOUT_SEC, MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
 };
 
 pub const MAX_CLIENTS: usize = 256;
 
 const CLIENT_TIMEOUT_SECS: i32 = 10;
 
+/// A connection request for which the [`ConnectionRequestHandler`] hasn't made a decision yet
+struct PendingRequest {
+    client_id: ClientId,
+    decision: PendingDecision,
+    /// Time after which we stop waiting for the decision
+    expire_time: f64,
+}
+
 #[derive(Clone, Copy)]
 struct TokenEntry {
     time: f64,
@@ -246,6 +256,9 @@ pub struct ServerConfig<Ctx> {
     token_expire_secs: i32,
     client_timeout_secs: i32,
     connection_request_handler: Arc<dyn ConnectionRequestHandler>,
+    protocol_fingerprint: Option<u64>,
+    #[cfg(feature = "zstd")]
+    payload_dictionary: Option<(i32, ZstdDictionary)>,
     server_addr: SocketAddr,
     context: Ctx,
     on_connect: Option<Callback<Ctx>>,
@@ -260,6 +273,9 @@ impl Default for ServerConfig<()> {
             token_expire_secs: TOKEN_EXPIRE_SEC,
             client_timeout_secs: CLIENT_TIMEOUT_SECS,
             connection_request_handler: Arc::new(DefaultConnectionRequestHandler),
+            protocol_fingerprint: None,
+            #[cfg(feature = "zstd")]
+            payload_dictionary: None,
             server_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
             context: (),
             on_connect: None,
@@ -281,6 +297,9 @@ impl<Ctx> ServerConfig<Ctx> {
             token_expire_secs: TOKEN_EXPIRE_SEC,
             client_timeout_secs: CLIENT_TIMEOUT_SECS,
             connection_request_handler: Arc::new(DefaultConnectionRequestHandler),
+            protocol_fingerprint: None,
+            #[cfg(feature = "zstd")]
+            payload_dictionary: None,
             server_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
             context: ctx,
             on_connect: None,
@@ -311,6 +330,19 @@ impl<Ctx> ServerConfig<Ctx> {
         self.token_expire_secs = expire_secs;
         self
     }
+    /// Set the fingerprint of the server's protocol.
+    /// Connection requests from clients with a different fingerprint are denied with [`DeniedReason::ProtocolMismatch`].
+    pub fn protocol_fingerprint(mut self, protocol_fingerprint: u64) -> Self {
+        self.protocol_fingerprint = Some(protocol_fingerprint);
+        self
+    }
+    /// Compress the payload of the packets with a zstd dictionary before encrypting them.
+    /// The clients must use the same dictionary.
+    #[cfg(feature = "zstd")]
+    pub fn payload_dictionary(mut self, level: i32, dictionary: ZstdDictionary) -> Self {
+        self.payload_dictionary = Some((level, dictionary));
+        self
+    }
     /// Set the socket address of the server.
     // TODO: This actually NEEDS to be set, change the API to force this
     pub fn server_addr(mut self, server_addr: SocketAddr) -> Self {
@@ -384,6 +416,9 @@ pub struct NetcodeServer<Ctx = ()> {
     protocol_id: u64,
     conn_cache: ConnectionCache,
     token_entries: TokenEntries,
+    pending_requests: HashMap<SocketAddr, PendingRequest>,
+    #[cfg(feature = "zstd")]
+    payload_compression: Option<ZstdPayloadCompression>,
     cfg: ServerConfig<Ctx>,
     client_errors: Vec<ConnectionError>,
 }
@@ -403,6 +438,9 @@ impl NetcodeServer {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            pending_requests: HashMap::new(),
+            #[cfg(feature = "zstd")]
+            payload_compression: None,
             cfg: ServerConfig::default(),
             client_errors: vec![],
         };
@@ -428,6 +466,12 @@ impl<Ctx> NetcodeServer<Ctx> {
     /// let server = NetcodeServer::with_config(protocol_id, private_key, cfg).unwrap();
     /// ```
     pub fn with_config(protocol_id: u64, private_key: Key, cfg: ServerConfig<Ctx>) -> Result<Self> {
+        #[cfg(feature = "zstd")]
+        let payload_compression = cfg
+            .payload_dictionary
+            .as_ref()
+            .map(|(level, dictionary)| ZstdPayloadCompression::new(*level, dictionary))
+            .transpose()?;
         let server = NetcodeServer {
             time: 0.0,
             private_key,
@@ -438,6 +482,9 @@ impl<Ctx> NetcodeServer<Ctx> {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            pending_requests: HashMap::new(),
+            #[cfg(feature = "zstd")]
+            payload_compression,
             cfg,
             client_errors: vec![],
         };
@@ -478,6 +525,13 @@ impl<Ctx> NetcodeServer<Ctx> {
             conn.confirm();
         }
     }
+    fn read_payload(&mut self, payload: &[u8]) -> Result<RecvPayload> {
+        #[cfg(feature = "zstd")]
+        if let Some(compression) = self.payload_compression.as_mut() {
+            return Ok(compression.decompress(payload)?);
+        }
+        Ok(bytes::Bytes::copy_from_slice(payload))
+    }
     fn process_packet(
         &mut self,
         addr: SocketAddr,
@@ -510,7 +564,7 @@ impl<Ctx> NetcodeServer<Ctx> {
                     // self.conn_cache.buffer_pool.attach(reader);
 
                     // TODO: use a pool of buffers to avoid re-allocation
-                    let buf = bytes::Bytes::copy_from_slice(packet.buf);
+                    let buf = self.read_payload(packet.buf)?;
                     self.conn_cache.packet_queue.push_back((buf, idx));
                 }
                 Ok(())
@@ -621,11 +675,66 @@ impl<Ctx> NetcodeServer<Ctx> {
             )?;
             return Err(Error::ServerIsFull(id::ClientId::Netcode(token.client_id)));
         };
-        if let Some(denied_reason) = self
+        if self
             .cfg
-            .connection_request_handler
-            .handle_request(crate::prelude::ClientId::Netcode(token.client_id))
+            .protocol_fingerprint
+            .is_some_and(|fingerprint| fingerprint != packet.protocol_fingerprint)
         {
+            self.send_to_addr(
+                DeniedPacket::create(DeniedReason::ProtocolMismatch),
+                from_addr,
+                token.server_to_client_key,
+                sender,
+            )?;
+            return Err(Error::ProtocolMismatch(id::ClientId::Netcode(
+                token.client_id,
+            )));
+        }
+        let denied_reason = match self.pending_requests.get(&from_addr) {
+            // the client is re-sending a request for which the decision was pending
+            Some(pending) if pending.client_id == token.client_id => {
+                let Some(denied_reason) = pending.decision.try_resolve() else {
+                    trace!(client_id = ?token.client_id, "connection request decision is still pending");
+                    return Ok(());
+                };
+                self.pending_requests.remove(&from_addr);
+                denied_reason
+            }
+            _ => {
+                let request = ConnectionRequest {
+                    client_id: id::ClientId::Netcode(token.client_id),
+                    client_addr: from_addr,
+                    user_data: token.user_data,
+                };
+                match self
+                    .cfg
+                    .connection_request_handler
+                    .handle_connection_request(&request)
+                {
+                    ConnectionRequestDecision::Accept => None,
+                    ConnectionRequestDecision::Deny(denied_reason) => Some(denied_reason),
+                    ConnectionRequestDecision::Pending(decision) => {
+                        debug!(client_id = ?token.client_id, "connection request decision is pending");
+                        // the client gives up if it doesn't hear from the server before its timeout
+                        let timeout = if token.timeout_seconds.is_positive() {
+                            token.timeout_seconds
+                        } else {
+                            CONNECTION_TIMEOUT_SEC
+                        };
+                        self.pending_requests.insert(
+                            from_addr,
+                            PendingRequest {
+                                client_id: token.client_id,
+                                decision,
+                                expire_time: self.time + timeout as f64,
+                            },
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        if let Some(denied_reason) = denied_reason {
             self.send_to_addr(
                 DeniedPacket::create(denied_reason),
                 from_addr,
@@ -719,6 +828,14 @@ impl<Ctx> NetcodeServer<Ctx> {
         Ok(())
     }
     fn check_for_timeouts(&mut self) {
+        let time = self.time;
+        self.pending_requests.retain(|addr, pending| {
+            if pending.expire_time < time {
+                debug!(?addr, client_id = ?pending.client_id, "dropping pending connection request: no decision before the timeout");
+                return false;
+            }
+            true
+        });
         for id in self.conn_cache.ids() {
             let Some(client) = self.conn_cache.clients.get_mut(&id) else {
                 continue;
@@ -905,6 +1022,13 @@ impl<Ctx> NetcodeServer<Ctx> {
             // send a keep-alive packet to the client to confirm the connection
             self.send_to_client(KeepAlivePacket::create(client_id), client_id, io)?;
         }
+        #[cfg(feature = "zstd")]
+        if let Some(compression) = self.payload_compression.as_mut() {
+            let mut compressed = [0u8; MAX_PKT_BUF_SIZE];
+            let size = compression.compress(buf, &mut compressed)?;
+            let packet = PayloadPacket::create(&compressed[..size]);
+            return self.send_to_client(packet, client_id, io);
+        }
         let packet = PayloadPacket::create(buf);
         self.send_to_client(packet, client_id, io)
     }
@@ -1044,6 +1168,7 @@ impl<Ctx> NetcodeServer<Ctx> {
 pub(crate) mod connection {
     use super::*;
     use crate::connection::server::ConnectionError;
+    use crate::protocol::fingerprint::ProtocolFingerprint;
     use core::result::Result;
 
     #[derive(Default)]
@@ -1162,14 +1287,33 @@ pub(crate) mod connection {
     }
 
     impl Server {
-        pub(crate) fn new(config: NetcodeConfig, io_config: IoConfig) -> Self {
+        pub(crate) fn new(
+            config: NetcodeConfig,
+            io_config: IoConfig,
+            protocol_fingerprint: ProtocolFingerprint,
+        ) -> Self {
             // create context
             let context = NetcodeServerContext::default();
+            #[cfg(not(target_family = "wasm"))]
+            let (connected_ids, disconnected_ids) = (
+                config.issued_client_ids.clone(),
+                config.issued_client_ids.clone(),
+            );
             let mut cfg = ServerConfig::with_context(context)
-                .on_connect(|id, addr, ctx| {
+                .on_connect(move |id, addr, ctx| {
+                    // the client id stays in use after the token expires
+                    #[cfg(not(target_family = "wasm"))]
+                    if let Some(issued_client_ids) = &connected_ids {
+                        issued_client_ids.connect(id);
+                    }
                     ctx.connections.push(id::ClientId::Netcode(id));
                 })
-                .on_disconnect(|id, addr, ctx| {
+                .on_disconnect(move |id, addr, ctx| {
+                    // the token service can issue the client id again
+                    #[cfg(not(target_family = "wasm"))]
+                    if let Some(issued_client_ids) = &disconnected_ids {
+                        issued_client_ids.release(id);
+                    }
                     // notify the io that a client got disconnected
                     if let Some(sender) = &mut ctx.sender {
                         debug!("Notify the io that client {id:?} got disconnected, so that we can stop the corresponding task");
@@ -1185,6 +1329,14 @@ pub(crate) mod connection {
             cfg = cfg.num_disconnect_packets(config.num_disconnect_packets);
             cfg = cfg.client_timeout_secs(config.client_timeout_secs);
             cfg.connection_request_handler = config.connection_request_handler;
+            cfg = cfg.protocol_fingerprint(protocol_fingerprint.0);
+            // the payloads are compressed with the dictionary before being encrypted
+            #[cfg(feature = "zstd")]
+            let mut io_config = io_config;
+            #[cfg(feature = "zstd")]
+            if let Some((level, dictionary)) = io_config.compression.take_dictionary() {
+                cfg = cfg.payload_dictionary(level, dictionary);
+            }
             let server = NetcodeServer::with_config(config.protocol_id, config.private_key, cfg)
                 .expect("Could not create server netcode");
 
@@ -0,0 +1,535 @@
+//! A small service that hands out [`ConnectToken`]s to clients.
+//!
+//! In the netcode protocol, clients must get a [`ConnectToken`] from a backend before they can connect to
+//! the game server. The backend must share the `protocol_id` and `private_key` of the game server.
+//!
+//! The [`TokenService`] is a minimal implementation of such a backend:
+//! - clients open a TCP connection and send their credentials (arbitrary bytes)
+//! - the credentials are checked by a pluggable [`CredentialValidator`]
+//! - if they are accepted, the service generates a [`ConnectToken`] with a unique client id and the
+//!   `user_data` returned by the validator, and sends it back to the client
+//!
+//! On the client, use [`Authentication::RequestToken`](crate::prelude::client::Authentication::RequestToken)
+//! to fetch a new token from the service every time the client connects, or [`request_token`] to fetch it yourself.
+//!
+//! The connection is not encrypted, so the service should only be exposed on a trusted network,
+//! or behind a TLS-terminating proxy.
+//!
+//! ```rust,no_run
+//! use std::net::SocketAddr;
+//! use bevy::tasks::{IoTaskPool, TaskPool};
+//! use lightyear::connection::netcode::{generate_key, TokenGrant, TokenService};
+//!
+//! IoTaskPool::get_or_init(TaskPool::new);
+//! let game_server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+//! let service = TokenService::new(game_server_addr, 0, generate_key())
+//!     .with_validator(|credentials: Vec<u8>, _addr: SocketAddr| async move {
+//!         // check the credentials against a database, etc.
+//!         (credentials == b"password").then(TokenGrant::default)
+//!     });
+//! service.spawn(SocketAddr::from(([127, 0, 0, 1], 4000))).detach();
+//! ```
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_compat::Compat;
+use bevy::tasks::{IoTaskPool, Task};
+use bevy::utils::HashMap;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use parking_lot::RwLock;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, error, info, trace};
+
+use super::token::TOKEN_EXPIRE_SEC;
+use super::{
+    utils, ConnectToken, InvalidTokenError, Key, CONNECTION_TIMEOUT_SEC, CONNECT_TOKEN_BYTES,
+    USER_DATA_BYTES,
+};
+
+/// Maximum size of the credentials sent by the client
+pub const MAX_CREDENTIALS_BYTES: usize = 1024;
+
+const RESPONSE_ACCEPTED: u8 = 0;
+const RESPONSE_DENIED: u8 = 1;
+
+/// Errors that can happen while requesting a [`ConnectToken`] from a [`TokenService`]
+#[derive(thiserror::Error, Debug)]
+pub enum TokenServiceError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("the credentials are larger than {MAX_CREDENTIALS_BYTES} bytes")]
+    CredentialsTooLarge,
+    #[error("the token service denied the request")]
+    Denied,
+    #[error("the token service sent an invalid response")]
+    InvalidResponse,
+    #[error("invalid connect token: {0}")]
+    InvalidToken(#[from] InvalidTokenError),
+}
+
+/// What the [`CredentialValidator`] grants to a client whose credentials were accepted
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenGrant {
+    /// The client id to use in the [`ConnectToken`].
+    ///
+    /// If None, the [`TokenService`] will generate a random client id that is not currently in use.
+    pub client_id: Option<u64>,
+    /// The `user_data` to include in the [`ConnectToken`]. It is readable by the game server when the client connects.
+    pub user_data: [u8; USER_DATA_BYTES],
+}
+
+impl Default for TokenGrant {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            user_data: [0; USER_DATA_BYTES],
+        }
+    }
+}
+
+impl TokenGrant {
+    pub fn with_client_id(mut self, client_id: u64) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    pub fn with_user_data(mut self, user_data: [u8; USER_DATA_BYTES]) -> Self {
+        self.user_data = user_data;
+        self
+    }
+}
+
+/// Checks the credentials sent by a client to the [`TokenService`].
+///
+/// Returns a [`TokenGrant`] if the client should receive a [`ConnectToken`], or None to deny the request.
+///
+/// This is implemented for any async closure `Fn(Vec<u8>, SocketAddr) -> Option<TokenGrant>`.
+pub trait CredentialValidator: Send + Sync + 'static {
+    fn validate(
+        &self,
+        credentials: Vec<u8>,
+        client_addr: SocketAddr,
+    ) -> BoxFuture<'static, Option<TokenGrant>>;
+}
+
+impl<F, Fut> CredentialValidator for F
+where
+    F: Fn(Vec<u8>, SocketAddr) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<TokenGrant>> + Send + 'static,
+{
+    fn validate(
+        &self,
+        credentials: Vec<u8>,
+        client_addr: SocketAddr,
+    ) -> BoxFuture<'static, Option<TokenGrant>> {
+        self(credentials, client_addr).boxed()
+    }
+}
+
+/// [`CredentialValidator`] that accepts every request. Only useful for testing.
+pub struct AcceptAll;
+
+impl CredentialValidator for AcceptAll {
+    fn validate(&self, _: Vec<u8>, _: SocketAddr) -> BoxFuture<'static, Option<TokenGrant>> {
+        futures::future::ready(Some(TokenGrant::default())).boxed()
+    }
+}
+
+/// The set of client ids that were handed out by the [`TokenService`].
+///
+/// Ids are added when a [`ConnectToken`] is issued, and are released so that they can be re-used:
+/// - when the token expires, if the client did not connect to the game server with it
+/// - when the client disconnects from the game server, if the server shares the ids
+///   with [`NetcodeConfig::with_issued_client_ids`](crate::prelude::server::NetcodeConfig::with_issued_client_ids)
+#[derive(Clone, Default, Debug)]
+pub struct IssuedClientIds(Arc<RwLock<HashMap<u64, IssuedClientId>>>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IssuedClientId {
+    /// The client hasn't connected yet; the id is released when the token expires (seconds since unix epoch)
+    Pending { expire_timestamp: u64 },
+    /// The client connected to the game server; the id is released when the client disconnects
+    Connected,
+}
+
+impl IssuedClientIds {
+    pub fn contains(&self, client_id: u64) -> bool {
+        let now = utils::now();
+        self.0
+            .read()
+            .get(&client_id)
+            .is_some_and(|id| !id.is_expired(now))
+    }
+
+    /// Mark a client id as available again
+    pub fn release(&self, client_id: u64) {
+        self.0.write().remove(&client_id);
+    }
+
+    /// Keep the client id until the client disconnects, even after its token expires
+    pub(crate) fn connect(&self, client_id: u64) {
+        if let Some(id) = self.0.write().get_mut(&client_id) {
+            *id = IssuedClientId::Connected;
+        }
+    }
+
+    /// Reserve a client id until its token is generated. Returns false if it was already in use.
+    pub(crate) fn reserve(&self, client_id: u64) -> bool {
+        let now = utils::now();
+        let mut ids = self.0.write();
+        ids.retain(|_, id| !id.is_expired(now));
+        if ids.contains_key(&client_id) {
+            return false;
+        }
+        ids.insert(
+            client_id,
+            IssuedClientId::Pending {
+                expire_timestamp: u64::MAX,
+            },
+        );
+        true
+    }
+
+    /// Reserve a random client id that is not in use until its token is generated
+    fn reserve_random(&self) -> u64 {
+        loop {
+            let client_id = rand::random();
+            if self.reserve(client_id) {
+                return client_id;
+            }
+        }
+    }
+
+    /// Release the client id when its token expires, if the client hasn't connected by then
+    fn expire_at(&self, client_id: u64, expire_timestamp: u64) {
+        if let Some(id @ IssuedClientId::Pending { .. }) = self.0.write().get_mut(&client_id) {
+            *id = IssuedClientId::Pending { expire_timestamp };
+        }
+    }
+}
+
+impl IssuedClientId {
+    fn is_expired(&self, now: u64) -> bool {
+        match self {
+            IssuedClientId::Pending { expire_timestamp } => *expire_timestamp <= now,
+            IssuedClientId::Connected => false,
+        }
+    }
+}
+
+/// Service that listens for TCP connections and hands out [`ConnectToken`]s to clients with valid credentials.
+#[derive(Clone)]
+pub struct TokenService {
+    game_server_addr: SocketAddr,
+    protocol_id: u64,
+    private_key: Key,
+    expire_secs: i32,
+    timeout_secs: i32,
+    validator: Arc<dyn CredentialValidator>,
+    client_ids: IssuedClientIds,
+}
+
+impl TokenService {
+    /// Create a service that issues tokens for the game server at `game_server_addr`.
+    ///
+    /// The `protocol_id` and `private_key` must match the ones used by the game server.
+    /// By default, every request is accepted; use [`TokenService::with_validator`] to check the credentials.
+    pub fn new(game_server_addr: SocketAddr, protocol_id: u64, private_key: Key) -> Self {
+        Self {
+            game_server_addr,
+            protocol_id,
+            private_key,
+            expire_secs: TOKEN_EXPIRE_SEC,
+            timeout_secs: CONNECTION_TIMEOUT_SEC,
+            validator: Arc::new(AcceptAll),
+            client_ids: IssuedClientIds::default(),
+        }
+    }
+
+    pub fn with_validator(mut self, validator: impl CredentialValidator) -> Self {
+        self.validator = Arc::new(validator);
+        self
+    }
+
+    /// Number of seconds before the issued tokens expire
+    pub fn with_expire_secs(mut self, expire_secs: i32) -> Self {
+        self.expire_secs = expire_secs;
+        self
+    }
+
+    /// Connection timeout (in seconds) written in the issued tokens
+    pub fn with_timeout_secs(mut self, timeout_secs: i32) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    /// Share the set of issued client ids, for example with a game server that runs in the same process
+    pub fn with_client_ids(mut self, client_ids: IssuedClientIds) -> Self {
+        self.client_ids = client_ids;
+        self
+    }
+
+    /// The set of client ids that were handed out by this service
+    pub fn client_ids(&self) -> &IssuedClientIds {
+        &self.client_ids
+    }
+
+    /// Start the service in a task on the [`IoTaskPool`], listening on `addr`.
+    ///
+    /// The service stops when the [`Task`] is dropped, so call [`Task::detach`] to keep it running.
+    pub fn spawn(self, addr: SocketAddr) -> Task<io::Result<()>> {
+        IoTaskPool::get().spawn(Compat::new(async move {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            self.serve(listener).await
+        }))
+    }
+
+    /// Start the service in a task on the [`IoTaskPool`], using an already bound listener.
+    pub fn spawn_with_listener(self, listener: std::net::TcpListener) -> Task<io::Result<()>> {
+        IoTaskPool::get().spawn(Compat::new(async move {
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            self.serve(listener).await
+        }))
+    }
+
+    async fn serve(self, listener: tokio::net::TcpListener) -> io::Result<()> {
+        info!(
+            "Listening for ConnectToken requests on {}",
+            listener.local_addr()?
+        );
+        loop {
+            let (stream, client_addr) = listener.accept().await?;
+            let service = self.clone();
+            // handle each request in its own task so that a slow validation doesn't block other clients
+            IoTaskPool::get()
+                .spawn(Compat::new(async move {
+                    if let Err(e) = service.handle_request(stream, client_addr).await {
+                        error!(?client_addr, "Failed to handle ConnectToken request: {e}");
+                    }
+                }))
+                .detach();
+        }
+    }
+
+    async fn handle_request(
+        &self,
+        mut stream: tokio::net::TcpStream,
+        client_addr: SocketAddr,
+    ) -> Result<(), TokenServiceError> {
+        let len = stream.read_u16_le().await? as usize;
+        if len > MAX_CREDENTIALS_BYTES {
+            stream.write_u8(RESPONSE_DENIED).await?;
+            return Err(TokenServiceError::CredentialsTooLarge);
+        }
+        let mut credentials = vec![0; len];
+        stream.read_exact(&mut credentials).await?;
+
+        let Some(token) = self.issue_token(credentials, client_addr).await else {
+            debug!(?client_addr, "ConnectToken request denied");
+            stream.write_u8(RESPONSE_DENIED).await?;
+            return Ok(());
+        };
+        let token_bytes = token.try_into_bytes()?;
+        stream.write_u8(RESPONSE_ACCEPTED).await?;
+        stream.write_all(&token_bytes).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    /// Validate the credentials and generate a [`ConnectToken`] if they are accepted
+    async fn issue_token(
+        &self,
+        credentials: Vec<u8>,
+        client_addr: SocketAddr,
+    ) -> Option<ConnectToken> {
+        let grant = self.validator.validate(credentials, client_addr).await?;
+        let client_id = match grant.client_id {
+            Some(client_id) => {
+                if !self.client_ids.reserve(client_id) {
+                    debug!(?client_id, "ConnectToken denied: client id already in use");
+                    return None;
+                }
+                client_id
+            }
+            None => self.client_ids.reserve_random(),
+        };
+        match ConnectToken::build(
+            self.game_server_addr,
+            self.protocol_id,
+            client_id,
+            self.private_key,
+        )
+        .expire_seconds(self.expire_secs)
+        .timeout_seconds(self.timeout_secs)
+        .user_data(grant.user_data)
+        .generate()
+        {
+            Ok(token) => {
+                trace!(?client_id, ?client_addr, "Issued ConnectToken");
+                self.client_ids.expire_at(client_id, token.expire_timestamp);
+                Some(token)
+            }
+            Err(e) => {
+                error!(?client_id, "Failed to generate ConnectToken: {e}");
+                self.client_ids.release(client_id);
+                None
+            }
+        }
+    }
+}
+
+/// Request a [`ConnectToken`] from the [`TokenService`] listening on `addr`.
+///
+/// This must be run on a tokio runtime (for example by wrapping it in [`Compat`]).
+pub async fn request_token(
+    addr: SocketAddr,
+    credentials: &[u8],
+) -> Result<ConnectToken, TokenServiceError> {
+    if credentials.len() > MAX_CREDENTIALS_BYTES {
+        return Err(TokenServiceError::CredentialsTooLarge);
+    }
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+    stream.write_u16_le(credentials.len() as u16).await?;
+    stream.write_all(credentials).await?;
+    stream.flush().await?;
+    match stream.read_u8().await? {
+        RESPONSE_ACCEPTED => {
+            let mut buffer = [0u8; CONNECT_TOKEN_BYTES];
+            stream.read_exact(&mut buffer).await?;
+            Ok(ConnectToken::try_from_bytes(&buffer)?)
+        }
+        RESPONSE_DENIED => Err(TokenServiceError::Denied),
+        _ => Err(TokenServiceError::InvalidResponse),
+    }
+}
+
+/// Blocking version of [`request_token`]. Gives up after `timeout`.
+pub fn request_token_blocking(
+    addr: SocketAddr,
+    credentials: &[u8],
+    timeout: Duration,
+) -> Result<ConnectToken, TokenServiceError> {
+    if credentials.len() > MAX_CREDENTIALS_BYTES {
+        return Err(TokenServiceError::CredentialsTooLarge);
+    }
+    let mut stream = std::net::TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_u16::<LittleEndian>(credentials.len() as u16)?;
+    stream.write_all(credentials)?;
+    stream.flush()?;
+    match stream.read_u8()? {
+        RESPONSE_ACCEPTED => {
+            let mut buffer = [0u8; CONNECT_TOKEN_BYTES];
+            stream.read_exact(&mut buffer)?;
+            Ok(ConnectToken::try_from_bytes(&buffer)?)
+        }
+        RESPONSE_DENIED => Err(TokenServiceError::Denied),
+        _ => Err(TokenServiceError::InvalidResponse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::TaskPool;
+
+    use super::super::generate_key;
+    use super::super::token::ConnectTokenPrivate;
+    use super::*;
+
+    fn start_service(service: TokenService) -> SocketAddr {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        service.spawn_with_listener(listener).detach();
+        addr
+    }
+
+    #[test]
+    fn test_request_token() {
+        let protocol_id = 1;
+        let private_key = generate_key();
+        let game_server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let service = TokenService::new(game_server_addr, protocol_id, private_key).with_validator(
+            |credentials: Vec<u8>, _: SocketAddr| async move {
+                match credentials.as_slice() {
+                    b"alice" => Some(
+                        TokenGrant::default()
+                            .with_client_id(7)
+                            .with_user_data([1; USER_DATA_BYTES]),
+                    ),
+                    b"bob" => Some(TokenGrant::default()),
+                    _ => None,
+                }
+            },
+        );
+        let client_ids = service.client_ids().clone();
+        let addr = start_service(service);
+        let timeout = Duration::from_secs(5);
+
+        let mut token = request_token_blocking(addr, b"alice", timeout).unwrap();
+        assert_eq!(token.protocol_id, protocol_id);
+        let private = ConnectTokenPrivate::decrypt(
+            &mut token.private_data,
+            protocol_id,
+            token.expire_timestamp,
+            token.nonce,
+            &private_key,
+        )
+        .unwrap();
+        assert_eq!(private.client_id, 7);
+        assert_eq!(private.user_data, [1; USER_DATA_BYTES]);
+        assert!(client_ids.contains(7));
+
+        // the client id 7 is already in use
+        assert!(matches!(
+            request_token_blocking(addr, b"alice", timeout),
+            Err(TokenServiceError::Denied)
+        ));
+        // once the id is released, it can be issued again
+        client_ids.release(7);
+        assert!(request_token_blocking(addr, b"alice", timeout).is_ok());
+
+        // random client ids are generated if the validator doesn't provide one
+        assert!(request_token_blocking(addr, b"bob", timeout).is_ok());
+
+        // invalid credentials
+        assert!(matches!(
+            request_token_blocking(addr, b"eve", timeout),
+            Err(TokenServiceError::Denied)
+        ));
+    }
+
+    #[test]
+    fn test_release_expired_client_ids() {
+        let service =
+            TokenService::new(SocketAddr::from(([127, 0, 0, 1], 5000)), 1, generate_key())
+                .with_validator(|_: Vec<u8>, _: SocketAddr| async move {
+                    Some(TokenGrant::default().with_client_id(7))
+                })
+                // the tokens expire immediately
+                .with_expire_secs(0);
+        let client_ids = service.client_ids().clone();
+        let addr = start_service(service);
+        let timeout = Duration::from_secs(5);
+
+        // the client never connected with its token, so the id is released when the token expires
+        assert!(request_token_blocking(addr, b"alice", timeout).is_ok());
+        assert!(!client_ids.contains(7));
+        assert!(request_token_blocking(addr, b"alice", timeout).is_ok());
+
+        // the id of a connected client is kept after its token expires
+        client_ids.connect(7);
+        assert!(client_ids.contains(7));
+        assert!(matches!(
+            request_token_blocking(addr, b"alice", timeout),
+            Err(TokenServiceError::Denied)
+        ));
+    }
+}
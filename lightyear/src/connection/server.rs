@@ -1,20 +1,26 @@
 use bevy::prelude::Resource;
+use bevy::tasks::IoTaskPool;
 use bevy::utils::HashMap;
 use enum_dispatch::enum_dispatch;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use crate::connection::id::ClientId;
+use crate::connection::netcode::USER_DATA_BYTES;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::connection::steam::{server::SteamConfig, steamworks_client::SteamworksClient};
 use crate::packet::packet_builder::RecvPayload;
 use crate::prelude::server::ServerTransport;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::prelude::LinkConditionerConfig;
+use crate::protocol::fingerprint::ProtocolFingerprint;
+use crate::serialize::SerializationError;
 use crate::server::config::NetcodeConfig;
 use crate::server::io::Io;
 use crate::transport::config::SharedIoConfig;
@@ -29,6 +35,138 @@ pub enum DeniedReason {
     TokenAlreadyUsed,
     InvalidToken,
     Custom(String),
+    /// Serialized application-defined payload, delivered to the client.
+    ///
+    /// Use [`DeniedReason::custom_payload`] to create it from a typed value, and
+    /// [`DeniedReason::decode_custom_payload`] to read it back on the client.
+    CustomPayload(Vec<u8>),
+    /// The client's protocol (registered components, messages and channels) or zstd compression dictionary
+    /// is different from the server's, see [`ProtocolFingerprint`](crate::prelude::ProtocolFingerprint)
+    ProtocolMismatch,
+}
+
+impl DeniedReason {
+    /// Maximum size of a [`DeniedReason::CustomPayload`], so that it fits in a single packet
+    pub const MAX_CUSTOM_PAYLOAD_BYTES: usize = 1024;
+
+    /// Create a [`DeniedReason::CustomPayload`] by serializing `payload`
+    pub fn custom_payload<T: Serialize>(payload: &T) -> Result<Self, SerializationError> {
+        let bytes = bincode::serde::encode_to_vec(payload, bincode::config::standard())?;
+        if bytes.len() > Self::MAX_CUSTOM_PAYLOAD_BYTES {
+            return Err(SerializationError::MessageTooBig(bytes.len()));
+        }
+        Ok(Self::CustomPayload(bytes))
+    }
+
+    /// Deserialize the payload of a [`DeniedReason::CustomPayload`].
+    ///
+    /// Returns None if this is not a `CustomPayload`, or if the payload is not a valid `T`.
+    pub fn decode_custom_payload<T: DeserializeOwned>(&self) -> Option<T> {
+        let DeniedReason::CustomPayload(bytes) = self else {
+            return None;
+        };
+        bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+            .ok()
+            .map(|(payload, _)| payload)
+    }
+}
+
+/// A connection request received by the server, passed to the [`ConnectionRequestHandler`]
+#[derive(Debug, Clone)]
+pub struct ConnectionRequest {
+    pub client_id: ClientId,
+    /// Address of the remote client
+    pub client_addr: SocketAddr,
+    /// The `user_data` contained in the client's `ConnectToken`.
+    ///
+    /// Transports that don't use `ConnectToken`s (e.g. Steam) provide zeroed `user_data`
+    pub user_data: [u8; USER_DATA_BYTES],
+}
+
+/// The decision of a [`ConnectionRequestHandler`] about a connection request
+#[derive(Debug)]
+pub enum ConnectionRequestDecision {
+    Accept,
+    Deny(DeniedReason),
+    /// The decision is not known yet (for example because we need to query a database).
+    ///
+    /// The client keeps re-sending its connection request while the decision is pending, and the server
+    /// answers as soon as the [`PendingDecision`] is resolved. The request is dropped if the decision takes
+    /// longer than the client's connection timeout.
+    Pending(PendingDecision),
+}
+
+impl From<Option<DeniedReason>> for ConnectionRequestDecision {
+    fn from(value: Option<DeniedReason>) -> Self {
+        match value {
+            None => ConnectionRequestDecision::Accept,
+            Some(reason) => ConnectionRequestDecision::Deny(reason),
+        }
+    }
+}
+
+/// A decision about a connection request that will be resolved later.
+///
+/// It can be resolved either by a future (see [`PendingDecision::from_future`]), or manually from anywhere
+/// (for example from a bevy system reacting to an event) with the [`DecisionSender`] returned by [`PendingDecision::new`].
+#[derive(Debug)]
+pub struct PendingDecision {
+    receiver: async_channel::Receiver<Option<DeniedReason>>,
+}
+
+/// Resolves a [`PendingDecision`]
+#[derive(Debug, Clone)]
+pub struct DecisionSender {
+    sender: async_channel::Sender<Option<DeniedReason>>,
+}
+
+impl DecisionSender {
+    /// Accept the connection request
+    pub fn accept(&self) {
+        let _ = self.sender.try_send(None);
+    }
+
+    /// Deny the connection request
+    pub fn deny(&self, reason: DeniedReason) {
+        let _ = self.sender.try_send(Some(reason));
+    }
+}
+
+impl PendingDecision {
+    /// Create a pending decision, along with the [`DecisionSender`] that can be used to resolve it
+    pub fn new() -> (DecisionSender, Self) {
+        let (sender, receiver) = async_channel::bounded(1);
+        (DecisionSender { sender }, Self { receiver })
+    }
+
+    /// Create a pending decision that is resolved by a future running on the [`IoTaskPool`].
+    ///
+    /// The future returns None to accept the connection, or the reason why it is denied.
+    pub fn from_future(
+        future: impl Future<Output = Option<DeniedReason>> + Send + 'static,
+    ) -> Self {
+        let (sender, pending) = Self::new();
+        IoTaskPool::get()
+            .spawn(async move {
+                match future.await {
+                    None => sender.accept(),
+                    Some(reason) => sender.deny(reason),
+                }
+            })
+            .detach();
+        pending
+    }
+
+    /// Returns the decision if it has been resolved.
+    ///
+    /// If the [`DecisionSender`] was dropped without resolving the decision, the request is denied.
+    pub fn try_resolve(&self) -> Option<Option<DeniedReason>> {
+        match self.receiver.try_recv() {
+            Ok(decision) => Some(decision),
+            Err(async_channel::TryRecvError::Empty) => None,
+            Err(async_channel::TryRecvError::Closed) => Some(Some(DeniedReason::InternalError)),
+        }
+    }
 }
 
 /// Trait for handling connection requests from clients.
@@ -36,7 +174,18 @@ pub trait ConnectionRequestHandler: Debug + Send + Sync {
     /// Handle a connection request from a client.
     /// Returns None if the connection is accepted,
     /// Returns Some(reason) if the connection is denied.
-    fn handle_request(&self, client_id: ClientId) -> Option<DeniedReason>;
+    fn handle_request(&self, client_id: ClientId) -> Option<DeniedReason> {
+        None
+    }
+
+    /// Handle a connection request from a client, with access to the client's address and the
+    /// `user_data` of its `ConnectToken`.
+    ///
+    /// The decision can be deferred by returning [`ConnectionRequestDecision::Pending`].
+    /// By default, this calls [`ConnectionRequestHandler::handle_request`].
+    fn handle_connection_request(&self, request: &ConnectionRequest) -> ConnectionRequestDecision {
+        self.handle_request(request.client_id).into()
+    }
 }
 
 /// By default, all connection requests are accepted by the server.
@@ -141,10 +290,15 @@ impl Default for NetConfig {
 }
 
 impl NetConfig {
-    pub fn build_server(self) -> ServerConnection {
+    /// Build the [`ServerConnection`].
+    ///
+    /// Netcode connection requests from clients whose protocol has a different `protocol_fingerprint`
+    /// are denied with [`DeniedReason::ProtocolMismatch`].
+    pub fn build_server(self, protocol_fingerprint: ProtocolFingerprint) -> ServerConnection {
         match self {
             NetConfig::Netcode { config, io } => {
-                let server = super::netcode::Server::new(config, io);
+                let protocol_fingerprint = protocol_fingerprint.with_compression(&io.compression);
+                let server = super::netcode::Server::new(config, io, protocol_fingerprint);
                 ServerConnection::Netcode(server)
             }
             // TODO: might want to distinguish between steam with direct ip connections
@@ -186,10 +340,10 @@ pub struct ServerConnections {
 }
 
 impl ServerConnections {
-    pub(crate) fn new(config: Vec<NetConfig>) -> Self {
+    pub(crate) fn new(config: Vec<NetConfig>, protocol_fingerprint: ProtocolFingerprint) -> Self {
         let mut servers = vec![];
         for config in config {
-            let server = config.build_server();
+            let server = config.build_server(protocol_fingerprint);
             servers.push(server);
         }
         ServerConnections {
@@ -261,7 +415,10 @@ pub enum ConnectionError {
 
 #[cfg(test)]
 mod tests {
-    use crate::connection::server::{NetServer, ServerConnections};
+    #[cfg(not(target_family = "wasm"))]
+    use crate::connection::netcode::IssuedClientIds;
+    use crate::connection::server::{NetConfig, NetServer, ServerConnections};
+    use crate::prelude::server::ServerConfig;
     use crate::prelude::ClientId;
     use crate::tests::stepper::{BevyStepper, TEST_CLIENT_ID};
     use crate::transport::LOCAL_SOCKET;
@@ -296,6 +453,36 @@ mod tests {
         );
     }
 
+    /// The client ids issued by a token service are released when the clients disconnect
+    #[cfg(not(target_family = "wasm"))]
+    #[test]
+    fn test_server_disconnect_releases_issued_client_id() {
+        let mut stepper = BevyStepper::default_no_init();
+        let issued_client_ids = IssuedClientIds::default();
+        assert!(issued_client_ids.reserve(TEST_CLIENT_ID));
+        let mut config = stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConfig>();
+        let NetConfig::Netcode { config, .. } = &mut config.net[0] else {
+            unreachable!()
+        };
+        config.issued_client_ids = Some(issued_client_ids.clone());
+        stepper.init();
+        assert!(issued_client_ids.contains(TEST_CLIENT_ID));
+
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConnections>()
+            .disconnect(ClientId::Netcode(TEST_CLIENT_ID))
+            .unwrap();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert!(!issued_client_ids.contains(TEST_CLIENT_ID));
+    }
+
     #[test]
     fn test_server_get_client_addr() {
         let mut stepper = BevyStepper::default();
@@ -1,7 +1,8 @@
 use crate::connection::id::ClientId;
-use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::connection::netcode::{MAX_PACKET_SIZE, USER_DATA_BYTES};
 use crate::connection::server::{
-    ConnectionError, ConnectionRequestHandler, DefaultConnectionRequestHandler, NetServer,
+    ConnectionError, ConnectionRequest, ConnectionRequestDecision, ConnectionRequestHandler,
+    DefaultConnectionRequestHandler, DeniedReason, NetServer,
 };
 use crate::packet::packet_builder::RecvPayload;
 use crate::prelude::LinkConditionerConfig;
@@ -14,7 +15,7 @@ use std::sync::Arc;
 use steamworks::networking_sockets::{ListenSocket, NetConnection};
 use steamworks::networking_types::{ListenSocketEvent, NetConnectionEnd, SendFlags};
 use steamworks::{ClientManager, ServerMode, SteamError};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use super::steamworks_client::SteamworksClient;
 
@@ -240,12 +241,32 @@ impl NetServer for Server {
                         continue;
                     };
                     info!("Client with id: {:?} requesting connection!", steam_id);
-                    if let Some(denied_reason) = self
+                    let request = ConnectionRequest {
+                        client_id: ClientId::Steam(steam_id.raw()),
+                        // steam connections are not identified by a socket address
+                        client_addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+                        user_data: [0; USER_DATA_BYTES],
+                    };
+                    let denied_reason = match self
                         .config
                         .connection_request_handler
-                        .handle_request(ClientId::Steam(steam_id.raw()))
+                        .handle_connection_request(&request)
                     {
-                        event.reject(NetConnectionEnd::AppGeneric, Some("{denied_reason:?}"));
+                        ConnectionRequestDecision::Accept => None,
+                        ConnectionRequestDecision::Deny(denied_reason) => Some(denied_reason),
+                        ConnectionRequestDecision::Pending(_) => {
+                            // steam requires the connection request to be answered immediately
+                            warn!(
+                                "Pending connection request decisions are not supported with Steam"
+                            );
+                            Some(DeniedReason::InternalError)
+                        }
+                    };
+                    if let Some(denied_reason) = denied_reason {
+                        event.reject(
+                            NetConnectionEnd::AppGeneric,
+                            Some(&format!("{denied_reason:?}")),
+                        );
                         continue;
                     } else {
                         if let Err(e) = event.accept() {
@@ -3,13 +3,16 @@ use crate::inputs::leafwing::action_diff::ActionDiff;
 use crate::inputs::leafwing::input_buffer::InputBuffer;
 use crate::prelude::{Deserialize, LeafwingUserAction, Serialize, Tick};
 use bevy::ecs::entity::MapEntities;
-use bevy::prelude::{Entity, EntityMapper, Reflect};
+use bevy::prelude::{EntityMapper, Reflect};
 use leafwing_input_manager::action_state::ActionState;
 use leafwing_input_manager::Actionlike;
 use std::fmt::{Formatter, Write};
 
+pub use crate::inputs::InputTarget;
+
+/// Inputs of a single [`InputTarget`] contained in an [`InputMessage`]
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Reflect)]
-pub(crate) struct PerTargetData<A: Actionlike> {
+pub struct PerTargetData<A: Actionlike> {
     pub(crate) target: InputTarget,
     // The ActionState is the state at tick end_tick-N
     pub(crate) start_state: ActionState<A>,
@@ -35,6 +38,35 @@ pub struct InputMessage<A: Actionlike> {
     pub(crate) diffs: Vec<PerTargetData<A>>,
 }
 
+impl<A: Actionlike> PerTargetData<A> {
+    /// The entity (or the global inputs) that these inputs are for
+    pub fn target(&self) -> InputTarget {
+        self.target
+    }
+
+    /// The `ActionState` at tick `end_tick - diffs.len()`
+    pub fn start_state(&self) -> &ActionState<A> {
+        &self.start_state
+    }
+
+    pub fn start_state_mut(&mut self) -> &mut ActionState<A> {
+        &mut self.start_state
+    }
+
+    /// The `ActionDiff`s to apply to the start state, for each tick from `end_tick - diffs.len() + 1`
+    /// to `end_tick` (included)
+    pub fn diffs(&self) -> &[Vec<ActionDiff<A>>] {
+        &self.diffs
+    }
+
+    /// Mutable access to the `ActionDiff`s of each tick.
+    ///
+    /// The number of ticks cannot change, since it determines the tick of the start state.
+    pub fn diffs_mut(&mut self) -> &mut [Vec<ActionDiff<A>>] {
+        &mut self.diffs
+    }
+}
+
 impl<A: LeafwingUserAction> std::fmt::Display for InputMessage<A> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let ty = A::short_type_path();
@@ -65,16 +97,6 @@ impl<A: LeafwingUserAction> std::fmt::Display for InputMessage<A> {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Reflect)]
-pub enum InputTarget {
-    /// the input is for a global resource
-    Global,
-    /// the input is for a predicted or confirmed entity: on the client, the server's local entity is mapped to the client's confirmed entity
-    Entity(Entity),
-    /// the input is for a pre-predicted entity: on the server, the server's local entity is mapped to the client's pre-predicted entity
-    PrePredictedEntity(Entity),
-}
-
 impl<A: LeafwingUserAction> MapEntities for InputMessage<A> {
     // NOTE: we do NOT map the entities for input-message because when already convert
     //  the entities on the message to the corresponding client entities when we write them
@@ -147,6 +169,31 @@ impl<A: LeafwingUserAction> InputMessage<A> {
     }
 
     // TODO: do we want to send the inputs if there are no diffs?
+    /// Tick of the last inputs contained in the message
+    pub fn end_tick(&self) -> Tick {
+        self.end_tick
+    }
+
+    /// Iterate through the targets that the message contains inputs for
+    pub fn targets(&self) -> impl Iterator<Item = InputTarget> + '_ {
+        self.diffs.iter().map(|data| data.target)
+    }
+
+    /// Iterate through the inputs of each target
+    pub fn inputs(&self) -> impl Iterator<Item = &PerTargetData<A>> {
+        self.diffs.iter()
+    }
+
+    /// Iterate mutably through the inputs of each target, for example to sanitize them
+    pub fn inputs_mut(&mut self) -> impl Iterator<Item = &mut PerTargetData<A>> {
+        self.diffs.iter_mut()
+    }
+
+    /// Only keep the inputs of the targets for which `f` returns `true`
+    pub fn retain_targets(&mut self, mut f: impl FnMut(InputTarget) -> bool) {
+        self.diffs.retain(|data| f(data.target));
+    }
+
     pub fn is_empty(&self) -> bool {
         self.diffs.iter().all(|data| {
             data.diffs
@@ -184,6 +231,39 @@ mod tests {
         assert!(input_message.is_empty());
     }
 
+    #[test]
+    fn test_modify_input_message() {
+        let mut input_buffer = InputBuffer::default();
+        let mut action_state = ActionState::<Action>::default();
+        input_buffer.set(Tick(9), &action_state);
+        action_state.press(&Action::Jump);
+        input_buffer.set(Tick(10), &action_state);
+        let mut input_message = InputMessage::<Action>::new(Tick(10));
+        input_message.add_inputs(2, InputTarget::Global, &input_buffer);
+        assert_eq!(
+            input_message.inputs().next().unwrap().diffs(),
+            &[vec![ActionDiff::Pressed {
+                action: Action::Jump
+            }]]
+        );
+
+        // remove the jump inputs
+        for data in input_message.inputs_mut() {
+            data.start_state_mut().release(&Action::Jump);
+            data.diffs_mut().iter_mut().for_each(|diffs| {
+                diffs.retain(|diff| {
+                    !matches!(
+                        diff,
+                        ActionDiff::Pressed {
+                            action: Action::Jump
+                        }
+                    )
+                })
+            });
+        }
+        assert!(input_message.is_empty());
+    }
+
     // #[test]
     // fn test_create_message() {
     //     let mut input_buffer = InputBuffer::default();
@@ -1,8 +1,22 @@
 //! Handles networking client inputs
 
+use bevy::prelude::{Entity, Reflect};
+use serde::{Deserialize, Serialize};
+
 // TODO: import this as inputs, check how xwt/party does it
 #[cfg(feature = "leafwing")]
 #[cfg_attr(docsrs, doc(cfg(feature = "leafwing")))]
 pub mod leafwing;
 
 pub mod native;
+
+/// Which entity (if any) the inputs contained in an input message are for
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+pub enum InputTarget {
+    /// the input is for a global resource
+    Global,
+    /// the input is for a predicted or confirmed entity: on the client, the server's local entity is mapped to the client's confirmed entity
+    Entity(Entity),
+    /// the input is for a pre-predicted entity: on the server, the server's local entity is mapped to the client's pre-predicted entity
+    PrePredictedEntity(Entity),
+}
@@ -1,9 +1,11 @@
 use std::collections::VecDeque;
 use std::fmt::Debug;
 
-use bevy::prelude::{Reflect, Resource};
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::{EntityMapper, Reflect, Resource};
 use serde::{Deserialize, Serialize};
 
+use crate::inputs::InputTarget;
 use crate::shared::tick_manager::Tick;
 
 use super::UserAction;
@@ -23,26 +25,63 @@ pub(crate) enum InputData<T> {
     Input(T),
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Reflect)]
+pub(crate) struct PerTargetData<T> {
+    pub(crate) target: InputTarget,
+    // first element is tick end_tick-N+1, last element is end_tick
+    pub(crate) inputs: Vec<InputData<T>>,
+}
+
 // TODO: use Mode to specify how to serialize a message (serde vs bitcode)! + can specify custom serialize function as well (similar to interpolation mode)
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Reflect)]
 /// Message that we use to send the client inputs to the server
-/// We will store the last N inputs starting from start_tick (in case of packet loss)
+/// We will store the last N inputs starting from start_tick (in case of packet loss),
+/// for the global input buffer and for each entity controlled by the client
 pub struct InputMessage<T> {
     pub(crate) end_tick: Tick,
-    // first element is tick end_tick-N+1, last element is end_tick
-    pub(crate) inputs: Vec<InputData<T>>,
+    pub(crate) inputs: Vec<PerTargetData<T>>,
+}
+
+impl<T: UserAction> MapEntities for InputMessage<T> {
+    // NOTE: we only map the inputs for the pre-predicted entities, the other entities
+    //  are converted to the server entities by the client when it writes the message
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.inputs.iter_mut().for_each(|data| {
+            if let InputTarget::PrePredictedEntity(ref mut e) = data.target {
+                *e = entity_mapper.map_entity(*e);
+            }
+        });
+    }
 }
 
 impl<T: UserAction> InputMessage<T> {
-    pub fn is_empty(&self) -> bool {
-        if self.inputs.len() == 0 {
-            return true;
+    pub fn new(end_tick: Tick) -> Self {
+        Self {
+            end_tick,
+            inputs: vec![],
         }
-        let mut iter = self.inputs.iter();
-        if iter.next().unwrap() == &InputData::Absent {
-            return iter.all(|x| x == &InputData::SameAsPrecedent);
+    }
+
+    /// Add the inputs of the `input_buffer` for the `num_ticks` ticks up to `self.end_tick` (included)
+    ///
+    /// Nothing is added if all the inputs are absent.
+    pub(crate) fn add_inputs(
+        &mut self,
+        num_ticks: u16,
+        target: InputTarget,
+        input_buffer: &InputBuffer<T>,
+    ) {
+        let inputs = input_buffer.compress(self.end_tick, num_ticks);
+        let mut iter = inputs.iter();
+        if iter.next() == Some(&InputData::Absent) && iter.all(|x| x == &InputData::SameAsPrecedent)
+        {
+            return;
         }
-        false
+        self.inputs.push(PerTargetData { target, inputs });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
     }
 }
 
@@ -126,14 +165,15 @@ impl<T: UserAction> InputBuffer<T> {
         *self.buffer.get_mut((tick - start_tick) as usize).unwrap() = value;
     }
 
-    /// We received a new input message from the user, and use it to update the input buffer
+    /// We received a new input message from the user, and use the inputs of one of its targets
+    /// to update the input buffer
     /// TODO: should we keep track of which inputs in the input buffer are absent and only update those?
     ///  The current tick is the current server tick, no need to update the buffer for ticks that are older than that
-    pub(crate) fn update_from_message(&mut self, message: InputMessage<T>) {
-        let message_start_tick = Tick(message.end_tick.0) - message.inputs.len() as u16 + 1;
+    pub(crate) fn update_from_message(&mut self, end_tick: Tick, inputs: Vec<InputData<T>>) {
+        let message_start_tick = Tick(end_tick.0) - inputs.len() as u16 + 1;
         let mut prev_value = None;
 
-        for (delta, input) in message.inputs.into_iter().enumerate() {
+        for (delta, input) in inputs.into_iter().enumerate() {
             let tick = message_start_tick + Tick(delta as u16);
             match input {
                 InputData::Absent => {
@@ -155,9 +195,8 @@ impl<T: UserAction> InputBuffer<T> {
         }
     }
 
-    // Convert the last N ticks up to end_tick included into a compressed message that we can send to the server
-    // Return None if the last N inputs are all Absent
-    pub(crate) fn create_message(&self, end_tick: Tick, num_ticks: u16) -> InputMessage<T> {
+    // Convert the last N ticks up to end_tick included into compressed inputs that we can send to the server
+    pub(crate) fn compress(&self, end_tick: Tick, num_ticks: u16) -> Vec<InputData<T>> {
         let mut inputs = Vec::new();
         // start with the first value
         let start_tick = Tick(end_tick.0) - num_ticks + 1;
@@ -181,12 +220,14 @@ impl<T: UserAction> InputBuffer<T> {
                 inputs.push(value);
             }
         }
-        InputMessage { inputs, end_tick }
+        inputs
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use bevy::prelude::Entity;
+
     use super::*;
 
     #[test]
@@ -217,21 +258,28 @@ mod tests {
         input_buffer.set(Tick(6), Some(1));
         input_buffer.set(Tick(7), Some(1));
 
-        let message = input_buffer.create_message(Tick(10), 8);
+        let entity = Entity::from_raw(1);
+        let mut message = InputMessage::new(Tick(10));
+        message.add_inputs(8, InputTarget::Entity(entity), &input_buffer);
+        // all the inputs are absent, so the global buffer is not included in the message
+        message.add_inputs(2, InputTarget::Global, &input_buffer);
         assert_eq!(
             message,
             InputMessage {
                 end_tick: Tick(10),
-                inputs: vec![
-                    InputData::Absent,
-                    InputData::Input(0),
-                    InputData::Absent,
-                    InputData::Input(1),
-                    InputData::SameAsPrecedent,
-                    InputData::Absent,
-                    InputData::SameAsPrecedent,
-                    InputData::SameAsPrecedent,
-                ],
+                inputs: vec![PerTargetData {
+                    target: InputTarget::Entity(entity),
+                    inputs: vec![
+                        InputData::Absent,
+                        InputData::Input(0),
+                        InputData::Absent,
+                        InputData::Input(1),
+                        InputData::SameAsPrecedent,
+                        InputData::Absent,
+                        InputData::SameAsPrecedent,
+                        InputData::SameAsPrecedent,
+                    ],
+                }],
             }
         );
     }
@@ -240,20 +288,17 @@ mod tests {
     fn test_update_from_message() {
         let mut input_buffer = InputBuffer::default();
 
-        let message = InputMessage {
-            end_tick: Tick(20),
-            inputs: vec![
-                InputData::Absent,
-                InputData::Input(0),
-                InputData::Absent,
-                InputData::Input(1),
-                InputData::SameAsPrecedent,
-                InputData::Absent,
-                InputData::SameAsPrecedent,
-                InputData::SameAsPrecedent,
-            ],
-        };
-        input_buffer.update_from_message(message);
+        let inputs = vec![
+            InputData::Absent,
+            InputData::Input(0),
+            InputData::Absent,
+            InputData::Input(1),
+            InputData::SameAsPrecedent,
+            InputData::Absent,
+            InputData::SameAsPrecedent,
+            InputData::SameAsPrecedent,
+        ];
+        input_buffer.update_from_message(Tick(20), inputs);
 
         assert_eq!(input_buffer.get(Tick(20)), None);
         assert_eq!(input_buffer.get(Tick(19)), None);
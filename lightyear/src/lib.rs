@@ -175,8 +175,9 @@ fn component_inserted(query: Query<Entity, (With<Replicated>, Added<MyComponent>
 
 // re-exports
 #[doc(hidden)]
-pub(crate) mod _internal {
+pub mod _internal {
     pub use paste::paste;
+    pub use serde;
 }
 
 /// Prelude containing commonly used types
@@ -192,14 +193,30 @@ pub mod prelude {
     pub use crate::connection::id::ClientId;
     pub use crate::connection::netcode::{generate_key, ConnectToken, Key};
     #[cfg(feature = "leafwing")]
-    pub use crate::inputs::leafwing::{input_message::InputMessage, LeafwingUserAction};
+    pub use crate::inputs::leafwing::{
+        action_diff::ActionDiff,
+        input_message::{InputMessage, PerTargetData},
+        LeafwingUserAction,
+    };
     pub use crate::inputs::native::UserAction;
+    pub use crate::inputs::InputTarget;
+    pub use crate::packet::congestion::{
+        CongestionControlConfig, CongestionControlMode, CongestionDiagnosticsPlugin,
+    };
     pub use crate::packet::error::PacketError;
-    pub use crate::packet::message::Message;
+    pub use crate::packet::message::{Message, MessageId};
+    pub use crate::packet::mtu::MtuDiscoveryConfig;
     pub use crate::protocol::channel::{AppChannelExt, ChannelKind, ChannelRegistry};
-    pub use crate::protocol::component::{AppComponentExt, ComponentRegistry, Linear};
+    pub use crate::protocol::component::{
+        AppComponentExt, ComponentRegistry, ExtrapolateFn, Linear,
+    };
     pub use crate::protocol::event::AppEventExt;
+    pub use crate::protocol::fingerprint::ProtocolFingerprint;
     pub use crate::protocol::message::{AppMessageExt, MessageRegistry};
+    pub use crate::protocol::schema::{
+        ChannelModeSchema, ChannelSchema, ComponentSchema, MessageSchema, MessageTypeSchema,
+        ProtocolSchema,
+    };
     pub use crate::protocol::serialize::AppSerializeExt;
     pub use crate::shared::config::{Mode, SharedConfig};
     pub use crate::shared::events::EventSend;
@@ -215,6 +232,7 @@ pub mod prelude {
         PrePredicted, ReplicateHierarchy, ReplicateOnceComponent, Replicated, Replicating,
         ReplicationGroup, ShouldBePredicted, TargetEntity,
     };
+    pub use crate::shared::replication::delta::Diffable;
     pub use crate::shared::replication::entity_map::RemoteEntityMap;
     pub use crate::shared::replication::hierarchy::ParentSync;
     pub use crate::shared::replication::network_target::NetworkTarget;
@@ -223,13 +241,25 @@ pub mod prelude {
     pub use crate::shared::replication::resources::{
         ReplicateResourceExt, ReplicateResourceMetadata, StopReplicateResourceExt,
     };
+    pub use crate::shared::rpc::{AppRpcExt, RpcError, RpcId, RpcRequestEvent, RpcResponseEvent};
     pub use crate::shared::run_conditions::*;
     pub use crate::shared::sets::{FixedUpdateSet, MainSet};
     pub use crate::shared::tick_manager::TickManager;
     pub use crate::shared::tick_manager::{Tick, TickConfig};
     pub use crate::shared::time_manager::TimeManager;
+    #[cfg(not(target_family = "wasm"))]
+    pub use crate::transport::middleware::capture::read_capture_file;
+    pub use crate::transport::middleware::capture::{
+        read_capture, CaptureConfig, CapturedPacket, PacketDirection,
+    };
+    #[cfg(feature = "zstd")]
+    pub use crate::transport::middleware::compression::zstd::ZstdDictionary;
     pub use crate::transport::middleware::compression::CompressionConfig;
-    pub use crate::transport::middleware::conditioner::LinkConditionerConfig;
+    pub use crate::transport::middleware::conditioner::{
+        BandwidthLimit, BurstLossConfig, LinkConditionerConfig, LinkConditions, ScheduledConditions,
+    };
+    #[cfg(not(target_family = "wasm"))]
+    pub use crate::transport::replay::{ManualReplayClock, ReplayClock};
     pub use crate::utils::history_buffer::{HistoryBuffer, HistoryState};
 
     mod rename {
@@ -274,9 +304,11 @@ pub mod prelude {
         #[cfg(feature = "leafwing")]
         pub use crate::client::input::leafwing::LeafwingInputConfig;
         pub use crate::client::input::native::{InputConfig, InputManager};
+        pub use crate::client::interpolation::interpolate::InterpolationMode;
         pub use crate::client::interpolation::interpolation_history::ConfirmedHistory;
         pub use crate::client::interpolation::plugin::{
-            InterpolationConfig, InterpolationDelay, InterpolationSet,
+            AdaptiveDelayConfig, ExtrapolationConfig, InterpolationConfig, InterpolationDelay,
+            InterpolationSet,
         };
         pub use crate::client::interpolation::{
             InterpolateStatus, Interpolated, VisualInterpolateStatus, VisualInterpolationPlugin,
@@ -288,8 +320,10 @@ pub mod prelude {
         pub use crate::client::prediction::correction::Correction;
         pub use crate::client::prediction::despawn::PredictionDespawnCommandsExt;
         pub use crate::client::prediction::plugin::is_in_rollback;
-        pub use crate::client::prediction::plugin::{PredictionConfig, PredictionSet};
-        pub use crate::client::prediction::rollback::{Rollback, RollbackState};
+        pub use crate::client::prediction::plugin::{
+            PredictionConfig, PredictionSet, RollbackMode,
+        };
+        pub use crate::client::prediction::rollback::{Rollback, RollbackQuery, RollbackState};
         pub use crate::client::prediction::Predicted;
         pub use crate::client::replication::commands::DespawnReplicationCommandExt;
         pub use crate::client::replication::send::{Replicate, ReplicateToServer};
@@ -305,23 +339,36 @@ pub mod prelude {
         #[cfg(all(feature = "webtransport", not(target_family = "wasm")))]
         pub use wtransport::tls::Identity;
 
-        pub use crate::connection::server::{IoConfig, NetConfig, NetServer, ServerConnection};
+        pub use crate::connection::server::{
+            ConnectionRequest, ConnectionRequestDecision, ConnectionRequestHandler, DecisionSender,
+            DeniedReason, IoConfig, NetConfig, NetServer, PendingDecision, ServerConnection,
+        };
         #[cfg(all(feature = "steam", not(target_family = "wasm")))]
         pub use crate::connection::steam::server::{SocketConfig, SteamConfig};
         pub use crate::server::clients::ControlledEntities;
         pub use crate::server::config::{NetcodeConfig, PacketConfig, ServerConfig};
         pub use crate::server::connection::ConnectionManager;
+        pub use crate::server::diagnostics::ServerDiagnosticsPlugin;
         pub use crate::server::error::ServerError;
         pub use crate::server::events::{
             ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
             DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
         };
+        #[cfg(feature = "leafwing")]
+        pub use crate::server::input::leafwing::ReceivedInputMessages;
         pub use crate::server::io::config::ServerTransport;
         pub use crate::server::io::Io;
+        pub use crate::server::lag_compensation::{
+            LagCompensatedState, LagCompensation, LagCompensationHistory, LagCompensationPlugin,
+            LagCompensationSet,
+        };
         pub use crate::server::networking::{NetworkingState, ServerCommands};
         pub use crate::server::plugin::ServerPlugins;
         pub use crate::server::relevance::immediate::RelevanceManager;
         pub use crate::server::relevance::room::{RoomId, RoomManager};
+        pub use crate::server::relevance::spatial::{
+            SpatialGrid, SpatialPosition, SpatialRelevancePlugin, SpatialViewer,
+        };
         pub use crate::server::replication::commands::AuthorityCommandExt;
         pub use crate::server::replication::commands::DespawnReplicationCommandExt;
         pub use crate::server::replication::{
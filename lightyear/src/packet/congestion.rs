@@ -0,0 +1,274 @@
+//! Congestion control: adjust the send budget of a connection based on the network conditions
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::{App, Plugin, Reflect};
+use bevy::utils::Duration;
+
+use crate::connection::netcode::MAX_PACKET_SIZE;
+
+/// Signals used to detect congestion
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub enum CongestionControlMode {
+    /// Additive increase, multiplicative decrease: the budget shrinks when packets are being lost,
+    /// and grows slowly otherwise
+    #[default]
+    Aimd,
+    /// Same as [`CongestionControlMode::Aimd`], but the budget also shrinks when the RTT grows above the
+    /// minimum RTT observed on the connection. This reacts to queues building up before packets start getting dropped.
+    DelayBased,
+}
+
+/// Configuration of the congestion controller.
+///
+/// If enabled, the bandwidth quota of the connection is replaced with a send budget that adapts
+/// to the RTT and the packet loss of the connection.
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct CongestionControlConfig {
+    pub mode: CongestionControlMode,
+    /// Send budget when the connection starts, in bytes per second
+    pub initial_bandwidth: u32,
+    /// The budget never goes below this value, in bytes per second
+    pub min_bandwidth: u32,
+    /// The budget never goes above this value, in bytes per second
+    pub max_bandwidth: u32,
+    /// How much the budget grows every second while no congestion is detected, in bytes per second
+    pub additive_increase: u32,
+    /// Factor applied to the budget when congestion is detected
+    pub multiplicative_decrease: f32,
+    /// Packet loss above which new losses are considered to be caused by congestion.
+    /// Represented as a value between 0 and 1
+    pub loss_threshold: f32,
+    /// For [`CongestionControlMode::DelayBased`], how much the RTT can grow above the minimum RTT
+    /// before we consider that the link is congested
+    pub delay_threshold: Duration,
+    /// Maximum number of bytes that can be sent in a burst, expressed as a duration of the current budget
+    pub burst_duration: Duration,
+}
+
+impl Default for CongestionControlConfig {
+    fn default() -> Self {
+        Self {
+            mode: CongestionControlMode::Aimd,
+            initial_bandwidth: 56_000,
+            min_bandwidth: 8_000,
+            max_bandwidth: 1_000_000,
+            additive_increase: 8_000,
+            multiplicative_decrease: 0.75,
+            loss_threshold: 0.05,
+            delay_threshold: Duration::from_millis(50),
+            burst_duration: Duration::from_millis(100),
+        }
+    }
+}
+
+impl CongestionControlConfig {
+    pub fn with_mode(mut self, mode: CongestionControlMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_bandwidth_range(mut self, min_bandwidth: u32, max_bandwidth: u32) -> Self {
+        self.min_bandwidth = min_bandwidth;
+        self.max_bandwidth = max_bandwidth;
+        self
+    }
+
+    pub fn with_initial_bandwidth(mut self, initial_bandwidth: u32) -> Self {
+        self.initial_bandwidth = initial_bandwidth;
+        self
+    }
+}
+
+/// Keeps track of the send budget of a connection
+#[derive(Debug)]
+pub(crate) struct CongestionController {
+    config: CongestionControlConfig,
+    /// Current send budget, in bytes per second
+    budget: f64,
+    /// Number of bytes that can be sent right now. Can be negative if we sent more than the budget
+    tokens: f64,
+    /// Minimum RTT observed on the connection, used as the baseline for the delay-based mode
+    min_rtt: Option<Duration>,
+    /// Time elapsed since the budget was last decreased
+    since_decrease: Duration,
+}
+
+impl CongestionController {
+    pub(crate) fn new(config: CongestionControlConfig) -> Self {
+        let budget = config
+            .initial_bandwidth
+            .clamp(config.min_bandwidth, config.max_bandwidth) as f64;
+        let mut controller = Self {
+            config,
+            budget,
+            tokens: 0.0,
+            min_rtt: None,
+            since_decrease: Duration::MAX,
+        };
+        controller.tokens = controller.capacity();
+        controller
+    }
+
+    /// Current send budget, in bytes per second
+    pub(crate) fn budget(&self) -> u32 {
+        self.budget as u32
+    }
+
+    /// Maximum number of tokens that can be accumulated. We always allow at least one full packet
+    fn capacity(&self) -> f64 {
+        (self.budget * self.config.burst_duration.as_secs_f64()).max(MAX_PACKET_SIZE as f64)
+    }
+
+    /// Update the budget using the latest statistics of the connection.
+    ///
+    /// `lost_packets` is the number of packets that were declared lost since the last update,
+    /// and `packet_loss` the packet loss over a rolling window.
+    pub(crate) fn update(
+        &mut self,
+        delta: Duration,
+        rtt: Duration,
+        packet_loss: f32,
+        lost_packets: usize,
+    ) {
+        self.since_decrease = self.since_decrease.saturating_add(delta);
+        if rtt > Duration::ZERO {
+            self.min_rtt = Some(self.min_rtt.map_or(rtt, |min_rtt| min_rtt.min(rtt)));
+        }
+        let loss_congestion = lost_packets > 0 && packet_loss > self.config.loss_threshold;
+        let delay_congestion = self.config.mode == CongestionControlMode::DelayBased
+            && self
+                .min_rtt
+                .is_some_and(|min_rtt| rtt > min_rtt + self.config.delay_threshold);
+        if loss_congestion || delay_congestion {
+            // only react once per round-trip: the effect of the previous decrease
+            // cannot be observed before that
+            if self.since_decrease >= rtt {
+                self.budget *= self.config.multiplicative_decrease as f64;
+                self.since_decrease = Duration::ZERO;
+            }
+        } else {
+            self.budget += self.config.additive_increase as f64 * delta.as_secs_f64();
+        }
+        self.budget = self.budget.clamp(
+            self.config.min_bandwidth as f64,
+            self.config.max_bandwidth as f64,
+        );
+        self.tokens = (self.tokens + self.budget * delta.as_secs_f64()).min(self.capacity());
+    }
+
+    /// Consume the tokens for a message of `bytes` bytes if there is enough budget left.
+    pub(crate) fn try_consume(&mut self, bytes: u32) -> bool {
+        if self.tokens < bytes as f64 {
+            return false;
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
+
+    /// Consume tokens even if the budget is exceeded
+    pub(crate) fn consume(&mut self, bytes: u32) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+/// Plugin that registers the diagnostics related to congestion control
+pub struct CongestionDiagnosticsPlugin {
+    pub history_len: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for CongestionDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_len: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl CongestionDiagnosticsPlugin {
+    /// Current send budget of the connection
+    pub const SEND_BUDGET: DiagnosticPath =
+        DiagnosticPath::const_new("congestion.send_budget.kbps");
+
+    pub(crate) fn add_measurements(budget: Option<u32>, mut diagnostics: Diagnostics) {
+        if let Some(budget) = budget {
+            diagnostics.add_measurement(&Self::SEND_BUDGET, || budget as f64 / 1000.0);
+        }
+    }
+}
+
+impl Plugin for CongestionDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(
+            Diagnostic::new(Self::SEND_BUDGET)
+                .with_suffix("kB/s")
+                .with_max_history_length(self.history_len),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aimd() {
+        let config = CongestionControlConfig::default();
+        let mut controller = CongestionController::new(config);
+        let rtt = Duration::from_millis(100);
+
+        // no congestion: additive increase
+        controller.update(Duration::from_secs(1), rtt, 0.0, 0);
+        assert_eq!(controller.budget(), 64_000);
+
+        // losses under the threshold are not considered congestion
+        controller.update(Duration::from_secs(1), rtt, 0.01, 1);
+        assert_eq!(controller.budget(), 72_000);
+
+        // congestion: multiplicative decrease
+        controller.update(Duration::from_millis(50), rtt, 0.1, 1);
+        assert_eq!(controller.budget(), 54_000);
+        // we don't decrease again before a RTT has elapsed
+        controller.update(Duration::from_millis(50), rtt, 0.1, 1);
+        assert_eq!(controller.budget(), 54_000);
+        controller.update(Duration::from_millis(50), rtt, 0.1, 1);
+        assert_eq!(controller.budget(), 40_500);
+
+        // the budget is bounded
+        for _ in 0..100 {
+            controller.update(Duration::from_secs(1), rtt, 0.1, 1);
+        }
+        assert_eq!(controller.budget(), config.min_bandwidth);
+    }
+
+    #[test]
+    fn test_delay_based() {
+        let config =
+            CongestionControlConfig::default().with_mode(CongestionControlMode::DelayBased);
+        let mut controller = CongestionController::new(config);
+        controller.update(Duration::from_secs(1), Duration::from_millis(50), 0.0, 0);
+        assert_eq!(controller.budget(), 64_000);
+        // the RTT increased because of queuing
+        controller.update(
+            Duration::from_millis(10),
+            Duration::from_millis(150),
+            0.0,
+            0,
+        );
+        assert_eq!(controller.budget(), 48_000);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let config = CongestionControlConfig::default()
+            .with_initial_bandwidth(100_000)
+            .with_bandwidth_range(100_000, 100_000);
+        let mut controller = CongestionController::new(config);
+        // the burst capacity is 100ms of budget
+        assert!(controller.try_consume(10_000));
+        assert!(!controller.try_consume(1));
+        controller.update(Duration::from_millis(10), Duration::ZERO, 0.0, 0);
+        assert!(controller.try_consume(1_000));
+        assert!(!controller.try_consume(1));
+    }
+}
@@ -1,4 +1,4 @@
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 use byteorder::NetworkEndian;
 use byteorder::ReadBytesExt;
 use ringbuffer::{ConstGenericRingBuffer, RingBuffer};
@@ -106,6 +106,9 @@ pub struct PacketHeaderManager {
     // so we can resend them when dropped
     // sent_packets_not_acked: HashSet<PacketId>,
     sent_packets_not_acked: HashMap<PacketId, WrappedTime>,
+    /// Path MTU probes that have not been acked yet. They are expected to be lost,
+    /// so they are not included in the packet stats
+    sent_probes_not_acked: HashSet<PacketId>,
     stats_manager: PacketStatsManager,
 
     // channel to notify the sender of the packet_id of the packets that were delivered
@@ -133,6 +136,7 @@ impl PacketHeaderManager {
             stats_manager: PacketStatsManager::default(),
             // sent_packets_not_acked: HashSet::with_capacity(MAX_SEND_PACKET_QUEUE_SIZE as usize),
             sent_packets_not_acked: HashMap::new(),
+            sent_probes_not_acked: HashSet::new(),
             recv_buffer: ReceiveBuffer::new(),
             // ack_notification_sender,
             // ack_notification_receiver,
@@ -161,7 +165,9 @@ impl PacketHeaderManager {
             if self.current_time - (*time_sent) > nack_duration {
                 trace!("sent packet got lost");
                 lost_packets.push(*packet_id);
-                self.stats_manager.sent_packet_lost();
+                if !self.sent_probes_not_acked.remove(packet_id) {
+                    self.stats_manager.sent_packet_lost();
+                }
                 return false;
             }
             true
@@ -169,6 +175,16 @@ impl PacketHeaderManager {
         lost_packets
     }
 
+    /// Fraction of the packets we sent recently that were lost
+    pub(crate) fn packet_loss(&self) -> f32 {
+        self.stats_manager.packet_loss()
+    }
+
+    /// Fraction of the packets sent recently by the remote peer that we did not receive
+    pub(crate) fn received_packet_loss(&self) -> f32 {
+        self.stats_manager.received_packet_loss()
+    }
+
     // /// Get the receiver for the ack notification channel
     // /// It can be cloned if we need multiple receivers
     // pub fn get_ack_receiver(&self) -> &Receiver<PacketId> {
@@ -196,21 +212,20 @@ impl PacketHeaderManager {
     pub(crate) fn process_recv_packet_header(&mut self, header: &PacketHeader) -> Vec<PacketId> {
         // update the receive buffer
         self.stats_manager.received_packet();
-        self.recv_buffer.recv_packet(header.packet_id);
+        let new_packet_ids = self.recv_buffer.recv_packet(header.packet_id);
+        self.stats_manager.expected_received_packets(new_packet_ids);
 
         let mut newly_acked_packets = Vec::new();
 
         // read the ack information (ack id + ack bitfield) from the received header, and update
         // the list of our sent packets that have not been acked yet
         if let Some(packet) = self.update_sent_packets_not_acked(&header.last_ack_packet_id) {
-            self.stats_manager.sent_packet_acked();
             newly_acked_packets.push(packet);
         }
         for i in 1..=ACK_BITFIELD_SIZE {
             let packet_id = PacketId(header.last_ack_packet_id.wrapping_sub(i as u16));
             if header.get_bitfield_bit(i - 1) {
                 if let Some(packet) = self.update_sent_packets_not_acked(&packet_id) {
-                    self.stats_manager.sent_packet_acked();
                     newly_acked_packets.push(packet)
                 }
             }
@@ -230,6 +245,9 @@ impl PacketHeaderManager {
             // self.ack_notification_sender.send(*packet_id)?;
 
             self.sent_packets_not_acked.remove(packet_id);
+            if !self.sent_probes_not_acked.remove(packet_id) {
+                self.stats_manager.sent_packet_acked();
+            }
             return Some(*packet_id);
         }
         None
@@ -252,7 +270,11 @@ impl PacketHeaderManager {
             tick: Tick(0),
         };
         // we build the header only when we actually send the packet, so computing the stats here is valid
-        self.stats_manager.sent_packet();
+        if packet_type == PacketType::Probe {
+            self.sent_probes_not_acked.insert(self.next_packet_id);
+        } else {
+            self.stats_manager.sent_packet();
+        }
         // keep track of when we sent the packet (so that if we don't get an ack after a certain amount of time we can consider it lost)
         self.sent_packets_not_acked
             .insert(self.next_packet_id, self.current_time);
@@ -289,17 +311,20 @@ impl ReceiveBuffer {
     }
 
     /// Receive a new packet id and update the receive buffer accordingly
-    fn recv_packet(&mut self, id: PacketId) {
+    ///
+    /// Returns by how much the most recent packet id increased, i.e. how many new packets
+    /// the remote peer has sent (including the ones that we did not receive)
+    fn recv_packet(&mut self, id: PacketId) -> u32 {
         // special case: this is the first packet we receive
         if self.last_recv_packet_id.is_none() {
             self.last_recv_packet_id = Some(id);
-            return;
+            return 1;
         }
 
         let bitfield_size = ACK_BITFIELD_SIZE as i16;
         let diff = self.last_recv_packet_id.unwrap() - id;
         if diff > bitfield_size {
-            return;
+            return 0;
         }
         // the packet id is in the existing bitfield; update the corresponding bit
         if diff > 0 {
@@ -325,7 +350,9 @@ impl ReceiveBuffer {
 
             // update the most recent packet received
             self.last_recv_packet_id = Some(id);
+            return diff.unsigned_abs() as u32;
         }
+        0
     }
 
     /// Convert the Receive Buffer to the bitfield that we need to send in the PacketHeader
@@ -349,6 +376,8 @@ impl ReceiveBuffer {
 // TODO: add test for notification of packet delivered
 #[cfg(test)]
 mod tests {
+    use bevy::utils::Duration;
+
     use crate::serialize::ToBytes;
 
     use super::*;
@@ -385,7 +414,7 @@ mod tests {
 
         // receive a packet which is in the past
         // -ACK_BITFIELD_SIZE < diff_id < 0
-        recv_buffer.recv_packet(PacketId(2));
+        assert_eq!(recv_buffer.recv_packet(PacketId(2)), 0);
         assert_eq!(recv_buffer.last_recv_packet_id, Some(PacketId(6)));
         assert_eq!(recv_buffer.get_bitfield(), 0b0011_1100u32);
 
@@ -399,11 +428,40 @@ mod tests {
 
         // receive a packet that is too far in the past
         // diff_id < -ACK_BITFIELD_SIZE
-        recv_buffer.recv_packet(PacketId(49));
+        assert_eq!(recv_buffer.recv_packet(PacketId(49)), 0);
         assert_eq!(recv_buffer.last_recv_packet_id, Some(PacketId(82)));
         assert_eq!(recv_buffer.get_bitfield(), 1 << (32 - 1));
     }
 
+    /// Path MTU probes are not included in the packet loss
+    #[test]
+    fn test_probes_packet_loss() {
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(Default::default());
+        let mut header_manager = PacketHeaderManager::new(1.5);
+        let acked = header_manager.prepare_send_packet_header(PacketType::Data);
+        let lost = header_manager.prepare_send_packet_header(PacketType::Data);
+        let probe = header_manager.prepare_send_packet_header(PacketType::Probe);
+
+        let header = PacketHeader {
+            packet_type: PacketType::Data,
+            packet_id: PacketId(0),
+            last_ack_packet_id: acked.packet_id,
+            ack_bitfield: 0,
+            tick: Tick(0),
+        };
+        assert_eq!(
+            header_manager.process_recv_packet_header(&header),
+            vec![acked.packet_id]
+        );
+
+        time_manager.update(Duration::from_secs(10));
+        let mut lost_packets = header_manager.update(&time_manager, &ping_manager);
+        lost_packets.sort();
+        assert_eq!(lost_packets, vec![lost.packet_id, probe.packet_id]);
+        assert_eq!(header_manager.packet_loss(), 1.0 / 2.0);
+    }
+
     #[test]
     fn test_serde_header() -> Result<(), SerializationError> {
         let header = PacketHeader {
@@ -119,20 +119,35 @@ pub struct SingleData {
     // TODO: MessageId is from 1 to 65535, so that we can use 0 to represent None?
     pub id: Option<MessageId>,
     pub bytes: Bytes,
+    /// True if the message expired or was cancelled on the sender side: it has no content, and
+    /// only tells the reliable receivers that they should not wait for this message id anymore
+    pub tombstone: bool,
 }
 
+const SINGLE_NO_ID: u8 = 0;
+const SINGLE_WITH_ID: u8 = 1;
+const SINGLE_TOMBSTONE: u8 = 2;
+
 impl ToBytes for SingleData {
     // TODO: how to avoid the option taking 1 byte?
     fn len(&self) -> usize {
+        if self.tombstone {
+            return 3;
+        }
         varint_len(self.bytes.len() as u64) + self.bytes.len() + self.id.map_or(1, |_| 3)
     }
 
     fn to_bytes<T: WriteBytesExt>(&self, buffer: &mut T) -> Result<(), SerializationError> {
+        if self.tombstone {
+            buffer.write_u8(SINGLE_TOMBSTONE)?;
+            buffer.write_u16::<NetworkEndian>(self.id.map_or(0, |id| id.0))?;
+            return Ok(());
+        }
         if let Some(id) = self.id {
-            buffer.write_u8(1)?;
+            buffer.write_u8(SINGLE_WITH_ID)?;
             buffer.write_u16::<NetworkEndian>(id.0)?;
         } else {
-            buffer.write_u8(0)?;
+            buffer.write_u8(SINGLE_NO_ID)?;
         }
         self.bytes.to_bytes(buffer)?;
         // buffer.write_varint(self.bytes.len() as u64)?;
@@ -144,21 +159,42 @@ impl ToBytes for SingleData {
     where
         Self: Sized,
     {
-        let id = if buffer.read_u8()? == 1 {
-            Some(MessageId(buffer.read_u16::<NetworkEndian>()?))
-        } else {
-            None
+        let id = match buffer.read_u8()? {
+            SINGLE_NO_ID => None,
+            SINGLE_WITH_ID => Some(MessageId(buffer.read_u16::<NetworkEndian>()?)),
+            SINGLE_TOMBSTONE => {
+                let id = MessageId(buffer.read_u16::<NetworkEndian>()?);
+                return Ok(Self::tombstone(id));
+            }
+            _ => return Err(SerializationError::InvalidValue),
         };
         let bytes = Bytes::from_bytes(buffer)?;
         // let len = buffer.read_varint()? as usize;
         // let bytes = buffer.split_len(len);
-        Ok(Self { id, bytes })
+        Ok(Self {
+            id,
+            bytes,
+            tombstone: false,
+        })
     }
 }
 
 impl SingleData {
     pub fn new(id: Option<MessageId>, bytes: Bytes) -> Self {
-        Self { id, bytes }
+        Self {
+            id,
+            bytes,
+            tombstone: false,
+        }
+    }
+
+    /// Placeholder sent on reliable channels instead of a message that expired or was cancelled
+    pub fn tombstone(id: MessageId) -> Self {
+        Self {
+            id: Some(id),
+            bytes: Bytes::new(),
+            tombstone: true,
+        }
     }
 }
 
@@ -245,6 +281,18 @@ mod tests {
             let decoded = SingleData::from_bytes(&mut reader).unwrap();
             assert_eq!(decoded, data);
         }
+        {
+            let data = SingleData::tombstone(MessageId(1));
+            let mut writer = vec![];
+            data.to_bytes(&mut writer).unwrap();
+
+            assert_eq!(writer.len(), data.len());
+
+            let mut reader = writer.into();
+            let decoded = SingleData::from_bytes(&mut reader).unwrap();
+            assert_eq!(decoded, data);
+            assert_ne!(decoded, SingleData::new(Some(MessageId(1)), Bytes::new()));
+        }
     }
 
     #[test]
@@ -1,3 +1,4 @@
+use bevy::utils::Duration;
 use byteorder::ReadBytesExt;
 use bytes::Bytes;
 use crossbeam_channel::{Receiver, Sender};
@@ -16,7 +17,8 @@ use crate::packet::header::PacketHeader;
 use crate::packet::message::{
     FragmentData, MessageAck, MessageId, ReceiveMessage, SendMessage, SingleData,
 };
-use crate::packet::packet::PacketId;
+use crate::packet::mtu::{MtuDiscovery, MtuDiscoveryConfig};
+use crate::packet::packet::{fragment_size, PacketId};
 use crate::packet::packet_builder::{PacketBuilder, Payload, RecvPayload};
 use crate::packet::packet_type::PacketType;
 use crate::packet::priority_manager::{PriorityConfig, PriorityManager};
@@ -50,6 +52,8 @@ pub struct MessageManager {
     /// reliable senders can stop trying to send a message that has already been received
     packet_to_message_ack_map: HashMap<PacketId, Vec<(ChannelKind, MessageAck)>>,
     nack_senders: Vec<Sender<MessageId>>,
+    /// Finds the largest packet size that can be used on this connection
+    mtu_discovery: Option<MtuDiscovery>,
 }
 
 impl MessageManager {
@@ -57,15 +61,37 @@ impl MessageManager {
         channel_registry: &ChannelRegistry,
         nack_rtt_multiple: f32,
         priority_config: PriorityConfig,
+        mtu_discovery: Option<MtuDiscoveryConfig>,
     ) -> Self {
-        Self {
+        let mut manager = Self {
             packet_manager: PacketBuilder::new(nack_rtt_multiple),
             priority_manager: PriorityManager::new(priority_config),
             channels: channel_registry.channels(),
             channel_registry: channel_registry.clone(),
             packet_to_message_ack_map: HashMap::new(),
             nack_senders: vec![],
+            mtu_discovery: mtu_discovery.map(MtuDiscovery::new),
+        };
+        if let Some(mtu_discovery) = &manager.mtu_discovery {
+            // fragments must fit in a packet even if the packet size shrinks later,
+            // since reliable fragments are resent without being re-fragmented
+            let fragment_size = fragment_size(mtu_discovery.min_packet_size());
+            manager.set_packet_size(mtu_discovery.packet_size());
+            for channel in manager.channels.values_mut() {
+                channel.sender.set_fragment_size(fragment_size);
+            }
         }
+        manager
+    }
+
+    /// Maximum size of the packets sent on this connection
+    pub(crate) fn packet_size(&self) -> usize {
+        self.packet_manager.packet_size()
+    }
+
+    /// Update the maximum size of the packets
+    fn set_packet_size(&mut self, packet_size: usize) {
+        self.packet_manager.set_packet_size(packet_size);
     }
 
     pub(crate) fn get_replication_update_send_receiver(&mut self) -> Receiver<MessageId> {
@@ -73,6 +99,16 @@ impl MessageManager {
             .subscribe_replication_update_sent_messages()
     }
 
+    /// Fraction of the packets sent recently by the remote peer that we did not receive
+    pub(crate) fn received_packet_loss(&self) -> f32 {
+        self.packet_manager.header_manager.received_packet_loss()
+    }
+
+    /// Current send budget in bytes per second, if congestion control is enabled
+    pub(crate) fn send_budget(&self) -> Option<u32> {
+        self.priority_manager.send_budget()
+    }
+
     /// Update bookkeeping
     #[cfg_attr(feature = "trace", instrument(level = Level::INFO, skip_all))]
     pub fn update(
@@ -82,10 +118,28 @@ impl MessageManager {
         tick_manager: &TickManager,
     ) {
         // on the sender side, gather the list of packets that haven't been received by the remote peer
-        let lost_packets = self
+        let mut lost_packets = self
             .packet_manager
             .header_manager
             .update(time_manager, ping_manager);
+        if let Some(mtu_discovery) = self.mtu_discovery.as_mut() {
+            mtu_discovery.update(time_manager.delta());
+            // lost probes are expected and don't contain any messages
+            lost_packets.retain(|packet_id| !mtu_discovery.packet_lost(*packet_id));
+            // the packet size shrinks if the network path stopped supporting it
+            let packet_size = mtu_discovery.packet_size();
+            if packet_size != self.packet_size() {
+                self.set_packet_size(packet_size);
+            }
+        }
+        if let Some(controller) = self.priority_manager.congestion_controller.as_mut() {
+            controller.update(
+                time_manager.delta(),
+                ping_manager.rtt(),
+                self.packet_manager.header_manager.packet_loss(),
+                lost_packets.len(),
+            );
+        }
         // notify that some messages have been lost
         for lost_packet in lost_packets {
             if let Some(message_map) = self.packet_to_message_ack_map.remove(&lost_packet) {
@@ -148,6 +202,64 @@ impl MessageManager {
         Ok(channel.sender.buffer_send(message, priority)?)
     }
 
+    /// Buffer a message to be sent on this connection, with a time-to-live that overrides the one of the channel
+    /// (`None` means that the message never expires).
+    /// Returns the message id associated with the message, if there is one
+    pub fn buffer_send_with_ttl(
+        &mut self,
+        message: Bytes,
+        channel_kind: ChannelKind,
+        priority: f32,
+        ttl: Option<Duration>,
+    ) -> Result<Option<MessageId>, PacketError> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        Ok(channel
+            .sender
+            .buffer_send_with_ttl(message, priority, ttl)?)
+    }
+
+    /// Stop trying to send a message that hasn't been acked yet.
+    ///
+    /// Returns true if the message was cancelled
+    pub fn cancel(
+        &mut self,
+        message_id: MessageId,
+        channel_kind: ChannelKind,
+    ) -> Result<bool, PacketError> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        Ok(channel.sender.cancel(message_id))
+    }
+
+    /// Get notified of the ids of the messages of a channel that were acked by the remote peer
+    pub fn subscribe_acks(
+        &mut self,
+        channel_kind: ChannelKind,
+    ) -> Result<Receiver<MessageId>, PacketError> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        Ok(channel.sender.subscribe_acks())
+    }
+
+    /// Get notified of the ids of the messages of a channel that were lost or expired
+    pub fn subscribe_nacks(
+        &mut self,
+        channel_kind: ChannelKind,
+    ) -> Result<Receiver<MessageId>, PacketError> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .ok_or(PacketError::ChannelNotFound)?;
+        Ok(channel.sender.subscribe_nacks())
+    }
+
     /// Prepare buckets from the internal send buffers, and return the bytes to send
     // TODO: maybe pass TickManager instead of Tick? Find a more elegant way to pass extra data that might not be used?
     //  (ticks are not purely necessary without client prediction)
@@ -157,6 +269,24 @@ impl MessageManager {
         // Step 1. Get the list of packets to send from all channels
         // for each channel, prepare packets using the buffered messages that are ready to be sent
         // TODO: iterate through the channels in order of channel priority? (with accumulation)
+        let mut bytes = Vec::new();
+        // Step 0. Send a probe to discover the path MTU, if needed
+        if let Some(size) = self
+            .mtu_discovery
+            .as_ref()
+            .and_then(|mtu_discovery| mtu_discovery.next_probe_size())
+        {
+            let (packet_id, probe) = self.packet_manager.build_probe_packet(size, current_tick)?;
+            trace!(?packet_id, ?size, "sending path MTU probe");
+            if let Some(mtu_discovery) = self.mtu_discovery.as_mut() {
+                mtu_discovery.probe_sent(packet_id, size);
+            }
+            if self.priority_manager.is_enabled() {
+                self.priority_manager.add_sent_bytes(size as u32);
+            }
+            bytes.push(probe);
+        }
+
         let mut data_to_send: Vec<(NetId, (VecDeque<SendMessage>, VecDeque<SendMessage>))> = vec![];
         let mut has_data_to_send = false;
         for (channel_kind, channel) in self.channels.iter_mut() {
@@ -165,6 +295,10 @@ impl MessageManager {
                 .get_net_from_kind(channel_kind)
                 .ok_or(PacketError::ChannelNotFound)?;
             let (single_data, fragment_data) = channel.sender.send_packet();
+            #[cfg(feature = "trace")]
+            channel
+                .sender_stats
+                .add_retransmissions(channel.sender.take_num_retransmissions());
 
             if !single_data.is_empty() || !fragment_data.is_empty() {
                 trace!(?channel_id, "send message with channel_id");
@@ -174,7 +308,7 @@ impl MessageManager {
         }
         // return early if there are no messages to send
         if !has_data_to_send {
-            return Ok(vec![]);
+            return Ok(bytes);
         }
 
         // priority manager: get the list of messages we can send according to the rate limiter
@@ -223,7 +357,7 @@ impl MessageManager {
         //     trace!(?packet, "packet to send");
         // }
 
-        let mut bytes = Vec::new();
+        let num_probes = bytes.len();
         for mut packet in packets {
             trace!(packet_id = ?packet.packet_id, num_messages = ?packet.num_messages(), "sending packet");
             // TODO: should we update this to include fragment info as well?
@@ -259,16 +393,13 @@ impl MessageManager {
         }
 
         // adjust the real amount of bytes that we sent through the limiter (to account for the actual packet size)
-        if self.priority_manager.config.enabled {
-            let total_bytes_sent = bytes.iter().map(|b| b.len() as u32).sum::<u32>();
-            if let Ok(remaining_bytes_to_add) =
-                (total_bytes_sent - num_bytes_added_to_limiter).try_into()
-            {
-                let _ = self
-                    .priority_manager
-                    .limiter
-                    .check_n(remaining_bytes_to_add);
-            }
+        if self.priority_manager.is_enabled() {
+            let total_bytes_sent = bytes[num_probes..]
+                .iter()
+                .map(|b| b.len() as u32)
+                .sum::<u32>();
+            self.priority_manager
+                .add_sent_bytes(total_bytes_sent.saturating_sub(num_bytes_added_to_limiter));
         }
 
         Ok(bytes)
@@ -303,6 +434,13 @@ impl MessageManager {
         // Step 3. Update the list of messages that have been acked
         for acked_packet in acked_packets {
             trace!("Acked packet {:?}", acked_packet);
+            if let Some(packet_size) = self
+                .mtu_discovery
+                .as_mut()
+                .and_then(|mtu_discovery| mtu_discovery.packet_acked(acked_packet))
+            {
+                self.set_packet_size(packet_size);
+            }
             if let Some(message_acks) = self.packet_to_message_ack_map.remove(&acked_packet) {
                 for (channel_kind, message_ack) in message_acks {
                     let channel_name = self
@@ -323,6 +461,11 @@ impl MessageManager {
             }
         }
 
+        // probes only contain padding
+        if header.get_packet_type() == PacketType::Probe {
+            return Ok(tick);
+        }
+
         // Step 4. Parse the payload into messages, put them in the internal buffers for each channel
         // we read directly from the packet and don't create intermediary datastructures to avoid allocations
         // TODO: maybe do this in a helper function?
@@ -436,6 +579,7 @@ mod tests {
 
     use bevy::prelude::default;
 
+    use crate::connection::netcode::MAX_PACKET_SIZE;
     use crate::packet::message::MessageId;
     use crate::packet::packet::FRAGMENT_SIZE;
     use crate::packet::priority_manager::PriorityConfig;
@@ -458,9 +602,9 @@ mod tests {
 
         // Create message managers
         let client_message_manager =
-            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default(), None);
         let server_message_manager =
-            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default());
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default(), None);
         (client_message_manager, server_message_manager)
     }
 
@@ -687,4 +831,112 @@ mod tests {
         assert_eq!(update_acks_tracker.try_recv().unwrap(), message_id);
         Ok(())
     }
+
+    #[test]
+    /// The packet size grows once a probe has been acked
+    fn test_message_manager_mtu_discovery() -> Result<(), PacketError> {
+        let (_, mut server_message_manager) = setup();
+        let mtu_config = MtuDiscoveryConfig::default()
+            .with_packet_size_range(508, MAX_PACKET_SIZE)
+            .with_probe_interval(bevy::utils::Duration::ZERO);
+        let mut client_message_manager = MessageManager::new(
+            &server_message_manager.channel_registry,
+            1.5,
+            PriorityConfig::default(),
+            Some(mtu_config),
+        );
+        assert_eq!(client_message_manager.packet_size(), 508);
+
+        // the message is fragmented according to the minimum packet size
+        let message = Bytes::from(vec![1u8; 1000]);
+        client_message_manager.buffer_send(message.clone(), Channel1::kind())?;
+        let payloads = client_message_manager.send_packets(Tick(0))?;
+        // 1 probe + 3 fragments
+        assert_eq!(payloads.len(), 4);
+        assert_eq!(payloads[0].len(), MAX_PACKET_SIZE);
+        assert!(payloads[1..].iter().all(|payload| payload.len() <= 508));
+
+        for payload in payloads {
+            server_message_manager.recv_packet(payload.into())?;
+        }
+        let data = MessageManager::collect_messages(server_message_manager.read_messages());
+        assert_eq!(
+            data.get(&Channel1::kind()).unwrap(),
+            &vec![(Tick(0), message)]
+        );
+
+        // the server acks the probe
+        server_message_manager.buffer_send(vec![1].into(), Channel1::kind())?;
+        for payload in server_message_manager.send_packets(Tick(0))? {
+            client_message_manager.recv_packet(payload.into())?;
+        }
+        assert_eq!(client_message_manager.packet_size(), MAX_PACKET_SIZE);
+        Ok(())
+    }
+
+    #[test]
+    /// A reliable message that was fragmented before the path MTU shrank must still be delivered
+    fn test_message_manager_mtu_shrinks_with_fragments_in_flight() -> Result<(), PacketError> {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        });
+        let mtu_config = MtuDiscoveryConfig {
+            probe_interval: Duration::ZERO,
+            research_interval: Duration::ZERO,
+            ..MtuDiscoveryConfig::default().with_packet_size_range(508, MAX_PACKET_SIZE)
+        };
+        let mut client_message_manager = MessageManager::new(
+            &channel_registry,
+            1.5,
+            PriorityConfig::default(),
+            Some(mtu_config),
+        );
+        let mut server_message_manager =
+            MessageManager::new(&channel_registry, 1.5, PriorityConfig::default(), None);
+        let mut time_manager = TimeManager::default();
+        let ping_manager = PingManager::new(crate::shared::ping::manager::PingConfig::default());
+        let tick_manager = TickManager::from_config(crate::shared::tick_manager::TickConfig::new(
+            Duration::from_millis(16),
+        ));
+
+        // the path supports the maximum packet size
+        for payload in client_message_manager.send_packets(Tick(0))? {
+            server_message_manager.recv_packet(payload.into())?;
+        }
+        server_message_manager.buffer_send(vec![1].into(), Channel1::kind())?;
+        for payload in server_message_manager.send_packets(Tick(0))? {
+            client_message_manager.recv_packet(payload.into())?;
+        }
+        assert_eq!(client_message_manager.packet_size(), MAX_PACKET_SIZE);
+
+        // a big reliable message is sent, but all its fragments are lost
+        let message = Bytes::from(vec![1u8; 2000]);
+        client_message_manager.buffer_send(message.clone(), Channel1::kind())?;
+        let payloads = client_message_manager.send_packets(Tick(0))?;
+        assert!(payloads.iter().all(|payload| payload.len() <= 508));
+
+        // the path now drops the packets bigger than 1000 bytes: the packet size shrinks
+        // and the fragments that are resent still go through
+        let mut received = vec![];
+        for _ in 0..30 {
+            time_manager.update(Duration::from_secs(1));
+            client_message_manager.update(&time_manager, &ping_manager, &tick_manager);
+            for payload in client_message_manager.send_packets(Tick(0))? {
+                if payload.len() <= 1000 {
+                    server_message_manager.recv_packet(payload.into())?;
+                }
+            }
+            let mut data = MessageManager::collect_messages(server_message_manager.read_messages());
+            received.extend(data.remove(&Channel1::kind()).unwrap_or_default());
+            server_message_manager.buffer_send(vec![1].into(), Channel1::kind())?;
+            for payload in server_message_manager.send_packets(Tick(0))? {
+                client_message_manager.recv_packet(payload.into())?;
+            }
+        }
+        assert!(client_message_manager.packet_size() <= 1000);
+        assert!(received.iter().any(|(_, bytes)| *bytes == message));
+        Ok(())
+    }
 }
@@ -19,9 +19,15 @@ Packets that are over the maximum packet size will be fragmented into multiple [
 [`FragmentData`]: message::FragmentData
 */
 
+/// Adjusts the send budget of a connection based on the RTT and packet loss
+pub mod congestion;
+
 /// Manages the [`PacketHeader`](header::PacketHeader) which includes important packet information
 pub(crate) mod header;
 
+/// Discovers the largest packet size that can be used on a connection
+pub mod mtu;
+
 pub(crate) mod message;
 
 /// Manages sending and receiving [`Packets`](packet::Packet) over the network
@@ -0,0 +1,303 @@
+//! Path MTU discovery: find the largest packet size that can reach the remote peer without being dropped
+//!
+//! The connection starts with a conservative packet size. Padded probe packets of increasing size are sent
+//! periodically; a probe that is acked (via the regular packet header acks) confirms that packets of that size
+//! go through, a probe that is lost multiple times means that the size is too big.
+//!
+//! Once the search has converged, the current packet size is periodically confirmed with a probe of that size.
+//! If those probes keep getting lost, the network path changed: the packet size falls back to the minimum and
+//! the search starts again.
+//!
+//! Only the packets that contain small messages use the discovered packet size. Big messages are always fragmented
+//! according to the minimum packet size: reliable fragments are resent as-is until they are acked, so they must still
+//! fit in a packet if the packet size shrinks.
+use bevy::prelude::Reflect;
+use bevy::utils::Duration;
+use tracing::debug;
+
+use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::packet::packet::PacketId;
+
+/// Configuration of the path MTU discovery
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct MtuDiscoveryConfig {
+    /// Packet size used when the connection starts. Packets of this size are assumed to always
+    /// reach the remote peer
+    pub min_packet_size: usize,
+    /// Largest packet size that will be probed. Cannot be bigger than [`MAX_PACKET_SIZE`]
+    pub max_packet_size: usize,
+    /// Minimum duration between two probes
+    pub probe_interval: Duration,
+    /// Number of probes of a given size that must be lost before we consider that the size is too big
+    pub max_probe_attempts: u8,
+    /// The search stops when the gap between the largest size that went through and the smallest size
+    /// that didn't is below this number of bytes
+    pub precision: usize,
+    /// Once the search has stopped, how long to wait before confirming that the current packet size still goes
+    /// through and probing for a larger size again (in case the network path changed)
+    pub research_interval: Duration,
+}
+
+impl Default for MtuDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            // 576 bytes (minimum IPv4 datagram size that must be supported) - 60 (IPv4 header) - 8 (UDP header)
+            min_packet_size: 508,
+            max_packet_size: MAX_PACKET_SIZE,
+            probe_interval: Duration::from_millis(250),
+            max_probe_attempts: 3,
+            precision: 16,
+            research_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl MtuDiscoveryConfig {
+    pub fn with_packet_size_range(
+        mut self,
+        min_packet_size: usize,
+        max_packet_size: usize,
+    ) -> Self {
+        self.min_packet_size = min_packet_size;
+        self.max_packet_size = max_packet_size;
+        self
+    }
+
+    pub fn with_probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+}
+
+/// A probe that has been sent but not acked or lost yet
+#[derive(Debug, Clone, Copy)]
+struct MtuProbe {
+    packet_id: PacketId,
+    size: usize,
+}
+
+/// Keeps track of the packet size that can be used on a connection
+#[derive(Debug)]
+pub(crate) struct MtuDiscovery {
+    config: MtuDiscoveryConfig,
+    max_packet_size: usize,
+    /// Largest packet size that is known to reach the remote peer
+    packet_size: usize,
+    /// Smallest packet size that is known to not reach the remote peer
+    upper_bound: usize,
+    probe: Option<MtuProbe>,
+    /// Number of probes of size `next_probe_size` that have been lost
+    failed_attempts: u8,
+    /// True if we are checking that packets of size `packet_size` still reach the remote peer
+    confirming: bool,
+    /// Time elapsed since the last probe was sent
+    elapsed: Duration,
+}
+
+impl MtuDiscovery {
+    pub(crate) fn new(config: MtuDiscoveryConfig) -> Self {
+        let max_packet_size = config.max_packet_size.min(MAX_PACKET_SIZE);
+        Self {
+            config,
+            max_packet_size,
+            packet_size: config.min_packet_size.min(max_packet_size),
+            upper_bound: max_packet_size + 1,
+            probe: None,
+            failed_attempts: 0,
+            confirming: false,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Largest packet size that can be used on this connection
+    pub(crate) fn packet_size(&self) -> usize {
+        self.packet_size
+    }
+
+    /// Packet size that is assumed to always reach the remote peer
+    pub(crate) fn min_packet_size(&self) -> usize {
+        self.config.min_packet_size.min(self.max_packet_size)
+    }
+
+    fn is_converged(&self) -> bool {
+        self.packet_size >= self.max_packet_size
+            || self.upper_bound - self.packet_size <= self.config.precision
+    }
+
+    pub(crate) fn update(&mut self, delta: Duration) {
+        self.elapsed = self.elapsed.saturating_add(delta);
+        if self.probe.is_none()
+            && !self.confirming
+            && self.is_converged()
+            && self.elapsed >= self.config.research_interval
+        {
+            self.elapsed = Duration::ZERO;
+            if self.packet_size > self.config.min_packet_size {
+                // the network path might have changed: check that the current size still goes through
+                self.confirming = true;
+            } else {
+                self.search_larger_sizes();
+            }
+        }
+    }
+
+    /// Try bigger sizes again, if the search stopped below the maximum packet size
+    fn search_larger_sizes(&mut self) {
+        if self.upper_bound <= self.max_packet_size {
+            self.upper_bound = self.max_packet_size + 1;
+        }
+    }
+
+    /// Size of the probe to send, if we should send one now
+    pub(crate) fn next_probe_size(&self) -> Option<usize> {
+        if self.probe.is_some() || self.elapsed < self.config.probe_interval {
+            return None;
+        }
+        if self.confirming {
+            return Some(self.packet_size);
+        }
+        if self.is_converged() {
+            return None;
+        }
+        // try the maximum size first, since most network paths support it
+        if self.upper_bound > self.max_packet_size {
+            return Some(self.max_packet_size);
+        }
+        Some(self.packet_size + (self.upper_bound - self.packet_size) / 2)
+    }
+
+    pub(crate) fn probe_sent(&mut self, packet_id: PacketId, size: usize) {
+        self.probe = Some(MtuProbe { packet_id, size });
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Handle the ack of a packet.
+    ///
+    /// Returns the new packet size if the packet was a probe that increased it
+    pub(crate) fn packet_acked(&mut self, packet_id: PacketId) -> Option<usize> {
+        let probe = self.probe.filter(|probe| probe.packet_id == packet_id)?;
+        self.probe = None;
+        self.failed_attempts = 0;
+        if self.confirming {
+            self.confirming = false;
+            self.search_larger_sizes();
+        }
+        if probe.size <= self.packet_size {
+            return None;
+        }
+        debug!(size = probe.size, "path MTU probe acked");
+        self.packet_size = probe.size;
+        Some(self.packet_size)
+    }
+
+    /// Handle the loss of a packet. If the current packet size could not be confirmed, [`Self::packet_size`]
+    /// falls back to the minimum packet size.
+    ///
+    /// Returns true if the packet was a probe
+    pub(crate) fn packet_lost(&mut self, packet_id: PacketId) -> bool {
+        let Some(probe) = self.probe.filter(|probe| probe.packet_id == packet_id) else {
+            return false;
+        };
+        self.probe = None;
+        self.failed_attempts += 1;
+        if self.failed_attempts >= self.config.max_probe_attempts {
+            self.failed_attempts = 0;
+            if self.confirming {
+                debug!(size = probe.size, "path MTU shrank, restarting the search");
+                self.confirming = false;
+                self.packet_size = self.min_packet_size();
+            } else {
+                debug!(size = probe.size, "path MTU probe lost");
+            }
+            self.upper_bound = self.upper_bound.min(probe.size);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the discovery on a network path that drops packets bigger than `path_mtu`
+    fn discover(discovery: &mut MtuDiscovery, path_mtu: usize, num_probes: u16) {
+        for i in 0..num_probes {
+            discovery.update(Duration::from_millis(250));
+            let Some(size) = discovery.next_probe_size() else {
+                continue;
+            };
+            let packet_id = PacketId(i);
+            discovery.probe_sent(packet_id, size);
+            if size <= path_mtu {
+                discovery.packet_acked(packet_id);
+            } else {
+                assert!(discovery.packet_lost(packet_id));
+            }
+        }
+    }
+
+    #[test]
+    fn test_discovery() {
+        let config = MtuDiscoveryConfig::default();
+        let mut discovery = MtuDiscovery::new(config);
+        assert_eq!(discovery.packet_size(), 508);
+
+        // the path supports the maximum size
+        discover(&mut discovery, 1500, 10);
+        assert_eq!(discovery.packet_size(), MAX_PACKET_SIZE);
+        assert_eq!(discovery.next_probe_size(), None);
+
+        // the path only supports a smaller size
+        let mut discovery = MtuDiscovery::new(config);
+        discover(&mut discovery, 1000, 100);
+        assert!(discovery.packet_size() <= 1000);
+        assert!(discovery.packet_size() > 1000 - config.precision);
+        assert_eq!(discovery.next_probe_size(), None);
+
+        // after a while, the current size is confirmed and bigger sizes are probed again
+        discovery.update(config.research_interval);
+        discovery.update(config.probe_interval);
+        let packet_size = discovery.packet_size();
+        assert_eq!(discovery.next_probe_size(), Some(packet_size));
+        discovery.probe_sent(PacketId(1000), packet_size);
+        assert_eq!(discovery.packet_acked(PacketId(1000)), None);
+        discovery.update(config.probe_interval);
+        assert_eq!(discovery.next_probe_size(), Some(MAX_PACKET_SIZE));
+    }
+
+    #[test]
+    fn test_probe_retries() {
+        let config = MtuDiscoveryConfig::default();
+        let mut discovery = MtuDiscovery::new(config);
+        discovery.update(config.probe_interval);
+        // a single lost probe is not enough to give up on a size
+        discovery.probe_sent(PacketId(0), MAX_PACKET_SIZE);
+        assert!(discovery.packet_lost(PacketId(0)));
+        // other packets are not probes
+        assert!(!discovery.packet_lost(PacketId(1)));
+        discovery.update(config.probe_interval);
+        assert_eq!(discovery.next_probe_size(), Some(MAX_PACKET_SIZE));
+        discovery.probe_sent(PacketId(2), MAX_PACKET_SIZE);
+        assert_eq!(discovery.packet_acked(PacketId(2)), Some(MAX_PACKET_SIZE));
+    }
+
+    #[test]
+    fn test_path_mtu_shrinks() {
+        let config = MtuDiscoveryConfig::default();
+        let mut discovery = MtuDiscovery::new(config);
+        discover(&mut discovery, 1500, 10);
+        assert_eq!(discovery.packet_size(), MAX_PACKET_SIZE);
+
+        // after a while, the current size is confirmed
+        discovery.update(config.research_interval);
+        discovery.update(config.probe_interval);
+        assert_eq!(discovery.next_probe_size(), Some(MAX_PACKET_SIZE));
+
+        // the network path now only supports a smaller size: the confirmation probes are lost
+        // and the search starts again from the minimum size
+        discover(&mut discovery, 1000, 100);
+        assert!(discovery.packet_size() <= 1000);
+        assert!(discovery.packet_size() > 1000 - config.precision);
+        assert_eq!(discovery.next_probe_size(), None);
+    }
+}
@@ -28,7 +28,12 @@ const HEADER_BYTES: usize = 11;
 /// MAX_PACKET_SIZE - HEADER_BYTES - 1 (channel_net_id) - 6 (message_id/fragment_id/num_fragments) - 2 (num bytes in fragment)
 // NOTE: this considers that we use 2 bytes for the fragment id and num_fragments, but in reality we are using
 //  varints so it could be more or less!
-pub(crate) const FRAGMENT_SIZE: usize = MAX_PACKET_SIZE - HEADER_BYTES - 9;
+pub(crate) const FRAGMENT_SIZE: usize = fragment_size(MAX_PACKET_SIZE);
+
+/// The maximum number of bytes for a message before it is fragmented, for packets of size `packet_size`
+pub(crate) const fn fragment_size(packet_size: usize) -> usize {
+    packet_size - HEADER_BYTES - 9
+}
 
 /// Data structure that will help us write the packet
 #[derive(Debug)]
@@ -39,12 +44,14 @@ pub(crate) struct Packet {
     pub(crate) packet_id: PacketId,
     // How many bytes we know we are going to have to write in the packet, but haven't written yet
     pub(crate) prewritten_size: usize,
+    /// Maximum size of the packet
+    pub(crate) max_size: usize,
 }
 
 impl Packet {
     /// Check that we can still fit some data in the buffer
     pub(crate) fn can_fit(&self, size: usize) -> bool {
-        self.payload.len() + size + self.prewritten_size <= MAX_PACKET_SIZE
+        self.payload.len() + size + self.prewritten_size <= self.max_size
     }
 
     /// Check if we can write a channel_id + the number of messages in the packet.
@@ -2,7 +2,7 @@
 use crate::connection::netcode::MAX_PACKET_SIZE;
 use crate::packet::header::PacketHeaderManager;
 use crate::packet::message::{FragmentData, MessageAck, SingleData};
-use crate::packet::packet::{Packet, FRAGMENT_SIZE};
+use crate::packet::packet::{fragment_size, Packet, PacketId};
 use crate::packet::packet_type::PacketType;
 use crate::prelude::Tick;
 use crate::protocol::channel::ChannelId;
@@ -11,6 +11,7 @@ use crate::serialize::varint::varint_len;
 use crate::serialize::{SerializationError, ToBytes};
 use byteorder::WriteBytesExt;
 use bytes::Bytes;
+use rand::{thread_rng, Rng};
 use std::collections::VecDeque;
 use tracing::trace;
 #[cfg(feature = "trace")]
@@ -31,6 +32,8 @@ pub type RecvPayload = Bytes;
 pub(crate) struct PacketBuilder {
     pub(crate) header_manager: PacketHeaderManager,
     current_packet: Option<Packet>,
+    /// Maximum size of the packets that we build
+    packet_size: usize,
     // Pre-allocated buffer to encode/decode without allocation.
     // TODO: should this be associated with Packet?
     // cursor: Vec<u8>,
@@ -45,6 +48,7 @@ impl PacketBuilder {
         Self {
             header_manager: PacketHeaderManager::new(nack_rtt_multiple),
             current_packet: None,
+            packet_size: MAX_PACKET_SIZE,
             // cursor: Vec::with_capacity(PACKET_BUFFER_CAPACITY),
             // acks: Vec::new(),
 
@@ -55,9 +59,40 @@ impl PacketBuilder {
         }
     }
 
+    /// Maximum size of the packets that we build
+    pub(crate) fn packet_size(&self) -> usize {
+        self.packet_size
+    }
+
+    /// Set the maximum size of the packets that we build (for example after discovering the path MTU)
+    pub(crate) fn set_packet_size(&mut self, packet_size: usize) {
+        self.packet_size = packet_size;
+    }
+
     // TODO: get the vec from a pool of preallocated buffers
     fn get_new_buffer(&self) -> Payload {
-        Vec::with_capacity(MAX_PACKET_SIZE)
+        Vec::with_capacity(self.packet_size)
+    }
+
+    /// Build a probe packet of exactly `size` bytes, used to check if packets of that size can reach
+    /// the remote peer.
+    ///
+    /// The packet is padded with random bytes so that it cannot be shrunk by compression.
+    pub(crate) fn build_probe_packet(
+        &mut self,
+        size: usize,
+        current_tick: Tick,
+    ) -> Result<(PacketId, Payload), SerializationError> {
+        let mut cursor = Vec::with_capacity(size);
+        let mut header = self
+            .header_manager
+            .prepare_send_packet_header(PacketType::Probe);
+        header.tick = current_tick;
+        header.to_bytes(&mut cursor)?;
+        let header_len = cursor.len();
+        cursor.resize(size.max(header_len), 0);
+        thread_rng().fill(&mut cursor[header_len..]);
+        Ok((header.packet_id, cursor))
     }
 
     /// Start building new packet, we start with an empty packet
@@ -80,6 +115,7 @@ impl PacketBuilder {
             message_acks: vec![],
             packet_id: header.packet_id,
             prewritten_size: 0,
+            max_size: self.packet_size,
         });
         Ok(())
     }
@@ -112,6 +148,7 @@ impl PacketBuilder {
             )],
             packet_id: header.packet_id,
             prewritten_size: 0,
+            max_size: self.packet_size,
         });
         Ok(())
 
@@ -172,7 +209,7 @@ impl PacketBuilder {
         // try to fill the packet with fragment messages first
         for (channel_id, mut fragment_messages) in fragment_data.into_iter() {
             while let Some(fragment_data) = fragment_messages.pop_front() {
-                debug_assert!(fragment_data.bytes.len() <= FRAGMENT_SIZE);
+                debug_assert!(fragment_data.bytes.len() <= fragment_size(self.packet_size));
                 self.build_new_fragment_packet(channel_id, &fragment_data, current_tick)?;
                 if !fragment_data.is_last_fragment() {
                     // big fragment, write packet immediately
@@ -427,6 +464,7 @@ mod tests {
 
     use crate::channel::senders::fragment_sender::FragmentSender;
     use crate::packet::message::MessageId;
+    use crate::packet::packet::FRAGMENT_SIZE;
     use crate::prelude::*;
 
     use super::*;
@@ -687,5 +725,30 @@ mod tests {
         Ok(())
     }
 
+    /// Packets are smaller when the packet size is reduced (for example because of path MTU discovery)
+    #[test]
+    fn test_pack_with_packet_size() -> Result<(), PacketError> {
+        let channel_registry = get_channel_registry();
+        let mut manager = PacketBuilder::new(1.5);
+        manager.set_packet_size(600);
+        let channel_kind1 = ChannelKind::of::<Channel1>();
+        let channel_id1 = channel_registry.get_net_from_kind(&channel_kind1).unwrap();
+
+        let small_bytes = Bytes::from(vec![7u8; 500]);
+        let small_message = SingleData::new(None, small_bytes.clone());
+        let single_data = vec![(
+            *channel_id1,
+            VecDeque::from(vec![small_message.clone(), small_message.clone()]),
+        )];
+        let packets = manager.build_packets(Tick(0), single_data, vec![])?;
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|packet| packet.payload.len() <= 600));
+
+        let (packet_id, payload) = manager.build_probe_packet(700, Tick(0))?;
+        assert_eq!(packet_id, PacketId(2));
+        assert_eq!(payload.len(), 700);
+        Ok(())
+    }
+
     // TODO: ADD MORE TESTS
 }
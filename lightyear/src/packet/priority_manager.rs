@@ -9,6 +9,7 @@ use tracing::{debug, error, trace};
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
+use crate::packet::congestion::{CongestionControlConfig, CongestionController};
 use crate::packet::message::{FragmentData, MessageData, MessageId, SendMessage, SingleData};
 use crate::prelude::{ChannelRegistry, Tick};
 use crate::protocol::channel::ChannelId;
@@ -29,6 +30,8 @@ pub struct PriorityConfig {
     pub bandwidth_quota: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub enabled: bool,
+    /// If set, the bandwidth quota is replaced by a send budget that adapts to the network conditions
+    pub congestion_control: Option<CongestionControlConfig>,
 }
 
 // this is mostly for testing
@@ -38,6 +41,7 @@ impl Default for PriorityConfig {
             // 56 KB/s bandwidth cap
             bandwidth_quota: Quota::per_second(nonzero!(56000u32)),
             enabled: false,
+            congestion_control: None,
         }
     }
 }
@@ -47,6 +51,7 @@ impl From<crate::client::config::PacketConfig> for PriorityConfig {
         Self {
             bandwidth_quota: value.send_bandwidth_cap,
             enabled: value.bandwidth_cap_enabled,
+            congestion_control: value.congestion_control,
         }
     }
 }
@@ -56,6 +61,7 @@ impl From<crate::server::config::PacketConfig> for PriorityConfig {
         Self {
             bandwidth_quota: value.per_client_send_bandwidth_cap,
             enabled: value.bandwidth_cap_enabled,
+            congestion_control: value.congestion_control,
         }
     }
 }
@@ -65,6 +71,8 @@ pub(crate) struct PriorityManager {
     pub(crate) config: PriorityConfig,
     // TODO: can I do without this limiter?
     pub(crate) limiter: DefaultDirectRateLimiter,
+    /// Adjusts the send budget dynamically. If present, it is used instead of the `limiter`
+    pub(crate) congestion_controller: Option<CongestionController>,
     // // Internal buffer of data that we want to send
     // // Reuse allocation across frames
     // data_to_send: BTreeMap<ChannelId, (VecDeque<SendMessage>, VecDeque<SendMessage>)>,
@@ -79,12 +87,37 @@ impl PriorityManager {
         Self {
             config: config.clone(),
             limiter: DefaultDirectRateLimiter::direct(config.bandwidth_quota),
+            congestion_controller: config.congestion_control.map(CongestionController::new),
             // data_to_send: BTreeMap::new(),
             // buffered_data: Vec::new(),
             replication_update_senders: Vec::new(),
         }
     }
 
+    /// Returns true if the messages are filtered according to a bandwidth quota or a congestion controller
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.config.enabled || self.congestion_controller.is_some()
+    }
+
+    /// Current send budget in bytes per second, if congestion control is enabled
+    pub(crate) fn send_budget(&self) -> Option<u32> {
+        self.congestion_controller
+            .as_ref()
+            .map(|controller| controller.budget())
+    }
+
+    /// Account for bytes that were sent but not counted by [`PriorityManager::priority_filter`] (packet headers, etc.)
+    pub(crate) fn add_sent_bytes(&mut self, bytes: u32) {
+        match &mut self.congestion_controller {
+            Some(controller) => controller.consume(bytes),
+            None => {
+                if let Ok(bytes) = NonZeroU32::try_from(bytes) {
+                    let _ = self.limiter.check_n(bytes);
+                }
+            }
+        }
+    }
+
     /// Create a channel to notify when a replication update message is actually sent (included in packet)
     /// (as opposed to dropped because of the bandwidth quota)
     pub(crate) fn subscribe_replication_update_sent_messages(&mut self) -> Receiver<MessageId> {
@@ -110,7 +143,7 @@ impl PriorityManager {
     ) {
         // if the bandwidth quota is disabled, just pass all messages through
         // As an optimization: no need to send the tick of the message, it is the same as the header tick
-        if !self.config.enabled {
+        if !self.is_enabled() {
             let mut single_data = vec![];
             let mut fragment_data = vec![];
             for (net_id, (single, fragment)) in data {
@@ -186,18 +219,22 @@ impl PriorityManager {
             // we don't use the exact size of the message, but the size of the bytes
             // we will adjust for this later
             let message_bytes = buffered_message.data.len() as u32;
-            let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
-            let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
-                error!("the bandwidth does not have enough capacity for a message of this size!");
-                break;
+            let within_quota = match &mut self.congestion_controller {
+                Some(controller) => controller.try_consume(message_bytes),
+                None => {
+                    let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
+                    let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
+                        error!("the bandwidth does not have enough capacity for a message of this size!");
+                        break;
+                    };
+                    result.is_ok()
+                }
             };
 
             // above BYPASS_QUOTA_PRIORITY, we still send the message
-            if buffered_message.priority < BYPASS_QUOTA_PRIORITY {
-                let Ok(()) = result else {
-                    debug!("Bandwidth quota reached, no more messages can be sent this tick");
-                    break;
-                };
+            if buffered_message.priority < BYPASS_QUOTA_PRIORITY && !within_quota {
+                debug!("Bandwidth quota reached, no more messages can be sent this tick");
+                break;
             }
             trace!(channel=?buffered_message.channel_net_id, "Sending message with priority {:?}", buffered_message.priority);
 
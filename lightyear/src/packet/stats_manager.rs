@@ -14,6 +14,8 @@ pub(crate) mod packet {
         num_sent_packets_acked: u32,
         num_sent_packets_lost: u32,
         num_received_packets: u32,
+        /// Number of packets sent by the remote peer, estimated from the ids of the packets we received
+        num_expected_received_packets: u32,
     }
 
     impl AddAssign for PacketStats {
@@ -22,6 +24,7 @@ pub(crate) mod packet {
             self.num_sent_packets_acked += other.num_sent_packets_acked;
             self.num_sent_packets_lost += other.num_sent_packets_lost;
             self.num_received_packets += other.num_received_packets;
+            self.num_expected_received_packets += other.num_expected_received_packets;
         }
     }
 
@@ -31,12 +34,14 @@ pub(crate) mod packet {
             self.num_sent_packets_acked -= other.num_sent_packets_acked;
             self.num_sent_packets_lost -= other.num_sent_packets_lost;
             self.num_received_packets -= other.num_received_packets;
+            self.num_expected_received_packets -= other.num_expected_received_packets;
         }
     }
 
     #[derive(Default, Debug)]
     struct FinalStats {
         packet_loss: f32,
+        received_packet_loss: f32,
     }
 
     #[derive(Debug)]
@@ -95,6 +100,23 @@ pub(crate) mod packet {
                 self.final_stats.packet_loss = self.rolling_stats.num_sent_packets_lost as f32
                     / self.rolling_stats.num_sent_packets as f32;
             }
+            if self.rolling_stats.num_expected_received_packets > 0 {
+                // packets that arrive out of order can be counted as received in a later window
+                self.final_stats.received_packet_loss = (1.0
+                    - self.rolling_stats.num_received_packets as f32
+                        / self.rolling_stats.num_expected_received_packets as f32)
+                    .max(0.0);
+            }
+        }
+
+        /// Fraction of the sent packets that were lost, over the duration of the stats buffer
+        pub(crate) fn packet_loss(&self) -> f32 {
+            self.final_stats.packet_loss
+        }
+
+        /// Fraction of the packets sent by the remote peer that we did not receive, over the duration of the stats buffer
+        pub(crate) fn received_packet_loss(&self) -> f32 {
+            self.final_stats.received_packet_loss
         }
 
         // TODO: we could just emit raw stats, and then compute packet loss over an interval using prometheus/grafana
@@ -129,6 +151,12 @@ pub(crate) mod packet {
 
             self.current_stats.num_received_packets += 1;
         }
+
+        /// Notify that the remote peer sent `num_packets` new packets, based on the gaps in the ids of
+        /// the packets we received
+        pub(crate) fn expected_received_packets(&mut self, num_packets: u32) {
+            self.current_stats.num_expected_received_packets += num_packets;
+        }
     }
 
     #[cfg(test)]
@@ -168,6 +196,7 @@ pub(crate) mod packet {
                     num_sent_packets_acked: 0,
                     num_sent_packets_lost: 1,
                     num_received_packets: 0,
+                    num_expected_received_packets: 0,
                 }
             );
             packet_stats_manager.update(&time_manager);
@@ -189,10 +218,30 @@ pub(crate) mod packet {
                     num_sent_packets_acked: 0,
                     num_sent_packets_lost: 1,
                     num_received_packets: 0,
+                    num_expected_received_packets: 0,
                 }
             );
             packet_stats_manager.compute_stats();
             assert_eq!(packet_stats_manager.final_stats.packet_loss, 1.0 / 2.0);
         }
+
+        #[test]
+        fn test_received_packet_loss() {
+            let mut time_manager = TimeManager::default();
+            let mut packet_stats_manager = PacketStatsManager::new(Duration::from_secs(2));
+            time_manager.update(Duration::from_secs(3));
+
+            // we received packets 0, 1 and 3: packet 2 was lost
+            packet_stats_manager.received_packet();
+            packet_stats_manager.expected_received_packets(1);
+            packet_stats_manager.received_packet();
+            packet_stats_manager.expected_received_packets(1);
+            packet_stats_manager.received_packet();
+            packet_stats_manager.expected_received_packets(2);
+            packet_stats_manager.update(&time_manager);
+            assert_eq!(packet_stats_manager.received_packet_loss(), 1.0 / 4.0);
+            // the packets we send don't affect the received packet loss
+            assert_eq!(packet_stats_manager.packet_loss(), 0.0);
+        }
     }
 }
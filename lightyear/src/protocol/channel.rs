@@ -5,13 +5,15 @@ use std::any::TypeId;
 use std::collections::HashMap;
 
 use crate::channel::builder::{
-    AuthorityChannel, Channel, ChannelBuilder, ChannelSettings, PongChannel,
+    AuthorityChannel, Channel, ChannelBuilder, ChannelSettings, PongChannel, RpcChannel,
 };
 use crate::channel::builder::{
     ChannelContainer, EntityActionsChannel, EntityUpdatesChannel, InputChannel, PingChannel,
 };
 use crate::prelude::{ChannelMode, ReliableSettings};
+use crate::protocol::fingerprint::FingerprintHasher;
 use crate::protocol::registry::{NetId, TypeKind, TypeMapper};
+use crate::protocol::schema::{ChannelModeSchema, ChannelSchema};
 
 // TODO: derive Reflect once we reach bevy 0.14
 /// ChannelKind - internal wrapper around the type of the channel
@@ -116,6 +118,11 @@ impl ChannelRegistry {
             // we want to send the authority transfers as soon as possible
             priority: 10.0,
         });
+        registry.add_channel::<RpcChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            send_frequency: Duration::default(),
+            priority: 1.0,
+        });
         registry
     }
 
@@ -153,6 +160,45 @@ impl ChannelRegistry {
         self.builder_map.insert(kind, C::get_builder(settings));
     }
 
+    /// Stable hash of the registered channels, their order and their reliability/ordering guarantees
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        for (_, name, kind) in self.kind_map.iter_registered() {
+            hasher.write_str(name);
+            let mode = self
+                .builder_map
+                .get(kind)
+                .map(|builder| &builder.settings.mode);
+            hasher.write_u8(match mode {
+                None => 0,
+                Some(ChannelMode::UnorderedUnreliableWithAcks) => 1,
+                Some(ChannelMode::UnorderedUnreliable) => 2,
+                Some(ChannelMode::SequencedUnreliable) => 3,
+                Some(ChannelMode::UnorderedReliable(_)) => 4,
+                Some(ChannelMode::SequencedReliable(_)) => 5,
+                Some(ChannelMode::OrderedReliable(_)) => 6,
+            });
+        }
+        hasher.finish()
+    }
+
+    /// Describe the registered channels, in the order of their [`ChannelId`]
+    pub fn schema(&self) -> Vec<ChannelSchema> {
+        self.kind_map
+            .iter_registered()
+            .filter_map(|(net_id, name, kind)| {
+                let settings = &self.builder_map.get(kind)?.settings;
+                Some(ChannelSchema {
+                    net_id,
+                    type_name: name.to_string(),
+                    mode: ChannelModeSchema::from(&settings.mode),
+                    send_frequency_ms: settings.send_frequency.as_millis() as u64,
+                    priority: settings.priority,
+                })
+            })
+            .collect()
+    }
+
     /// get the registered object for a given type
     pub fn get_builder_from_kind(&self, channel_kind: &ChannelKind) -> Option<&ChannelBuilder> {
         self.builder_map.get(channel_kind)
@@ -15,7 +15,10 @@ use tracing::{debug, error, trace};
 
 use crate::client::components::ComponentSyncMode;
 use crate::client::config::ClientConfig;
-use crate::client::interpolation::{add_interpolation_systems, add_prepare_interpolation_systems};
+use crate::client::interpolation::plugin::ExtrapolationConfig;
+use crate::client::interpolation::{
+    add_hermite_interpolation_systems, add_interpolation_systems, add_prepare_interpolation_systems,
+};
 use crate::client::prediction::plugin::{
     add_non_networked_rollback_systems, add_prediction_systems, add_resource_rollback_systems,
 };
@@ -23,8 +26,11 @@ use crate::prelude::client::SyncComponent;
 use crate::prelude::server::ServerConfig;
 use crate::prelude::{ChannelDirection, Message, Tick};
 use crate::protocol::delta::ErasedDeltaFns;
+use crate::protocol::fingerprint::FingerprintHasher;
 use crate::protocol::registry::{NetId, TypeKind, TypeMapper};
+use crate::protocol::schema::ComponentSchema;
 use crate::protocol::serialize::{ErasedSerializeFns, SerializeFns};
+use crate::serialize::quantize::Quantize;
 use crate::serialize::reader::Reader;
 use crate::serialize::SerializationError;
 use crate::shared::events::connection::ConnectionEvents;
@@ -83,6 +89,12 @@ pub enum ComponentError {
 /// Provided that your type implements [`MapEntities`], you can extend the protocol to support this behaviour, by
 /// calling the [`add_map_entities`](ComponentRegistration::add_map_entities) method.
 ///
+/// #### Quantization
+/// By default, components are serialized with `bincode`, which is byte-aligned and doesn't take into account the range
+/// or precision that the values actually need.
+/// If your component implements the [`Quantize`] trait, you can call the [`add_quantization`](ComponentRegistration::add_quantization)
+/// method to pack it at bit granularity instead. See the [`quantize`](crate::serialize::quantize) module for the available quantization specs.
+///
 /// #### Prediction
 /// When client-prediction is enabled, we create two distinct entities on the client when the server replicates an entity: a Confirmed entity and a Predicted entity.
 /// The Confirmed entity will just get updated when the client receives the server updates, while the Predicted entity will be updated by the client's prediction system.
@@ -195,6 +207,11 @@ pub struct InterpolationMetadata {
     pub interpolation_mode: ComponentSyncMode,
     pub interpolation: Option<unsafe fn()>,
     pub custom_interpolation: bool,
+    /// If set, the component will be extrapolated when we run out of confirmed states to interpolate towards
+    pub extrapolation: Option<ExtrapolationConfig>,
+    pub extrapolation_fn: Option<unsafe fn()>,
+    /// Cubic Hermite interpolation function, along with the kind of the velocity component it uses
+    pub hermite_interpolation: Option<(ComponentKind, unsafe fn())>,
 }
 
 type RawRemoveFn = fn(&ComponentRegistry, &mut EntityWorldMut);
@@ -222,6 +239,19 @@ type RawBufferInsertFn = fn(
 /// t goes from 0.0 (`start`) to 1.0 (`other`)
 pub type LerpFn<C> = fn(start: &C, other: &C, t: f32) -> C;
 
+/// Function used to extrapolate a component past the last confirmed state (`last`)
+/// - `previous` is the confirmed state that was received before `last`
+/// - `interval` is the time (in seconds) between `previous` and `last`
+/// - `elapsed` is the time (in seconds) since `last`
+pub type ExtrapolateFn<C> = fn(previous: &C, last: &C, interval: f32, elapsed: f32) -> C;
+
+/// Function used to interpolate from one component state (`start`) to another (`end`) with cubic Hermite
+/// interpolation, using the values of a velocity component `V` at both states as tangents.
+/// - t goes from 0.0 (`start`) to 1.0 (`end`)
+/// - `duration` is the time (in seconds) between `start` and `end`
+pub type HermiteFn<C, V> =
+    fn(start: &C, start_velocity: &V, end: &C, end_velocity: &V, t: f32, duration: f32) -> C;
+
 /// Function that returns true if a rollback is needed, by comparing the server's value with the client's predicted value.
 /// Defaults to PartialEq::ne
 type ShouldRollbackFn<C> = fn(this: &C, that: &C) -> bool;
@@ -285,6 +315,63 @@ impl ComponentRegistry {
         }
     }
 
+    /// Stable hash of the registered components, their order, replication direction and sync modes
+    pub fn fingerprint(&self) -> u64 {
+        fn sync_mode(mode: Option<ComponentSyncMode>) -> u8 {
+            match mode {
+                None => 0,
+                Some(ComponentSyncMode::Full) => 1,
+                Some(ComponentSyncMode::Simple) => 2,
+                Some(ComponentSyncMode::Once) => 3,
+                Some(ComponentSyncMode::None) => 4,
+            }
+        }
+        let mut hasher = FingerprintHasher::default();
+        for (_, name, kind) in self.kind_map.iter_registered() {
+            hasher.write_str(name);
+            hasher.write_u8(match self.direction(*kind) {
+                None => 0,
+                Some(ChannelDirection::ClientToServer) => 1,
+                Some(ChannelDirection::ServerToClient) => 2,
+                Some(ChannelDirection::Bidirectional) => 3,
+            });
+            hasher.write_u8(sync_mode(
+                self.prediction_map
+                    .get(kind)
+                    .map(|metadata| metadata.prediction_mode),
+            ));
+            hasher.write_u8(sync_mode(
+                self.interpolation_map
+                    .get(kind)
+                    .map(|metadata| metadata.interpolation_mode),
+            ));
+            hasher.write_u8(self.delta_fns_map.contains_key(kind) as u8);
+        }
+        hasher.finish()
+    }
+
+    /// Describe the registered components, in the order of their [`ComponentNetId`]
+    pub fn schema(&self) -> Vec<ComponentSchema> {
+        self.kind_map
+            .iter_registered()
+            .map(|(net_id, name, kind)| ComponentSchema {
+                net_id,
+                type_name: name.to_string(),
+                direction: self.direction(*kind),
+                prediction_mode: self
+                    .prediction_map
+                    .get(kind)
+                    .map(|metadata| metadata.prediction_mode),
+                interpolation_mode: self
+                    .interpolation_map
+                    .get(kind)
+                    .map(|metadata| metadata.interpolation_mode),
+                map_entities: self.erased_is_map_entities(*kind),
+                delta_compression: self.delta_fns_map.contains_key(kind),
+            })
+            .collect()
+    }
+
     pub(crate) fn register_component<C: Message + Serialize + DeserializeOwned>(&mut self) {
         let component_kind = self.kind_map.add::<C>();
         self.serialize_fns_map
@@ -329,6 +416,17 @@ mod serialize {
             erased_fns.add_map_entities::<C>();
         }
 
+        pub(crate) fn add_quantization<C: Message + Quantize>(&mut self) {
+            let kind = ComponentKind::of::<C>();
+            let erased_fns = self.serialize_fns_map.get_mut(&kind).unwrap_or_else(|| {
+                panic!(
+                    "Component {} is not part of the protocol",
+                    std::any::type_name::<C>()
+                )
+            });
+            erased_fns.set_quantized::<C>();
+        }
+
         /// Returns true if we have a registered `map_entities` function for this component type
         pub(crate) fn is_map_entities<C: 'static>(&self) -> bool {
             let kind = ComponentKind::of::<C>();
@@ -530,6 +628,9 @@ mod interpolation {
                     interpolation_mode: mode,
                     interpolation: None,
                     custom_interpolation: false,
+                    extrapolation: None,
+                    extrapolation_fn: None,
+                    hermite_interpolation: None,
                 })
                 .interpolation_mode = mode;
         }
@@ -546,6 +647,9 @@ mod interpolation {
                     interpolation_mode: ComponentSyncMode::Full,
                     interpolation: None,
                     custom_interpolation: false,
+                    extrapolation: None,
+                    extrapolation_fn: None,
+                    hermite_interpolation: None,
                 })
                 .interpolation = Some(unsafe {
                 std::mem::transmute::<for<'a, 'b> fn(&'a C, &'b C, f32) -> C, unsafe fn()>(
@@ -572,6 +676,102 @@ mod interpolation {
                 unsafe { std::mem::transmute(interpolation_metadata.interpolation.unwrap()) };
             interpolation_fn(start, end, t)
         }
+
+        pub(crate) fn set_extrapolation<C: Component>(&mut self, config: ExtrapolationConfig) {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get_mut(&kind)
+                .expect("the component must be registered for interpolation")
+                .extrapolation = Some(config);
+        }
+
+        pub(crate) fn set_extrapolation_fn<C: Component>(
+            &mut self,
+            extrapolation_fn: ExtrapolateFn<C>,
+        ) {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get_mut(&kind)
+                .expect("the component must be registered for interpolation")
+                .extrapolation_fn = Some(unsafe {
+                std::mem::transmute::<for<'a, 'b> fn(&'a C, &'b C, f32, f32) -> C, unsafe fn()>(
+                    extrapolation_fn,
+                )
+            });
+        }
+
+        pub(crate) fn set_hermite_interpolation<C: Component, V: Component>(
+            &mut self,
+            hermite_fn: HermiteFn<C, V>,
+        ) {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get_mut(&kind)
+                .expect("the component must be registered for interpolation")
+                .hermite_interpolation = Some((ComponentKind::of::<V>(), unsafe {
+                std::mem::transmute::<
+                    for<'a, 'b, 'c, 'd> fn(&'a C, &'b V, &'c C, &'d V, f32, f32) -> C,
+                    unsafe fn(),
+                >(hermite_fn)
+            }));
+        }
+
+        /// Returns the Hermite interpolation function of the component `C`, if it was registered
+        /// with the velocity component `V`
+        pub(crate) fn hermite_interpolation<C: Component, V: Component>(
+            &self,
+        ) -> Option<HermiteFn<C, V>> {
+            let kind = ComponentKind::of::<C>();
+            let (velocity_kind, hermite_fn) = self
+                .interpolation_map
+                .get(&kind)
+                .and_then(|metadata| metadata.hermite_interpolation)?;
+            if velocity_kind != ComponentKind::of::<V>() {
+                return None;
+            }
+            Some(unsafe { std::mem::transmute::<unsafe fn(), HermiteFn<C, V>>(hermite_fn) })
+        }
+
+        /// Returns the [`ExtrapolationConfig`] of the component, if extrapolation is enabled
+        pub(crate) fn extrapolation<C: Component>(&self) -> Option<ExtrapolationConfig> {
+            let kind = ComponentKind::of::<C>();
+            self.interpolation_map
+                .get(&kind)
+                .and_then(|metadata| metadata.extrapolation)
+        }
+
+        /// Extrapolate the component past `last`.
+        ///
+        /// If no extrapolation function was provided, we keep going along the line from `previous` to `last`
+        /// using the interpolation function.
+        pub(crate) fn extrapolate<C: Component>(
+            &self,
+            previous: &C,
+            last: &C,
+            interval: f32,
+            elapsed: f32,
+        ) -> C {
+            let kind = ComponentKind::of::<C>();
+            let interpolation_metadata = self
+                .interpolation_map
+                .get(&kind)
+                .expect("the component is not part of the protocol");
+            match interpolation_metadata.extrapolation_fn {
+                Some(extrapolation_fn) => {
+                    let extrapolation_fn: ExtrapolateFn<C> =
+                        unsafe { std::mem::transmute(extrapolation_fn) };
+                    extrapolation_fn(previous, last, interval, elapsed)
+                }
+                None => {
+                    let t = if interval > 0.0 {
+                        1.0 + elapsed / interval
+                    } else {
+                        1.0
+                    };
+                    self.interpolate(previous, last, t)
+                }
+            }
+        }
     }
 }
 
@@ -1235,6 +1435,24 @@ pub trait AppComponentExt {
     /// Add a `Interpolation` behaviour to this component.
     fn add_interpolation_fn<C: SyncComponent>(&mut self, interpolation_fn: LerpFn<C>);
 
+    /// Extrapolate the component when there are no more confirmed states to interpolate towards.
+    ///
+    /// The component must have been registered with [`ComponentSyncMode::Full`] interpolation.
+    fn add_extrapolation<C: SyncComponent>(&mut self, config: ExtrapolationConfig);
+
+    /// Use a custom function to extrapolate the component.
+    /// (by default we keep interpolating past the last confirmed state with the interpolation function)
+    fn add_extrapolation_fn<C: SyncComponent>(&mut self, extrapolation_fn: ExtrapolateFn<C>);
+
+    /// Interpolate the component with cubic Hermite interpolation, using the velocity component `V`
+    /// to compute the tangents at each confirmed state.
+    ///
+    /// Both `C` and `V` must be registered with [`ComponentSyncMode::Full`] interpolation.
+    fn add_hermite_interpolation_fn<C: SyncComponent, V: SyncComponent>(
+        &mut self,
+        hermite_fn: HermiteFn<C, V>,
+    );
+
     /// Enable delta compression when serializing this component
     fn add_delta_compression<C: Component + PartialEq + Diffable>(&mut self)
     where
@@ -1258,6 +1476,19 @@ impl<C> ComponentRegistration<'_, C> {
         self
     }
 
+    /// Serialize the component with its [`Quantize`] implementation instead of the default serde-based serialization.
+    ///
+    /// The component is packed at bit granularity, which can drastically reduce the size of the updates
+    /// for components such as positions or rotations.
+    pub fn add_quantization(self) -> Self
+    where
+        C: Message + Quantize,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<ComponentRegistry>();
+        registry.add_quantization::<C>();
+        self
+    }
+
     /// Enable prediction systems for this component.
     /// You can specify the prediction [`ComponentSyncMode`]
     pub fn add_prediction(self, prediction_mode: ComponentSyncMode) -> Self
@@ -1336,6 +1567,39 @@ impl<C> ComponentRegistration<'_, C> {
         self
     }
 
+    /// Extrapolate the component when there are no more confirmed states to interpolate towards.
+    ///
+    /// The component must have been registered with [`ComponentSyncMode::Full`] interpolation.
+    pub fn add_extrapolation(self, config: ExtrapolationConfig) -> Self
+    where
+        C: SyncComponent,
+    {
+        self.app.add_extrapolation::<C>(config);
+        self
+    }
+
+    /// Use a custom function to extrapolate the component.
+    /// (by default we keep interpolating past the last confirmed state with the interpolation function)
+    pub fn add_extrapolation_fn(self, extrapolation_fn: ExtrapolateFn<C>) -> Self
+    where
+        C: SyncComponent,
+    {
+        self.app.add_extrapolation_fn::<C>(extrapolation_fn);
+        self
+    }
+
+    /// Interpolate the component with cubic Hermite interpolation, using the velocity component `V`
+    /// to compute the tangents at each confirmed state.
+    ///
+    /// Both `C` and `V` must be registered with [`ComponentSyncMode::Full`] interpolation.
+    pub fn add_hermite_interpolation_fn<V: SyncComponent>(self, hermite_fn: HermiteFn<C, V>) -> Self
+    where
+        C: SyncComponent,
+    {
+        self.app.add_hermite_interpolation_fn::<C, V>(hermite_fn);
+        self
+    }
+
     /// Enable delta compression when serializing this component
     pub fn add_delta_compression(self) -> Self
     where
@@ -1475,6 +1739,28 @@ impl AppComponentExt for App {
         registry.set_interpolation::<C>(interpolation_fn);
     }
 
+    fn add_extrapolation<C: SyncComponent>(&mut self, config: ExtrapolationConfig) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_extrapolation::<C>(config);
+    }
+
+    fn add_extrapolation_fn<C: SyncComponent>(&mut self, extrapolation_fn: ExtrapolateFn<C>) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_extrapolation_fn::<C>(extrapolation_fn);
+    }
+
+    fn add_hermite_interpolation_fn<C: SyncComponent, V: SyncComponent>(
+        &mut self,
+        hermite_fn: HermiteFn<C, V>,
+    ) {
+        let mut registry = self.world_mut().resource_mut::<ComponentRegistry>();
+        registry.set_hermite_interpolation::<C, V>(hermite_fn);
+        let is_client = self.world().get_resource::<ClientConfig>().is_some();
+        if is_client {
+            add_hermite_interpolation_systems::<C, V>(self);
+        }
+    }
+
     fn add_delta_compression<C: Component + PartialEq + Diffable>(&mut self)
     where
         C::Delta: Serialize + DeserializeOwned,
@@ -1530,4 +1816,70 @@ mod tests {
             .unwrap();
         assert_eq!(component, read);
     }
+
+    #[test]
+    fn test_extrapolate() {
+        let mut registry = ComponentRegistry::default();
+        registry.set_interpolation_mode::<ComponentSyncModeFull>(ComponentSyncMode::Full);
+        registry.set_linear_interpolation::<ComponentSyncModeFull>();
+        assert_eq!(registry.extrapolation::<ComponentSyncModeFull>(), None);
+        registry.set_extrapolation::<ComponentSyncModeFull>(ExtrapolationConfig::default());
+        assert_eq!(
+            registry.extrapolation::<ComponentSyncModeFull>(),
+            Some(ExtrapolationConfig::default())
+        );
+
+        // by default, keep going along the line between the last two confirmed states
+        let previous = ComponentSyncModeFull(1.0);
+        let last = ComponentSyncModeFull(2.0);
+        assert_eq!(
+            registry.extrapolate(&previous, &last, 0.1, 0.05),
+            ComponentSyncModeFull(2.5)
+        );
+
+        // custom extrapolation function
+        registry.set_extrapolation_fn::<ComponentSyncModeFull>(|_, last, _, elapsed| {
+            ComponentSyncModeFull(last.0 + elapsed)
+        });
+        assert_eq!(
+            registry.extrapolate(&previous, &last, 0.1, 0.5),
+            ComponentSyncModeFull(2.5)
+        );
+    }
+
+    #[test]
+    fn test_hermite_interpolation() {
+        let mut registry = ComponentRegistry::default();
+        registry.set_interpolation_mode::<ComponentSyncModeFull>(ComponentSyncMode::Full);
+        assert!(registry
+            .hermite_interpolation::<ComponentSyncModeFull, ComponentSyncModeSimple>()
+            .is_none());
+        registry.set_hermite_interpolation::<ComponentSyncModeFull, ComponentSyncModeSimple>(
+            |start, start_velocity, end, end_velocity, t, duration| {
+                ComponentSyncModeFull(
+                    start.0 * (1.0 - t)
+                        + end.0 * t
+                        + (start_velocity.0 - end_velocity.0) * duration,
+                )
+            },
+        );
+        // the velocity component must match the one that was registered
+        assert!(registry
+            .hermite_interpolation::<ComponentSyncModeFull, ComponentSyncModeOnce>()
+            .is_none());
+        let hermite_fn = registry
+            .hermite_interpolation::<ComponentSyncModeFull, ComponentSyncModeSimple>()
+            .unwrap();
+        assert_eq!(
+            hermite_fn(
+                &ComponentSyncModeFull(0.0),
+                &ComponentSyncModeSimple(2.0),
+                &ComponentSyncModeFull(1.0),
+                &ComponentSyncModeSimple(1.0),
+                0.5,
+                2.0
+            ),
+            ComponentSyncModeFull(2.5)
+        );
+    }
 }
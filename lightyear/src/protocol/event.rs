@@ -216,6 +216,7 @@ impl AppEventInternalExt for App {
         if !registry.is_registered::<E>() {
             registry.add_message::<E>();
         }
+        registry.add_registration_metadata::<E>(direction, MessageType::Event);
         debug!("register event {}", std::any::type_name::<E>());
         register_event_receive::<E>(self, direction);
         MessageRegistration {
@@ -233,6 +234,7 @@ impl AppEventInternalExt for App {
         if !registry.is_registered::<E>() {
             registry.add_message_custom_serde::<E>(serialize_fns);
         }
+        registry.add_registration_metadata::<E>(direction, MessageType::Event);
         debug!("register message {}", std::any::type_name::<E>());
         register_event_receive::<E>(self, direction);
         MessageRegistration {
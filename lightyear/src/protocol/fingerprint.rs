@@ -0,0 +1,205 @@
+//! Compute a fingerprint of the protocol, to detect client and server binaries that are not compatible
+//!
+//! The [`NetId`](crate::protocol::registry::NetId) of each component, message and channel depends on the order in which
+//! they were registered. If the client and the server register them in a different order (or register different types),
+//! the remote peer will deserialize garbage.
+//!
+//! The fingerprint is a hash of the type names, registration order, directions and sync modes of the protocol.
+//! Netcode connections also include the id of the zstd dictionary used to compress packets, if any.
+//! The client sends it during the netcode handshake and the server denies the connection with
+//! [`DeniedReason::ProtocolMismatch`](crate::connection::server::DeniedReason::ProtocolMismatch) if it differs from its own.
+//!
+//! Only data that is identical on both peers is hashed (for example the registration direction of a message, not
+//! whether the local peer can receive it), so that a client and a server built from the same protocol always agree.
+//!
+//! The type names come from [`std::any::type_name`], so the client and server binaries must be compiled with the same
+//! version of the compiler.
+//!
+//! Note that the fingerprint is appended to the netcode connection request packet: lightyear clients and servers
+//! are therefore not wire-compatible with standard netcode.io implementations.
+use bevy::prelude::World;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::channel::ChannelRegistry;
+use crate::protocol::component::ComponentRegistry;
+use crate::protocol::message::MessageRegistry;
+use crate::transport::middleware::compression::CompressionConfig;
+
+/// Stable hash of the [`ComponentRegistry`], [`MessageRegistry`] and [`ChannelRegistry`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProtocolFingerprint(pub u64);
+
+impl ProtocolFingerprint {
+    pub fn new(
+        component_registry: &ComponentRegistry,
+        message_registry: &MessageRegistry,
+        channel_registry: &ChannelRegistry,
+    ) -> Self {
+        let mut hasher = FingerprintHasher::default();
+        hasher.write_u64(component_registry.fingerprint());
+        hasher.write_u64(message_registry.fingerprint());
+        hasher.write_u64(channel_registry.fingerprint());
+        Self(hasher.finish())
+    }
+
+    /// Compute the fingerprint of the protocol registered in the [`World`]
+    pub(crate) fn from_world(world: &World) -> Self {
+        Self::new(
+            world.resource::<ComponentRegistry>(),
+            world.resource::<MessageRegistry>(),
+            world.resource::<ChannelRegistry>(),
+        )
+    }
+
+    /// Include the compression settings of the transport that have to match between the client and the server
+    /// (the id of the zstd dictionary), so that a mismatch is detected during the handshake
+    pub(crate) fn with_compression(self, compression: &CompressionConfig) -> Self {
+        match compression {
+            #[cfg(feature = "zstd")]
+            CompressionConfig::ZstdDictionary { dictionary, .. } => {
+                let mut hasher = FingerprintHasher::default();
+                hasher.write_u64(self.0);
+                hasher.write_u64(dictionary.id() as u64);
+                Self(hasher.finish())
+            }
+            _ => self,
+        }
+    }
+}
+
+/// FNV-1a hasher.
+///
+/// We cannot use the std `DefaultHasher` because its output is not guaranteed to be the same
+/// across releases of the standard library.
+pub(crate) struct FingerprintHasher(u64);
+
+impl Default for FingerprintHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl FingerprintHasher {
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Write a string, prefixed with its length so that consecutive strings cannot collide
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::client::ComponentSyncMode;
+    use crate::prelude::client::NetworkingState;
+    use crate::prelude::{ChannelMode, ChannelSettings};
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::BevyStepper;
+    use bevy::prelude::State;
+
+    #[test]
+    fn test_fingerprint() {
+        let mut channel_registry = ChannelRegistry::default();
+        channel_registry.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..Default::default()
+        });
+        channel_registry.add_channel::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..Default::default()
+        });
+        let fingerprint = channel_registry.fingerprint();
+        // the fingerprint is deterministic
+        assert_eq!(fingerprint, channel_registry.clone().fingerprint());
+
+        // the registration order is part of the fingerprint
+        let mut other_channel_registry = ChannelRegistry::default();
+        other_channel_registry.add_channel::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..Default::default()
+        });
+        other_channel_registry.add_channel::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..Default::default()
+        });
+        assert_ne!(fingerprint, other_channel_registry.fingerprint());
+
+        let mut component_registry = ComponentRegistry::default();
+        component_registry.register_component::<ComponentSyncModeFull>();
+        let mut other_component_registry = ComponentRegistry::default();
+        other_component_registry.register_component::<ComponentSyncModeFull>();
+        assert_eq!(
+            component_registry.fingerprint(),
+            other_component_registry.fingerprint()
+        );
+
+        // the sync modes are part of the fingerprint
+        component_registry.set_prediction_mode::<ComponentSyncModeFull>(ComponentSyncMode::Full);
+        assert_ne!(
+            component_registry.fingerprint(),
+            other_component_registry.fingerprint()
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_fingerprint_compression() {
+        use crate::transport::middleware::compression::zstd::ZstdDictionary;
+        // zstd dictionaries start with a magic number followed by the id of the dictionary
+        let dictionary = |id: u8| ZstdDictionary::new([0x37, 0xa4, 0x30, 0xec, id, 0, 0, 0]);
+        let with_dictionary = |id: u8| {
+            ProtocolFingerprint(1).with_compression(&CompressionConfig::ZstdDictionary {
+                level: 3,
+                dictionary: dictionary(id),
+            })
+        };
+        assert_eq!(dictionary(7).id(), 7);
+        // the compression does not change the fingerprint if there is no dictionary
+        assert_eq!(
+            ProtocolFingerprint(1).with_compression(&CompressionConfig::Zstd { level: 3 }),
+            ProtocolFingerprint(1)
+        );
+        assert_ne!(with_dictionary(7), ProtocolFingerprint(1));
+        assert_ne!(with_dictionary(7), with_dictionary(8));
+    }
+
+    /// The client and the server register the same protocol, so the client must not be denied
+    /// even though they do not receive the same messages
+    #[test]
+    fn test_client_connects_with_same_protocol() {
+        let stepper = BevyStepper::default();
+        assert_eq!(
+            ProtocolFingerprint::from_world(stepper.client_app.world()),
+            ProtocolFingerprint::from_world(stepper.server_app.world()),
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<State<NetworkingState>>()
+                .get(),
+            &NetworkingState::Connected
+        );
+    }
+}
@@ -4,7 +4,9 @@ use crate::packet::message::Message;
 use crate::prelude::server::ServerConfig;
 use crate::prelude::{client, server, ClientId};
 use crate::prelude::{ChannelDirection, UserAction};
+use crate::protocol::fingerprint::FingerprintHasher;
 use crate::protocol::registry::{NetId, TypeKind, TypeMapper};
+use crate::protocol::schema::{MessageSchema, MessageTypeSchema};
 use crate::protocol::serialize::{ErasedSerializeFns, SerializeFns};
 use crate::serialize::reader::Reader;
 use crate::serialize::writer::Writer;
@@ -103,6 +105,8 @@ pub struct MessageRegistry {
     /// metadata needed to receive a message
     pub(crate) message_receive_map: HashMap<MessageKind, MessageMetadata>,
     pub(crate) serialize_fns_map: HashMap<MessageKind, ErasedSerializeFns>,
+    /// direction and type of each message, as provided when the message was registered
+    pub(crate) registration_map: HashMap<MessageKind, MessageRegistrationMetadata>,
     pub(crate) kind_map: TypeMapper<MessageKind>,
 }
 
@@ -114,6 +118,12 @@ pub(crate) type ReceiveMessageFn = fn(
     &mut ReceiveEntityMap,
 ) -> Result<(), MessageError>;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MessageRegistrationMetadata {
+    pub(crate) direction: ChannelDirection,
+    pub(crate) message_type: MessageType,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MessageMetadata {
     pub(crate) message_type: MessageType,
@@ -256,6 +266,7 @@ impl AppMessageInternalExt for App {
         if !registry.is_registered::<M>() {
             registry.add_message::<M>();
         }
+        registry.add_registration_metadata::<M>(direction, message_type);
         debug!("register message {}", std::any::type_name::<M>());
         register_message_receive::<M>(self, direction, message_type);
         MessageRegistration {
@@ -274,6 +285,7 @@ impl AppMessageInternalExt for App {
         if !registry.is_registered::<M>() {
             registry.add_message_custom_serde::<M>(serialize_fns);
         }
+        registry.add_registration_metadata::<M>(direction, message_type);
         debug!("register message {}", std::any::type_name::<M>());
         register_message_receive::<M>(self, direction, message_type);
         MessageRegistration {
@@ -372,12 +384,74 @@ impl MessageRegistry {
         self.kind_map.net_id(&MessageKind::of::<M>()).is_some()
     }
 
+    /// Stable hash of the registered messages, their order, direction and type (message, event, input)
+    ///
+    /// Only the registration metadata is hashed: it is the same on the client and the server,
+    /// whereas the receive metadata only exists on the peer that receives the message.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        for (_, name, kind) in self.kind_map.iter_registered() {
+            hasher.write_str(name);
+            let metadata = self.registration_map.get(kind);
+            hasher.write_u8(match metadata.map(|metadata| metadata.direction) {
+                None => 0,
+                Some(ChannelDirection::ClientToServer) => 1,
+                Some(ChannelDirection::ServerToClient) => 2,
+                Some(ChannelDirection::Bidirectional) => 3,
+            });
+            hasher.write_u8(match metadata.map(|metadata| metadata.message_type) {
+                None => 0,
+                #[cfg(feature = "leafwing")]
+                Some(MessageType::LeafwingInput) => 1,
+                Some(MessageType::NativeInput) => 2,
+                Some(MessageType::Normal) => 3,
+                Some(MessageType::Event) => 4,
+            });
+        }
+        hasher.finish()
+    }
+
+    /// Describe the registered messages, in the order of their [`NetId`]
+    pub fn schema(&self) -> Vec<MessageSchema> {
+        self.kind_map
+            .iter_registered()
+            .map(|(net_id, name, kind)| {
+                let registration = self.registration_map.get(kind);
+                MessageSchema {
+                    net_id,
+                    type_name: name.to_string(),
+                    direction: registration.map(|metadata| metadata.direction),
+                    message_type: registration
+                        .map(|metadata| MessageTypeSchema::from(metadata.message_type)),
+                    map_entities: self
+                        .serialize_fns_map
+                        .get(kind)
+                        .is_some_and(|erased_fns| erased_fns.map_entities.is_some()),
+                }
+            })
+            .collect()
+    }
+
     pub(crate) fn add_message<M: Message + Serialize + DeserializeOwned>(&mut self) {
         let message_kind = self.kind_map.add::<M>();
         self.serialize_fns_map
             .insert(message_kind, ErasedSerializeFns::new::<M>());
     }
 
+    pub(crate) fn add_registration_metadata<M: Message>(
+        &mut self,
+        direction: ChannelDirection,
+        message_type: MessageType,
+    ) {
+        self.registration_map.insert(
+            MessageKind::of::<M>(),
+            MessageRegistrationMetadata {
+                direction,
+                message_type,
+            },
+        );
+    }
+
     pub(crate) fn add_receive_message_metadata<M: Message>(&mut self, message_type: MessageType) {
         let message_kind = MessageKind::of::<M>();
         let send_message_fn: ReceiveMessageFn = Self::receive_message_internal::<M>;
@@ -534,12 +608,7 @@ mod inputs {
             if let Some(mut input_buffers) = world.get_resource_mut::<InputBuffers<A>>() {
                 let message = self.deserialize::<InputMessage<A>>(reader, entity_map)?;
                 trace!("Received input message: {:?}", message);
-                input_buffers
-                    .buffers
-                    .entry(from)
-                    .or_default()
-                    .1
-                    .update_from_message(message);
+                input_buffers.update_from_message(from, message);
             }
             Ok(())
         }
@@ -21,8 +21,12 @@ pub(crate) mod message;
 
 pub(crate) mod delta;
 pub(crate) mod event;
+/// Computes a fingerprint of the protocol, to check that the client and server protocols are compatible
+pub(crate) mod fingerprint;
 /// Provides a mapping from a type to a unique identifier that can be serialized
 pub(crate) mod registry;
+/// Exports a machine-readable description of the protocol, for external tools
+pub(crate) mod schema;
 pub(crate) mod serialize;
 
 pub use serialize::SerializeFns;
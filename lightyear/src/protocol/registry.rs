@@ -35,6 +35,8 @@ pub struct TypeMapper<K: TypeKind> {
     pub(crate) next_net_id: NetId,
     pub(crate) kind_map: HashMap<K, NetId>,
     pub(crate) id_map: HashMap<NetId, K>,
+    /// Name of each registered type, indexed by [`NetId`]
+    pub(crate) type_names: Vec<&'static str>,
 }
 
 impl<K: TypeKind> Default for TypeMapper<K> {
@@ -49,6 +51,7 @@ impl<K: TypeKind> TypeMapper<K> {
             next_net_id: 0,
             kind_map: HashMap::new(),
             id_map: HashMap::new(),
+            type_names: Vec::new(),
         }
     }
 
@@ -61,6 +64,7 @@ impl<K: TypeKind> TypeMapper<K> {
         let net_id = self.next_net_id;
         self.kind_map.insert(kind, net_id);
         self.id_map.insert(net_id, kind);
+        self.type_names.push(std::any::type_name::<T>());
         self.next_net_id += 1;
         kind
     }
@@ -73,6 +77,18 @@ impl<K: TypeKind> TypeMapper<K> {
         self.kind_map.get(kind)
     }
 
+    /// Iterate through the registered types in the order in which they were registered
+    /// (which is also the order of their [`NetId`]s), along with their [`NetId`] and type name
+    pub(crate) fn iter_registered(&self) -> impl Iterator<Item = (NetId, &'static str, &K)> + '_ {
+        self.type_names
+            .iter()
+            .enumerate()
+            .filter_map(|(net_id, name)| {
+                let net_id = net_id as NetId;
+                Some((net_id, *name, self.id_map.get(&net_id)?))
+            })
+    }
+
     #[cfg(test)]
     pub(in crate::protocol) fn len(&self) -> usize {
         self.kind_map.len()
@@ -0,0 +1,219 @@
+//! Export a machine-readable description of the protocol, for tools that don't link with the game binary
+//! (load-test bots, packet inspectors, clients written in other languages, etc.)
+//!
+//! The [`ProtocolSchema`] lists every channel, component and message of the protocol in the order of their
+//! [`NetId`], which is the id that identifies them on the wire.
+//! It implements [`Serialize`], so it can be written with any serde format. The output only depends on the
+//! registered protocol (the registries' hashmaps are never iterated), so it can be committed and diffed in code review.
+//!
+//! ```rust,ignore
+//! let schema = ProtocolSchema::from_world(app.world());
+//! std::fs::write("protocol.json", serde_json::to_string_pretty(&schema)?)?;
+//! ```
+use bevy::prelude::World;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::builder::{ChannelDirection, ChannelMode};
+use crate::client::components::ComponentSyncMode;
+use crate::protocol::channel::ChannelRegistry;
+use crate::protocol::component::ComponentRegistry;
+use crate::protocol::fingerprint::ProtocolFingerprint;
+use crate::protocol::message::{MessageRegistry, MessageType};
+use crate::protocol::registry::NetId;
+
+/// Description of the channels, components and messages of the protocol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProtocolSchema {
+    /// Fingerprint sent by the client during the connection handshake
+    pub fingerprint: ProtocolFingerprint,
+    pub channels: Vec<ChannelSchema>,
+    pub components: Vec<ComponentSchema>,
+    pub messages: Vec<MessageSchema>,
+}
+
+impl ProtocolSchema {
+    pub fn new(
+        component_registry: &ComponentRegistry,
+        message_registry: &MessageRegistry,
+        channel_registry: &ChannelRegistry,
+    ) -> Self {
+        Self {
+            fingerprint: ProtocolFingerprint::new(
+                component_registry,
+                message_registry,
+                channel_registry,
+            ),
+            channels: channel_registry.schema(),
+            components: component_registry.schema(),
+            messages: message_registry.schema(),
+        }
+    }
+
+    /// Describe the protocol registered in the [`World`]
+    pub fn from_world(world: &World) -> Self {
+        Self::new(
+            world.resource::<ComponentRegistry>(),
+            world.resource::<MessageRegistry>(),
+            world.resource::<ChannelRegistry>(),
+        )
+    }
+}
+
+/// Description of a registered [`Channel`](crate::prelude::Channel)
+///
+/// Channels don't have a direction: the direction is defined by the messages and components that are sent on them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelSchema {
+    pub net_id: NetId,
+    pub type_name: String,
+    pub mode: ChannelModeSchema,
+    /// Minimum duration between two sends on this channel, in milliseconds. 0 means that we send every frame
+    pub send_frequency_ms: u64,
+    pub priority: f32,
+}
+
+/// Reliability and ordering guarantees of a channel, see [`ChannelMode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelModeSchema {
+    UnorderedUnreliableWithAcks,
+    UnorderedUnreliable,
+    SequencedUnreliable,
+    UnorderedReliable,
+    SequencedReliable,
+    OrderedReliable,
+}
+
+impl From<&ChannelMode> for ChannelModeSchema {
+    fn from(mode: &ChannelMode) -> Self {
+        match mode {
+            ChannelMode::UnorderedUnreliableWithAcks => Self::UnorderedUnreliableWithAcks,
+            ChannelMode::UnorderedUnreliable => Self::UnorderedUnreliable,
+            ChannelMode::SequencedUnreliable => Self::SequencedUnreliable,
+            ChannelMode::UnorderedReliable(_) => Self::UnorderedReliable,
+            ChannelMode::SequencedReliable(_) => Self::SequencedReliable,
+            ChannelMode::OrderedReliable(_) => Self::OrderedReliable,
+        }
+    }
+}
+
+/// Description of a registered component
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentSchema {
+    pub net_id: NetId,
+    pub type_name: String,
+    /// Direction in which the component is replicated. `None` if the component is only registered for serialization
+    pub direction: Option<ChannelDirection>,
+    /// How the component is synced to predicted entities. `None` if it is not synced
+    pub prediction_mode: Option<ComponentSyncMode>,
+    /// How the component is synced to interpolated entities. `None` if it is not synced
+    pub interpolation_mode: Option<ComponentSyncMode>,
+    /// The component contains entities that are mapped between the client and the server
+    pub map_entities: bool,
+    /// The component is replicated with delta-compression
+    pub delta_compression: bool,
+}
+
+/// Description of a registered message, event or input
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageSchema {
+    pub net_id: NetId,
+    pub type_name: String,
+    /// Direction in which the message can be sent. `None` if the message was added to the registry
+    /// without going through the [`App`](bevy::prelude::App) registration methods
+    pub direction: Option<ChannelDirection>,
+    pub message_type: Option<MessageTypeSchema>,
+    /// The message contains entities that are mapped between the client and the server
+    pub map_entities: bool,
+}
+
+/// What a registered message is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageTypeSchema {
+    /// A message sent with `send_message`
+    Message,
+    /// A bevy [`Event`](bevy::prelude::Event) sent with `send_event`
+    Event,
+    /// Inputs of a native [`UserAction`](crate::prelude::UserAction)
+    NativeInput,
+    /// Inputs of a leafwing `Actionlike`
+    LeafwingInput,
+}
+
+impl From<MessageType> for MessageTypeSchema {
+    fn from(message_type: MessageType) -> Self {
+        match message_type {
+            MessageType::Normal => Self::Message,
+            MessageType::Event => Self::Event,
+            MessageType::NativeInput => Self::NativeInput,
+            #[cfg(feature = "leafwing")]
+            MessageType::LeafwingInput => Self::LeafwingInput,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::BevyStepper;
+
+    #[test]
+    fn test_schema() {
+        let stepper = BevyStepper::default();
+        let client_schema = ProtocolSchema::from_world(stepper.client_app.world());
+        let server_schema = ProtocolSchema::from_world(stepper.server_app.world());
+        // the client and server describe the same protocol, even though they don't receive the same messages
+        assert_eq!(client_schema.fingerprint, server_schema.fingerprint);
+        assert_eq!(client_schema, server_schema);
+        // the schema is deterministic
+        assert_eq!(
+            client_schema,
+            ProtocolSchema::from_world(stepper.client_app.world())
+        );
+
+        // entries are sorted by net id
+        for (i, channel) in client_schema.channels.iter().enumerate() {
+            assert_eq!(channel.net_id, i as NetId);
+        }
+        for (i, component) in client_schema.components.iter().enumerate() {
+            assert_eq!(component.net_id, i as NetId);
+        }
+        for (i, message) in client_schema.messages.iter().enumerate() {
+            assert_eq!(message.net_id, i as NetId);
+        }
+
+        let component = client_schema
+            .components
+            .iter()
+            .find(|c| c.type_name == std::any::type_name::<ComponentSyncModeFull>())
+            .unwrap();
+        assert_eq!(component.direction, Some(ChannelDirection::Bidirectional));
+        assert_eq!(component.prediction_mode, Some(ComponentSyncMode::Full));
+        assert_eq!(component.interpolation_mode, Some(ComponentSyncMode::Full));
+        assert!(!component.delta_compression);
+
+        let message = client_schema
+            .messages
+            .iter()
+            .find(|m| m.type_name == std::any::type_name::<EntityMessage>())
+            .unwrap();
+        assert_eq!(message.direction, Some(ChannelDirection::Bidirectional));
+        assert_eq!(message.message_type, Some(MessageTypeSchema::Message));
+        assert!(message.map_entities);
+
+        // messages that are only received by one peer are described the same way on both peers
+        let message = server_schema
+            .messages
+            .iter()
+            .find(|m| m.type_name == std::any::type_name::<Resource1>())
+            .unwrap();
+        assert_eq!(message.direction, Some(ChannelDirection::ServerToClient));
+
+        let channel = client_schema
+            .channels
+            .iter()
+            .find(|c| c.type_name == std::any::type_name::<Channel1>())
+            .unwrap();
+        assert_eq!(channel.mode, ChannelModeSchema::UnorderedUnreliable);
+    }
+}
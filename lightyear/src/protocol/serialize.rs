@@ -1,4 +1,6 @@
 use crate::prelude::{ComponentRegistry, Message, MessageRegistry};
+use crate::serialize::bits::{BitReader, BitWriter};
+use crate::serialize::quantize::Quantize;
 use crate::serialize::{reader::Reader, writer::Writer, SerializationError};
 use crate::shared::replication::entity_map::{EntityMap, ReceiveEntityMap, SendEntityMap};
 use bevy::app::App;
@@ -98,6 +100,23 @@ fn default_deserialize<M: Message + DeserializeOwned>(
     Ok(data)
 }
 
+/// Serialize function that packs the message at bit granularity
+fn quantized_serialize<M: Message + Quantize>(
+    message: &M,
+    buffer: &mut Writer,
+) -> Result<(), SerializationError> {
+    let mut writer = BitWriter::new(buffer);
+    message.quantize(&mut writer)?;
+    writer.finish()
+}
+
+/// Deserialize function for messages packed with [`quantized_serialize`]
+fn quantized_deserialize<M: Message + Quantize>(
+    buffer: &mut Reader,
+) -> Result<M, SerializationError> {
+    M::dequantize(&mut BitReader::new(buffer))
+}
+
 fn erased_clone<M: Clone>(message: &M) -> M {
     message.clone()
 }
@@ -151,6 +170,15 @@ impl ErasedSerializeFns {
         }
     }
 
+    /// Replace the serialization functions with the ones provided by the [`Quantize`] implementation
+    pub(crate) fn set_quantized<M: Message + Quantize>(&mut self) {
+        debug_assert_eq!(self.type_id, TypeId::of::<M>());
+        let serialize: SerializeFn<M> = quantized_serialize::<M>;
+        let deserialize: DeserializeFn<M> = quantized_deserialize::<M>;
+        self.serialize = unsafe { std::mem::transmute(serialize) };
+        self.deserialize = unsafe { std::mem::transmute(deserialize) };
+    }
+
     pub(crate) unsafe fn typed<M: 'static>(&self) -> SerializeFns<M> {
         debug_assert_eq!(
             self.type_id,
@@ -0,0 +1,156 @@
+//! Reader and writer that operate at bit granularity instead of byte granularity.
+//!
+//! Bits are packed least-significant first. The [`BitWriter`] pads the last byte with zeroes when it is
+//! finished, so a value packed with a [`BitWriter`] always occupies a whole number of bytes in the
+//! underlying buffer, and can be read back with a [`BitReader`] without any length prefix.
+use crate::serialize::SerializationError;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+
+/// Maximum number of bits that can be written or read in a single operation
+const MAX_CHUNK_BITS: u32 = 32;
+
+/// Mask that keeps the `num_bits` lowest bits of a value
+fn mask(num_bits: u32) -> u64 {
+    if num_bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << num_bits) - 1
+    }
+}
+
+/// Number of bits needed to represent all the values in `0..=max_value`
+pub(crate) fn bits_for(max_value: u64) -> u32 {
+    u64::BITS - max_value.leading_zeros()
+}
+
+/// Packs values at bit granularity into an underlying byte writer.
+///
+/// [`BitWriter::finish`] must be called once all the values have been written, to flush the last partial byte.
+pub struct BitWriter<'a, W: Write> {
+    inner: &'a mut W,
+    /// Bits that have been written but not flushed to `inner` yet
+    scratch: u64,
+    /// Number of bits stored in `scratch`
+    num_bits: u32,
+}
+
+impl<'a, W: Write> BitWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Self {
+            inner,
+            scratch: 0,
+            num_bits: 0,
+        }
+    }
+
+    /// Write the `num_bits` lowest bits of `value`
+    pub fn write_bits(&mut self, value: u64, num_bits: u32) -> Result<(), SerializationError> {
+        debug_assert!(num_bits <= 64);
+        if num_bits > MAX_CHUNK_BITS {
+            self.write_bits(value, MAX_CHUNK_BITS)?;
+            return self.write_bits(value >> MAX_CHUNK_BITS, num_bits - MAX_CHUNK_BITS);
+        }
+        self.scratch |= (value & mask(num_bits)) << self.num_bits;
+        self.num_bits += num_bits;
+        while self.num_bits >= 8 {
+            self.inner.write_u8(self.scratch as u8)?;
+            self.scratch >>= 8;
+            self.num_bits -= 8;
+        }
+        Ok(())
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> Result<(), SerializationError> {
+        self.write_bits(value as u64, 1)
+    }
+
+    /// Flush the remaining bits to the underlying writer, padding the last byte with zeroes
+    pub fn finish(self) -> Result<(), SerializationError> {
+        if self.num_bits > 0 {
+            self.inner.write_u8(self.scratch as u8)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads values that were packed by a [`BitWriter`].
+///
+/// Bytes are only consumed from the underlying reader when they are needed, so the padding bits of the last byte
+/// are simply dropped with the [`BitReader`].
+pub struct BitReader<'a, R: Read> {
+    inner: &'a mut R,
+    /// Bits that have been read from `inner` but not consumed yet
+    scratch: u64,
+    /// Number of bits stored in `scratch`
+    num_bits: u32,
+}
+
+impl<'a, R: Read> BitReader<'a, R> {
+    pub fn new(inner: &'a mut R) -> Self {
+        Self {
+            inner,
+            scratch: 0,
+            num_bits: 0,
+        }
+    }
+
+    /// Read a value that was written on `num_bits` bits
+    pub fn read_bits(&mut self, num_bits: u32) -> Result<u64, SerializationError> {
+        debug_assert!(num_bits <= 64);
+        if num_bits > MAX_CHUNK_BITS {
+            let low = self.read_bits(MAX_CHUNK_BITS)?;
+            let high = self.read_bits(num_bits - MAX_CHUNK_BITS)?;
+            return Ok(low | (high << MAX_CHUNK_BITS));
+        }
+        while self.num_bits < num_bits {
+            self.scratch |= (self.inner.read_u8()? as u64) << self.num_bits;
+            self.num_bits += 8;
+        }
+        let value = self.scratch & mask(num_bits);
+        self.scratch >>= num_bits;
+        self.num_bits -= num_bits;
+        Ok(value)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SerializationError> {
+        Ok(self.read_bits(1)? != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_round_trip() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        writer.write_bits(5, 3).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_bits(0, 0).unwrap();
+        writer.write_bits(1000, 10).unwrap();
+        writer.write_bits(u64::MAX - 1, 64).unwrap();
+        writer.finish().unwrap();
+        // 3 + 1 + 10 + 64 = 78 bits
+        assert_eq!(buffer.len(), 10);
+
+        let mut slice = buffer.as_slice();
+        let mut reader = BitReader::new(&mut slice);
+        assert_eq!(reader.read_bits(3).unwrap(), 5);
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_bits(0).unwrap(), 0);
+        assert_eq!(reader.read_bits(10).unwrap(), 1000);
+        assert_eq!(reader.read_bits(64).unwrap(), u64::MAX - 1);
+        assert!(slice.is_empty());
+    }
+
+    #[test]
+    fn test_bits_for() {
+        assert_eq!(bits_for(0), 0);
+        assert_eq!(bits_for(1), 1);
+        assert_eq!(bits_for(255), 8);
+        assert_eq!(bits_for(256), 9);
+        assert_eq!(bits_for(u64::MAX), 64);
+    }
+}
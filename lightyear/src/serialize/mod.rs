@@ -7,6 +7,8 @@ use byteorder::{ReadBytesExt, WriteBytesExt};
 use bytes::Bytes;
 use std::hash::{BuildHasher, Hash};
 
+pub mod bits;
+pub mod quantize;
 pub mod reader;
 pub(crate) mod varint;
 pub mod writer;
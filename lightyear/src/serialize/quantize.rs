@@ -0,0 +1,364 @@
+//! Quantization of values, to serialize them with fewer bits than their in-memory representation.
+//!
+//! A type that implements [`Quantize`] describes how to pack itself into a [`BitWriter`], usually by combining
+//! the quantization specs provided here:
+//! - [`FixedPoint`]: a float in a known range, sent with a fixed precision
+//! - [`BoundedInt`]: an integer in a known range
+//! - [`SmallestThree`]: a unit quaternion, sent as its 3 smallest components
+//!
+//! The component can then be registered with [`add_quantization`](crate::protocol::component::ComponentRegistration::add_quantization)
+//! to replace its default serialization.
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use lightyear::prelude::*;
+//! use lightyear::serialize::bits::{BitReader, BitWriter};
+//! use lightyear::serialize::quantize::{FixedPoint, Quantize, SmallestThree};
+//! use lightyear::serialize::SerializationError;
+//! use std::io::{Read, Write};
+//!
+//! #[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
+//! struct PlayerTransform {
+//!     translation: Vec3,
+//!     rotation: Quat,
+//! }
+//!
+//! // positions between -1000.0 and 1000.0 with a 1cm precision: 18 bits per coordinate
+//! const POSITION: FixedPoint = FixedPoint::new(-1000.0, 1000.0, 0.01);
+//! // 2 + 3 * 10 = 32 bits per rotation
+//! const ROTATION: SmallestThree = SmallestThree::new(10);
+//!
+//! impl Quantize for PlayerTransform {
+//!     fn quantize<W: Write>(&self, writer: &mut BitWriter<W>) -> Result<(), SerializationError> {
+//!         POSITION.write_vec3(self.translation, writer)?;
+//!         ROTATION.write(self.rotation, writer)
+//!     }
+//!
+//!     fn dequantize<R: Read>(reader: &mut BitReader<R>) -> Result<Self, SerializationError> {
+//!         Ok(Self {
+//!             translation: POSITION.read_vec3(reader)?,
+//!             rotation: ROTATION.read(reader)?,
+//!         })
+//!     }
+//! }
+//!
+//! fn add_components(app: &mut App) {
+//!     app.register_component::<PlayerTransform>(ChannelDirection::ServerToClient)
+//!         .add_quantization();
+//! }
+//! ```
+use crate::serialize::bits::{bits_for, BitReader, BitWriter};
+use crate::serialize::SerializationError;
+use bevy::math::{Quat, Vec2, Vec3};
+use std::io::{Read, Write};
+
+/// A type that can be serialized at bit granularity, usually with a loss of precision.
+pub trait Quantize: Sized {
+    /// Pack the value into the writer
+    fn quantize<W: Write>(&self, writer: &mut BitWriter<W>) -> Result<(), SerializationError>;
+
+    /// Read back a value that was packed with [`Quantize::quantize`]
+    fn dequantize<R: Read>(reader: &mut BitReader<R>) -> Result<Self, SerializationError>;
+}
+
+/// Quantizes a float in the range `[min, max]` to a multiple of `precision`.
+///
+/// Values outside the range are clamped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedPoint {
+    pub min: f32,
+    pub max: f32,
+    pub precision: f32,
+}
+
+impl FixedPoint {
+    pub const fn new(min: f32, max: f32, precision: f32) -> Self {
+        Self {
+            min,
+            max,
+            precision,
+        }
+    }
+
+    /// Number of quantization steps between `min` and `max`
+    fn steps(&self) -> u64 {
+        ((self.max - self.min) / self.precision).ceil() as u64
+    }
+
+    /// Number of bits used to serialize a value
+    pub fn bits(&self) -> u32 {
+        bits_for(self.steps())
+    }
+
+    pub fn quantize(&self, value: f32) -> u64 {
+        let value = value.clamp(self.min, self.max);
+        (((value - self.min) / self.precision).round() as u64).min(self.steps())
+    }
+
+    pub fn dequantize(&self, quantized: u64) -> f32 {
+        (self.min + quantized as f32 * self.precision).min(self.max)
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        value: f32,
+        writer: &mut BitWriter<W>,
+    ) -> Result<(), SerializationError> {
+        writer.write_bits(self.quantize(value), self.bits())
+    }
+
+    pub fn read<R: Read>(&self, reader: &mut BitReader<R>) -> Result<f32, SerializationError> {
+        let quantized = reader.read_bits(self.bits())?;
+        if quantized > self.steps() {
+            return Err(SerializationError::InvalidValue);
+        }
+        Ok(self.dequantize(quantized))
+    }
+
+    pub fn write_vec2<W: Write>(
+        &self,
+        value: Vec2,
+        writer: &mut BitWriter<W>,
+    ) -> Result<(), SerializationError> {
+        self.write(value.x, writer)?;
+        self.write(value.y, writer)
+    }
+
+    pub fn read_vec2<R: Read>(
+        &self,
+        reader: &mut BitReader<R>,
+    ) -> Result<Vec2, SerializationError> {
+        Ok(Vec2::new(self.read(reader)?, self.read(reader)?))
+    }
+
+    pub fn write_vec3<W: Write>(
+        &self,
+        value: Vec3,
+        writer: &mut BitWriter<W>,
+    ) -> Result<(), SerializationError> {
+        self.write(value.x, writer)?;
+        self.write(value.y, writer)?;
+        self.write(value.z, writer)
+    }
+
+    pub fn read_vec3<R: Read>(
+        &self,
+        reader: &mut BitReader<R>,
+    ) -> Result<Vec3, SerializationError> {
+        Ok(Vec3::new(
+            self.read(reader)?,
+            self.read(reader)?,
+            self.read(reader)?,
+        ))
+    }
+}
+
+/// Serializes an integer in the range `[min, max]` with the minimum number of bits.
+///
+/// Values outside the range are clamped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundedInt {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl BoundedInt {
+    pub const fn new(min: i64, max: i64) -> Self {
+        Self { min, max }
+    }
+
+    fn range(&self) -> u64 {
+        self.max.wrapping_sub(self.min) as u64
+    }
+
+    /// Number of bits used to serialize a value
+    pub fn bits(&self) -> u32 {
+        bits_for(self.range())
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        value: i64,
+        writer: &mut BitWriter<W>,
+    ) -> Result<(), SerializationError> {
+        let value = value.clamp(self.min, self.max);
+        writer.write_bits(value.wrapping_sub(self.min) as u64, self.bits())
+    }
+
+    pub fn read<R: Read>(&self, reader: &mut BitReader<R>) -> Result<i64, SerializationError> {
+        let value = reader.read_bits(self.bits())?;
+        if value > self.range() {
+            return Err(SerializationError::InvalidValue);
+        }
+        Ok(self.min.wrapping_add(value as i64))
+    }
+}
+
+/// Serializes a unit quaternion with the 'smallest three' method.
+///
+/// We only send the index of the largest component (2 bits) and the three other components, quantized
+/// on `bits` bits each. The largest component can be recomputed from the other three since the quaternion is normalized.
+/// Its sign is not needed because `q` and `-q` represent the same rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmallestThree {
+    pub bits: u32,
+}
+
+impl SmallestThree {
+    /// The smallest three components of a unit quaternion are in the range `[-1/sqrt(2), 1/sqrt(2)]`
+    const RANGE: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    pub const fn new(bits: u32) -> Self {
+        Self { bits }
+    }
+
+    fn max_quantized(&self) -> u64 {
+        (1u64 << self.bits) - 1
+    }
+
+    fn quantize_component(&self, value: f32) -> u64 {
+        let max = self.max_quantized();
+        let normalized =
+            (value.clamp(-Self::RANGE, Self::RANGE) + Self::RANGE) / (2.0 * Self::RANGE);
+        ((normalized * max as f32).round() as u64).min(max)
+    }
+
+    fn dequantize_component(&self, quantized: u64) -> f32 {
+        quantized as f32 / self.max_quantized() as f32 * 2.0 * Self::RANGE - Self::RANGE
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        value: Quat,
+        writer: &mut BitWriter<W>,
+    ) -> Result<(), SerializationError> {
+        let mut components = value.normalize().to_array();
+        let (largest, _) = components.iter().enumerate().fold(
+            (0, 0.0),
+            |(max_index, max_value), (index, value)| {
+                if value.abs() > max_value {
+                    (index, value.abs())
+                } else {
+                    (max_index, max_value)
+                }
+            },
+        );
+        if components[largest] < 0.0 {
+            components.iter_mut().for_each(|c| *c = -*c);
+        }
+        writer.write_bits(largest as u64, 2)?;
+        for (_, value) in components.iter().enumerate().filter(|(i, _)| *i != largest) {
+            writer.write_bits(self.quantize_component(*value), self.bits)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(&self, reader: &mut BitReader<R>) -> Result<Quat, SerializationError> {
+        let largest = reader.read_bits(2)? as usize;
+        let mut components = [0.0; 4];
+        let mut sum_squares = 0.0;
+        for (_, value) in components
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| *i != largest)
+        {
+            *value = self.dequantize_component(reader.read_bits(self.bits)?);
+            sum_squares += *value * *value;
+        }
+        components[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+        Ok(Quat::from_array(components).normalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Packed {
+        position: Vec3,
+        rotation: Quat,
+        health: i64,
+        alive: bool,
+    }
+
+    const POSITION: FixedPoint = FixedPoint::new(-1000.0, 1000.0, 0.01);
+    const ROTATION: SmallestThree = SmallestThree::new(10);
+    const HEALTH: BoundedInt = BoundedInt::new(0, 100);
+
+    impl Quantize for Packed {
+        fn quantize<W: Write>(&self, writer: &mut BitWriter<W>) -> Result<(), SerializationError> {
+            POSITION.write_vec3(self.position, writer)?;
+            ROTATION.write(self.rotation, writer)?;
+            HEALTH.write(self.health, writer)?;
+            writer.write_bool(self.alive)
+        }
+
+        fn dequantize<R: Read>(reader: &mut BitReader<R>) -> Result<Self, SerializationError> {
+            Ok(Self {
+                position: POSITION.read_vec3(reader)?,
+                rotation: ROTATION.read(reader)?,
+                health: HEALTH.read(reader)?,
+                alive: reader.read_bool()?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_quantize_round_trip() {
+        let value = Packed {
+            position: Vec3::new(-12.345, 0.0, 999.99),
+            rotation: Quat::from_euler(bevy::math::EulerRot::XYZ, 0.3, -2.0, 1.2),
+            health: 57,
+            alive: true,
+        };
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        value.quantize(&mut writer).unwrap();
+        writer.finish().unwrap();
+        // 3 * 18 + 2 + 3 * 10 + 7 + 1 = 94 bits
+        assert_eq!(POSITION.bits(), 18);
+        assert_eq!(buffer.len(), 12);
+
+        let mut slice = buffer.as_slice();
+        let mut reader = BitReader::new(&mut slice);
+        let read = Packed::dequantize(&mut reader).unwrap();
+        assert!(read
+            .position
+            .abs_diff_eq(value.position, POSITION.precision));
+        assert!(read.rotation.angle_between(value.rotation) < 0.01);
+        assert_eq!(read.health, value.health);
+        assert_eq!(read.alive, value.alive);
+    }
+
+    #[test]
+    fn test_quantize_clamp() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        HEALTH.write(150, &mut writer).unwrap();
+        HEALTH.write(-3, &mut writer).unwrap();
+        POSITION.write(2000.0, &mut writer).unwrap();
+        writer.finish().unwrap();
+
+        let mut slice = buffer.as_slice();
+        let mut reader = BitReader::new(&mut slice);
+        assert_eq!(HEALTH.read(&mut reader).unwrap(), 100);
+        assert_eq!(HEALTH.read(&mut reader).unwrap(), 0);
+        assert_eq!(POSITION.read(&mut reader).unwrap(), 1000.0);
+    }
+
+    #[test]
+    fn test_smallest_three_sign() {
+        // q and -q represent the same rotation
+        let rotation = Quat::from_rotation_y(1.0);
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        ROTATION.write(-rotation, &mut writer).unwrap();
+        writer.finish().unwrap();
+        let mut slice = buffer.as_slice();
+        let read = ROTATION.read(&mut BitReader::new(&mut slice)).unwrap();
+        assert!(read.angle_between(rotation) < 0.01);
+        // the largest component is always sent as positive
+        assert!(read.abs_diff_eq(rotation, 0.01));
+    }
+}
@@ -4,10 +4,14 @@ use governor::Quota;
 use nonzero_ext::nonzero;
 use std::sync::Arc;
 
+#[cfg(not(target_family = "wasm"))]
+use crate::connection::netcode::IssuedClientIds;
 use crate::connection::netcode::{Key, PRIVATE_KEY_BYTES};
 use crate::connection::server::{
     ConnectionRequestHandler, DefaultConnectionRequestHandler, NetConfig,
 };
+use crate::packet::congestion::CongestionControlConfig;
+use crate::packet::mtu::MtuDiscoveryConfig;
 use crate::prelude::ReplicationConfig;
 use crate::shared::config::SharedConfig;
 use crate::shared::ping::manager::PingConfig;
@@ -24,6 +28,10 @@ pub struct NetcodeConfig {
     pub private_key: Key,
     /// A closure that will be used to accept or reject incoming connections
     pub connection_request_handler: Arc<dyn ConnectionRequestHandler>,
+    /// The client ids issued by a [`TokenService`](crate::connection::netcode::TokenService) running in the
+    /// same process. The ids are kept while the clients are connected, and released when they disconnect.
+    #[cfg(not(target_family = "wasm"))]
+    pub issued_client_ids: Option<IssuedClientIds>,
 }
 
 impl Default for NetcodeConfig {
@@ -35,6 +43,8 @@ impl Default for NetcodeConfig {
             protocol_id: 0,
             private_key: [0; PRIVATE_KEY_BYTES],
             connection_request_handler: Arc::new(DefaultConnectionRequestHandler),
+            #[cfg(not(target_family = "wasm"))]
+            issued_client_ids: None,
         }
     }
 }
@@ -53,6 +63,14 @@ impl NetcodeConfig {
         self.client_timeout_secs = client_timeout_secs;
         self
     }
+
+    /// Release the client ids issued by a [`TokenService`](crate::connection::netcode::TokenService)
+    /// when the clients disconnect
+    #[cfg(not(target_family = "wasm"))]
+    pub fn with_issued_client_ids(mut self, issued_client_ids: IssuedClientIds) -> Self {
+        self.issued_client_ids = Some(issued_client_ids);
+        self
+    }
 }
 
 /// Configuration related to sending packets
@@ -67,6 +85,12 @@ pub struct PacketConfig {
     pub per_client_send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// If set, the bandwidth cap is replaced with a send budget that adapts to the RTT and packet loss
+    /// of the connection
+    pub congestion_control: Option<CongestionControlConfig>,
+    /// If set, the packet size starts small and grows up to the largest size that can reach the remote peer.
+    /// Otherwise packets can be up to [`MAX_PACKET_SIZE`](crate::connection::netcode::MAX_PACKET_SIZE) bytes.
+    pub mtu_discovery: Option<MtuDiscoveryConfig>,
 }
 
 impl Default for PacketConfig {
@@ -76,6 +100,8 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             per_client_send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            congestion_control: None,
+            mtu_discovery: None,
         }
     }
 }
@@ -96,6 +122,16 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn with_congestion_control(mut self, congestion_control: CongestionControlConfig) -> Self {
+        self.congestion_control = Some(congestion_control);
+        self
+    }
+
+    pub fn with_mtu_discovery(mut self, mtu_discovery: MtuDiscoveryConfig) -> Self {
+        self.mtu_discovery = Some(mtu_discovery);
+        self
+    }
 }
 
 /// Configuration for the server plugin.
@@ -119,13 +155,16 @@ pub struct ServerConfig {
 mod tests {
     use super::*;
     use crate::client::networking::NetworkingState;
-    use crate::connection::server::DeniedReason;
+    use crate::connection::netcode::USER_DATA_BYTES;
+    use crate::connection::server::{
+        ConnectionRequest, ConnectionRequestDecision, DecisionSender, DeniedReason, PendingDecision,
+    };
     use crate::prelude::ClientId;
 
     use crate::tests::stepper::{BevyStepper, TEST_CLIENT_ID};
     use bevy::prelude::State;
     use std::fmt::Debug;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
 
     #[derive(Debug, Clone)]
     struct CustomConnectionRequestHandler;
@@ -142,6 +181,69 @@ mod tests {
         }
     }
 
+    /// Defers the decision until the test resolves it
+    #[derive(Debug, Default)]
+    struct PendingConnectionRequestHandler {
+        sender: Mutex<Option<DecisionSender>>,
+        user_data: Mutex<Option<[u8; USER_DATA_BYTES]>>,
+    }
+
+    impl ConnectionRequestHandler for PendingConnectionRequestHandler {
+        fn handle_connection_request(
+            &self,
+            request: &ConnectionRequest,
+        ) -> ConnectionRequestDecision {
+            let (sender, pending) = PendingDecision::new();
+            *self.sender.lock().unwrap() = Some(sender);
+            *self.user_data.lock().unwrap() = Some(request.user_data);
+            ConnectionRequestDecision::Pending(pending)
+        }
+    }
+
+    #[test]
+    fn test_pending_connection_request() {
+        let mut stepper = BevyStepper::default();
+        stepper.stop();
+
+        let handler = Arc::new(PendingConnectionRequestHandler::default());
+        for netconfig in &mut stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConfig>()
+            .net
+        {
+            netconfig.set_connection_request_handler(handler.clone());
+        }
+
+        // the client cannot connect while the decision is pending
+        stepper.start();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<State<NetworkingState>>()
+                .get(),
+            &NetworkingState::Connecting
+        );
+        // the handler has access to the user_data of the connect token
+        assert_eq!(
+            *handler.user_data.lock().unwrap(),
+            Some([0; USER_DATA_BYTES])
+        );
+
+        // accept the connection
+        handler.sender.lock().unwrap().as_ref().unwrap().accept();
+        stepper.wait_for_connection();
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .resource::<State<NetworkingState>>()
+                .get(),
+            &NetworkingState::Connected
+        );
+    }
+
     #[test]
     fn test_accept_connection_request_fn() {
         let mut stepper = BevyStepper::default();
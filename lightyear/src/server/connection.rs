@@ -6,12 +6,13 @@ use bevy::ptr::Ptr;
 use bevy::utils::{hashbrown, hashbrown::hash_map::Entry};
 use bevy::utils::{Duration, HashMap};
 use bytes::Bytes;
+use crossbeam_channel::Receiver;
 use tracing::{debug, info, info_span, trace, trace_span};
 #[cfg(feature = "trace")]
 use tracing::{instrument, Level};
 
 use crate::channel::builder::{
-    EntityActionsChannel, EntityUpdatesChannel, PingChannel, PongChannel,
+    EntityActionsChannel, EntityUpdatesChannel, PingChannel, PongChannel, RpcChannel,
 };
 
 use crate::channel::receivers::ChannelReceive;
@@ -19,7 +20,8 @@ use crate::channel::senders::ChannelSend;
 use crate::client::message::ClientMessage;
 use crate::connection::id::ClientId;
 use crate::connection::netcode::MAX_PACKET_SIZE;
-use crate::packet::message_manager::MessageManager;
+use crate::packet::message::MessageId;
+use crate::packet::message_manager::{MessageManager, DEFAULT_MESSAGE_PRIORITY};
 use crate::packet::packet_builder::{Payload, RecvPayload};
 use crate::prelude::server::{DisconnectEvent, RoomId, RoomManager};
 use crate::prelude::{
@@ -31,7 +33,7 @@ use crate::protocol::component::{
     ComponentError, ComponentKind, ComponentNetId, ComponentRegistry,
 };
 use crate::protocol::event::EventReplicationMode;
-use crate::protocol::message::{MessageError, MessageRegistry, MessageType};
+use crate::protocol::message::{MessageError, MessageKind, MessageRegistry, MessageType};
 use crate::protocol::registry::NetId;
 use crate::serialize::reader::Reader;
 use crate::serialize::writer::Writer;
@@ -53,6 +55,7 @@ use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::{EntityActionsMessage, EntityUpdatesMessage, ReplicationPeer};
 use crate::shared::replication::{ReplicationReceive, ReplicationSend};
+use crate::shared::rpc::{RpcFailure, RpcId, RpcManager, RpcRequest, RpcResponse};
 use crate::shared::sets::ServerMarker;
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
@@ -72,6 +75,8 @@ pub struct ConnectionManager {
     // (we want to keep track of them because we need to replicate the entire world state to them)
     pub(crate) new_clients: Vec<ClientId>,
     pub(crate) writer: Writer,
+    /// Requests sent to clients that failed, and that still need to be emitted as events
+    pub(crate) rpc_failures: Vec<(ClientId, RpcFailure)>,
 
     // CONFIG
     replication_config: ReplicationConfig,
@@ -108,6 +113,7 @@ impl ConnectionManager {
             delta_manager: DeltaManager::default(),
             new_clients: vec![],
             writer: Writer::with_capacity(MAX_PACKET_SIZE),
+            rpc_failures: vec![],
             replication_config,
             packet_config,
             ping_config,
@@ -163,6 +169,139 @@ impl ConnectionManager {
         self.send_message_to_target::<C, M>(message, NetworkTarget::Single(client_id))
     }
 
+    /// Queues up a message to be sent to a client, with a time-to-live that overrides the one
+    /// set in the [`ReliableSettings`](crate::prelude::ReliableSettings) of the channel.
+    /// If `ttl` is `None`, the time-to-live of the channel is used.
+    ///
+    /// If the message is not acked before the ttl elapses, the sender stops resending it.
+    /// Returns the [`MessageId`] of the message, which can be used to cancel it or to match it with the
+    /// nacks obtained via [`subscribe_nacks`](Self::subscribe_nacks). Messages sent to a local client are not
+    /// assigned a [`MessageId`].
+    pub fn send_message_with_ttl<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        message: &M,
+        ttl: Option<Duration>,
+    ) -> Result<Option<MessageId>, ServerError> {
+        self.buffer_message_with_ttl::<C, M>(client_id, message, ttl.map(Some))
+    }
+
+    /// Queues up a message to be sent to a client, and keep resending it until it is acked
+    /// even if the channel has a time-to-live.
+    ///
+    /// Returns the [`MessageId`] of the message, which can be used to cancel it. Messages sent to a
+    /// local client are not assigned a [`MessageId`].
+    pub fn send_message_without_ttl<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        message: &M,
+    ) -> Result<Option<MessageId>, ServerError> {
+        self.buffer_message_with_ttl::<C, M>(client_id, message, Some(None))
+    }
+
+    /// Buffer a message with the time-to-live of the channel if `ttl` is `None`, or with the
+    /// time-to-live `ttl` otherwise (`Some(None)` means that the message never expires)
+    fn buffer_message_with_ttl<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        message: &M,
+        ttl: Option<Option<Duration>>,
+    ) -> Result<Option<MessageId>, ServerError> {
+        let connection = self
+            .connections
+            .get_mut(&client_id)
+            .ok_or(ServerError::ClientIdNotFound(client_id))?;
+        let message_bytes = serialize_message_for(
+            &self.message_registry,
+            &mut self.writer,
+            connection,
+            message,
+        )?;
+        // for local clients, we don't want to buffer messages in the MessageManager since
+        // there is no io
+        if connection.is_local_client() {
+            connection.local_messages_to_send.push(message_bytes);
+            return Ok(None);
+        }
+        let channel_kind = ChannelKind::of::<C>();
+        Ok(match ttl {
+            None => connection.message_manager.buffer_send_with_priority(
+                message_bytes,
+                channel_kind,
+                DEFAULT_MESSAGE_PRIORITY,
+            )?,
+            Some(ttl) => connection.message_manager.buffer_send_with_ttl(
+                message_bytes,
+                channel_kind,
+                DEFAULT_MESSAGE_PRIORITY,
+                ttl,
+            )?,
+        })
+    }
+
+    /// Stop sending a message that was sent to a client on a reliable channel and is not acked yet.
+    ///
+    /// Returns true if the message was cancelled.
+    pub fn cancel_message<C: Channel>(
+        &mut self,
+        client_id: ClientId,
+        message_id: MessageId,
+    ) -> Result<bool, ServerError> {
+        Ok(self
+            .connection_mut(client_id)?
+            .message_manager
+            .cancel(message_id, ChannelKind::of::<C>())?)
+    }
+
+    /// Get notified of the [`MessageId`]s of the messages sent to a client on channel `C`
+    /// that were lost or expired
+    pub fn subscribe_nacks<C: Channel>(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<Receiver<MessageId>, ServerError> {
+        Ok(self
+            .connection_mut(client_id)?
+            .message_manager
+            .subscribe_nacks(ChannelKind::of::<C>())?)
+    }
+
+    /// Send a request to a client on the [`RpcChannel`].
+    ///
+    /// The request/response pair must have been registered with [`register_rpc`](crate::prelude::AppRpcExt::register_rpc).
+    /// Returns the [`RpcId`] of the request; a [`RpcResponseEvent<Res>`](crate::prelude::RpcResponseEvent) with the same id
+    /// will be emitted when the response is received, or when no response was received before the `timeout`.
+    pub fn send_request<Req: Message, Res: Message>(
+        &mut self,
+        client_id: ClientId,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<RpcId, ServerError> {
+        let id = self.connection_mut(client_id)?.rpc_manager.next_id();
+        // stop resending the request once nobody is waiting for the response
+        let message_id = self.send_message_with_ttl::<RpcChannel, _>(
+            client_id,
+            &RpcRequest { id, request },
+            Some(timeout),
+        )?;
+        self.connection_mut(client_id)?.rpc_manager.add_pending(
+            id,
+            MessageKind::of::<RpcResponse<Res>>(),
+            message_id,
+            timeout,
+        );
+        Ok(id)
+    }
+
+    /// Answer a request that was received from a client
+    pub fn send_response<Res: Message>(
+        &mut self,
+        client_id: ClientId,
+        id: RpcId,
+        response: Res,
+    ) -> Result<(), ServerError> {
+        self.send_message::<RpcChannel, _>(client_id, &RpcResponse { id, response })
+    }
+
     /// Send an event to all clients in a room
     pub fn send_event_to_room<C: Channel, E: Event + Message>(
         &mut self,
@@ -228,6 +367,32 @@ impl ConnectionManager {
         Ok(())
     }
 
+    /// Scale the priority of a `ReplicationGroup` for a given client.
+    ///
+    /// Every send_interval, the priority of each group is accumulated by `base_priority * multiplier`,
+    /// where the multiplier is specific to each client. This lets you make a group less important for a client
+    /// (for example because it is far away from that client's player) without affecting the other clients.
+    ///
+    /// The multiplier stays in effect until it is updated again. A good place to update it is in the
+    /// [`ServerReplicationSet::UpdatePriority`](crate::prelude::server::ServerReplicationSet::UpdatePriority) system set.
+    pub fn set_priority_multiplier(
+        &mut self,
+        replication_group_id: ReplicationGroupId,
+        client_id: ClientId,
+        multiplier: f32,
+    ) -> Result<(), ServerError> {
+        trace!(
+            ?client_id,
+            ?replication_group_id,
+            "Set priority multiplier to {:?}",
+            multiplier
+        );
+        self.connection_mut(client_id)?
+            .replication_sender
+            .update_priority_multiplier(replication_group_id, multiplier);
+        Ok(())
+    }
+
     /// Find the list of connected clients that match the provided [`NetworkTarget`]
     pub(crate) fn connected_targets(
         &self,
@@ -293,9 +458,18 @@ impl ConnectionManager {
         time_manager: &TimeManager,
         tick_manager: &TickManager,
     ) {
-        self.connections.values_mut().for_each(|connection| {
-            connection.update(world_tick, time_manager, tick_manager);
-        });
+        self.connections
+            .iter_mut()
+            .for_each(|(client_id, connection)| {
+                connection.update(world_tick, time_manager, tick_manager);
+                self.rpc_failures.extend(
+                    connection
+                        .rpc_manager
+                        .failures
+                        .drain(..)
+                        .map(|failure| (*client_id, failure)),
+                );
+            });
     }
 
     /// Add a new [`Connection`] to the list of connections with the given [`ClientId`]
@@ -333,10 +507,19 @@ impl ConnectionManager {
             self.events
                 .add_disconnect_event(DisconnectEvent { client_id, entity });
         }
-        if self.connections.remove(&client_id).is_some() {
+        if let Some(mut connection) = self.connections.remove(&client_id) {
             #[cfg(feature = "metrics")]
             metrics::gauge!("server::connected_clients").decrement(1.0);
             info!("Client {} disconnected", client_id);
+            // the pending requests will never get a response
+            connection.rpc_manager.disconnect();
+            self.rpc_failures.extend(
+                connection
+                    .rpc_manager
+                    .failures
+                    .drain(..)
+                    .map(|failure| (client_id, failure)),
+            );
         };
     }
 
@@ -375,12 +558,8 @@ impl ConnectionManager {
             .iter_mut()
             .filter(|(id, _)| target.targets(id))
             .try_for_each(|(_, c)| {
-                self.message_registry.serialize(
-                    message,
-                    &mut self.writer,
-                    Some(&mut c.replication_receiver.remote_entity_map.local_to_remote),
-                )?;
-                let message_bytes = self.writer.split();
+                let message_bytes =
+                    serialize_message_for(&self.message_registry, &mut self.writer, c, message)?;
                 // for local clients, we don't want to buffer messages in the MessageManager since
                 // there is no io
                 if c.is_local_client() {
@@ -502,6 +681,26 @@ impl ConnectionManager {
     }
 }
 
+/// Serialize a message for a specific client, mapping its entities with the entity map of that connection
+fn serialize_message_for<M: Message>(
+    message_registry: &MessageRegistry,
+    writer: &mut Writer,
+    connection: &mut Connection,
+    message: &M,
+) -> Result<Bytes, ServerError> {
+    message_registry.serialize(
+        message,
+        writer,
+        Some(
+            &mut connection
+                .replication_receiver
+                .remote_entity_map
+                .local_to_remote,
+        ),
+    )?;
+    Ok(writer.split())
+}
+
 /// Wrapper that handles the connection between the server and a client
 pub struct Connection {
     client_id: ClientId,
@@ -528,6 +727,8 @@ pub struct Connection {
     is_local_client: bool,
     /// Messages to send to the local client (we don't buffer them in the MessageManager because there is no io)
     pub(crate) local_messages_to_send: Vec<Bytes>,
+    /// Requests sent to the client that are waiting for a response
+    pub(crate) rpc_manager: RpcManager,
 }
 
 impl Connection {
@@ -539,12 +740,14 @@ impl Connection {
         packet_config: PacketConfig,
         ping_config: PingConfig,
     ) -> Self {
-        let bandwidth_cap_enabled = packet_config.bandwidth_cap_enabled;
+        let bandwidth_cap_enabled =
+            packet_config.bandwidth_cap_enabled || packet_config.congestion_control.is_some();
         // create the message manager and the channels
         let mut message_manager = MessageManager::new(
             channel_registry,
             packet_config.nack_rtt_multiple,
             packet_config.into(),
+            packet_config.mtu_discovery,
         );
         // get notified about acks/nacks for replication-update messages
         let entity_updates_sender = &mut message_manager
@@ -565,6 +768,7 @@ impl Connection {
             bandwidth_cap_enabled,
         );
         let replication_receiver = ReplicationReceiver::new();
+        let rpc_manager = RpcManager::new(&mut message_manager);
         Self {
             client_id,
             entity,
@@ -581,6 +785,7 @@ impl Connection {
             messages_to_rebroadcast: vec![],
             is_local_client: false,
             local_messages_to_send: vec![],
+            rpc_manager,
         }
     }
 
@@ -604,12 +809,23 @@ impl Connection {
         self.ping_manager.jitter()
     }
 
+    /// Current send budget in bytes per second, if congestion control is enabled
+    pub fn send_budget(&self) -> Option<u32> {
+        self.message_manager.send_budget()
+    }
+
+    /// Maximum size of the packets sent to this client
+    pub fn packet_size(&self) -> usize {
+        self.message_manager.packet_size()
+    }
+
     pub(crate) fn update(
         &mut self,
         world_tick: BevyTick,
         time_manager: &TimeManager,
         tick_manager: &TickManager,
     ) {
+        self.rpc_manager.update(time_manager.delta());
         if self.is_local_client() {
             return;
         }
@@ -1100,10 +1316,7 @@ impl ConnectionManager {
             let connection = self.connections.get_mut(&client_id).ok_or(ServerError::ClientIdNotFound(client_id))?;
             let send_tick = connection
                 .replication_sender
-                .group_channels
-                .entry(group_id)
-                .or_default()
-                .send_tick;
+                .get_changes_since_tick(group_id);
             // send the update for all changes newer than the last send_tick for the group
             trace!(
                 name = ?registry.name(kind),
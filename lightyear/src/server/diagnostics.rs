@@ -0,0 +1,97 @@
+//! Collect diagnostics about the client connections
+use bevy::diagnostic::{Diagnostic, DiagnosticMeasurement, DiagnosticPath, DiagnosticsStore};
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::{Duration, Instant};
+
+use crate::connection::id::ClientId;
+use crate::server::connection::ConnectionManager;
+use crate::server::events::DisconnectEvent;
+use crate::server::run_conditions::is_started;
+
+/// Plugin in charge of collecting diagnostics for each client connection.
+pub struct ServerDiagnosticsPlugin {
+    /// Number of diagnostics to keep in history
+    history_length: usize,
+    /// How often to flush the stored data into the Diagnostics
+    flush_interval: Duration,
+}
+
+impl Default for ServerDiagnosticsPlugin {
+    fn default() -> Self {
+        Self {
+            history_length: 60,
+            flush_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Resource holding the settings of the [`ServerDiagnosticsPlugin`], used to register the diagnostics
+/// of new clients
+#[derive(Resource)]
+struct ServerDiagnosticsSettings {
+    history_length: usize,
+}
+
+impl ServerDiagnosticsPlugin {
+    /// Current send budget of the connection to a client, if congestion control is enabled
+    pub fn send_budget(client_id: ClientId) -> DiagnosticPath {
+        DiagnosticPath::new(format!("congestion.send_budget.{client_id}.kbps"))
+    }
+
+    fn flush_measurements(
+        connection_manager: Res<ConnectionManager>,
+        settings: Res<ServerDiagnosticsSettings>,
+        mut store: ResMut<DiagnosticsStore>,
+    ) {
+        let now = Instant::now();
+        for (client_id, connection) in connection_manager.connections.iter() {
+            let Some(budget) = connection.send_budget() else {
+                continue;
+            };
+            // the diagnostics are registered dynamically, when the client connects
+            let path = Self::send_budget(*client_id);
+            if store.get(&path).is_none() {
+                store.add(
+                    Diagnostic::new(path.clone())
+                        .with_suffix("kB/s")
+                        .with_max_history_length(settings.history_length),
+                );
+            }
+            if let Some(diagnostic) = store.get_mut(&path) {
+                // the diagnostic was disabled if the client disconnected before
+                diagnostic.is_enabled = true;
+                diagnostic.add_measurement(DiagnosticMeasurement {
+                    time: now,
+                    value: budget as f64 / 1000.0,
+                });
+            }
+        }
+    }
+
+    /// Stop reporting the diagnostics of a client when it disconnects
+    fn handle_client_disconnect(
+        trigger: Trigger<DisconnectEvent>,
+        mut store: ResMut<DiagnosticsStore>,
+    ) {
+        // the DiagnosticsStore cannot remove a diagnostic, so we disable it and drop its history instead
+        if let Some(diagnostic) = store.get_mut(&Self::send_budget(trigger.event().client_id)) {
+            diagnostic.is_enabled = false;
+            diagnostic.clear_history();
+        }
+    }
+}
+
+impl Plugin for ServerDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DiagnosticsStore>();
+        app.insert_resource(ServerDiagnosticsSettings {
+            history_length: self.history_length,
+        });
+        app.add_systems(
+            PostUpdate,
+            Self::flush_measurements.run_if(on_timer(self.flush_interval).and(is_started)),
+        );
+        app.add_observer(Self::handle_client_disconnect);
+    }
+}
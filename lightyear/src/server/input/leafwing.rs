@@ -1,30 +1,69 @@
 //! Handles client-generated inputs
+//!
+//! ### Relaying inputs to other clients
+//!
+//! If [`LeafwingInputConfig::rebroadcast_inputs`] is enabled, the server will forward the inputs it receives from
+//! a client to the other clients that the controlled entity is replicated to (taking network relevance and rooms into account).
+//! The other clients use them to fill the [`InputBuffer`] of their predicted copy of the entity, so that remote players can be
+//! predicted with their real inputs instead of their last known state.
+//!
+//! Before being used by the server and relayed, the messages of the current frame are stored in the [`ReceivedInputMessages`]
+//! resource. You can add systems in [`InputSystemSet::ValidateInputs`] to inspect, sanitize or reject them:
+//!
+//! ```rust,ignore
+//! fn validate_inputs(
+//!     mut messages: ResMut<ReceivedInputMessages<PlayerActions>>,
+//!     controlled: Query<&ControlledBy>,
+//! ) {
+//!     messages.retain(|client_id, message| {
+//!         // clients can only send inputs for the entities that they control
+//!         message.retain_targets(|target| match target {
+//!             InputTarget::Entity(entity) | InputTarget::PrePredictedEntity(entity) => controlled
+//!                 .get(entity)
+//!                 .is_ok_and(|controlled_by| controlled_by.targets(&client_id)),
+//!             InputTarget::Global => false,
+//!         });
+//!         message.targets().next().is_some()
+//!     });
+//! }
+//!
+//! app.add_systems(PreUpdate, validate_inputs.in_set(InputSystemSet::ValidateInputs));
+//! ```
 use std::ops::DerefMut;
 
+use crate::channel::builder::InputChannel;
+use crate::client::input::leafwing::LeafwingInputConfig;
 use crate::inputs::leafwing::input_buffer::InputBuffer;
-use crate::inputs::leafwing::input_message::InputTarget;
+use crate::inputs::leafwing::input_message::{InputTarget, PerTargetData};
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
 use crate::inputs::leafwing::LeafwingUserAction;
-use crate::prelude::server::MessageEvent;
-use crate::prelude::{server::is_started, InputMessage, MessageRegistry, Mode, TickManager};
+use crate::prelude::server::{MessageEvent, ReplicationTarget};
+use crate::prelude::{
+    server::is_started, ClientId, InputMessage, MessageRegistry, MessageSend, Mode, TickManager,
+};
 use crate::protocol::message::MessageKind;
 use crate::serialize::reader::Reader;
 use crate::server::config::ServerConfig;
 use crate::server::connection::ConnectionManager;
+use crate::server::relevance::immediate::{CachedNetworkRelevance, ClientRelevance};
 use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
 
 pub struct LeafwingInputPlugin<A> {
-    marker: std::marker::PhantomData<A>,
+    config: LeafwingInputConfig<A>,
+}
+
+impl<A> LeafwingInputPlugin<A> {
+    pub fn new(config: LeafwingInputConfig<A>) -> Self {
+        Self { config }
+    }
 }
 
 impl<A> Default for LeafwingInputPlugin<A> {
     fn default() -> Self {
-        Self {
-            marker: std::marker::PhantomData,
-        }
+        Self::new(LeafwingInputConfig::default())
     }
 }
 
@@ -34,15 +73,56 @@ pub enum InputSystemSet {
     AddBuffers,
     /// Receive the latest ActionDiffs from the client
     ReceiveInputs,
+    /// Inspect, sanitize or reject the input messages stored in [`ReceivedInputMessages`].
+    /// The User should add their validation systems here!
+    ValidateInputs,
+    /// Use the validated input messages to update the [`InputBuffer`]s, and relay them to the other clients
+    ApplyInputs,
     /// Use the ActionDiff received from the client to update the [`ActionState`]
     Update,
 }
 
+/// Input messages received from the clients during the current frame.
+///
+/// Systems in [`InputSystemSet::ValidateInputs`] can modify or remove messages before they are used to update
+/// the [`InputBuffer`]s on the server and relayed to the other clients.
+///
+/// The start state and the diffs of each target can be modified with [`InputMessage::inputs_mut`].
+#[derive(Resource, Debug)]
+pub struct ReceivedInputMessages<A: LeafwingUserAction> {
+    pub(crate) messages: Vec<(ClientId, InputMessage<A>)>,
+}
+
+impl<A: LeafwingUserAction> Default for ReceivedInputMessages<A> {
+    fn default() -> Self {
+        Self {
+            messages: Vec::default(),
+        }
+    }
+}
+
+impl<A: LeafwingUserAction> ReceivedInputMessages<A> {
+    /// Iterate through the messages, along with the client that sent them
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ClientId, &mut InputMessage<A>)> {
+        self.messages
+            .iter_mut()
+            .map(|(client_id, message)| (*client_id, message))
+    }
+
+    /// Only keep the messages for which `f` returns `true`
+    pub fn retain(&mut self, mut f: impl FnMut(ClientId, &mut InputMessage<A>) -> bool) {
+        self.messages
+            .retain_mut(|(client_id, message)| f(*client_id, message));
+    }
+}
+
 impl<A: LeafwingUserAction> Plugin for LeafwingInputPlugin<A> {
     fn build(&self, app: &mut App) {
         // RESOURCES
         // app.init_resource::<GlobalActions<A>>();
         // TODO: (global action states) add a resource tracking the action-state of all clients
+        app.insert_resource(self.config);
+        app.init_resource::<ReceivedInputMessages<A>>();
         // SETS
         app.configure_sets(
             PreUpdate,
@@ -50,6 +130,8 @@ impl<A: LeafwingUserAction> Plugin for LeafwingInputPlugin<A> {
                 InternalMainSet::<ServerMarker>::Receive,
                 InputSystemSet::AddBuffers,
                 InputSystemSet::ReceiveInputs,
+                InputSystemSet::ValidateInputs,
+                InputSystemSet::ApplyInputs,
             )
                 .chain()
                 .run_if(is_started),
@@ -63,6 +145,7 @@ impl<A: LeafwingUserAction> Plugin for LeafwingInputPlugin<A> {
                 add_action_diff_buffer::<A>.in_set(InputSystemSet::AddBuffers),
                 // TODO: can disable this in host-server mode!
                 receive_input_message::<A>.in_set(InputSystemSet::ReceiveInputs),
+                apply_input_messages::<A>.in_set(InputSystemSet::ApplyInputs),
             ),
         );
         app.add_systems(
@@ -90,14 +173,12 @@ fn add_action_diff_buffer<A: LeafwingUserAction>(
     }
 }
 
-/// Read the input messages from the server events to update the InputBuffers
+/// Read the input messages received from the clients and store them in [`ReceivedInputMessages`]
 fn receive_input_message<A: LeafwingUserAction>(
     message_registry: Res<MessageRegistry>,
     mut connection_manager: ResMut<ConnectionManager>,
-    // TODO: currently we do not handle entities that are controlled by multiple clients
-    mut query: Query<Option<&mut InputBuffer<A>>>,
+    mut received_messages: ResMut<ReceivedInputMessages<A>>,
     mut commands: Commands,
-    mut events: EventWriter<MessageEvent<InputMessage<A>>>,
 ) {
     let kind = MessageKind::of::<InputMessage<A>>();
     let Some(net) = message_registry.kind_map.net_id(&kind).copied() else {
@@ -107,11 +188,11 @@ fn receive_input_message<A: LeafwingUserAction>(
         );
         return;
     };
-    // re-borrow to allow split borrows
-    let connection_manager = connection_manager.deref_mut();
     for (client_id, connection) in connection_manager.connections.iter_mut() {
         if let Some(message_list) = connection.received_leafwing_input_messages.remove(&net) {
-            for (message_bytes, target, channel_kind) in message_list {
+            // NOTE: the target requested by the client is ignored, the inputs are only relayed by the server
+            //  after they have been validated
+            for (message_bytes, _, _) in message_list {
                 let mut reader = Reader::from(message_bytes);
                 match message_registry.deserialize::<InputMessage<A>>(
                     &mut reader,
@@ -130,78 +211,7 @@ fn receive_input_message<A: LeafwingUserAction>(
                                 .entity(connection.entity)
                                 .insert(interpolation_delay);
                         }
-                        // TODO: UPDATE THIS
-                        for data in &message.diffs {
-                            match data.target {
-                                // - for pre-predicted entities, we already did the mapping on server side upon receiving the message
-                                // (which is possible because the server received the entity)
-                                // - for non-pre predicted entities, the mapping was already done on client side
-                                // (client converted from their local entity to the remote server entity)
-                                InputTarget::Entity(entity)
-                                | InputTarget::PrePredictedEntity(entity) => {
-                                    // TODO Don't update input buffer if inputs arrived too late?
-                                    trace!("received input for entity: {:?}", entity);
-
-                                    if let Ok(buffer) = query.get_mut(entity) {
-                                        if let Some(mut buffer) = buffer {
-                                            trace!(
-                                                ?target,
-                                                "Update InputBuffer: {} using InputMessage: {}",
-                                                buffer.as_ref(),
-                                                message
-                                            );
-                                            buffer.update_from_message(
-                                                message.end_tick,
-                                                &data.start_state,
-                                                &data.diffs,
-                                            );
-                                        } else {
-                                            debug!("Adding InputBuffer and ActionState which are missing on the entity");
-                                            commands.entity(entity).insert((
-                                                InputBuffer::<A>::default(),
-                                                ActionState::<A>::default(),
-                                            ));
-                                        }
-                                    } else {
-                                        debug!(?entity, ?data.diffs, end_tick = ?message.end_tick, "received input message for unrecognized entity");
-                                    }
-                                }
-                                InputTarget::Global => {
-                                    // TODO: handle global diffs for each client! How? create one entity per client?
-                                    //  or have a resource containing the global ActionState for each client?
-                                    // if let Some(ref mut buffer) = global {
-                                    //     buffer.update_from_message(message.end_tick, std::mem::take(&mut message.global_diffs))
-                                    // }
-                                }
-                            }
-                        }
-
-                        // TODO: rebroadcast is never used right now because
-                        //  - it's hard to specify on the client who we want to rebroadcast to
-                        //  - we shouldn't rebroadcast immediately, instead we want to let the server inspect the input
-                        //    to verify that there's no cheating
-                        //  Instead, add a system that manually rebroadcast inputs
-
-                        // rebroadcast
-                        if target != NetworkTarget::None {
-                            if let Ok(()) = message_registry.serialize(
-                                &message,
-                                &mut connection_manager.writer,
-                                Some(
-                                    &mut connection
-                                        .replication_receiver
-                                        .remote_entity_map
-                                        .local_to_remote,
-                                ),
-                            ) {
-                                connection.messages_to_rebroadcast.push((
-                                    reader.consume(),
-                                    target,
-                                    channel_kind,
-                                ));
-                            }
-                        }
-                        events.send(MessageEvent::new(message, *client_id));
+                        received_messages.messages.push((*client_id, message));
                     }
                     Err(e) => {
                         error!(?e, "could not deserialize leafwing input message");
@@ -212,6 +222,134 @@ fn receive_input_message<A: LeafwingUserAction>(
     }
 }
 
+/// Use the validated input messages to update the InputBuffers, then relay them to the other clients
+fn apply_input_messages<A: LeafwingUserAction>(
+    config: Res<LeafwingInputConfig<A>>,
+    mut received_messages: ResMut<ReceivedInputMessages<A>>,
+    mut connection_manager: ResMut<ConnectionManager>,
+    // TODO: currently we do not handle entities that are controlled by multiple clients
+    mut query: Query<Option<&mut InputBuffer<A>>>,
+    relevance_query: Query<(&ReplicationTarget, Option<&CachedNetworkRelevance>)>,
+    mut commands: Commands,
+    mut events: EventWriter<MessageEvent<InputMessage<A>>>,
+) {
+    for (client_id, message) in received_messages.messages.drain(..) {
+        for data in &message.diffs {
+            match data.target {
+                // - for pre-predicted entities, we already did the mapping on server side upon receiving the message
+                // (which is possible because the server received the entity)
+                // - for non-pre predicted entities, the mapping was already done on client side
+                // (client converted from their local entity to the remote server entity)
+                InputTarget::Entity(entity) | InputTarget::PrePredictedEntity(entity) => {
+                    // TODO Don't update input buffer if inputs arrived too late?
+                    trace!("received input for entity: {:?}", entity);
+
+                    if let Ok(buffer) = query.get_mut(entity) {
+                        if let Some(mut buffer) = buffer {
+                            trace!(
+                                ?client_id,
+                                "Update InputBuffer: {} using InputMessage: {}",
+                                buffer.as_ref(),
+                                message
+                            );
+                            buffer.update_from_message(
+                                message.end_tick,
+                                &data.start_state,
+                                &data.diffs,
+                            );
+                        } else {
+                            debug!("Adding InputBuffer and ActionState which are missing on the entity");
+                            commands
+                                .entity(entity)
+                                .insert((InputBuffer::<A>::default(), ActionState::<A>::default()));
+                        }
+                    } else {
+                        debug!(?entity, ?data.diffs, end_tick = ?message.end_tick, "received input message for unrecognized entity");
+                    }
+                }
+                InputTarget::Global => {
+                    // TODO: handle global diffs for each client! How? create one entity per client?
+                    //  or have a resource containing the global ActionState for each client?
+                    // if let Some(ref mut buffer) = global {
+                    //     buffer.update_from_message(message.end_tick, std::mem::take(&mut message.global_diffs))
+                    // }
+                }
+            }
+        }
+        if config.rebroadcast_inputs {
+            rebroadcast_input_message(
+                client_id,
+                &message,
+                connection_manager.deref_mut(),
+                &relevance_query,
+            );
+        }
+        events.send(MessageEvent::new(message, client_id));
+    }
+}
+
+/// Send the inputs of `client_id` to the other clients that the target entities are replicated to
+fn rebroadcast_input_message<A: LeafwingUserAction>(
+    client_id: ClientId,
+    message: &InputMessage<A>,
+    connection_manager: &mut ConnectionManager,
+    relevance_query: &Query<(&ReplicationTarget, Option<&CachedNetworkRelevance>)>,
+) {
+    // the local client in host-server mode shares the server's entities, it doesn't need to predict them
+    let mut excluded = NetworkTarget::Only(
+        connection_manager
+            .connections
+            .iter()
+            .filter(|(_, connection)| connection.is_local_client())
+            .map(|(id, _)| *id)
+            .collect(),
+    );
+    excluded.union(&NetworkTarget::Single(client_id));
+    for data in &message.diffs {
+        let entity = match data.target {
+            InputTarget::Entity(entity) | InputTarget::PrePredictedEntity(entity) => entity,
+            // remote clients only use the inputs of entities
+            InputTarget::Global => continue,
+        };
+        let Ok((replication_target, relevance)) = relevance_query.get(entity) else {
+            continue;
+        };
+        // only send the inputs to the clients that the entity is replicated to
+        let mut target = match relevance {
+            Some(relevance) => NetworkTarget::Only(
+                relevance
+                    .clients_cache
+                    .iter()
+                    .filter(|(_, relevance)| **relevance != ClientRelevance::Lost)
+                    .map(|(id, _)| *id)
+                    .collect(),
+            ),
+            None => NetworkTarget::All,
+        };
+        target.intersection(&replication_target.target);
+        target.exclude(&excluded);
+        if target.is_empty() {
+            continue;
+        }
+        // the entity is now a server entity, which the other clients can map to their local entity
+        let relayed_message = InputMessage {
+            interpolation_delay: None,
+            end_tick: message.end_tick,
+            diffs: vec![PerTargetData {
+                target: InputTarget::Entity(entity),
+                start_state: data.start_state.clone(),
+                diffs: data.diffs.clone(),
+            }],
+        };
+        trace!(?client_id, ?entity, ?target, "rebroadcast input message");
+        connection_manager
+            .send_message_to_target::<InputChannel, _>(&relayed_message, target)
+            .unwrap_or_else(|err| {
+                error!("Error while rebroadcasting input message: {:?}", err);
+            });
+    }
+}
+
 /// Read the InputState for the current tick from the buffer, and use them to update the ActionState
 fn update_action_state<A: LeafwingUserAction>(
     tick_manager: Res<TickManager>,
@@ -256,6 +394,7 @@ mod tests {
 
     use crate::prelude::client;
     use crate::prelude::server::*;
+    use crate::tests::multi_stepper::MultiBevyStepper;
     use crate::tests::protocol::*;
     use crate::tests::stepper::BevyStepper;
 
@@ -361,4 +500,91 @@ mod tests {
             .unwrap()
             .released(&LeafwingInput1::Jump));
     }
+
+    /// Check that the server relays the inputs of a client to the other clients, who use them to
+    /// update the InputBuffer of their predicted entity
+    #[test]
+    fn test_rebroadcast_inputs() {
+        let mut stepper = MultiBevyStepper::default();
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<LeafwingInputConfig<LeafwingInput1>>()
+            .rebroadcast_inputs = true;
+
+        // create an entity on server, that is predicted by all clients
+        let server_entity = stepper
+            .server_app
+            .world_mut()
+            .spawn((
+                ActionState::<LeafwingInput1>::default(),
+                Replicate {
+                    sync: SyncTarget {
+                        prediction: NetworkTarget::All,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // client 1 controls the entity
+        let client_entity_1 = stepper
+            .client_app_1
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        stepper
+            .client_app_1
+            .world_mut()
+            .entity_mut(client_entity_1)
+            .insert(InputMap::<LeafwingInput1>::new([(
+                LeafwingInput1::Jump,
+                KeyCode::KeyA,
+            )]));
+        let confirmed_entity_2 = stepper
+            .client_app_2
+            .world()
+            .resource::<client::ConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        stepper.frame_step();
+        let predicted_entity_2 = stepper
+            .client_app_2
+            .world()
+            .get::<client::Confirmed>(confirmed_entity_2)
+            .unwrap()
+            .predicted
+            .unwrap();
+
+        stepper
+            .client_app_1
+            .world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyA);
+        stepper.frame_step();
+        let client_tick = stepper
+            .client_app_1
+            .world()
+            .resource::<TickManager>()
+            .tick();
+        // the inputs go from client 1 to the server, then from the server to client 2
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app_2
+            .world()
+            .get::<InputBuffer<LeafwingInput1>>(predicted_entity_2)
+            .unwrap()
+            .get(client_tick)
+            .unwrap()
+            .pressed(&LeafwingInput1::Jump));
+    }
 }
@@ -1,16 +1,17 @@
 //! Handles client-generated inputs
+use bevy::ecs::entity::Entities;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 
 use crate::inputs::native::input_buffer::InputBuffer;
 use crate::inputs::native::InputMessage;
+use crate::inputs::InputTarget;
 use crate::prelude::server::DisconnectEvent;
 use crate::prelude::{server::is_started, ClientId, MessageRegistry, TickManager, UserAction};
 use crate::protocol::message::MessageKind;
 use crate::serialize::reader::Reader;
 use crate::server::connection::ConnectionManager;
 use crate::server::events::InputEvent;
-use crate::shared::replication::network_target::NetworkTarget;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
 
 pub struct InputPlugin<A: UserAction> {
@@ -19,9 +20,33 @@ pub struct InputPlugin<A: UserAction> {
 
 #[derive(Resource, Debug)]
 pub struct InputBuffers<A> {
+    /// The inputs of each client, for the client's global inputs (`None`) and for each of the entities
+    /// that the client controls.
+    ///
     /// The first element stores the last input we have received from the client.
     /// In case we are missing the client input for a tick, we will fallback to using this.
-    pub(crate) buffers: HashMap<ClientId, (Option<A>, InputBuffer<A>)>,
+    pub(crate) buffers: HashMap<(ClientId, Option<Entity>), (Option<A>, InputBuffer<A>)>,
+}
+
+impl<A: UserAction> InputBuffers<A> {
+    /// Update the input buffers of the client `client_id` with a message received from that client
+    pub(crate) fn update_from_message(&mut self, client_id: ClientId, message: InputMessage<A>) {
+        for data in message.inputs {
+            // - for pre-predicted entities, the mapping was done on the server when receiving the message
+            // - for other entities, the client already converted their local entity to the server entity
+            let entity = match data.target {
+                InputTarget::Global => None,
+                InputTarget::Entity(entity) | InputTarget::PrePredictedEntity(entity) => {
+                    Some(entity)
+                }
+            };
+            self.buffers
+                .entry((client_id, entity))
+                .or_default()
+                .1
+                .update_from_message(message.end_tick, data.inputs);
+        }
+    }
 }
 
 impl<A> Default for InputBuffers<A> {
@@ -93,7 +118,10 @@ fn handle_client_disconnect<A: UserAction>(
     trigger: Trigger<DisconnectEvent>,
     mut input_buffers: ResMut<InputBuffers<A>>,
 ) {
-    input_buffers.buffers.remove(&trigger.event().client_id);
+    let client_id = trigger.event().client_id;
+    input_buffers
+        .buffers
+        .retain(|(buffer_client_id, _), _| *buffer_client_id != client_id);
 }
 
 /// Read the message received from the client and emit the MessageEvent event
@@ -112,7 +140,9 @@ fn receive_input_message<A: UserAction>(
     };
     for (client_id, connection) in connection_manager.connections.iter_mut() {
         if let Some(message_list) = connection.received_input_messages.remove(&net) {
-            for (message_bytes, target, channel_kind) in message_list {
+            // NOTE: the native input messages are only sent from the clients to the server, so they
+            //  are not relayed to the other clients and their target is ignored
+            for (message_bytes, _, _) in message_list {
                 let mut reader = Reader::from(message_bytes);
                 match message_registry.deserialize::<InputMessage<A>>(
                     &mut reader,
@@ -123,21 +153,7 @@ fn receive_input_message<A: UserAction>(
                 ) {
                     Ok(message) => {
                         trace!("Received input message: {:?}", message);
-                        input_buffers
-                            .buffers
-                            .entry(*client_id)
-                            .or_default()
-                            .1
-                            .update_from_message(message);
-                        if target != NetworkTarget::None {
-                            // NOTE: we can re-send the same bytes directly because InputMessage does not include any Entity references
-                            connection.messages_to_rebroadcast.push((
-                                // TODO: avoid clone, or use bytes?
-                                reader.consume(),
-                                target,
-                                channel_kind,
-                            ));
-                        }
+                        input_buffers.update_from_message(*client_id, message);
                     }
                     Err(e) => {
                         error!("Error deserializing input message: {:?}", e);
@@ -153,15 +169,24 @@ fn receive_input_message<A: UserAction>(
 // Do it in this system because we want an input for every tick
 fn write_input_event<A: UserAction>(
     tick_manager: Res<TickManager>,
+    entities: &Entities,
     mut input_buffers: ResMut<InputBuffers<A>>,
     mut input_events: EventWriter<InputEvent<A>>,
 ) {
     let tick = tick_manager.tick();
+    // stop emitting inputs for entities that were despawned
     input_buffers
         .buffers
-        .iter_mut()
-        .for_each(move |(client_id, (last_input, input_buffer))| {
-            trace!(?input_buffer, ?tick, ?client_id, "input buffer for client");
+        .retain(|(_, entity), _| entity.map_or(true, |e| entities.contains(e)));
+    input_buffers.buffers.iter_mut().for_each(
+        move |((client_id, entity), (last_input, input_buffer))| {
+            trace!(
+                ?input_buffer,
+                ?tick,
+                ?client_id,
+                ?entity,
+                "input buffer for client"
+            );
             let received_input = input_buffer.pop(tick);
             let fallback = received_input.is_none();
 
@@ -178,6 +203,7 @@ fn write_input_event<A: UserAction>(
                 // TODO: do not log this while clients are syncing..
                 trace!(
                 ?client_id,
+                ?entity,
                 ?tick,
                 fallback_input = ?&input,
                 "Missed client input!"
@@ -186,8 +212,13 @@ fn write_input_event<A: UserAction>(
             // TODO: We should also let the user know that it needs to send inputs a bit earlier so that
             //  we have more of a buffer. Send a SyncMessage to tell the user to speed up?
             //  See Overwatch GDC video
-            input_events.send(InputEvent::new(input, *client_id));
-        });
+            let event = match entity {
+                None => InputEvent::new(input, *client_id),
+                Some(entity) => InputEvent::new_for_entity(input, *client_id, *entity),
+            };
+            input_events.send(event);
+        },
+    );
 }
 
 /// System that clears the input events.
@@ -5,12 +5,14 @@ use crate::transport::channels::Channels;
 use crate::transport::config::SharedIoConfig;
 use crate::transport::dummy::DummyIo;
 use crate::transport::io::IoStats;
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::middleware::capture::PacketCapture;
 #[cfg(feature = "zstd")]
 use crate::transport::middleware::compression::zstd::compression::ZstdCompressor;
 #[cfg(feature = "zstd")]
 use crate::transport::middleware::compression::zstd::decompression::ZstdDecompressor;
 use crate::transport::middleware::conditioner::LinkConditioner;
-use crate::transport::middleware::PacketReceiverWrapper;
+use crate::transport::middleware::{PacketReceiverWrapper, PacketSenderWrapper};
 use crate::transport::udp::UdpSocketBuilder;
 #[cfg(all(feature = "websocket", not(target_family = "wasm")))]
 use crate::transport::websocket::server::WebSocketServerSocketBuilder;
@@ -122,8 +124,12 @@ impl SharedIoConfig<ServerTransport> {
         let (mut sender, receiver) = transport.split();
         #[allow(unused_mut)]
         let mut receiver: BoxedReceiver = if let Some(conditioner_config) = self.conditioner {
-            let conditioner = LinkConditioner::new(conditioner_config);
-            Box::new(conditioner.wrap(receiver))
+            if conditioner_config.conditions_outgoing() {
+                let conditioner = LinkConditioner::outgoing(&conditioner_config);
+                sender = Box::new(PacketSenderWrapper::wrap(conditioner, sender));
+            }
+            let conditioner = LinkConditioner::incoming(&conditioner_config);
+            Box::new(PacketReceiverWrapper::wrap(conditioner, receiver))
         } else {
             Box::new(receiver)
         };
@@ -131,15 +137,20 @@ impl SharedIoConfig<ServerTransport> {
             CompressionConfig::None => {}
             #[cfg(feature = "zstd")]
             CompressionConfig::Zstd { level } => {
-                use crate::transport::middleware::PacketSenderWrapper;
                 let compressor = ZstdCompressor::new(level);
                 sender = Box::new(compressor.wrap(sender));
                 let decompressor = ZstdDecompressor::new();
                 receiver = Box::new(decompressor.wrap(receiver));
             }
+            #[cfg(feature = "zstd")]
+            CompressionConfig::ZstdDictionary { level, dictionary } => {
+                let compressor = ZstdCompressor::with_dictionary(level, &dictionary)?;
+                sender = Box::new(compressor.wrap(sender));
+                let decompressor = ZstdDecompressor::with_dictionary(&dictionary)?;
+                receiver = Box::new(decompressor.wrap(receiver));
+            }
             #[cfg(feature = "lz4")]
             CompressionConfig::Lz4 => {
-                use crate::transport::middleware::PacketSenderWrapper;
                 let compressor =
                     crate::transport::middleware::compression::lz4::Compressor::default();
                 sender = Box::new(compressor.wrap(sender));
@@ -148,6 +159,16 @@ impl SharedIoConfig<ServerTransport> {
                 receiver = Box::new(decompressor.wrap(receiver));
             }
         }
+        if let Some(capture_config) = self.capture {
+            #[cfg(not(target_family = "wasm"))]
+            {
+                let capture = PacketCapture::new(&capture_config)?;
+                sender = Box::new(PacketSenderWrapper::wrap(capture.clone(), sender));
+                receiver = Box::new(PacketReceiverWrapper::wrap(capture, receiver));
+            }
+            #[cfg(target_family = "wasm")]
+            bevy::log::warn!(?capture_config, "Packet capture is not supported on wasm");
+        }
         Ok(BaseIo {
             local_addr,
             sender,
@@ -0,0 +1,316 @@
+//! Lag compensation: rewind the state of server entities to what a client saw when it sent its inputs.
+//!
+//! Clients see remote entities in the past, because of the interpolation delay. When a client shoots at an
+//! interpolated entity, the server needs to check the hit against the state that the client actually saw,
+//! not against the current server state.
+//!
+//! The [`LagCompensationPlugin<C>`] records, for every entity that has a [`LagCompensationHistory<C>`], the value
+//! of the component `C` at every server tick. The [`InterpolationDelay`] sent by the client alongside its inputs can
+//! then be used to retrieve the value of the component that the client saw, with [`LagCompensation::get`].
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use lightyear::prelude::client::InterpolationDelay;
+//! use lightyear::prelude::server::*;
+//!
+//! fn setup(app: &mut App) {
+//!     app.add_plugins(LagCompensationPlugin::<Transform>::default());
+//! }
+//!
+//! fn hit_detection(
+//!     lag_compensation: LagCompensation<Transform>,
+//!     shooters: Query<&InterpolationDelay>,
+//! ) {
+//!     for delay in shooters.iter() {
+//!         for (target, state) in lag_compensation.iter(delay) {
+//!             // the transform of the target at the time the client fired
+//!             let transform = state.interpolate(|start, end, t| {
+//!                 Transform::from_translation(start.translation.lerp(end.translation, t))
+//!             });
+//!         }
+//!     }
+//! }
+//! ```
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use tracing::trace;
+
+use crate::client::interpolation::plugin::InterpolationDelay;
+use crate::prelude::{Linear, Tick, TickManager};
+use crate::utils::history_buffer::HistoryBuffer;
+
+/// Plugin that records the history of the component `C` on the server, for lag compensation.
+///
+/// Only the entities that have a [`LagCompensationHistory<C>`] component are tracked.
+pub struct LagCompensationPlugin<C> {
+    /// Maximum number of ticks that we will keep in the history.
+    /// This determines how far back in time we can rewind the component.
+    pub max_history_ticks: u16,
+    _marker: PhantomData<C>,
+}
+
+impl<C> LagCompensationPlugin<C> {
+    pub fn new(max_history_ticks: u16) -> Self {
+        Self {
+            max_history_ticks,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> Default for LagCompensationPlugin<C> {
+    fn default() -> Self {
+        // 35 ticks corresponds to ~500ms assuming 64Hz ticks
+        Self::new(35)
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LagCompensationSet {
+    /// Record the current value of the components in the history.
+    ///
+    /// Systems that perform lag-compensated queries should run after this set.
+    UpdateHistory,
+}
+
+/// Stores the value of the component `C` at every server tick, so that it can be rewound for lag compensation.
+///
+/// Add this component to the server entities that should be lag-compensated.
+#[derive(Component, Debug)]
+pub struct LagCompensationHistory<C: Component> {
+    pub(crate) history: HistoryBuffer<C>,
+}
+
+impl<C: Component> Default for LagCompensationHistory<C> {
+    fn default() -> Self {
+        Self {
+            history: HistoryBuffer::default(),
+        }
+    }
+}
+
+/// The value of a component that a client saw at a given point in time.
+///
+/// Clients interpolate between two ticks, so the state is given as the value at `tick`, the value at `tick + 1`
+/// and the interpolation fraction between the two.
+#[derive(Debug, PartialEq)]
+pub struct LagCompensatedState<'a, C> {
+    /// Tick at which the client's interpolation started
+    pub tick: Tick,
+    /// Value of the component at `tick`
+    pub start: &'a C,
+    /// Value of the component at `tick + 1`, if it was recorded
+    pub end: Option<&'a C>,
+    /// Fraction of the way between `start` and `end` (between 0.0 and 1.0)
+    pub overstep: f32,
+}
+
+impl<C: Clone> LagCompensatedState<'_, C> {
+    /// Compute the exact value that the client saw, by interpolating between `start` and `end`
+    pub fn interpolate(&self, lerp: impl FnOnce(&C, &C, f32) -> C) -> C {
+        match self.end {
+            Some(end) => lerp(self.start, end, self.overstep),
+            None => self.start.clone(),
+        }
+    }
+
+    /// Compute the exact value that the client saw, using linear interpolation
+    pub fn lerp(&self) -> C
+    where
+        C: Linear,
+    {
+        self.interpolate(C::lerp)
+    }
+}
+
+impl<C: Component> LagCompensationHistory<C> {
+    /// Get the value of the component at the given tick
+    pub fn at_tick(&self, tick: Tick) -> Option<&C> {
+        // the history only contains values up to the current tick; find the most recent value at or before `tick`
+        self.history
+            .iter()
+            .take_while(|(history_tick, _)| *history_tick <= tick)
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    /// Get the value of the component that a client with the given [`InterpolationDelay`] was seeing
+    /// at the current tick.
+    ///
+    /// Returns None if the history doesn't go back far enough.
+    pub fn at_interpolation_delay(
+        &self,
+        current_tick: Tick,
+        tick_duration: std::time::Duration,
+        interpolation_delay: &InterpolationDelay,
+    ) -> Option<LagCompensatedState<'_, C>> {
+        let (tick, overstep) = interpolation_delay.tick_and_overstep(current_tick, tick_duration);
+        let start = self.at_tick(tick)?;
+        let end = self.at_tick(tick + 1);
+        Some(LagCompensatedState {
+            tick,
+            start,
+            end,
+            overstep,
+        })
+    }
+
+    /// Iterate through the recorded values, from the oldest to the most recent
+    pub fn iter(&self) -> impl Iterator<Item = (Tick, &C)> {
+        self.history.iter()
+    }
+}
+
+/// A [`SystemParam`] to query the value of a lag-compensated component as seen by a given client.
+///
+/// Systems using this parameter should run after [`LagCompensationSet::UpdateHistory`].
+#[derive(SystemParam)]
+pub struct LagCompensation<'w, 's, C: Component> {
+    tick_manager: Res<'w, TickManager>,
+    query: Query<'w, 's, (Entity, &'static LagCompensationHistory<C>)>,
+}
+
+impl<C: Component> LagCompensation<'_, '_, C> {
+    /// Get the value of the component `C` of `entity` that a client with the given [`InterpolationDelay`]
+    /// was seeing at the current tick.
+    pub fn get(
+        &self,
+        entity: Entity,
+        interpolation_delay: &InterpolationDelay,
+    ) -> Option<LagCompensatedState<'_, C>> {
+        self.query.get(entity).ok()?.1.at_interpolation_delay(
+            self.tick_manager.tick(),
+            self.tick_manager.config.tick_duration,
+            interpolation_delay,
+        )
+    }
+
+    /// Iterate through all the lag-compensated entities, along with the value of the component `C` that a client
+    /// with the given [`InterpolationDelay`] was seeing at the current tick.
+    pub fn iter<'a>(
+        &'a self,
+        interpolation_delay: &'a InterpolationDelay,
+    ) -> impl Iterator<Item = (Entity, LagCompensatedState<'a, C>)> + 'a {
+        let tick = self.tick_manager.tick();
+        let tick_duration = self.tick_manager.config.tick_duration;
+        self.query.iter().filter_map(move |(entity, history)| {
+            history
+                .at_interpolation_delay(tick, tick_duration, interpolation_delay)
+                .map(|state| (entity, state))
+        })
+    }
+}
+
+impl<C: Component + Clone> Plugin for LagCompensationPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LagCompensationMaxTicks::<C> {
+            max_history_ticks: self.max_history_ticks,
+            _marker: PhantomData,
+        });
+        // the history at tick N should contain the value of the component at the end of tick N,
+        // i.e. the same value that is replicated to clients for that tick
+        app.add_systems(
+            FixedPostUpdate,
+            update_history::<C>.in_set(LagCompensationSet::UpdateHistory),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct LagCompensationMaxTicks<C> {
+    max_history_ticks: u16,
+    _marker: PhantomData<C>,
+}
+
+/// Record the value of the component at the current tick
+fn update_history<C: Component + Clone>(
+    tick_manager: Res<TickManager>,
+    config: Res<LagCompensationMaxTicks<C>>,
+    mut query: Query<(&C, &mut LagCompensationHistory<C>)>,
+) {
+    let tick = tick_manager.tick();
+    query.iter_mut().for_each(|(component, mut history)| {
+        history.history.add_update(tick, component.clone());
+        // keep one value older than the max history so that we can still interpolate from it
+        history
+            .history
+            .clear_until_tick(tick - (config.max_history_ticks + 1));
+        trace!(?tick, "update lag compensation history");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Value(f32);
+
+    impl Linear for Value {
+        fn lerp(start: &Self, other: &Self, t: f32) -> Self {
+            Value(start.0 + (other.0 - start.0) * t)
+        }
+    }
+
+    #[test]
+    fn test_at_interpolation_delay() {
+        let mut history = LagCompensationHistory::<Value>::default();
+        for i in 0..10 {
+            history.history.add_update(Tick(i), Value(i as f32 * 10.0));
+        }
+        assert_eq!(history.at_tick(Tick(3)), Some(&Value(30.0)));
+        assert_eq!(history.at_tick(Tick(20)), Some(&Value(90.0)));
+
+        let tick_duration = Duration::from_millis(10);
+        // the client was seeing the world 25ms in the past: between ticks 6 and 7
+        let delay = InterpolationDelay { delay_ms: 25 };
+        let state = history
+            .at_interpolation_delay(Tick(9), tick_duration, &delay)
+            .unwrap();
+        assert_eq!(state.tick, Tick(6));
+        assert_eq!(state.start, &Value(60.0));
+        assert_eq!(state.end, Some(&Value(70.0)));
+        assert_eq!(state.lerp(), Value(65.0));
+
+        // the history doesn't go back far enough
+        let delay = InterpolationDelay { delay_ms: 200 };
+        assert!(history
+            .at_interpolation_delay(Tick(9), tick_duration, &delay)
+            .is_none());
+    }
+
+    #[test]
+    fn test_update_history() {
+        let mut app = App::new();
+        app.insert_resource(TickManager::from_config(
+            crate::shared::tick_manager::TickConfig::new(Duration::from_millis(10)),
+        ));
+        app.add_plugins(LagCompensationPlugin::<Value>::new(2));
+        let entity = app
+            .world_mut()
+            .spawn((Value(0.0), LagCompensationHistory::<Value>::default()))
+            .id();
+        for i in 0..5 {
+            app.world_mut().get_mut::<Value>(entity).unwrap().0 = i as f32;
+            app.world_mut().run_schedule(FixedPostUpdate);
+            app.world_mut()
+                .resource_mut::<TickManager>()
+                .increment_tick();
+        }
+        let history = app
+            .world()
+            .get::<LagCompensationHistory<Value>>(entity)
+            .unwrap();
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            vec![
+                (Tick(2), &Value(2.0)),
+                (Tick(3), &Value(3.0)),
+                (Tick(4), &Value(4.0))
+            ]
+        );
+    }
+}
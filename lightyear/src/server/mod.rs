@@ -7,12 +7,16 @@ pub mod config;
 
 pub mod connection;
 
+pub mod diagnostics;
+
 pub mod error;
 
 pub mod events;
 
 pub mod input;
 
+pub mod lag_compensation;
+
 pub(crate) mod io;
 
 pub mod plugin;
@@ -8,6 +8,7 @@ use crate::prelude::{
     is_host_server, ChannelRegistry, ClientId, MainSet, MessageRegistry, TickManager, TimeManager,
 };
 use crate::protocol::component::ComponentRegistry;
+use crate::protocol::fingerprint::ProtocolFingerprint;
 use crate::serialize::reader::Reader;
 use crate::server::clients::ControlledEntities;
 use crate::server::config::ServerConfig;
@@ -17,6 +18,7 @@ use crate::server::io::ServerIoEvent;
 use crate::server::run_conditions::is_started_ref;
 use crate::shared::sets::{InternalMainSet, ServerMarker};
 use crate::transport::error::Error as TransportError;
+use crate::transport::PacketSender;
 use async_channel::TryRecvError;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick};
 use bevy::prelude::*;
@@ -315,6 +317,14 @@ pub(crate) fn send(
         .unwrap_or_else(|e: ServerError| {
             error!("Error sending packets: {}", e);
         });
+    // release the packets that were delayed by the io middleware (e.g. the link conditioner)
+    for netserver in netservers.servers.iter_mut() {
+        if let Some(io) = netserver.io_mut() {
+            let _ = io
+                .flush()
+                .inspect_err(|e| error!("Error flushing io: {}", e));
+        }
+    }
 }
 
 fn log_client_error(error: ConnectionError) {
@@ -392,7 +402,8 @@ fn rebuild_server_connections(world: &mut World) {
     world.insert_resource(connection_manager);
 
     // rebuild the server connections and insert them
-    let server_connections = ServerConnections::new(server_config.net);
+    let protocol_fingerprint = ProtocolFingerprint::from_world(world);
+    let server_connections = ServerConnections::new(server_config.net, protocol_fingerprint);
     world.insert_resource(server_connections);
 }
 
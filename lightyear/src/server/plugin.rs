@@ -9,6 +9,7 @@
 //!
 //! Most plugins are truly necessary for the server functionality to work properly, but some could be disabled.
 use crate::server::clients::ClientsMetadataPlugin;
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
 use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
 
@@ -36,6 +37,7 @@ use super::config::ServerConfig;
 ///   disabled if you don't need client to server replication.
 /// - [`ServerReplicationSendPlugin`]: Handles the replication of entities and resources from the server to the client. This can be
 ///   disabled if you don't need server to client replication.
+/// - [`ServerDiagnosticsPlugin`]: Computes diagnostics about the client connections. Can be disabled if you don't need it.
 pub struct ServerPlugins {
     pub config: ServerConfig,
 }
@@ -68,6 +70,7 @@ impl PluginGroup for ServerPlugins {
             .add(ClientsMetadataPlugin)
             .add(ServerReplicationReceivePlugin { tick_interval })
             .add(ServerReplicationSendPlugin { tick_interval })
+            .add(ServerDiagnosticsPlugin::default())
     }
 }
 
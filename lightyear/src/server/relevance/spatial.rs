@@ -0,0 +1,504 @@
+/*! Spatial network relevance module, where the relevance of entities is computed automatically from their positions
+
+# Spatial relevance
+
+The [`SpatialRelevancePlugin`] provides distance-based interest management (area of interest).
+Entities are stored in a uniform grid (a spatial hash), and every time the server buffers replication messages
+the plugin computes which entities are within the view radius of each client's viewers.
+
+The plugin then emits the corresponding [`gain_relevance`](RelevanceManager::gain_relevance) and
+[`lose_relevance`](RelevanceManager::lose_relevance) events, so the rest of the replication code is unchanged.
+
+To use it:
+- add the [`SpatialRelevancePlugin`] with the component that provides the position of your entities
+  (any component that implements [`SpatialPosition`], for example [`Transform`])
+- replicate your entities with [`NetworkRelevanceMode::InterestManagement`]
+- add a [`SpatialViewer`] component on an entity (usually the player entity) to define the point of view
+  of a client. The viewer entity must also have the position component.
+
+```rust
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use lightyear::prelude::server::*;
+
+fn build(app: &mut App) {
+    app.add_plugins(SpatialRelevancePlugin::<Transform>::new(100.0));
+}
+
+fn spawn_player(mut commands: Commands) {
+    commands.spawn((
+        Transform::default(),
+        SpatialViewer::new(ClientId::Netcode(0), 150.0),
+        Replicate {
+            relevance_mode: NetworkRelevanceMode::InterestManagement,
+            ..default()
+        },
+    ));
+}
+```
+
+A client can have multiple viewers; an entity is relevant to the client if it is in range of any of them.
+
+Note that the spatial relevance is computed for every entity that has the position component and
+[`NetworkRelevanceMode::InterestManagement`]. You should not also manage those entities via the [`RoomManager`](crate::prelude::server::RoomManager),
+since the two systems would overwrite each other's relevance events.
+*/
+
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use tracing::trace;
+
+use crate::connection::id::ClientId;
+use crate::prelude::server::is_started;
+use crate::server::events::DisconnectEvent;
+use crate::server::relevance::immediate::{
+    CachedNetworkRelevance, NetworkRelevanceSet, RelevanceManager,
+};
+use crate::shared::sets::{InternalReplicationSet, ServerMarker};
+
+/// Component that can provide a position for the spatial relevance grid
+pub trait SpatialPosition: Component {
+    /// The position of the entity in world space
+    fn spatial_position(&self) -> Vec3;
+}
+
+impl SpatialPosition for Transform {
+    fn spatial_position(&self) -> Vec3 {
+        self.translation
+    }
+}
+
+impl SpatialPosition for GlobalTransform {
+    fn spatial_position(&self) -> Vec3 {
+        self.translation()
+    }
+}
+
+/// Marks an entity as a point of view for a client.
+///
+/// Every entity (with [`NetworkRelevanceMode::InterestManagement`](crate::prelude::NetworkRelevanceMode::InterestManagement))
+/// within `radius` of this entity will be relevant to the client.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct SpatialViewer {
+    /// The client that sees through this viewer
+    pub client_id: ClientId,
+    /// The view radius around the viewer's position
+    pub radius: f32,
+}
+
+impl SpatialViewer {
+    pub fn new(client_id: ClientId, radius: f32) -> Self {
+        Self { client_id, radius }
+    }
+}
+
+/// Coordinates of a cell in the [`SpatialGrid`]
+pub type CellId = IVec3;
+
+/// Uniform grid that stores the entities used for spatial relevance
+#[derive(Resource, Debug)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    /// Entities in each cell
+    cells: HashMap<CellId, EntityHashSet>,
+    /// Current cell and position of each entity in the grid
+    entities: EntityHashMap<(CellId, Vec3)>,
+    /// Entities that are currently relevant for each client
+    relevant: HashMap<ClientId, EntityHashSet>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "the cell size must be strictly positive");
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            entities: EntityHashMap::default(),
+            relevant: HashMap::default(),
+        }
+    }
+
+    /// Size of the side of a cell
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Cell that contains the given position
+    pub fn cell(&self, position: Vec3) -> CellId {
+        (position / self.cell_size).floor().as_ivec3()
+    }
+
+    /// Entities that are currently stored in the given cell
+    pub fn cell_entities(&self, cell: CellId) -> impl Iterator<Item = Entity> + '_ {
+        self.cells.get(&cell).into_iter().flatten().copied()
+    }
+
+    /// Returns true if the entity is currently relevant to the client
+    pub fn is_relevant(&self, client_id: ClientId, entity: Entity) -> bool {
+        self.relevant
+            .get(&client_id)
+            .is_some_and(|entities| entities.contains(&entity))
+    }
+
+    /// Iterate through all the entities within `radius` of `position`
+    pub fn entities_in_radius(
+        &self,
+        position: Vec3,
+        radius: f32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        let min = self.cell(position - Vec3::splat(radius));
+        let max = self.cell(position + Vec3::splat(radius));
+        let radius_squared = radius * radius;
+        (min.x..=max.x)
+            .flat_map(move |x| {
+                (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+            })
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |entity| {
+                self.entities
+                    .get(*entity)
+                    .is_some_and(|(_, p)| p.distance_squared(position) <= radius_squared)
+            })
+            .copied()
+    }
+
+    /// Insert the entity in the grid, or update its position
+    pub(crate) fn update_entity(&mut self, entity: Entity, position: Vec3) {
+        let cell = self.cell(position);
+        if let Some((previous_cell, previous_position)) = self.entities.get_mut(&entity) {
+            *previous_position = position;
+            if *previous_cell == cell {
+                return;
+            }
+            let previous_cell = std::mem::replace(previous_cell, cell);
+            self.remove_from_cell(previous_cell, entity);
+        } else {
+            self.entities.insert(entity, (cell, position));
+        }
+        self.cells.entry(cell).or_default().insert(entity);
+    }
+
+    /// Remove the entity from the grid and from the relevance caches
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        if let Some((cell, _)) = self.entities.remove(&entity) {
+            self.remove_from_cell(cell, entity);
+        }
+        self.relevant.values_mut().for_each(|entities| {
+            entities.remove(&entity);
+        });
+    }
+
+    fn remove_from_cell(&mut self, cell: CellId, entity: Entity) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.remove(&entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+}
+
+/// System sets related to spatial relevance
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SpatialRelevanceSet {
+    /// Update the positions of the entities in the [`SpatialGrid`]
+    UpdateGrid,
+    /// Compute the relevance of each entity for each client and buffer the relevance events
+    ComputeRelevance,
+}
+
+/// Plugin that computes network relevance from the positions of entities
+///
+/// `P` is the component that provides the position of the entities.
+pub struct SpatialRelevancePlugin<P: SpatialPosition> {
+    /// Size of the side of a grid cell.
+    ///
+    /// A good value is in the same order of magnitude as the typical view radius.
+    pub cell_size: f32,
+    _marker: std::marker::PhantomData<P>,
+}
+
+impl<P: SpatialPosition> SpatialRelevancePlugin<P> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: SpatialPosition> Plugin for SpatialRelevancePlugin<P> {
+    fn build(&self, app: &mut App) {
+        // REFLECT
+        app.register_type::<SpatialViewer>();
+        // RESOURCES
+        app.insert_resource(SpatialGrid::new(self.cell_size));
+        // SETS
+        app.configure_sets(
+            PostUpdate,
+            (
+                (
+                    // run after CachedNetworkRelevance has been added to the entities
+                    InternalReplicationSet::<ServerMarker>::BeforeBuffer,
+                    SpatialRelevanceSet::UpdateGrid,
+                    SpatialRelevanceSet::ComputeRelevance,
+                    NetworkRelevanceSet::UpdateRelevance,
+                )
+                    .run_if(is_started)
+                    .chain(),
+                // the spatial relevance only needs to be computed every send_interval
+                (
+                    SpatialRelevanceSet::UpdateGrid,
+                    SpatialRelevanceSet::ComputeRelevance,
+                )
+                    .in_set(InternalReplicationSet::<ServerMarker>::SendMessages),
+            ),
+        );
+        // SYSTEMS
+        app.add_systems(
+            PostUpdate,
+            (
+                systems::update_grid::<P>.in_set(SpatialRelevanceSet::UpdateGrid),
+                systems::compute_relevance::<P>.in_set(SpatialRelevanceSet::ComputeRelevance),
+            ),
+        );
+        app.add_observer(systems::handle_client_disconnect);
+        app.add_observer(systems::handle_entity_removal::<CachedNetworkRelevance>);
+        app.add_observer(systems::handle_entity_removal::<P>);
+    }
+}
+
+pub(super) mod systems {
+    use super::*;
+
+    /// Insert new entities in the grid, and update the cell of entities that moved
+    pub(super) fn update_grid<P: SpatialPosition>(
+        mut grid: ResMut<SpatialGrid>,
+        query: Query<(Entity, Ref<P>), With<CachedNetworkRelevance>>,
+    ) {
+        for (entity, position) in query.iter() {
+            if position.is_changed() || !grid.entities.contains_key(&entity) {
+                grid.update_entity(entity, position.spatial_position());
+            }
+        }
+    }
+
+    /// Compute the entities that are in range of each client's viewers, and compare them with the
+    /// entities that were previously relevant to generate relevance events
+    pub(super) fn compute_relevance<P: SpatialPosition>(
+        mut grid: ResMut<SpatialGrid>,
+        mut relevance_manager: ResMut<RelevanceManager>,
+        viewers: Query<(&SpatialViewer, &P)>,
+    ) {
+        let mut relevant: HashMap<ClientId, EntityHashSet> = HashMap::default();
+        for (viewer, position) in viewers.iter() {
+            let entities = relevant.entry(viewer.client_id).or_default();
+            entities.extend(grid.entities_in_radius(position.spatial_position(), viewer.radius));
+        }
+        let previous = std::mem::take(&mut grid.relevant);
+        for (client_id, entities) in relevant.iter() {
+            let previous_entities = previous.get(client_id);
+            for entity in entities {
+                if previous_entities.is_none_or(|p| !p.contains(entity)) {
+                    trace!(?client_id, ?entity, "entity gained spatial relevance");
+                    relevance_manager.gain_relevance(*client_id, *entity);
+                }
+            }
+        }
+        for (client_id, previous_entities) in previous.iter() {
+            let entities = relevant.get(client_id);
+            for entity in previous_entities {
+                if entities.is_none_or(|e| !e.contains(entity)) {
+                    trace!(?client_id, ?entity, "entity lost spatial relevance");
+                    relevance_manager.lose_relevance(*client_id, *entity);
+                }
+            }
+        }
+        grid.relevant = relevant;
+    }
+
+    /// Remove the entity from the grid if it doesn't participate in spatial relevance anymore.
+    ///
+    /// The entity loses relevance for every client that it was relevant to.
+    pub(super) fn handle_entity_removal<C: Component>(
+        trigger: Trigger<OnRemove, C>,
+        mut grid: ResMut<SpatialGrid>,
+        mut relevance_manager: ResMut<RelevanceManager>,
+    ) {
+        let entity = trigger.entity();
+        for (client_id, entities) in grid.relevant.iter() {
+            if entities.contains(&entity) {
+                trace!(?client_id, ?entity, "entity removed from the spatial grid");
+                relevance_manager.lose_relevance(*client_id, entity);
+            }
+        }
+        grid.remove_entity(entity);
+    }
+
+    /// Clear the relevance cache of a client when it disconnects
+    pub(super) fn handle_client_disconnect(
+        trigger: Trigger<DisconnectEvent>,
+        mut grid: ResMut<SpatialGrid>,
+    ) {
+        grid.relevant.remove(&trigger.event().client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app.init_resource::<RelevanceManager>();
+        app.insert_resource(SpatialGrid::new(10.0));
+        app
+    }
+
+    fn run_systems(app: &mut App) {
+        let _ = app
+            .world_mut()
+            .run_system_once(systems::update_grid::<Transform>);
+        let _ = app
+            .world_mut()
+            .run_system_once(systems::compute_relevance::<Transform>);
+    }
+
+    #[test]
+    fn test_entities_in_radius() {
+        let mut grid = SpatialGrid::new(10.0);
+        let near = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+        let other_cell = Entity::from_raw(3);
+        grid.update_entity(near, Vec3::new(1.0, 1.0, 0.0));
+        grid.update_entity(far, Vec3::new(100.0, 0.0, 0.0));
+        grid.update_entity(other_cell, Vec3::new(-4.0, 0.0, 0.0));
+        assert_eq!(grid.cell(Vec3::new(-4.0, 0.0, 0.0)), IVec3::new(-1, 0, 0));
+
+        let entities: EntityHashSet = grid.entities_in_radius(Vec3::ZERO, 5.0).collect();
+        assert_eq!(entities.len(), 2);
+        assert!(entities.contains(&near));
+        assert!(entities.contains(&other_cell));
+
+        // moving the entity to another cell updates the grid
+        grid.update_entity(far, Vec3::new(2.0, 0.0, 0.0));
+        assert!(grid.cell_entities(IVec3::new(10, 0, 0)).next().is_none());
+        assert_eq!(grid.entities_in_radius(Vec3::ZERO, 5.0).count(), 3);
+
+        grid.remove_entity(near);
+        assert_eq!(grid.entities_in_radius(Vec3::ZERO, 5.0).count(), 2);
+    }
+
+    #[test]
+    fn test_spatial_relevance_events() {
+        let mut app = setup();
+        let client = ClientId::Netcode(1);
+        let entity = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(20.0, 0.0, 0.0),
+                CachedNetworkRelevance::default(),
+            ))
+            .id();
+        let viewer = app
+            .world_mut()
+            .spawn((Transform::default(), SpatialViewer::new(client, 5.0)))
+            .id();
+
+        // entity is out of range
+        run_systems(&mut app);
+        assert!(app
+            .world()
+            .resource::<RelevanceManager>()
+            .events
+            .gained
+            .is_empty());
+
+        // viewer moves close to the entity: gain relevance
+        app.world_mut()
+            .entity_mut(viewer)
+            .insert(Transform::from_xyz(18.0, 0.0, 0.0));
+        run_systems(&mut app);
+        assert!(app
+            .world()
+            .resource::<RelevanceManager>()
+            .events
+            .gained
+            .get(&client)
+            .unwrap()
+            .contains(&entity));
+        assert!(app
+            .world()
+            .resource::<SpatialGrid>()
+            .is_relevant(client, entity));
+        app.world_mut()
+            .resource_mut::<RelevanceManager>()
+            .events
+            .gained
+            .clear();
+
+        // entity stays in range: no new events
+        run_systems(&mut app);
+        assert!(app
+            .world()
+            .resource::<RelevanceManager>()
+            .events
+            .gained
+            .is_empty());
+
+        // entity moves away: lose relevance
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(Transform::from_xyz(50.0, 0.0, 0.0));
+        run_systems(&mut app);
+        assert!(app
+            .world()
+            .resource::<RelevanceManager>()
+            .events
+            .lost
+            .get(&client)
+            .unwrap()
+            .contains(&entity));
+        assert!(!app
+            .world()
+            .resource::<SpatialGrid>()
+            .is_relevant(client, entity));
+    }
+
+    #[test]
+    fn test_entity_removal_loses_relevance() {
+        let mut app = setup();
+        app.add_observer(systems::handle_entity_removal::<Transform>);
+        let client = ClientId::Netcode(1);
+        let entity = app
+            .world_mut()
+            .spawn((Transform::default(), CachedNetworkRelevance::default()))
+            .id();
+        app.world_mut()
+            .spawn((Transform::default(), SpatialViewer::new(client, 5.0)));
+        run_systems(&mut app);
+        assert!(app
+            .world()
+            .resource::<SpatialGrid>()
+            .is_relevant(client, entity));
+
+        // the entity stops participating in spatial relevance: the client must lose it
+        app.world_mut().entity_mut(entity).remove::<Transform>();
+        assert!(app
+            .world()
+            .resource::<RelevanceManager>()
+            .events
+            .lost
+            .get(&client)
+            .unwrap()
+            .contains(&entity));
+        assert!(!app
+            .world()
+            .resource::<SpatialGrid>()
+            .is_relevant(client, entity));
+    }
+}
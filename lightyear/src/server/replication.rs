@@ -19,6 +19,42 @@ use crate::shared::sets::{InternalMainSet, InternalReplicationSet, ServerMarker}
 pub enum ServerReplicationSet {
     // You can use this SystemSet to add Replicate components to entities received from clients (to rebroadcast them to other clients)
     ClientReplication,
+    /// SystemSet where you can update the per-client priority of replication groups, via
+    /// [`ConnectionManager::set_priority_multiplier`].
+    ///
+    /// It runs every send_interval, right before the replication messages are buffered.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use lightyear::prelude::*;
+    /// use lightyear::prelude::server::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Player(ClientId);
+    ///
+    /// // entities that are far from a client's player are less important for that client
+    /// fn distance_priority(
+    ///     mut connection_manager: ResMut<ConnectionManager>,
+    ///     players: Query<(&Player, &Transform)>,
+    ///     entities: Query<(Entity, &ReplicationGroup, &Transform)>,
+    /// ) {
+    ///     for (player, player_transform) in players.iter() {
+    ///         for (entity, group, transform) in entities.iter() {
+    ///             let distance = player_transform.translation.distance(transform.translation);
+    ///             let _ = connection_manager.set_priority_multiplier(
+    ///                 group.group_id(Some(entity)),
+    ///                 player.0,
+    ///                 1.0 / (1.0 + distance / 100.0),
+    ///             );
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn build(app: &mut App) {
+    ///     app.add_systems(PostUpdate, distance_priority.in_set(ServerReplicationSet::UpdatePriority));
+    /// }
+    /// ```
+    UpdatePriority,
 }
 
 pub type ReplicationSet = InternalReplicationSet<ServerMarker>;
@@ -109,6 +145,15 @@ pub(crate) mod send {
                     PostUpdate,
                     InternalReplicationSet::<ServerMarker>::All.run_if(is_started),
                 )
+                .configure_sets(
+                    PostUpdate,
+                    // the priority needs to be updated before it gets accumulated in `buffer_replication_messages`
+                    ServerReplicationSet::UpdatePriority
+                        .run_if(is_started)
+                        .in_set(InternalReplicationSet::<ServerMarker>::SendMessages)
+                        .after(InternalReplicationSet::<ServerMarker>::BeforeBuffer)
+                        .before(InternalReplicationSet::<ServerMarker>::AfterBuffer),
+                )
                 // SYSTEMS
                 .add_systems(
                     PostUpdate,
@@ -2467,6 +2512,7 @@ pub(crate) mod send {
                     incoming_latency: Duration::from_millis(30),
                     incoming_jitter: Default::default(),
                     incoming_loss: 0.0,
+                    ..Default::default()
                 })
             }
             stepper.start();
@@ -2639,6 +2685,7 @@ pub(crate) mod send {
                     incoming_latency: Duration::from_millis(30),
                     incoming_jitter: Default::default(),
                     incoming_loss: 0.0,
+                    ..Default::default()
                 })
             }
             stepper.start();
@@ -36,11 +36,25 @@ impl<M: Message> MessageEvent<M> {
 pub struct InputEvent<I: crate::inputs::native::UserAction, Ctx = ()> {
     input: Option<I>,
     from: Ctx,
+    entity: Option<Entity>,
 }
 
 impl<I: crate::inputs::native::UserAction, Ctx: Copy> InputEvent<I, Ctx> {
     pub fn new(input: Option<I>, from: Ctx) -> Self {
-        Self { input, from }
+        Self {
+            input,
+            from,
+            entity: None,
+        }
+    }
+
+    /// Create an event for an input that targets a specific entity
+    pub fn new_for_entity(input: Option<I>, from: Ctx, entity: Entity) -> Self {
+        Self {
+            input,
+            from,
+            entity: Some(entity),
+        }
     }
 
     pub fn input(&self) -> &Option<I> {
@@ -50,6 +64,11 @@ impl<I: crate::inputs::native::UserAction, Ctx: Copy> InputEvent<I, Ctx> {
     pub fn from(&self) -> Ctx {
         self.from
     }
+
+    /// The entity that the input is for, or `None` if it is a global input
+    pub fn entity(&self) -> Option<Entity> {
+        self.entity
+    }
 }
 
 #[derive(Event)]
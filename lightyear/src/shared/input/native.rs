@@ -30,7 +30,9 @@ impl<A: UserAction> Plugin for InputPlugin<A> {
         app.register_message_internal::<InputMessage<A>>(
             ChannelDirection::ClientToServer,
             MessageType::NativeInput,
-        );
+        )
+        // map the pre-predicted entities when the server receives the message
+        .add_map_entities();
         let is_client = app.world().get_resource::<ClientConfig>().is_some();
         let is_server = app.world().get_resource::<ServerConfig>().is_some();
         if is_client {
@@ -10,6 +10,8 @@ pub mod plugin;
 
 pub mod replication;
 
+pub mod rpc;
+
 pub mod sets;
 
 pub mod tick_manager;
@@ -5,6 +5,7 @@ use bevy::utils::Duration;
 use tracing::{error, trace};
 
 use crate::shared::ping::message::{Ping, Pong};
+use crate::shared::ping::rto::RtoEstimator;
 use crate::shared::ping::store::{PingId, PingStore};
 use crate::shared::time_manager::{TimeManager, WrappedTime};
 use crate::utils::ready_buffer::ReadyBuffer;
@@ -51,6 +52,8 @@ pub struct PingManager {
     pub(crate) sync_stats: SyncStatsBuffer,
     /// Current best estimates of various networking statistics
     pub final_stats: FinalStats,
+    /// Estimator of the retransmission timeout, shared by all the reliable channels of the connection
+    pub(crate) rto_estimator: RtoEstimator,
     /// The number of pings we have sent
     pub(crate) pings_sent: u32,
     /// The number of pongs we have received
@@ -94,6 +97,7 @@ impl PingManager {
             // sync
             sync_stats: SyncStatsBuffer::new(),
             final_stats: FinalStats::default(),
+            rto_estimator: RtoEstimator::default(),
             pings_sent: 0,
             pongs_recv: 0,
         }
@@ -104,6 +108,11 @@ impl PingManager {
         self.final_stats.rtt
     }
 
+    /// Return the current retransmission timeout of the connection
+    pub fn rto(&self) -> Duration {
+        self.rto_estimator.rto()
+    }
+
     /// Return the latest estimate of jitter
     pub fn jitter(&self) -> Duration {
         self.final_stats.jitter
@@ -243,6 +252,11 @@ impl PingManager {
             trace!(?rtt, ?received_time, ?ping_sent_time, ?server_process_time, ?pong.pong_sent_time, ?pong.ping_received_time, "process pong");
             let round_trip_delay = (rtt - server_process_time).to_std().unwrap_or_default();
 
+            // the retransmission timeout also includes the time the remote waited before sending the pong,
+            // since acks are piggybacked on the next packet sent by the remote
+            self.rto_estimator
+                .add_sample(rtt.to_std().unwrap_or_default());
+
             // update stats buffer
             self.sync_stats
                 .push(received_time, SyncStats { round_trip_delay });
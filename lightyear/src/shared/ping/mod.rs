@@ -5,4 +5,5 @@ pub mod manager;
 pub mod message;
 
 pub mod diagnostics;
+pub mod rto;
 pub mod store;
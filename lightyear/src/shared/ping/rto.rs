@@ -0,0 +1,98 @@
+//! Estimate the retransmission timeout (RTO) of a connection, following [RFC 6298](https://datatracker.ietf.org/doc/html/rfc6298)
+//!
+//! The estimator keeps a smoothed RTT and an RTT variance, so that the timeout grows when the RTT is unstable
+//! (to avoid spurious resends under jitter) and quickly adapts after RTT spikes.
+//!
+//! The RTT samples come from the ping/pong exchange, so Karn's algorithm is not needed: a sample is never taken
+//! from a message that was retransmitted.
+use bevy::utils::Duration;
+
+/// Gain used to update the smoothed RTT
+const ALPHA: f64 = 1.0 / 8.0;
+/// Gain used to update the RTT variance
+const BETA: f64 = 1.0 / 4.0;
+/// Multiplier of the RTT variance in the timeout
+const K: f64 = 4.0;
+
+/// Estimator of the retransmission timeout of a connection
+#[derive(Debug, Clone, Copy)]
+pub struct RtoEstimator {
+    /// Smoothed round-trip time, in seconds
+    srtt: f64,
+    /// Round-trip time variation, in seconds
+    rttvar: f64,
+    has_sample: bool,
+}
+
+impl Default for RtoEstimator {
+    fn default() -> Self {
+        // start with a conservative estimate, like the default RTT of the PingManager
+        let initial_rtt = Duration::from_millis(100).as_secs_f64();
+        Self {
+            srtt: initial_rtt,
+            rttvar: initial_rtt / 2.0,
+            has_sample: false,
+        }
+    }
+}
+
+impl RtoEstimator {
+    /// Update the estimator with a new RTT measurement
+    pub fn add_sample(&mut self, rtt: Duration) {
+        let rtt = rtt.as_secs_f64();
+        if !self.has_sample {
+            self.srtt = rtt;
+            self.rttvar = rtt / 2.0;
+            self.has_sample = true;
+            return;
+        }
+        // RTTVAR must be updated before SRTT, since it uses the previous value of SRTT
+        self.rttvar = (1.0 - BETA) * self.rttvar + BETA * (self.srtt - rtt).abs();
+        self.srtt = (1.0 - ALPHA) * self.srtt + ALPHA * rtt;
+    }
+
+    /// Smoothed round-trip time
+    pub fn srtt(&self) -> Duration {
+        Duration::from_secs_f64(self.srtt)
+    }
+
+    /// Round-trip time variation
+    pub fn rttvar(&self) -> Duration {
+        Duration::from_secs_f64(self.rttvar)
+    }
+
+    /// Duration to wait for an ack before resending a message for the first time.
+    ///
+    /// Each channel clamps this value and applies its own backoff, see [`ReliableSettings`](crate::prelude::ReliableSettings).
+    pub fn rto(&self) -> Duration {
+        Duration::from_secs_f64(self.srtt + K * self.rttvar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rto_estimator() {
+        let mut estimator = RtoEstimator::default();
+        estimator.add_sample(Duration::from_millis(200));
+        assert_eq!(estimator.srtt(), Duration::from_millis(200));
+        assert_eq!(estimator.rttvar(), Duration::from_millis(100));
+        assert_eq!(estimator.rto(), Duration::from_millis(600));
+
+        // a stable RTT makes the timeout converge towards the RTT
+        for _ in 0..100 {
+            estimator.add_sample(Duration::from_millis(200));
+        }
+        assert!(estimator.rto() < Duration::from_millis(201));
+
+        // jitter increases the timeout
+        let stable_rto = estimator.rto();
+        for i in 0..10 {
+            let rtt = if i % 2 == 0 { 150 } else { 250 };
+            estimator.add_sample(Duration::from_millis(rtt));
+        }
+        assert!(estimator.rto() > stable_rto + Duration::from_millis(100));
+    }
+}
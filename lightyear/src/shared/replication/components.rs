@@ -297,7 +297,10 @@ impl ReplicationGroup {
         }
     }
 
-    pub(crate) fn group_id(&self, entity: Option<Entity>) -> ReplicationGroupId {
+    /// Returns the [`ReplicationGroupId`] of the group.
+    ///
+    /// The entity must be provided if the group id is derived from the entity (the default).
+    pub fn group_id(&self, entity: Option<Entity>) -> ReplicationGroupId {
         match self.id_builder {
             ReplicationGroupIdBuilder::FromEntity => {
                 ReplicationGroupId(entity.expect("need to provide an entity").to_bits())
@@ -366,6 +369,9 @@ pub enum NetworkRelevanceMode {
     ///
     /// You can also use the [`RoomManager`](crate::prelude::server::RoomManager) if you want to use rooms to control network relevance.
     ///
+    /// The [`SpatialRelevancePlugin`](crate::prelude::server::SpatialRelevancePlugin) can also compute the network relevance
+    /// automatically from the positions of the entities.
+    ///
     /// (the client still needs to be included in the [`NetworkTarget`], the room is simply an additional constraint)
     InterestManagement,
     /// We will replicate this entity to the client specified in the `replication_target`, without
@@ -12,6 +12,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::ptr::NonNull;
 
+pub use lightyear_macros::Diffable;
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 pub enum DeltaType {
     /// This delta is computed from a previous value
@@ -40,6 +42,9 @@ pub struct DeltaMessage<M> {
 /// - your component contains a hashmap, and your delta is `Add(key, value)` and `Remove(key)`
 /// - your component is a struct with multiple fields, and your delta only contains data for the fields that changed.
 ///   (to avoid sending the full struct every time over the network)
+///
+/// The last case can be derived with `#[derive(Diffable)]`: the generated delta contains a bit mask of the fields
+/// that changed, followed only by the values of those fields.
 pub trait Diffable: Clone {
     // /// Set to true if the Deltas are idempotent (applying the same delta multiple times has no effect)
     // const IDEMPOTENT: bool;
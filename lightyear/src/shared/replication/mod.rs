@@ -37,6 +37,7 @@ pub(crate) mod prespawn;
 pub(crate) mod receive;
 pub(crate) mod resources;
 pub(crate) mod send;
+pub(crate) mod snapshot;
 pub(crate) mod systems;
 pub(crate) mod utils;
 
@@ -40,6 +40,23 @@ pub enum SendUpdatesMode {
     ///
     /// If we receive a NACK (i.e. the packet got lost), we will send the updates since the last ACK.
     SinceLastSend,
+    /// Snapshot-style replication (as in Quake or Overwatch).
+    ///
+    /// Every send_interval, all the updates sent to a remote peer form a single snapshot of the world.
+    /// The updates are computed against the last snapshot that the remote peer fully acknowledged (the baseline),
+    /// for every replication group at once:
+    /// - packet sizes are bounded by the changes since the baseline
+    /// - recovering from packet loss is trivial: the lost changes are simply included in the next snapshot
+    ///
+    /// Components with delta-compression enabled are diffed against the last value that the remote peer acknowledged.
+    ///
+    /// Unlike in Quake-style snapshots, the entity spawns and despawns are **not** part of the snapshot: they are still
+    /// sent on the reliable actions channel, and the receiver only applies a snapshot's updates for a group after it has
+    /// applied all the actions that happened before them.
+    ///
+    /// This mode should not be combined with [`ReplicationGroup::set_send_frequency`](crate::prelude::ReplicationGroup::set_send_frequency),
+    /// since every group must be included in every snapshot.
+    Snapshot,
 }
 
 impl Default for ReplicationConfig {
@@ -26,6 +26,7 @@ use crate::shared::replication::components::ReplicationGroupId;
 use crate::shared::replication::delta::DeltaManager;
 use crate::shared::replication::error::ReplicationError;
 use crate::shared::replication::plugin::{ReplicationConfig, SendUpdatesMode};
+use crate::shared::replication::snapshot::SnapshotTracker;
 #[cfg(test)]
 use {
     super::{EntityActionsMessage, EntityUpdatesMessage},
@@ -75,6 +76,10 @@ pub(crate) struct ReplicationSender {
     /// We update the `send_tick` only when the message was actually sent.
     pub message_send_receiver: Receiver<MessageId>,
 
+    // SNAPSHOT
+    /// Keeps track of the snapshots sent to the remote, if we use [`SendUpdatesMode::Snapshot`]
+    pub(crate) snapshots: SnapshotTracker,
+
     replication_config: ReplicationConfig,
     bandwidth_cap_enabled: bool,
 }
@@ -99,6 +104,7 @@ impl ReplicationSender {
             replication_config,
             // PRIORITY
             message_send_receiver,
+            snapshots: SnapshotTracker::default(),
             bandwidth_cap_enabled,
         }
     }
@@ -141,15 +147,33 @@ impl ReplicationSender {
             match self.replication_config.send_updates_mode {
                 SendUpdatesMode::SinceLastSend => channel.send_tick,
                 SendUpdatesMode::SinceLastAck => channel.ack_bevy_tick,
+                SendUpdatesMode::Snapshot => self.snapshots.ack_bevy_tick,
             }
         })
     }
 
+    /// Get the bevy tick after which component changes should be replicated for a given group.
+    ///
+    /// In [`SendUpdatesMode::Snapshot`] this is the tick of the last snapshot that was fully received by the remote,
+    /// otherwise it is the `send_tick` of the group.
+    pub(crate) fn get_changes_since_tick(
+        &mut self,
+        group_id: ReplicationGroupId,
+    ) -> Option<BevyTick> {
+        if let SendUpdatesMode::Snapshot = self.replication_config.send_updates_mode {
+            return self.snapshots.ack_bevy_tick;
+        }
+        self.group_channels.entry(group_id).or_default().send_tick
+    }
+
     /// Internal bookkeeping:
     /// 1. handle all nack update messages (by resetting the send_tick to the previous ack_tick)
     pub(crate) fn update(&mut self, world_tick: BevyTick) {
         // 1. handle all nack update messages
         while let Ok(message_id) = self.updates_nack_receiver.try_recv() {
+            if let SendUpdatesMode::Snapshot = self.replication_config.send_updates_mode {
+                self.snapshots.receive_nack(message_id);
+            }
             // remember to remove the entry from the map to avoid memory leakage
             if let Some(UpdateMessageMetadata {
                 group_id,
@@ -240,6 +264,9 @@ impl ReplicationSender {
     ) {
         // TODO: handle errors that are not channel::isEmpty
         while let Ok(message_id) = self.updates_ack_receiver.try_recv() {
+            if let SendUpdatesMode::Snapshot = self.replication_config.send_updates_mode {
+                self.snapshots.receive_ack(message_id);
+            }
             // remember to remove the entry from the map to avoid memory leakage
             if let Some(UpdateMessageMetadata {
                 group_id,
@@ -292,6 +319,7 @@ impl ReplicationSender {
                 .delta_ack_ticks
                 .retain(|_, ack_tick| tick - *ack_tick <= delta);
         }
+        self.snapshots.cleanup(tick);
     }
 }
 
@@ -309,6 +337,21 @@ impl ReplicationSender {
             .base_priority = priority;
     }
 
+    /// Update the priority multiplier for a given group.
+    ///
+    /// The multiplier is specific to this connection, so it can be used to make a group more or less
+    /// important for a given remote peer (for example based on the distance to the peer's player).
+    pub(crate) fn update_priority_multiplier(
+        &mut self,
+        group_id: ReplicationGroupId,
+        multiplier: f32,
+    ) {
+        self.group_channels
+            .entry(group_id)
+            .or_default()
+            .priority_multiplier = multiplier;
+    }
+
     // TODO: how can I emit metrics here that contain the channel kind?
     //  use a OnceCell that gets set with the channel name mapping when the protocol is finalized?
     //  the other option is to have wrappers in Connection, but that's pretty ugly
@@ -574,7 +617,8 @@ impl ReplicationSender {
     //  Maybe we just want to run the accumulate priority system every frame.
     /// Before sending replication messages, we accumulate the priority for all replication groups.
     ///
-    /// (the priority starts at 0.0, and is accumulated for each group based on the base priority of the group)
+    /// (the priority starts at 0.0, and is accumulated for each group based on the base priority of the group,
+    /// scaled by the per-connection priority multiplier of the group)
     pub(crate) fn accumulate_priority(&mut self, time_manager: &TimeManager) {
         // let priority_multiplier = if self.replication_config.send_interval == Duration::default() {
         //     1.0
@@ -585,12 +629,13 @@ impl ReplicationSender {
         let priority_multiplier = 1.0;
         self.group_channels.values_mut().for_each(|channel| {
             trace!(
-                "in accumulate priority: accumulated={:?} base={:?} multiplier={:?}, send_interval={:?}, time_manager_delta={:?}",
-                channel.accumulated_priority, channel.base_priority, priority_multiplier,
+                "in accumulate priority: accumulated={:?} base={:?} group_multiplier={:?} multiplier={:?}, send_interval={:?}, time_manager_delta={:?}",
+                channel.accumulated_priority, channel.base_priority, channel.priority_multiplier, priority_multiplier,
                 self.replication_config.send_interval.as_nanos(),
                 time_manager.delta().as_nanos()
             );
-            channel.accumulated_priority += channel.base_priority * priority_multiplier;
+            channel.accumulated_priority +=
+                channel.base_priority * channel.priority_multiplier * priority_multiplier;
         });
     }
 
@@ -725,6 +770,11 @@ impl ReplicationSender {
         writer: &mut Writer,
         message_manager: &mut MessageManager,
     ) -> Result<(), PacketError> {
+        // all the updates buffered during this send_interval are part of the same snapshot
+        let snapshot_id = match self.replication_config.send_updates_mode {
+            SendUpdatesMode::Snapshot => Some(self.snapshots.start(tick, bevy_tick)),
+            _ => None,
+        };
         self.group_with_updates.drain().try_for_each(|group_id| {
             let channel = self.group_channels.get_mut(&group_id).unwrap();
             let updates = std::mem::take(&mut channel.pending_updates);
@@ -752,6 +802,9 @@ impl ReplicationSender {
                     priority,
                 )?
                 .expect("The entity actions channels should always return a message_id");
+            if let Some(snapshot_id) = snapshot_id {
+                self.snapshots.add_message(snapshot_id, message_id);
+            }
 
             // keep track of the message_id -> group mapping, so we can handle receiving an ACK for that message_id later
             debug!(
@@ -780,8 +833,12 @@ impl ReplicationSender {
             // restore the hashmap that we took out, so that we can reuse the allocated memory
             channel.pending_updates = message.updates;
             channel.pending_updates.clear();
-            Ok(())
-        })
+            Ok::<(), PacketError>(())
+        })?;
+        if let Some(snapshot_id) = snapshot_id {
+            self.snapshots.finish(snapshot_id);
+        }
+        Ok(())
         // TODO: also return for each message a list of the components that have delta-compression data?
     }
 }
@@ -837,6 +894,11 @@ pub struct GroupChannel {
     /// for this group because of the bandwidth cap, in which case it will be accumulated.
     pub accumulated_priority: f32,
     pub base_priority: f32,
+    /// Multiplier applied to the `base_priority` when accumulating priority.
+    ///
+    /// Since each connection has its own [`GroupChannel`], this lets the priority of a group
+    /// be different for each remote peer.
+    pub priority_multiplier: f32,
 }
 
 impl Default for GroupChannel {
@@ -852,13 +914,15 @@ impl Default for GroupChannel {
             last_action_tick: None,
             accumulated_priority: 0.0,
             base_priority: 1.0,
+            priority_multiplier: 1.0,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::prelude::server::Replicate;
+    use crate::client::connection::ConnectionManager as ClientConnectionManager;
+    use crate::prelude::server::{Replicate, ServerConfig};
     use crate::prelude::ClientId;
     use crate::server::connection::ConnectionManager;
 
@@ -987,6 +1051,103 @@ mod tests {
         assert_eq!(group_channel.send_tick, Some(*bevy_tick));
     }
 
+    /// Spawns and despawns are not part of the snapshots: check that they are still applied when the snapshots
+    /// that were sent after them are lost and the baseline falls back to an older snapshot
+    #[test]
+    fn test_snapshot_spawn_despawn_across_lost_snapshots() {
+        let mut stepper = BevyStepper::default_no_init();
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConfig>()
+            .replication
+            .send_updates_mode = SendUpdatesMode::Snapshot;
+        stepper.init();
+        macro_rules! sender {
+            () => {
+                stepper
+                    .server_app
+                    .world_mut()
+                    .resource_mut::<ConnectionManager>()
+                    .connections
+                    .get_mut(&ClientId::Netcode(TEST_CLIENT_ID))
+                    .unwrap()
+                    .replication_sender
+            };
+        }
+        macro_rules! client_entity {
+            ($server_entity:expr) => {
+                stepper
+                    .client_app
+                    .world()
+                    .resource::<ClientConnectionManager>()
+                    .replication_receiver
+                    .remote_entity_map
+                    .get_local($server_entity)
+            };
+        }
+
+        // the snapshot containing the update of entity A is acked and becomes the baseline
+        let server_entity_a = stepper
+            .server_app
+            .world_mut()
+            .spawn((ComponentSyncModeFull(1.0), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_entity_a)
+            .get_mut::<ComponentSyncModeFull>()
+            .unwrap()
+            .0 = 2.0;
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+        let baseline = sender!().snapshots.ack_bevy_tick;
+        assert!(baseline.is_some());
+        let client_entity_a = client_entity!(server_entity_a).expect("entity A was not replicated");
+
+        // despawn A and spawn B, then lose all the snapshots sent after the baseline
+        stepper.server_app.world_mut().despawn(server_entity_a);
+        let server_entity_b = stepper
+            .server_app
+            .world_mut()
+            .spawn((ComponentSyncModeFull(1.0), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(server_entity_b)
+            .get_mut::<ComponentSyncModeFull>()
+            .unwrap()
+            .0 = 2.0;
+        let mut snapshots = SnapshotTracker::default();
+        snapshots.ack_bevy_tick = baseline;
+        sender!().snapshots = snapshots;
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+
+        // the next snapshots are computed against the old baseline, which predates the spawn and the despawn
+        assert!(stepper
+            .client_app
+            .world()
+            .get_entity(client_entity_a)
+            .is_err());
+        let client_entity_b = client_entity!(server_entity_b).expect("entity B was not replicated");
+        assert_eq!(
+            stepper
+                .client_app
+                .world()
+                .entity(client_entity_b)
+                .get::<ComponentSyncModeFull>()
+                .unwrap(),
+            &ComponentSyncModeFull(2.0)
+        );
+    }
+
     #[test]
     fn test_send_tick_no_priority() {
         // create fake channels for receiving updates about acks and sends
@@ -1141,6 +1302,41 @@ mod tests {
         assert_eq!(group.ack_bevy_tick, None);
     }
 
+    /// The priority multiplier is applied per connection when accumulating priority
+    #[test]
+    fn test_accumulate_priority_multiplier() {
+        let (_, rx_ack) = crossbeam_channel::unbounded();
+        let (_, rx_nack) = crossbeam_channel::unbounded();
+        let (_, rx_send) = crossbeam_channel::unbounded();
+        let mut sender =
+            ReplicationSender::new(rx_ack, rx_nack, rx_send, ReplicationConfig::default(), true);
+        let group_1 = ReplicationGroupId(0);
+        let group_2 = ReplicationGroupId(1);
+        sender.update_base_priority(group_1, 2.0);
+        sender.update_base_priority(group_2, 2.0);
+        sender.update_priority_multiplier(group_2, 0.5);
+
+        let time_manager = TimeManager::default();
+        sender.accumulate_priority(&time_manager);
+        sender.accumulate_priority(&time_manager);
+        assert_eq!(
+            sender
+                .group_channels
+                .get(&group_1)
+                .unwrap()
+                .accumulated_priority,
+            4.0
+        );
+        assert_eq!(
+            sender
+                .group_channels
+                .get(&group_2)
+                .unwrap()
+                .accumulated_priority,
+            2.0
+        );
+    }
+
     // TODO: add tests for replication with entity relations!
     /// Test calling the `finalize` method to create the final replication messages
     /// from the buffered actions and updates
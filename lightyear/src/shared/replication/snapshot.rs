@@ -0,0 +1,207 @@
+//! Bookkeeping for the [`SendUpdatesMode::Snapshot`](crate::prelude::SendUpdatesMode::Snapshot) replication mode.
+//!
+//! In snapshot mode, all the update messages that are buffered for a remote peer during a given send_interval form
+//! a single snapshot of the world. A snapshot is considered received only when every update message it contains has
+//! been acked.
+//!
+//! The bevy tick of the most recent fully-received snapshot is used as the baseline for the next snapshots:
+//! we send all component changes that happened after it, for every replication group.
+//! - if a message is lost, the snapshot can never be fully received, so the baseline doesn't move and the next snapshot
+//!   automatically contains the lost changes.
+//! - the size of a snapshot is bounded by the changes since the baseline, instead of growing with each lost packet.
+//!
+//! A snapshot only contains component updates: it doesn't record the set of replicated entities. Entity spawns,
+//! despawns and component insertions/removals are still sent as reliable actions, so they are never lost and don't
+//! need to be reconciled when the baseline falls back to an older snapshot.
+use bevy::ecs::component::Tick as BevyTick;
+use bevy::utils::HashMap;
+use tracing::trace;
+
+use crate::packet::message::MessageId;
+use crate::prelude::Tick;
+
+/// Id of a snapshot. Snapshots with a higher id are more recent.
+pub(crate) type SnapshotId = u64;
+
+#[derive(Debug, PartialEq)]
+struct PendingSnapshot {
+    /// The bevy tick at which the changes included in the snapshot were collected
+    bevy_tick: BevyTick,
+    /// The tick at which the snapshot was sent
+    tick: Tick,
+    /// Number of update messages of this snapshot that have not been acked yet
+    remaining_messages: usize,
+}
+
+/// Keeps track of the snapshots that were sent to a remote peer.
+///
+/// Only the update messages are tracked; entity spawns and despawns are not part of the snapshots
+/// (see the [module-level documentation](self)).
+#[derive(Debug, Default)]
+pub(crate) struct SnapshotTracker {
+    next_id: SnapshotId,
+    /// Snapshots that were sent but not fully acked yet
+    pending: HashMap<SnapshotId, PendingSnapshot>,
+    /// Map from an update message to the snapshot that contains it
+    message_to_snapshot: HashMap<MessageId, SnapshotId>,
+    /// The most recent snapshot that was fully received by the remote
+    last_acked_id: Option<SnapshotId>,
+    /// The bevy tick of the most recent snapshot that was fully received by the remote.
+    ///
+    /// All component changes that happened after this tick need to be included in the next snapshot.
+    pub(crate) ack_bevy_tick: Option<BevyTick>,
+}
+
+impl SnapshotTracker {
+    /// Start a new snapshot; the update messages buffered until the next call to `start` are part of it.
+    pub(crate) fn start(&mut self, tick: Tick, bevy_tick: BevyTick) -> SnapshotId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(
+            id,
+            PendingSnapshot {
+                bevy_tick,
+                tick,
+                remaining_messages: 0,
+            },
+        );
+        id
+    }
+
+    /// Register that an update message is part of the snapshot
+    pub(crate) fn add_message(&mut self, id: SnapshotId, message_id: MessageId) {
+        if let Some(snapshot) = self.pending.get_mut(&id) {
+            snapshot.remaining_messages += 1;
+            self.message_to_snapshot.insert(message_id, id);
+        }
+    }
+
+    /// Finish building the snapshot.
+    ///
+    /// A snapshot without any update message doesn't need to be acked, so we can drop it.
+    pub(crate) fn finish(&mut self, id: SnapshotId) {
+        if self
+            .pending
+            .get(&id)
+            .is_some_and(|snapshot| snapshot.remaining_messages == 0)
+        {
+            self.pending.remove(&id);
+        }
+    }
+
+    /// An update message was received by the remote
+    pub(crate) fn receive_ack(&mut self, message_id: MessageId) {
+        let Some(id) = self.message_to_snapshot.remove(&message_id) else {
+            return;
+        };
+        let Some(snapshot) = self.pending.get_mut(&id) else {
+            return;
+        };
+        snapshot.remaining_messages -= 1;
+        if snapshot.remaining_messages > 0 {
+            return;
+        }
+        let bevy_tick = snapshot.bevy_tick;
+        self.pending.remove(&id);
+        if self.last_acked_id.is_some_and(|last_id| last_id > id) {
+            return;
+        }
+        trace!(
+            ?id,
+            ?bevy_tick,
+            "snapshot fully acked, updating the baseline"
+        );
+        self.last_acked_id = Some(id);
+        self.ack_bevy_tick = Some(bevy_tick);
+        // older snapshots are superseded by this one
+        self.pending.retain(|pending_id, _| *pending_id > id);
+        self.message_to_snapshot
+            .retain(|_, pending_id| *pending_id > id);
+    }
+
+    /// An update message was lost: the snapshot that contains it can never be fully received
+    pub(crate) fn receive_nack(&mut self, message_id: MessageId) {
+        if let Some(id) = self.message_to_snapshot.remove(&message_id) {
+            trace!(?id, "snapshot lost");
+            self.pending.remove(&id);
+        }
+    }
+
+    /// Drop the snapshots that are too old to ever get acked
+    /// (for example because their messages were never sent due to the bandwidth cap)
+    pub(crate) fn cleanup(&mut self, tick: Tick) {
+        let delta = (u16::MAX / 4) as i16;
+        self.pending
+            .retain(|_, snapshot| tick - snapshot.tick <= delta);
+        let pending = &self.pending;
+        self.message_to_snapshot
+            .retain(|_, id| pending.contains_key(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_ack() {
+        let mut tracker = SnapshotTracker::default();
+        let bevy_tick_1 = BevyTick::new(1);
+        let bevy_tick_2 = BevyTick::new(2);
+
+        // snapshot 1 contains two messages
+        let snapshot_1 = tracker.start(Tick(1), bevy_tick_1);
+        tracker.add_message(snapshot_1, MessageId(0));
+        tracker.add_message(snapshot_1, MessageId(1));
+        tracker.finish(snapshot_1);
+
+        // snapshot 2 contains one message
+        let snapshot_2 = tracker.start(Tick(2), bevy_tick_2);
+        tracker.add_message(snapshot_2, MessageId(2));
+        tracker.finish(snapshot_2);
+
+        // empty snapshots are dropped immediately
+        let snapshot_3 = tracker.start(Tick(3), BevyTick::new(3));
+        tracker.finish(snapshot_3);
+        assert_eq!(tracker.pending.len(), 2);
+
+        // the snapshot is only received when all its messages are acked
+        tracker.receive_ack(MessageId(0));
+        assert_eq!(tracker.ack_bevy_tick, None);
+        tracker.receive_ack(MessageId(1));
+        assert_eq!(tracker.ack_bevy_tick, Some(bevy_tick_1));
+
+        tracker.receive_ack(MessageId(2));
+        assert_eq!(tracker.ack_bevy_tick, Some(bevy_tick_2));
+        assert!(tracker.pending.is_empty());
+        assert!(tracker.message_to_snapshot.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_nack() {
+        let mut tracker = SnapshotTracker::default();
+        let snapshot_1 = tracker.start(Tick(1), BevyTick::new(1));
+        tracker.add_message(snapshot_1, MessageId(0));
+        tracker.add_message(snapshot_1, MessageId(1));
+        tracker.finish(snapshot_1);
+        let snapshot_2 = tracker.start(Tick(2), BevyTick::new(2));
+        tracker.add_message(snapshot_2, MessageId(2));
+        tracker.finish(snapshot_2);
+
+        // one message of the first snapshot is lost: the baseline doesn't move
+        tracker.receive_nack(MessageId(0));
+        tracker.receive_ack(MessageId(1));
+        assert_eq!(tracker.ack_bevy_tick, None);
+
+        // the next snapshot can still become the new baseline
+        tracker.receive_ack(MessageId(2));
+        assert_eq!(tracker.ack_bevy_tick, Some(BevyTick::new(2)));
+
+        // acks for older snapshots don't move the baseline back
+        let snapshot_3 = tracker.start(Tick(3), BevyTick::new(3));
+        tracker.add_message(snapshot_3, MessageId(3));
+        tracker.finish(snapshot_3);
+        tracker.receive_ack(MessageId(1));
+        assert_eq!(tracker.ack_bevy_tick, Some(BevyTick::new(2)));
+    }
+}
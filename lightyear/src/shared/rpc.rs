@@ -0,0 +1,522 @@
+//! Request/response messages (RPCs) with correlation ids and timeouts
+//!
+//! A request/response pair is registered with [`AppRpcExt::register_rpc`]. The requests and responses are regular
+//! messages sent on the reliable [`RpcChannel`], wrapped with an [`RpcId`] that is used to match a response
+//! with its request.
+//!
+//! - the peer that sends a request gets back its [`RpcId`], and later receives a [`RpcResponseEvent`] with
+//!   either the response or an [`RpcError`] (timeout, disconnection)
+//! - the peer that receives a request gets a [`RpcRequestEvent`], which is both buffered (to be read by a system
+//!   with an `EventReader`) and triggered (to be handled by an observer). It answers by calling `send_response`
+//!   on its `ConnectionManager`.
+//!
+//! ```rust,ignore
+//! app.register_rpc::<BuyItem, BuyResult>(ChannelDirection::ClientToServer);
+//!
+//! // client
+//! fn buy(mut manager: ResMut<ClientConnectionManager>) {
+//!     let rpc_id = manager
+//!         .send_request::<BuyItem, BuyResult>(BuyItem { item: 3 }, Duration::from_secs(2))
+//!         .unwrap();
+//! }
+//!
+//! fn handle_buy_result(mut events: EventReader<RpcResponseEvent<BuyResult>>) {
+//!     for event in events.read() {
+//!         match &event.result {
+//!             Ok(result) => info!(?result, "item bought"),
+//!             Err(e) => error!(?e, "could not buy item"),
+//!         }
+//!     }
+//! }
+//!
+//! // server
+//! fn answer_buy(trigger: Trigger<RpcRequestEvent<BuyItem>>, mut manager: ResMut<ServerConnectionManager>) {
+//!     let request = trigger.event();
+//!     let _ = manager.send_response(request.from, request.id, BuyResult::Ok);
+//! }
+//! ```
+use bevy::app::{App, PreUpdate};
+use bevy::prelude::{Commands, Event, EventWriter, Events, IntoSystemConfigs, ResMut};
+use bevy::utils::{Duration, HashMap};
+use crossbeam_channel::Receiver;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use crate::channel::builder::RpcChannel;
+use crate::client::config::ClientConfig;
+use crate::packet::message::MessageId;
+use crate::packet::message_manager::MessageManager;
+use crate::prelude::server::ServerConfig;
+use crate::prelude::{
+    AppMessageExt, ChannelDirection, ChannelKind, ClientConnectionManager, ClientId, Message,
+    ServerConnectionManager,
+};
+use crate::protocol::message::MessageKind;
+use crate::shared::events::components::MessageEvent;
+use crate::shared::sets::{ClientMarker, InternalMainSet, ServerMarker};
+
+/// Identifier of a request, used to match it with its response
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RpcId(pub u32);
+
+/// Message that carries a request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct RpcRequest<Req> {
+    pub(crate) id: RpcId,
+    pub(crate) request: Req,
+}
+
+/// Message that carries the response to a request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct RpcResponse<Res> {
+    pub(crate) id: RpcId,
+    pub(crate) response: Res,
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcError {
+    /// The remote peer did not acknowledge the request before the timeout, so it might not have received it
+    #[error("the request was not delivered before the timeout")]
+    Undelivered,
+    /// The remote peer received the request but did not respond before the timeout
+    #[error("no response was received before the timeout")]
+    Timeout,
+    #[error("the connection was closed before a response was received")]
+    Disconnected,
+}
+
+/// Event emitted when we receive a request from the remote peer
+///
+/// The event is both buffered and triggered, so it can be handled either by a system or by an observer.
+#[derive(Event, Debug, Clone)]
+pub struct RpcRequestEvent<Req: Message> {
+    /// Id of the request, that must be provided when sending the response
+    pub id: RpcId,
+    pub request: Req,
+    /// The client that sent the request.
+    /// If the server sent the request, we will just put ClientId::Local(0) here
+    pub from: ClientId,
+}
+
+/// Event emitted when we receive the response to one of our requests, or when the request failed
+#[derive(Event, Debug)]
+pub struct RpcResponseEvent<Res: Message> {
+    /// Id of the request, as returned by `send_request`
+    pub id: RpcId,
+    pub result: Result<Res, RpcError>,
+    /// The client that answered the request.
+    /// If the server answered the request, we will just put ClientId::Local(0) here
+    pub from: ClientId,
+}
+
+/// A request that is waiting for a response
+#[derive(Debug)]
+struct PendingRpc {
+    response_kind: MessageKind,
+    /// Id of the request message, if it was sent through the [`MessageManager`]
+    message_id: Option<MessageId>,
+    /// True if the remote peer acked the request message
+    delivered: bool,
+    time_left: Duration,
+}
+
+/// A request that will never get a response
+#[derive(Debug, PartialEq)]
+pub(crate) struct RpcFailure {
+    pub(crate) id: RpcId,
+    pub(crate) response_kind: MessageKind,
+    pub(crate) error: RpcError,
+}
+
+/// Keeps track of the requests that were sent on a connection and are waiting for a response
+#[derive(Debug)]
+pub(crate) struct RpcManager {
+    next_id: RpcId,
+    pending: HashMap<RpcId, PendingRpc>,
+    /// Map from the id of a request message to the id of the request
+    message_ids: HashMap<MessageId, RpcId>,
+    /// Get notified when a message sent on the [`RpcChannel`] gets acked
+    ack_receiver: Receiver<MessageId>,
+    /// Requests that failed, and that still need to be emitted as [`RpcResponseEvent`]s
+    pub(crate) failures: Vec<RpcFailure>,
+}
+
+impl Default for RpcManager {
+    fn default() -> Self {
+        Self {
+            next_id: RpcId::default(),
+            pending: HashMap::default(),
+            message_ids: HashMap::default(),
+            ack_receiver: crossbeam_channel::unbounded().1,
+            failures: Vec::new(),
+        }
+    }
+}
+
+impl RpcManager {
+    pub(crate) fn new(message_manager: &mut MessageManager) -> Self {
+        let ack_receiver = message_manager
+            .subscribe_acks(ChannelKind::of::<RpcChannel>())
+            .expect("the RpcChannel is always registered");
+        Self {
+            ack_receiver,
+            ..Default::default()
+        }
+    }
+
+    /// Get the id to use for a new request
+    pub(crate) fn next_id(&mut self) -> RpcId {
+        let id = self.next_id;
+        self.next_id.0 = self.next_id.0.wrapping_add(1);
+        id
+    }
+
+    /// Start waiting for the response to a request
+    pub(crate) fn add_pending(
+        &mut self,
+        id: RpcId,
+        response_kind: MessageKind,
+        message_id: Option<MessageId>,
+        timeout: Duration,
+    ) {
+        if let Some(message_id) = message_id {
+            self.message_ids.insert(message_id, id);
+        }
+        self.pending.insert(
+            id,
+            PendingRpc {
+                response_kind,
+                message_id,
+                delivered: false,
+                time_left: timeout,
+            },
+        );
+    }
+
+    /// Handle the response to a request.
+    ///
+    /// Returns false if we were not waiting for this response (for example if the request already timed out)
+    pub(crate) fn complete(&mut self, id: RpcId, response_kind: MessageKind) -> bool {
+        if !self
+            .pending
+            .get(&id)
+            .is_some_and(|pending| pending.response_kind == response_kind)
+        {
+            return false;
+        }
+        let pending = self.pending.remove(&id).unwrap();
+        if let Some(message_id) = pending.message_id {
+            self.message_ids.remove(&message_id);
+        }
+        true
+    }
+
+    /// Keep track of the acks of the requests, and fail the requests whose timeout has elapsed
+    pub(crate) fn update(&mut self, delta: Duration) {
+        for message_id in self.ack_receiver.try_iter() {
+            if let Some(pending) = self
+                .message_ids
+                .remove(&message_id)
+                .and_then(|id| self.pending.get_mut(&id))
+            {
+                pending.delivered = true;
+            }
+        }
+        self.pending.retain(|id, pending| {
+            pending.time_left = pending.time_left.saturating_sub(delta);
+            if !pending.time_left.is_zero() {
+                return true;
+            }
+            // requests that are not sent through the MessageManager (host-server) are never acked
+            let error = if pending.delivered || pending.message_id.is_none() {
+                RpcError::Timeout
+            } else {
+                RpcError::Undelivered
+            };
+            trace!(?id, ?error, "rpc request failed");
+            if let Some(message_id) = pending.message_id {
+                self.message_ids.remove(&message_id);
+            }
+            self.failures.push(RpcFailure {
+                id: *id,
+                response_kind: pending.response_kind,
+                error,
+            });
+            false
+        });
+    }
+
+    /// Fail all the pending requests because the connection was closed
+    pub(crate) fn disconnect(&mut self) {
+        self.message_ids.clear();
+        self.failures
+            .extend(self.pending.drain().map(|(id, pending)| RpcFailure {
+                id,
+                response_kind: pending.response_kind,
+                error: RpcError::Disconnected,
+            }));
+    }
+}
+
+/// Add request/response pairs to the protocol
+pub trait AppRpcExt {
+    /// Registers a request/response pair.
+    ///
+    /// `direction` is the direction in which the requests are sent; the responses are sent in the opposite direction.
+    /// A response type should only be used by a single request type.
+    fn register_rpc<Req, Res>(&mut self, direction: ChannelDirection)
+    where
+        Req: Message + Clone + Serialize + DeserializeOwned,
+        Res: Message + Serialize + DeserializeOwned;
+}
+
+impl AppRpcExt for App {
+    fn register_rpc<Req, Res>(&mut self, direction: ChannelDirection)
+    where
+        Req: Message + Clone + Serialize + DeserializeOwned,
+        Res: Message + Serialize + DeserializeOwned,
+    {
+        let response_direction = match direction {
+            ChannelDirection::ClientToServer => ChannelDirection::ServerToClient,
+            ChannelDirection::ServerToClient => ChannelDirection::ClientToServer,
+            ChannelDirection::Bidirectional => ChannelDirection::Bidirectional,
+        };
+        self.register_message::<RpcRequest<Req>>(direction);
+        self.register_message::<RpcResponse<Res>>(response_direction);
+
+        let is_client = self.world().get_resource::<ClientConfig>().is_some();
+        let is_server = self.world().get_resource::<ServerConfig>().is_some();
+        let (sends_requests, receives_requests) = match direction {
+            ChannelDirection::ClientToServer => (is_client, is_server),
+            ChannelDirection::ServerToClient => (is_server, is_client),
+            ChannelDirection::Bidirectional => (is_client || is_server, is_client || is_server),
+        };
+        if receives_requests {
+            self.add_event::<RpcRequestEvent<Req>>();
+            self.add_systems(
+                PreUpdate,
+                receive_rpc_requests::<Req>
+                    .after(InternalMainSet::<ClientMarker>::EmitEvents)
+                    .after(InternalMainSet::<ServerMarker>::EmitEvents),
+            );
+        }
+        if sends_requests {
+            self.add_event::<RpcResponseEvent<Res>>();
+            self.add_systems(
+                PreUpdate,
+                receive_rpc_responses::<Res>
+                    .after(InternalMainSet::<ClientMarker>::EmitEvents)
+                    .after(InternalMainSet::<ServerMarker>::EmitEvents),
+            );
+        }
+    }
+}
+
+/// Convert the received request messages into [`RpcRequestEvent`]s
+fn receive_rpc_requests<Req: Message + Clone>(
+    mut commands: Commands,
+    mut messages: ResMut<Events<MessageEvent<RpcRequest<Req>>>>,
+    mut events: EventWriter<RpcRequestEvent<Req>>,
+) {
+    for message in messages.drain() {
+        let event = RpcRequestEvent {
+            id: message.message.id,
+            request: message.message.request,
+            from: message.from,
+        };
+        commands.trigger(event.clone());
+        events.send(event);
+    }
+}
+
+/// Match the received response messages with the pending requests, and emit [`RpcResponseEvent`]s
+/// for the responses and for the requests that failed
+fn receive_rpc_responses<Res: Message>(
+    mut messages: ResMut<Events<MessageEvent<RpcResponse<Res>>>>,
+    mut events: EventWriter<RpcResponseEvent<Res>>,
+    mut client_manager: Option<ResMut<ClientConnectionManager>>,
+    mut server_manager: Option<ResMut<ServerConnectionManager>>,
+) {
+    let response_kind = MessageKind::of::<RpcResponse<Res>>();
+    for message in messages.drain() {
+        let id = message.message.id;
+        let completed = client_manager
+            .as_mut()
+            .is_some_and(|manager| manager.rpc_manager.complete(id, response_kind))
+            || server_manager.as_mut().is_some_and(|manager| {
+                manager
+                    .connection_mut(message.from)
+                    .is_ok_and(|connection| connection.rpc_manager.complete(id, response_kind))
+            });
+        if !completed {
+            trace!(?id, "ignoring response to a request that is not pending");
+            continue;
+        }
+        events.send(RpcResponseEvent {
+            id,
+            result: Ok(message.message.response),
+            from: message.from,
+        });
+    }
+    if let Some(manager) = client_manager.as_mut() {
+        manager.rpc_manager.failures.retain(|failure| {
+            if failure.response_kind != response_kind {
+                return true;
+            }
+            events.send(RpcResponseEvent {
+                id: failure.id,
+                result: Err(failure.error),
+                from: ClientId::Local(0),
+            });
+            false
+        });
+    }
+    if let Some(manager) = server_manager.as_mut() {
+        manager.rpc_failures.retain(|(client_id, failure)| {
+            if failure.response_kind != response_kind {
+                return true;
+            }
+            events.send(RpcResponseEvent {
+                id: failure.id,
+                result: Err(failure.error),
+                from: *client_id,
+            });
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::BevyStepper;
+
+    /// Step the apps until the client receives a [`RpcResponseEvent`]
+    fn wait_for_response(stepper: &mut BevyStepper) -> Vec<RpcResponseEvent<StringMessage>> {
+        for _ in 0..100 {
+            stepper.frame_step();
+            let responses: Vec<_> = stepper
+                .client_app
+                .world_mut()
+                .resource_mut::<Events<RpcResponseEvent<StringMessage>>>()
+                .drain()
+                .collect();
+            if !responses.is_empty() {
+                return responses;
+            }
+        }
+        panic!("no response was received");
+    }
+
+    #[test]
+    fn test_rpc_manager() {
+        let response_kind = MessageKind::of::<RpcResponse<StringMessage>>();
+        let mut manager = RpcManager::default();
+        let first = manager.next_id();
+        let second = manager.next_id();
+        assert_ne!(first, second);
+        manager.add_pending(
+            first,
+            response_kind,
+            Some(MessageId(0)),
+            Duration::from_millis(100),
+        );
+        manager.add_pending(
+            second,
+            response_kind,
+            Some(MessageId(1)),
+            Duration::from_millis(100),
+        );
+
+        // responses of the wrong type are ignored
+        assert!(!manager.complete(first, MessageKind::of::<RpcResponse<EntityMessage>>()));
+        assert!(manager.complete(first, response_kind));
+        assert!(!manager.complete(first, response_kind));
+
+        // the request was never acked
+        manager.update(Duration::from_millis(100));
+        assert_eq!(
+            manager.failures,
+            vec![RpcFailure {
+                id: second,
+                response_kind,
+                error: RpcError::Undelivered,
+            }]
+        );
+
+        manager.failures.clear();
+        let third = manager.next_id();
+        manager.add_pending(third, response_kind, None, Duration::from_millis(100));
+        manager.disconnect();
+        assert_eq!(
+            manager.failures,
+            vec![RpcFailure {
+                id: third,
+                response_kind,
+                error: RpcError::Disconnected,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rpc() {
+        let mut stepper = BevyStepper::default();
+        let request_id = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConnectionManager>()
+            .send_request::<StringMessage, StringMessage>(
+                StringMessage("ping".to_string()),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        let requests: Vec<_> = stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<Events<RpcRequestEvent<StringMessage>>>()
+            .drain()
+            .collect();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].id, request_id);
+        assert_eq!(requests[0].request, StringMessage("ping".to_string()));
+        stepper
+            .server_app
+            .world_mut()
+            .resource_mut::<ServerConnectionManager>()
+            .send_response(
+                requests[0].from,
+                requests[0].id,
+                StringMessage("pong".to_string()),
+            )
+            .unwrap();
+
+        let responses = wait_for_response(&mut stepper);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].id, request_id);
+        assert_eq!(responses[0].result, Ok(StringMessage("pong".to_string())));
+    }
+
+    #[test]
+    fn test_rpc_timeout() {
+        let mut stepper = BevyStepper::default();
+        let request_id = stepper
+            .client_app
+            .world_mut()
+            .resource_mut::<ClientConnectionManager>()
+            .send_request::<StringMessage, StringMessage>(
+                StringMessage("ping".to_string()),
+                Duration::from_millis(200),
+            )
+            .unwrap();
+        // the server receives the request but never answers
+        let responses = wait_for_response(&mut stepper);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].id, request_id);
+        assert_eq!(responses[0].result, Err(RpcError::Timeout));
+    }
+}
@@ -2,6 +2,7 @@
 use crate::client::networking::ClientCommands;
 use bevy::core::TaskPoolThreadAssignmentPolicy;
 use bevy::ecs::system::RunSystemOnce;
+use bevy::input::InputPlugin;
 use bevy::prelude::{
     default, App, Commands, PluginGroup, Real, TaskPoolOptions, TaskPoolPlugin, Time,
 };
@@ -150,6 +151,11 @@ impl MultiBevyStepper {
         };
         let plugin = server::ServerPlugins::new(config);
         server_app.add_plugins((plugin, ProtocolPlugin));
+        #[cfg(feature = "leafwing")]
+        {
+            server_app.add_plugins(LeafwingInputPlugin::<LeafwingInput1>::default());
+            server_app.add_plugins(LeafwingInputPlugin::<LeafwingInput2>::default());
+        }
         // Initialize Real time (needed only for the first TimeSystem run)
         server_app
             .world_mut()
@@ -171,6 +177,12 @@ impl MultiBevyStepper {
             };
             let plugin = client::ClientPlugins::new(config);
             client_app.add_plugins((plugin, ProtocolPlugin));
+            #[cfg(feature = "leafwing")]
+            {
+                client_app.add_plugins(LeafwingInputPlugin::<LeafwingInput1>::default());
+                client_app.add_plugins(LeafwingInputPlugin::<LeafwingInput2>::default());
+                client_app.add_plugins(InputPlugin);
+            }
             // Initialize Real time (needed only for the first TimeSystem run)
             client_app
                 .world_mut()
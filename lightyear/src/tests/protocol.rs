@@ -213,6 +213,8 @@ impl Plugin for ProtocolPlugin {
         app.register_message::<StringMessage>(ChannelDirection::Bidirectional);
         app.register_message::<EntityMessage>(ChannelDirection::Bidirectional)
             .add_map_entities();
+        // rpcs
+        app.register_rpc::<StringMessage, StringMessage>(ChannelDirection::ClientToServer);
         // inputs
         app.add_plugins(InputPlugin::<MyInput>::default());
         // components
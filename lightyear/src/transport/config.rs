@@ -1,3 +1,4 @@
+use crate::transport::middleware::capture::CaptureConfig;
 use crate::transport::middleware::compression::CompressionConfig;
 use crate::transport::middleware::conditioner::LinkConditionerConfig;
 use bevy::prelude::Reflect;
@@ -9,6 +10,8 @@ pub struct SharedIoConfig<T> {
     pub transport: T,
     pub conditioner: Option<LinkConditionerConfig>,
     pub compression: CompressionConfig,
+    /// If set, every packet sent or received will be recorded to a file
+    pub capture: Option<CaptureConfig>,
 }
 
 impl<T> SharedIoConfig<T> {
@@ -17,6 +20,7 @@ impl<T> SharedIoConfig<T> {
             transport,
             conditioner: None,
             compression: CompressionConfig::default(),
+            capture: None,
         }
     }
     pub fn with_conditioner(mut self, conditioner_config: LinkConditionerConfig) -> Self {
@@ -28,4 +32,9 @@ impl<T> SharedIoConfig<T> {
         self.compression = compression_config;
         self
     }
+
+    pub fn with_capture(mut self, capture_config: CaptureConfig) -> Self {
+        self.capture = Some(capture_config);
+        self
+    }
 }
@@ -16,6 +16,9 @@ pub enum Error {
     Channel(String),
     #[error("requested by user")]
     UserRequest,
+    #[cfg(feature = "zstd")]
+    #[error("received a packet compressed with zstd dictionary {received}, expected dictionary {expected}. Do both peers use the same dictionary?")]
+    ZstdDictionaryMismatch { expected: u32, received: u32 },
     #[cfg(feature = "lz4")]
     #[error("lz4 compression error")]
     CompressError(#[from] lz4_flex::block::CompressError),
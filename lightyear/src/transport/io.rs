@@ -86,6 +86,10 @@ impl<T: Send + Sync> PacketSender for BaseIo<T> {
         self.stats.packets_sent += 1;
         self.sender.as_mut().send(payload, address)
     }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sender.as_mut().flush()
+    }
 }
 
 pub struct IoDiagnosticsPlugin;
@@ -0,0 +1,344 @@
+//! Middleware that records every packet sent or received by the transport to a file,
+//! so that the traffic can be inspected or replayed later with a replay transport.
+//!
+//! The capture file starts with the magic bytes `LYCAP` and a version byte, followed by one record per packet:
+//! - direction: `u8` (0 = sent, 1 = received)
+//! - timestamp: `u64`, microseconds elapsed since the start of the capture
+//! - remote address: `u8` (4 or 6) followed by the ip bytes, and the port as a `u16`
+//! - payload: `u32` length followed by the payload bytes
+//!
+//! All integers are little-endian.
+//!
+//! Packets are recorded as they are seen by the connection layer: after the link conditioner and decompression
+//! for received packets, and before compression for sent packets.
+//!
+//! Writes are buffered: the file is flushed every [`CaptureConfig::flush_interval`] and when the capture is dropped.
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+const MAGIC: &[u8; 5] = b"LYCAP";
+const VERSION: u8 = 1;
+
+/// Configuration for the packet capture middleware
+#[derive(Clone, Debug, Reflect)]
+pub struct CaptureConfig {
+    /// Path of the file where the packets will be written. The file is truncated if it already exists.
+    pub path: PathBuf,
+    /// Maximum duration that recorded packets can stay buffered before being written to the file.
+    ///
+    /// Packets recorded since the last flush are lost if the app crashes.
+    pub flush_interval: Duration,
+}
+
+impl CaptureConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+}
+
+/// Whether a captured packet was sent or received by the local peer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketDirection {
+    Sent,
+    Received,
+}
+
+/// A packet that was recorded by the capture middleware
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedPacket {
+    /// Time elapsed between the start of the capture and the packet being sent or received
+    pub timestamp: Duration,
+    pub direction: PacketDirection,
+    /// Address of the remote peer that the packet was sent to or received from
+    pub remote_addr: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+impl CapturedPacket {
+    fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_u8(match self.direction {
+            PacketDirection::Sent => 0,
+            PacketDirection::Received => 1,
+        })?;
+        writer.write_u64::<LittleEndian>(self.timestamp.as_micros() as u64)?;
+        match self.remote_addr.ip() {
+            IpAddr::V4(ip) => {
+                writer.write_u8(4)?;
+                writer.write_all(&ip.octets())?;
+            }
+            IpAddr::V6(ip) => {
+                writer.write_u8(6)?;
+                writer.write_all(&ip.octets())?;
+            }
+        }
+        writer.write_u16::<LittleEndian>(self.remote_addr.port())?;
+        writer.write_u32::<LittleEndian>(self.payload.len() as u32)?;
+        writer.write_all(&self.payload)
+    }
+
+    /// Read the next packet. Returns None if we reached the end of the capture
+    fn read(reader: &mut impl Read) -> std::io::Result<Option<Self>> {
+        let direction = match reader.read_u8() {
+            Ok(0) => PacketDirection::Sent,
+            Ok(1) => PacketDirection::Received,
+            Ok(_) => return Err(invalid_data("invalid packet direction")),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let timestamp = Duration::from_micros(reader.read_u64::<LittleEndian>()?);
+        let ip = match reader.read_u8()? {
+            4 => {
+                let mut octets = [0; 4];
+                reader.read_exact(&mut octets)?;
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            6 => {
+                let mut octets = [0; 16];
+                reader.read_exact(&mut octets)?;
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return Err(invalid_data("invalid ip version")),
+        };
+        let port = reader.read_u16::<LittleEndian>()?;
+        let len = reader.read_u32::<LittleEndian>()? as usize;
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+        Ok(Some(Self {
+            timestamp,
+            direction,
+            remote_addr: SocketAddr::new(ip, port),
+            payload,
+        }))
+    }
+}
+
+fn invalid_data(error: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+fn write_header(writer: &mut impl Write) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_u8(VERSION)
+}
+
+/// Read all the packets of a capture
+pub fn read_capture(reader: &mut impl Read) -> std::io::Result<Vec<CapturedPacket>> {
+    let mut magic = [0; 5];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a packet capture"));
+    }
+    if reader.read_u8()? != VERSION {
+        return Err(invalid_data("unsupported packet capture version"));
+    }
+    let mut packets = vec![];
+    while let Some(packet) = CapturedPacket::read(reader)? {
+        packets.push(packet);
+    }
+    Ok(packets)
+}
+
+/// Read all the packets of a capture file
+#[cfg(not(target_family = "wasm"))]
+pub fn read_capture_file(
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<Vec<CapturedPacket>> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    read_capture(&mut reader)
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub(crate) use writer::PacketCapture;
+
+#[cfg(not(target_family = "wasm"))]
+mod writer {
+    use super::*;
+    use crate::transport::error::Result;
+    use crate::transport::middleware::{PacketReceiverWrapper, PacketSenderWrapper};
+    use crate::transport::{PacketReceiver, PacketSender};
+    use bevy::utils::Instant;
+    use parking_lot::Mutex;
+    use std::fs::File;
+    use std::io::BufWriter;
+    use std::sync::Arc;
+    use tracing::error;
+
+    struct CaptureWriter {
+        writer: BufWriter<File>,
+        start: Instant,
+        flush_interval: Duration,
+        last_flush: Instant,
+    }
+
+    impl Drop for CaptureWriter {
+        fn drop(&mut self) {
+            if let Err(e) = self.writer.flush() {
+                error!(?e, "could not flush the capture file");
+            }
+        }
+    }
+
+    /// Records the packets that go through the sender and the receiver that it wraps.
+    ///
+    /// The same [`PacketCapture`] should be used to wrap both the sender and the receiver,
+    /// so that all packets end up in the same file.
+    #[derive(Clone)]
+    pub(crate) struct PacketCapture {
+        inner: Arc<Mutex<CaptureWriter>>,
+    }
+
+    impl PacketCapture {
+        pub(crate) fn new(config: &CaptureConfig) -> Result<Self> {
+            let mut writer = BufWriter::new(File::create(&config.path)?);
+            write_header(&mut writer)?;
+            let start = Instant::now();
+            Ok(Self {
+                inner: Arc::new(Mutex::new(CaptureWriter {
+                    writer,
+                    start,
+                    flush_interval: config.flush_interval,
+                    last_flush: start,
+                })),
+            })
+        }
+
+        fn record(&self, direction: PacketDirection, remote_addr: SocketAddr, payload: &[u8]) {
+            let mut inner = self.inner.lock();
+            let packet = CapturedPacket {
+                timestamp: inner.start.elapsed(),
+                direction,
+                remote_addr,
+                payload: payload.to_vec(),
+            };
+            if let Err(e) = packet.write(&mut inner.writer) {
+                error!(?e, "could not write packet to the capture file");
+            }
+            // flush regularly so that the capture is still usable if the app crashes
+            if inner.last_flush.elapsed() >= inner.flush_interval {
+                inner.last_flush = Instant::now();
+                if let Err(e) = inner.writer.flush() {
+                    error!(?e, "could not flush the capture file");
+                }
+            }
+        }
+    }
+
+    struct CapturePacketSender<T: PacketSender> {
+        inner: T,
+        capture: PacketCapture,
+    }
+
+    impl<T: PacketSender> PacketSender for CapturePacketSender<T> {
+        fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+            self.capture
+                .record(PacketDirection::Sent, *address, payload);
+            self.inner.send(payload, address)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl<T: PacketSender> PacketSenderWrapper<T> for PacketCapture {
+        fn wrap(self, sender: T) -> impl PacketSender {
+            CapturePacketSender {
+                inner: sender,
+                capture: self,
+            }
+        }
+    }
+
+    struct CapturePacketReceiver<T: PacketReceiver> {
+        inner: T,
+        capture: PacketCapture,
+    }
+
+    impl<T: PacketReceiver> PacketReceiver for CapturePacketReceiver<T> {
+        fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+            let packet = self.inner.recv()?;
+            if let Some((payload, address)) = &packet {
+                self.capture
+                    .record(PacketDirection::Received, *address, payload);
+            }
+            Ok(packet)
+        }
+    }
+
+    impl<T: PacketReceiver> PacketReceiverWrapper<T> for PacketCapture {
+        fn wrap(self, receiver: T) -> impl PacketReceiver {
+            CapturePacketReceiver {
+                inner: receiver,
+                capture: self,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{SharedIoConfig, TransportConfig};
+    use crate::transport::LOCAL_SOCKET;
+
+    #[test]
+    fn test_capture_round_trip() {
+        let path = std::env::temp_dir().join("lightyear_test_capture_round_trip.cap");
+        let (send, recv) = crossbeam_channel::unbounded();
+        let config = TransportConfig::LocalChannel { send, recv };
+        let mut io = SharedIoConfig::from_transport(config)
+            .with_capture(CaptureConfig::new(&path))
+            .connect()
+            .unwrap();
+
+        // the local channel sends the packets back to us
+        io.sender.send(b"hello", &LOCAL_SOCKET).unwrap();
+        let (data, _) = io.receiver.recv().unwrap().unwrap();
+        assert_eq!(data.as_ref(), b"hello");
+        drop(io);
+
+        let packets = read_capture_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].direction, PacketDirection::Sent);
+        assert_eq!(packets[1].direction, PacketDirection::Received);
+        assert!(packets[0].timestamp <= packets[1].timestamp);
+        for packet in packets {
+            assert_eq!(packet.remote_addr, LOCAL_SOCKET);
+            assert_eq!(packet.payload, b"hello");
+        }
+    }
+
+    /// Buffered packets are written to the file when the capture is dropped
+    #[test]
+    fn test_capture_flush_on_drop() {
+        let path = std::env::temp_dir().join("lightyear_test_capture_flush_on_drop.cap");
+        let (send, recv) = crossbeam_channel::unbounded();
+        let config = TransportConfig::LocalChannel { send, recv };
+        let mut io = SharedIoConfig::from_transport(config)
+            .with_capture(CaptureConfig::new(&path).with_flush_interval(Duration::from_secs(60)))
+            .connect()
+            .unwrap();
+        io.sender.send(b"hello", &LOCAL_SOCKET).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        drop(io);
+
+        let packets = read_capture_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].payload, b"hello");
+    }
+}
@@ -50,6 +50,10 @@ pub(crate) mod compression {
             let compressed = self.compressor.compress(payload)?;
             self.inner.send(compressed, address)
         }
+
+        fn flush(&mut self) -> Result<()> {
+            self.inner.flush()
+        }
     }
 
     impl<T: PacketSender> PacketSenderWrapper<T> for Compressor {
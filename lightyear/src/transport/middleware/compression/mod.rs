@@ -7,12 +7,42 @@ pub(crate) mod zstd;
 #[cfg(feature = "lz4")]
 pub(crate) mod lz4;
 
-#[derive(Clone, Copy, Debug, Default, Reflect, Serialize, Deserialize)]
+/// Compression applied to every packet at the transport layer.
+///
+/// This is not `Copy` because [`CompressionConfig::ZstdDictionary`] owns the bytes of the dictionary;
+/// the config is only cloned when the io is built.
+#[derive(Clone, Debug, Default, Reflect, Serialize, Deserialize)]
 pub enum CompressionConfig {
     #[default]
     None,
     #[cfg(feature = "zstd")]
     Zstd { level: i32 },
+    /// Zstd compression using a dictionary trained on the packets of the protocol.
+    ///
+    /// Both peers must use the same dictionary, otherwise the server denies the connection.
+    ///
+    /// With a netcode connection, the payload of the packets is compressed before being encrypted
+    /// instead of at the transport layer, since encrypted data cannot be compressed.
+    #[cfg(feature = "zstd")]
+    ZstdDictionary {
+        level: i32,
+        dictionary: zstd::ZstdDictionary,
+    },
     #[cfg(feature = "lz4")]
     Lz4,
 }
+
+impl CompressionConfig {
+    /// Take the dictionary out of the config, so that the netcode connection can compress the payloads
+    /// before encrypting them. The transport then does not compress the packets.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn take_dictionary(&mut self) -> Option<(i32, zstd::ZstdDictionary)> {
+        match std::mem::take(self) {
+            CompressionConfig::ZstdDictionary { level, dictionary } => Some((level, dictionary)),
+            compression => {
+                *self = compression;
+                None
+            }
+        }
+    }
+}
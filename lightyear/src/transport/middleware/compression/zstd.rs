@@ -1,9 +1,115 @@
 //! Zstd compression
 
+use crate::connection::netcode::packet::Packet;
 use crate::connection::netcode::MAX_PKT_BUF_SIZE;
+use crate::packet::packet_builder::RecvPayload;
 use crate::transport::error::{Error, Result};
+use crate::transport::middleware::capture::{CapturedPacket, PacketDirection};
+use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
+/// A zstd dictionary trained on the packets of a protocol.
+///
+/// Packets are small, so compressing each of them independently gives poor ratios; a dictionary
+/// lets zstd reuse the patterns that are common to all the packets of the protocol.
+/// The client and the server must use the same dictionary. The id of the dictionary is part of the netcode
+/// [`ProtocolFingerprint`](crate::prelude::ProtocolFingerprint), so that a server that uses a different dictionary
+/// denies the connection with [`DeniedReason::ProtocolMismatch`](crate::connection::server::DeniedReason::ProtocolMismatch).
+///
+/// Encrypted data cannot be compressed, so with a netcode connection the dictionary is not applied at the transport
+/// layer: the payload of each netcode packet is compressed before being encrypted, and the handshake packets are not
+/// compressed.
+/// For other connections, the packets are compressed at the transport layer. The id of the dictionary is then written in
+/// every compressed packet, and receiving a packet compressed with a different dictionary returns an error.
+///
+/// The dictionary should be trained on the data that it compresses: for a netcode connection this is the
+/// unencrypted payload of the packets, which is different from what the [`CaptureConfig`](crate::prelude::CaptureConfig)
+/// middleware records, since the capture happens after the encryption.
+#[derive(Clone, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct ZstdDictionary {
+    bytes: Vec<u8>,
+}
+
+impl ZstdDictionary {
+    /// Load a dictionary, for example one that was previously trained with [`ZstdDictionary::train`]
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Train a dictionary of at most `max_size` bytes from a list of packets
+    pub fn train(samples: &[impl AsRef<[u8]>], max_size: usize) -> Result<Self> {
+        let bytes = zstd::dict::from_samples(samples, max_size)?;
+        Ok(Self::new(bytes))
+    }
+
+    /// Train a dictionary of at most `max_size` bytes from the packets recorded by the capture middleware.
+    ///
+    /// The capture records the packets sent on the transport, so this is only useful for connections
+    /// that do not encrypt their packets.
+    pub fn train_from_capture(packets: &[CapturedPacket], max_size: usize) -> Result<Self> {
+        let samples: Vec<&[u8]> = packets
+            .iter()
+            .filter(|packet| packet.direction == PacketDirection::Sent)
+            .map(|packet| packet.payload.as_slice())
+            .collect();
+        Self::train(&samples, max_size)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Id of the dictionary, or 0 if the dictionary was not trained by zstd
+    pub fn id(&self) -> u32 {
+        zstd::zstd_safe::get_dict_id_from_dict(&self.bytes).map_or(0, |id| id.get())
+    }
+}
+
+/// Returns true for the netcode packets that are exchanged before the connection is established and that
+/// the remote peer must be able to read even if it uses a different dictionary.
+///
+/// They contain encrypted data, so the dictionary would not help compressing them anyway.
+fn is_handshake_packet(data: &[u8]) -> bool {
+    data.first().is_some_and(|prefix| {
+        let (_, kind) = Packet::get_prefix(*prefix);
+        kind == Packet::REQUEST || kind == Packet::DENIED
+    })
+}
+
+/// Compresses the payload of the netcode packets with a dictionary, before they are encrypted.
+pub(crate) struct ZstdPayloadCompression {
+    compressor: zstd::bulk::Compressor<'static>,
+    decompressor: zstd::bulk::Decompressor<'static>,
+}
+
+impl ZstdPayloadCompression {
+    pub(crate) fn new(level: i32, dictionary: &ZstdDictionary) -> Result<Self> {
+        Ok(Self {
+            compressor: zstd::bulk::Compressor::with_dictionary(level, dictionary.as_bytes())?,
+            decompressor: zstd::bulk::Decompressor::with_dictionary(dictionary.as_bytes())?,
+        })
+    }
+
+    /// Compress the payload into `out`, and return the size of the compressed payload
+    pub(crate) fn compress(&mut self, payload: &[u8], out: &mut [u8]) -> Result<usize> {
+        self.compressor
+            .compress_to_buffer(payload, out)
+            .map_err(Error::Io)
+    }
+
+    pub(crate) fn decompress(&mut self, payload: &[u8]) -> Result<RecvPayload> {
+        let mut out = [0u8; MAX_PKT_BUF_SIZE];
+        let size = self
+            .decompressor
+            .decompress_to_buffer(payload, &mut out[..])
+            .map_err(Error::Io)?;
+        Ok(RecvPayload::copy_from_slice(&out[..size]))
+    }
+}
+
 pub(crate) mod compression {
     use super::*;
     use crate::transport::middleware::PacketSenderWrapper;
@@ -13,6 +119,8 @@ pub(crate) mod compression {
     pub(crate) struct ZstdCompressor {
         result: Vec<u8>,
         compressor: Compressor<'static>,
+        /// Compressor without dictionary, used for the handshake packets if `compressor` uses a dictionary
+        handshake_compressor: Option<Compressor<'static>>,
     }
 
     impl ZstdCompressor {
@@ -20,11 +128,24 @@ pub(crate) mod compression {
             ZstdCompressor {
                 result: Vec::with_capacity(MAX_PKT_BUF_SIZE),
                 compressor: Compressor::new(level).unwrap(),
+                handshake_compressor: None,
             }
         }
 
+        pub fn with_dictionary(level: i32, dictionary: &ZstdDictionary) -> Result<Self> {
+            Ok(ZstdCompressor {
+                result: Vec::with_capacity(MAX_PKT_BUF_SIZE),
+                compressor: Compressor::with_dictionary(level, dictionary.as_bytes())?,
+                handshake_compressor: Some(Compressor::new(level)?),
+            })
+        }
+
         pub fn compress(&mut self, data: &[u8]) -> Result<&[u8]> {
-            self.compressor
+            let compressor = match &mut self.handshake_compressor {
+                Some(handshake_compressor) if is_handshake_packet(data) => handshake_compressor,
+                _ => &mut self.compressor,
+            };
+            compressor
                 .compress_to_buffer(data, &mut self.result)
                 .map_err(|e| Error::Io(e))?;
             Ok(&self.result)
@@ -41,6 +162,10 @@ pub(crate) mod compression {
             let compressed = self.compressor.compress(payload)?;
             self.inner.send(compressed, address)
         }
+
+        fn flush(&mut self) -> Result<()> {
+            self.inner.flush()
+        }
     }
 
     impl<T: PacketSender> PacketSenderWrapper<T> for ZstdCompressor {
@@ -62,6 +187,10 @@ pub(crate) mod decompression {
     pub(crate) struct ZstdDecompressor {
         result: Vec<u8>,
         decompressor: Decompressor<'static>,
+        /// Id of the dictionary that the packets must have been compressed with (0 if no dictionary is used)
+        dictionary_id: u32,
+        /// Decompressor without dictionary, used for the handshake packets if `decompressor` uses a dictionary
+        handshake_decompressor: Option<Decompressor<'static>>,
     }
 
     impl ZstdDecompressor {
@@ -69,11 +198,36 @@ pub(crate) mod decompression {
             ZstdDecompressor {
                 result: Vec::with_capacity(MAX_PKT_BUF_SIZE),
                 decompressor: Decompressor::new().unwrap(),
+                dictionary_id: 0,
+                handshake_decompressor: None,
             }
         }
 
+        pub fn with_dictionary(dictionary: &ZstdDictionary) -> Result<Self> {
+            Ok(ZstdDecompressor {
+                result: Vec::with_capacity(MAX_PKT_BUF_SIZE),
+                decompressor: Decompressor::with_dictionary(dictionary.as_bytes())?,
+                dictionary_id: dictionary.id(),
+                handshake_decompressor: Some(Decompressor::new()?),
+            })
+        }
+
         pub fn decompress(&mut self, data: &[u8]) -> Result<&mut [u8]> {
-            self.decompressor
+            // check the dictionary id first to return a clear error if the peers use different dictionaries
+            let received = zstd::zstd_safe::get_dict_id_from_frame(data).map_or(0, |id| id.get());
+            // the handshake packets are compressed without the dictionary
+            let handshake = received == 0 && self.handshake_decompressor.is_some();
+            if received != self.dictionary_id && !handshake {
+                return Err(Error::ZstdDictionaryMismatch {
+                    expected: self.dictionary_id,
+                    received,
+                });
+            }
+            let decompressor = match &mut self.handshake_decompressor {
+                Some(handshake_decompressor) if handshake => handshake_decompressor,
+                _ => &mut self.decompressor,
+            };
+            decompressor
                 .decompress_to_buffer(data, &mut self.result)
                 .map_err(|e| Error::Io(e))?;
             Ok(&mut self.result)
@@ -108,6 +262,11 @@ pub(crate) mod decompression {
 
 #[cfg(test)]
 mod tests {
+    use super::compression::ZstdCompressor;
+    use super::decompression::ZstdDecompressor;
+    use super::*;
+    use crate::connection::netcode::generate_key;
+    use crate::connection::netcode::packet::PayloadPacket;
     use crate::prelude::{SharedIoConfig, TransportConfig};
     use crate::transport::middleware::compression::CompressionConfig;
     use crate::transport::LOCAL_SOCKET;
@@ -121,6 +280,7 @@ mod tests {
             transport: config,
             conditioner: None,
             compression: CompressionConfig::Zstd { level: 0 },
+            capture: None,
         };
         let mut io = io_config.connect().unwrap();
         let msg = b"hello world".as_slice();
@@ -131,4 +291,122 @@ mod tests {
         let (data, addr) = io.receiver.recv().unwrap().unwrap();
         assert_eq!(data.as_ref(), msg);
     }
+
+    #[test]
+    fn test_dictionary() {
+        let samples: Vec<Vec<u8>> = (0..1000u32)
+            .map(|i| {
+                format!(
+                    "{{\"entity\": {i}, \"position\": [{}, {}], \"health\": 100}}",
+                    i % 7,
+                    i % 13
+                )
+                .into_bytes()
+            })
+            .collect();
+        let dictionary = ZstdDictionary::train(&samples, 4096).unwrap();
+        assert_ne!(dictionary.id(), 0);
+
+        let mut compressor = ZstdCompressor::with_dictionary(3, &dictionary).unwrap();
+        let mut decompressor = ZstdDecompressor::with_dictionary(&dictionary).unwrap();
+        let msg = b"{\"entity\": 1001, \"position\": [0, 1], \"health\": 100}";
+        let compressed = compressor.compress(msg).unwrap().to_vec();
+        assert_eq!(decompressor.decompress(&compressed).unwrap(), msg);
+
+        // a peer that does not use the dictionary detects the mismatch
+        let mut decompressor = ZstdDecompressor::new();
+        let Err(Error::ZstdDictionaryMismatch { expected, received }) =
+            decompressor.decompress(&compressed)
+        else {
+            panic!("expected a dictionary mismatch");
+        };
+        assert_eq!(expected, 0);
+        assert_eq!(received, dictionary.id());
+    }
+
+    #[test]
+    fn test_dictionary_mismatch_handshake() {
+        let train = |name: &str| {
+            let samples: Vec<Vec<u8>> = (0..1000u32)
+                .map(|i| format!("{{\"{name}\": {i}, \"value\": {}}}", i % 7).into_bytes())
+                .collect();
+            ZstdDictionary::train(&samples, 4096).unwrap()
+        };
+        let client_dictionary = train("client");
+        let server_dictionary = train("server");
+        assert_ne!(client_dictionary.id(), server_dictionary.id());
+
+        let mut compressor = ZstdCompressor::with_dictionary(3, &client_dictionary).unwrap();
+        let mut decompressor = ZstdDecompressor::with_dictionary(&server_dictionary).unwrap();
+
+        // the connection request can be read by a server that uses a different dictionary,
+        // so that it can deny the connection
+        let mut request = vec![Packet::REQUEST];
+        request.extend_from_slice(b"netcode request");
+        let compressed = compressor.compress(&request).unwrap().to_vec();
+        assert_eq!(decompressor.decompress(&compressed).unwrap(), request);
+
+        // the other packets are compressed with the dictionary
+        let mut payload = vec![Packet::PAYLOAD | (1 << 4), 0];
+        payload.extend_from_slice(b"{\"client\": 3, \"value\": 3}");
+        let compressed = compressor.compress(&payload).unwrap().to_vec();
+        assert!(matches!(
+            decompressor.decompress(&compressed),
+            Err(Error::ZstdDictionaryMismatch { .. })
+        ));
+    }
+
+    /// The netcode packets are encrypted, so the dictionary only reduces their size if the payload
+    /// is compressed before the encryption
+    #[test]
+    fn test_dictionary_netcode_payload() {
+        let entity = |i: u32| {
+            format!(
+                "{{\"entity\": {i}, \"position\": [{}, {}], \"health\": 100}}",
+                i % 7,
+                i % 13
+            )
+        };
+        let samples: Vec<Vec<u8>> = (0..1000u32).map(|i| entity(i).into_bytes()).collect();
+        let dictionary = ZstdDictionary::train(&samples, 4096).unwrap();
+        let payload: Vec<u8> = (1000..1010u32)
+            .flat_map(|i| entity(i).into_bytes())
+            .collect();
+
+        let key = generate_key();
+        let protocol_id = 1;
+        let write = |payload: &[u8]| {
+            let mut buf = [0u8; MAX_PKT_BUF_SIZE];
+            let size = PayloadPacket::create(payload)
+                .write(&mut buf, 7, &key, protocol_id)
+                .unwrap();
+            buf[..size].to_vec()
+        };
+        let uncompressed = write(&payload);
+
+        // compressing the encrypted packet at the transport layer does not reduce its size
+        let mut compressor = ZstdCompressor::with_dictionary(3, &dictionary).unwrap();
+        assert!(compressor.compress(&uncompressed).unwrap().len() >= uncompressed.len());
+
+        // compressing the payload before the encryption does
+        let mut compression = ZstdPayloadCompression::new(3, &dictionary).unwrap();
+        let mut compressed = [0u8; MAX_PKT_BUF_SIZE];
+        let size = compression.compress(&payload, &mut compressed).unwrap();
+        let mut packet = write(&compressed[..size]);
+        assert!(
+            packet.len() * 2 < uncompressed.len(),
+            "compressed packet: {} bytes, uncompressed packet: {} bytes",
+            packet.len(),
+            uncompressed.len()
+        );
+
+        // the receiver decrypts the packet, then decompresses the payload
+        let Packet::Payload(received) =
+            Packet::read(&mut packet, protocol_id, 0, key, None, 1 << Packet::PAYLOAD).unwrap()
+        else {
+            panic!("expected a payload packet");
+        };
+        let received = compression.decompress(received.buf).unwrap();
+        assert_eq!(received.as_ref(), payload.as_slice());
+    }
 }
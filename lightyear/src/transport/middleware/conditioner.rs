@@ -8,12 +8,12 @@ use rand;
 use rand::{thread_rng, Rng};
 
 use crate::transport::error::Result;
-use crate::transport::middleware::PacketReceiverWrapper;
-use crate::transport::PacketReceiver;
+use crate::transport::middleware::{PacketReceiverWrapper, PacketSenderWrapper};
+use crate::transport::{PacketReceiver, PacketSender};
 use crate::utils::ready_buffer::ReadyBuffer;
 
 cfg_if! {
-    if #[cfg(test)] {
+    if #[cfg(any(test, feature = "mock_time"))] {
         use mock_instant::global::Instant;
     } else {
         use bevy::utils::Instant;
@@ -21,7 +21,7 @@ cfg_if! {
 }
 
 /// Contains configuration required to initialize a LinkConditioner
-#[derive(Clone, Debug, Reflect)]
+#[derive(Clone, Debug, Default, Reflect)]
 pub struct LinkConditionerConfig {
     /// Delay to receive incoming messages in milliseconds (half the RTT)
     pub incoming_latency: Duration,
@@ -32,49 +32,268 @@ pub struct LinkConditionerConfig {
     /// The % chance that an incoming packet will be dropped.
     /// Represented as a value between 0 and 1
     pub incoming_loss: f32,
+    /// Model incoming losses that happen in bursts. Replaces `incoming_loss` if set
+    pub incoming_burst_loss: Option<BurstLossConfig>,
+    /// The % chance that an incoming packet is delivered twice.
+    /// Represented as a value between 0 and 1
+    pub incoming_duplication: f32,
+    /// The % chance that an incoming packet is held back by an additional `incoming_reorder_delay`.
+    /// Represented as a value between 0 and 1
+    pub incoming_reorder: f32,
+    /// Additional delay applied to the incoming packets that are reordered
+    pub incoming_reorder_delay: Duration,
+    /// Limit the throughput of the incoming packets
+    pub incoming_bandwidth: Option<BandwidthLimit>,
+    /// Conditions applied to the packets sent to the remote peer
+    pub outgoing: LinkConditions,
+    /// Conditions that temporarily replace `incoming` or `outgoing`, for example to simulate a latency spike.
+    ///
+    /// If multiple entries are active at the same time, the last one in the list takes precedence.
+    pub schedule: Vec<ScheduledConditions>,
+}
+
+/// Network conditions applied to the packets travelling in one direction
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
+pub struct LinkConditions {
+    /// Delay applied to every packet (half the RTT if only one direction is conditioned)
+    pub latency: Duration,
+    /// The maximum additional random latency applied to packets.
+    /// This may be added OR subtracted from `latency`
+    pub jitter: Duration,
+    /// The % chance that a packet will be dropped.
+    /// Represented as a value between 0 and 1.
+    ///
+    /// Ignored if `burst_loss` is set.
+    pub loss: f32,
+    /// Model losses that happen in bursts instead of independently for each packet
+    pub burst_loss: Option<BurstLossConfig>,
+    /// The % chance that a packet is delivered twice.
+    /// Represented as a value between 0 and 1
+    pub duplication: f32,
+    /// The % chance that a packet is held back by an additional `reorder_delay`,
+    /// so that it gets delivered after the packets sent after it.
+    /// Represented as a value between 0 and 1
+    pub reorder: f32,
+    /// Additional delay applied to the packets that are reordered
+    pub reorder_delay: Duration,
+    /// Limit the throughput of the link
+    pub bandwidth: Option<BandwidthLimit>,
+}
+
+/// Gilbert-Elliott model of packet loss.
+///
+/// The link alternates between a 'good' and a 'bad' state, and each state has its own loss probability.
+/// The state transitions are evaluated for every packet, which produces bursts of consecutive losses.
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct BurstLossConfig {
+    /// Probability of going from the good state to the bad state
+    pub good_to_bad: f32,
+    /// Probability of going from the bad state to the good state.
+    /// The average length of a burst is `1 / bad_to_good` packets
+    pub bad_to_good: f32,
+    /// Probability that a packet is dropped while in the good state
+    pub good_loss: f32,
+    /// Probability that a packet is dropped while in the bad state
+    pub bad_loss: f32,
+}
+
+/// Token bucket that limits the throughput of the link.
+///
+/// Packets that exceed the rate are queued until enough tokens are available,
+/// and dropped if they would have to wait longer than `max_queue_delay`.
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct BandwidthLimit {
+    /// Rate at which the bucket is refilled
+    pub bytes_per_second: u32,
+    /// Capacity of the bucket, i.e. the maximum number of bytes that can be sent in a burst
+    pub burst_bytes: u32,
+    /// Maximum time a packet can spend in the queue before being dropped
+    pub max_queue_delay: Duration,
+}
+
+/// Network conditions that are only applied during a window of time
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct ScheduledConditions {
+    /// Time, relative to the creation of the link conditioner, at which the conditions start being applied
+    pub start: Duration,
+    /// How long the conditions are applied for
+    pub duration: Duration,
+    /// Conditions that replace the `incoming_*` conditions of [`LinkConditionerConfig`]. If None, the incoming packets are not affected
+    pub incoming: Option<LinkConditions>,
+    /// Conditions that replace [`LinkConditionerConfig::outgoing`]. If None, the outgoing packets are not affected
+    pub outgoing: Option<LinkConditions>,
 }
 
 pub(crate) type PacketLinkConditioner = LinkConditioner<(SocketAddr, Box<[u8]>)>;
 
 pub(crate) struct LinkConditioner<P: Eq> {
-    config: LinkConditionerConfig,
+    conditions: LinkConditions,
+    /// (start, end, conditions)
+    schedule: Vec<(Duration, Duration, LinkConditions)>,
+    /// Time at which the conditioner was created; the schedule is relative to it
+    start: Instant,
+    /// Whether the burst loss model is in the 'bad' state
+    bad_state: bool,
+    bucket: TokenBucket,
     pub time_queue: ReadyBuffer<Instant, P>,
     last_packet: Option<P>,
 }
 
-impl<P: Eq> LinkConditioner<P> {
-    pub fn new(config: LinkConditionerConfig) -> Self {
+impl<P: Eq + Clone> LinkConditioner<P> {
+    /// Create a conditioner for the packets received from the remote peer
+    pub fn incoming(config: &LinkConditionerConfig) -> Self {
+        Self::new(
+            config.incoming(),
+            config
+                .schedule
+                .iter()
+                .filter_map(|s| {
+                    s.incoming
+                        .clone()
+                        .map(|c| (s.start, s.start + s.duration, c))
+                })
+                .collect(),
+        )
+    }
+
+    /// Create a conditioner for the packets sent to the remote peer
+    pub fn outgoing(config: &LinkConditionerConfig) -> Self {
+        Self::new(
+            config.outgoing.clone(),
+            config
+                .schedule
+                .iter()
+                .filter_map(|s| {
+                    s.outgoing
+                        .clone()
+                        .map(|c| (s.start, s.start + s.duration, c))
+                })
+                .collect(),
+        )
+    }
+
+    fn new(
+        conditions: LinkConditions,
+        schedule: Vec<(Duration, Duration, LinkConditions)>,
+    ) -> Self {
         LinkConditioner {
-            config,
+            conditions,
+            schedule,
+            start: Instant::now(),
+            bad_state: false,
+            bucket: TokenBucket::default(),
             time_queue: ReadyBuffer::new(),
             last_packet: None,
         }
     }
 
-    /// Add latency/jitter/loss to a packet
-    fn condition_packet(&mut self, packet: P) {
+    /// Conditions that apply at the given time
+    fn current_conditions(&self, now: Instant) -> &LinkConditions {
+        let elapsed = duration_since(now, self.start);
+        self.schedule
+            .iter()
+            .rev()
+            .find(|(start, end, _)| *start <= elapsed && elapsed < *end)
+            .map_or(&self.conditions, |(_, _, conditions)| conditions)
+    }
+
+    /// Add loss/bandwidth limit/latency/jitter/reordering/duplication to a packet of `size` bytes
+    fn condition_packet(&mut self, packet: P, size: usize, now: Instant) {
         let mut rng = thread_rng();
-        if rng.gen_range(0.0..1.0) <= self.config.incoming_loss {
+        let conditions = self.current_conditions(now).clone();
+        let loss = match &conditions.burst_loss {
+            Some(burst_loss) => {
+                let transition = if self.bad_state {
+                    burst_loss.bad_to_good
+                } else {
+                    burst_loss.good_to_bad
+                };
+                if rng.gen_range(0.0..1.0) < transition {
+                    self.bad_state = !self.bad_state;
+                }
+                if self.bad_state {
+                    burst_loss.bad_loss
+                } else {
+                    burst_loss.good_loss
+                }
+            }
+            None => conditions.loss,
+        };
+        if rng.gen_range(0.0..1.0) < loss {
             return;
         }
-        let mut latency: i32 = self.config.incoming_latency.as_millis() as i32;
-        // TODO: how can i use the virtual time here?
-        let mut packet_timestamp = Instant::now();
-        if self.config.incoming_jitter > Duration::default() {
-            let jitter: i32 = self.config.incoming_jitter.as_millis() as i32;
-            latency += rng.gen_range(-jitter..jitter);
-        }
-        if latency > 0 {
-            packet_timestamp += Duration::from_millis(latency as u64);
+        let queue_delay = match &conditions.bandwidth {
+            Some(limit) => {
+                let Some(delay) = self.bucket.reserve(limit, size, now) else {
+                    return;
+                };
+                delay
+            }
+            None => Duration::ZERO,
+        };
+        let ready_time = |rng: &mut rand::rngs::ThreadRng| {
+            let mut latency = conditions.latency.as_micros() as i64;
+            if conditions.jitter > Duration::default() {
+                let jitter = conditions.jitter.as_micros() as i64;
+                latency += rng.gen_range(-jitter..jitter);
+            }
+            if rng.gen_range(0.0..1.0) < conditions.reorder {
+                latency += conditions.reorder_delay.as_micros() as i64;
+            }
+            now + queue_delay + Duration::from_micros(latency.max(0) as u64)
+        };
+        if rng.gen_range(0.0..1.0) < conditions.duplication {
+            self.time_queue.push(ready_time(&mut rng), packet.clone());
         }
-        self.time_queue.push(packet_timestamp, packet);
+        self.time_queue.push(ready_time(&mut rng), packet);
     }
 
     /// Check if a packet is ready to be returned
-    fn pop_packet(&mut self) -> Option<P> {
-        self.time_queue
-            .pop_item(&Instant::now())
-            .map(|(_, packet)| packet)
+    fn pop_packet(&mut self, now: Instant) -> Option<P> {
+        self.time_queue.pop_item(&now).map(|(_, packet)| packet)
+    }
+}
+
+fn duration_since(now: Instant, earlier: Instant) -> Duration {
+    if now > earlier {
+        now.duration_since(earlier)
+    } else {
+        Duration::ZERO
+    }
+}
+
+#[derive(Default)]
+struct TokenBucket {
+    /// Number of bytes that can be sent right away. Negative if packets are waiting in the queue
+    tokens: f64,
+    last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Consume the tokens for a packet of `size` bytes.
+    ///
+    /// Returns how long the packet has to wait in the queue, or None if the packet should be dropped
+    fn reserve(&mut self, limit: &BandwidthLimit, size: usize, now: Instant) -> Option<Duration> {
+        let rate = limit.bytes_per_second.max(1) as f64;
+        let capacity = limit.burst_bytes as f64;
+        self.tokens = match self.last_refill {
+            None => capacity,
+            Some(last_refill) => {
+                (self.tokens + duration_since(now, last_refill).as_secs_f64() * rate).min(capacity)
+            }
+        };
+        self.last_refill = Some(now);
+        let size = size as f64;
+        let delay = if self.tokens >= size {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((size - self.tokens) / rate)
+        };
+        if delay > limit.max_queue_delay {
+            return None;
+        }
+        self.tokens -= size;
+        Some(delay)
     }
 }
 
@@ -96,19 +315,22 @@ pub struct ConditionedPacketReceiver<T: PacketReceiver, P: Eq> {
 
 impl<T: PacketReceiver> PacketReceiver for ConditionedPacketReceiver<T, (SocketAddr, Box<[u8]>)> {
     fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        let now = Instant::now();
         loop {
             // keep trying to receive packets from the inner packet receiver
             let option = self.packet_receiver.recv()?;
             match option {
                 None => break,
                 // add conditioning (put the packets in the time queue)
-                Some((data, addr)) => self
-                    .conditioner
-                    .condition_packet((addr, data.to_vec().into_boxed_slice())),
+                Some((data, addr)) => self.conditioner.condition_packet(
+                    (addr, data.to_vec().into_boxed_slice()),
+                    data.len(),
+                    now,
+                ),
             }
         }
         // only return a packet if it is ready to be returned
-        match self.conditioner.pop_packet() {
+        match self.conditioner.pop_packet(now) {
             Some((addr, data)) => {
                 // we use `last_packet` to get ownership of the data
                 self.conditioner.last_packet = Some((addr, data));
@@ -122,43 +344,332 @@ impl<T: PacketReceiver> PacketReceiver for ConditionedPacketReceiver<T, (SocketA
     }
 }
 
+impl<T: PacketSender> PacketSenderWrapper<T> for LinkConditioner<(SocketAddr, Box<[u8]>)> {
+    fn wrap(self, sender: T) -> impl PacketSender {
+        ConditionedPacketSender {
+            packet_sender: sender,
+            conditioner: self,
+        }
+    }
+}
+
+/// A wrapper around a packet sender that simulates network conditions on outgoing packets.
+///
+/// Delayed packets are sent by the inner sender on the next call to [`PacketSender::send`]
+/// or [`PacketSender::flush`]; the networking systems flush the io every frame.
+pub struct ConditionedPacketSender<T: PacketSender, P: Eq> {
+    packet_sender: T,
+    conditioner: LinkConditioner<P>,
+}
+
+impl<T: PacketSender> PacketSender for ConditionedPacketSender<T, (SocketAddr, Box<[u8]>)> {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        self.conditioner.condition_packet(
+            (*address, payload.into()),
+            payload.len(),
+            Instant::now(),
+        );
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let now = Instant::now();
+        while let Some((addr, data)) = self.conditioner.pop_packet(now) {
+            self.packet_sender.send(&data, &addr)?;
+        }
+        self.packet_sender.flush()
+    }
+}
+
 impl LinkConditionerConfig {
-    /// Creates a new LinkConditionerConfig
+    /// Creates a new LinkConditionerConfig that only conditions incoming packets
     pub fn new(incoming_latency: Duration, incoming_jitter: Duration, incoming_loss: f32) -> Self {
         LinkConditionerConfig {
             incoming_latency,
             incoming_jitter,
             incoming_loss,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_incoming(mut self, incoming: LinkConditions) -> Self {
+        self.incoming_latency = incoming.latency;
+        self.incoming_jitter = incoming.jitter;
+        self.incoming_loss = incoming.loss;
+        self.incoming_burst_loss = incoming.burst_loss;
+        self.incoming_duplication = incoming.duplication;
+        self.incoming_reorder = incoming.reorder;
+        self.incoming_reorder_delay = incoming.reorder_delay;
+        self.incoming_bandwidth = incoming.bandwidth;
+        self
+    }
+
+    pub fn with_outgoing(mut self, outgoing: LinkConditions) -> Self {
+        self.outgoing = outgoing;
+        self
+    }
+
+    pub fn with_scheduled(mut self, scheduled: ScheduledConditions) -> Self {
+        self.schedule.push(scheduled);
+        self
+    }
+
+    /// Conditions applied to the packets received from the remote peer
+    pub fn incoming(&self) -> LinkConditions {
+        LinkConditions {
+            latency: self.incoming_latency,
+            jitter: self.incoming_jitter,
+            loss: self.incoming_loss,
+            burst_loss: self.incoming_burst_loss.clone(),
+            duplication: self.incoming_duplication,
+            reorder: self.incoming_reorder,
+            reorder_delay: self.incoming_reorder_delay,
+            bandwidth: self.incoming_bandwidth.clone(),
         }
     }
 
+    /// Returns true if the outgoing packets are affected by the conditioner
+    pub(crate) fn conditions_outgoing(&self) -> bool {
+        self.outgoing != LinkConditions::default()
+            || self.schedule.iter().any(|s| s.outgoing.is_some())
+    }
+
     /// Creates a new LinkConditioner that simulates a connection which is in a
     /// good condition
     pub fn good_condition() -> Self {
-        LinkConditionerConfig {
-            incoming_latency: Duration::from_millis(40),
-            incoming_jitter: Duration::from_millis(6),
-            incoming_loss: 0.002,
-        }
+        Self::new(Duration::from_millis(40), Duration::from_millis(6), 0.002)
     }
 
     /// Creates a new `LinkConditioner` that simulates a connection which is in an
     /// average condition
     pub fn average_condition() -> Self {
-        LinkConditionerConfig {
-            incoming_latency: Duration::from_millis(170),
-            incoming_jitter: Duration::from_millis(45),
-            incoming_loss: 0.02,
-        }
+        Self::new(Duration::from_millis(170), Duration::from_millis(45), 0.02)
     }
 
     /// Creates a new `LinkConditioner` that simulates a connection which is in an
     /// poor condition
     pub fn poor_condition() -> Self {
-        LinkConditionerConfig {
-            incoming_latency: Duration::from_millis(300),
-            incoming_jitter: Duration::from_millis(84),
-            incoming_loss: 0.04,
+        Self::new(Duration::from_millis(300), Duration::from_millis(84), 0.04)
+    }
+}
+
+impl LinkConditions {
+    pub fn new(latency: Duration, jitter: Duration, loss: f32) -> Self {
+        LinkConditions {
+            latency,
+            jitter,
+            loss,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_burst_loss(mut self, burst_loss: BurstLossConfig) -> Self {
+        self.burst_loss = Some(burst_loss);
+        self
+    }
+
+    pub fn with_duplication(mut self, duplication: f32) -> Self {
+        self.duplication = duplication;
+        self
+    }
+
+    pub fn with_reorder(mut self, reorder: f32, reorder_delay: Duration) -> Self {
+        self.reorder = reorder;
+        self.reorder_delay = reorder_delay;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bandwidth: BandwidthLimit) -> Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+}
+
+impl BurstLossConfig {
+    /// Gilbert model: no packets are lost in the good state, all packets are lost in the bad state
+    pub fn new(good_to_bad: f32, bad_to_good: f32) -> Self {
+        Self {
+            good_to_bad,
+            bad_to_good,
+            good_loss: 0.0,
+            bad_loss: 1.0,
+        }
+    }
+
+    /// Average fraction of packets that are lost
+    pub fn mean_loss(&self) -> f32 {
+        let transitions = self.good_to_bad + self.bad_to_good;
+        if transitions == 0.0 {
+            return self.good_loss;
+        }
+        let bad_probability = self.good_to_bad / transitions;
+        (1.0 - bad_probability) * self.good_loss + bad_probability * self.bad_loss
+    }
+}
+
+impl BandwidthLimit {
+    pub fn new(bytes_per_second: u32, burst_bytes: u32, max_queue_delay: Duration) -> Self {
+        Self {
+            bytes_per_second,
+            burst_bytes,
+            max_queue_delay,
+        }
+    }
+}
+
+impl ScheduledConditions {
+    pub fn new(start: Duration, duration: Duration) -> Self {
+        Self {
+            start,
+            duration,
+            incoming: None,
+            outgoing: None,
         }
     }
+
+    pub fn with_incoming(mut self, incoming: LinkConditions) -> Self {
+        self.incoming = Some(incoming);
+        self
+    }
+
+    pub fn with_outgoing(mut self, outgoing: LinkConditions) -> Self {
+        self.outgoing = Some(outgoing);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(conditioner: &mut LinkConditioner<u32>, now: Instant) -> Vec<u32> {
+        std::iter::from_fn(|| conditioner.pop_packet(now)).collect()
+    }
+
+    #[test]
+    fn test_duplication_and_reorder() {
+        let config = LinkConditionerConfig::default().with_incoming(
+            LinkConditions::new(Duration::from_millis(10), Duration::ZERO, 0.0)
+                .with_duplication(1.0),
+        );
+        let mut conditioner = LinkConditioner::incoming(&config);
+        let now = conditioner.start;
+        conditioner.condition_packet(1, 10, now);
+        assert!(drain(&mut conditioner, now).is_empty());
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(10)),
+            vec![1, 1]
+        );
+
+        let config = LinkConditionerConfig::default()
+            .with_incoming(LinkConditions::default().with_reorder(1.0, Duration::from_millis(50)));
+        let mut conditioner = LinkConditioner::incoming(&config);
+        let now = conditioner.start;
+        conditioner.condition_packet(1, 10, now);
+        conditioner.conditions.reorder = 0.0;
+        conditioner.condition_packet(2, 10, now + Duration::from_millis(10));
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(100)),
+            vec![2, 1]
+        );
+    }
+
+    struct ChannelSender(crossbeam_channel::Sender<Box<[u8]>>);
+
+    impl PacketSender for ChannelSender {
+        fn send(&mut self, payload: &[u8], _: &SocketAddr) -> Result<()> {
+            self.0.send(payload.into()).unwrap();
+            Ok(())
+        }
+    }
+
+    /// Delayed outgoing packets are released by `flush` even if nothing else is sent
+    #[test]
+    fn test_outgoing_flush() {
+        use mock_instant::global::MockClock;
+
+        let config = LinkConditionerConfig::default().with_outgoing(LinkConditions::new(
+            Duration::from_millis(50),
+            Duration::ZERO,
+            0.0,
+        ));
+        assert!(config.conditions_outgoing());
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut sender =
+            PacketSenderWrapper::wrap(PacketLinkConditioner::outgoing(&config), ChannelSender(tx));
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        sender.send(b"hello", &addr).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        MockClock::advance(Duration::from_millis(50));
+        sender.flush().unwrap();
+        assert_eq!(rx.try_recv().unwrap().as_ref(), b"hello");
+    }
+
+    #[test]
+    fn test_burst_loss() {
+        // enter the bad state on the first packet and never leave it
+        let config = LinkConditionerConfig::default().with_incoming(
+            LinkConditions::default().with_burst_loss(BurstLossConfig::new(1.0, 0.0)),
+        );
+        let mut conditioner = LinkConditioner::incoming(&config);
+        let now = conditioner.start;
+        for i in 0..10 {
+            conditioner.condition_packet(i, 10, now);
+        }
+        assert!(drain(&mut conditioner, now).is_empty());
+        assert_eq!(BurstLossConfig::new(0.25, 0.75).mean_loss(), 0.25);
+    }
+
+    #[test]
+    fn test_bandwidth_limit() {
+        let config = LinkConditionerConfig::default().with_incoming(
+            LinkConditions::default().with_bandwidth(BandwidthLimit::new(
+                1000,
+                100,
+                Duration::from_millis(150),
+            )),
+        );
+        let mut conditioner = LinkConditioner::incoming(&config);
+        let now = conditioner.start;
+        // the first packet uses the burst capacity
+        conditioner.condition_packet(1, 100, now);
+        // the second packet waits 100ms for the bucket to refill
+        conditioner.condition_packet(2, 100, now);
+        // the third packet would have to wait 200ms, so it is dropped
+        conditioner.condition_packet(3, 100, now);
+        assert_eq!(drain(&mut conditioner, now), vec![1]);
+        assert!(drain(&mut conditioner, now + Duration::from_millis(99)).is_empty());
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(100)),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_schedule() {
+        let spike = LinkConditions::new(Duration::from_millis(400), Duration::ZERO, 0.0);
+        let config = LinkConditionerConfig::default().with_scheduled(
+            ScheduledConditions::new(Duration::from_secs(10), Duration::from_secs(1))
+                .with_incoming(spike.clone()),
+        );
+        assert!(!config.conditions_outgoing());
+        let conditioner = LinkConditioner::<u32>::incoming(&config);
+        let start = conditioner.start;
+        assert_eq!(
+            conditioner.current_conditions(start + Duration::from_secs(5)),
+            &LinkConditions::default()
+        );
+        assert_eq!(
+            conditioner.current_conditions(start + Duration::from_millis(10500)),
+            &spike
+        );
+        assert_eq!(
+            conditioner.current_conditions(start + Duration::from_secs(11)),
+            &LinkConditions::default()
+        );
+        assert!(LinkConditioner::<u32>::outgoing(&config)
+            .schedule
+            .is_empty());
+    }
 }
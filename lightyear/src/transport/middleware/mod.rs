@@ -9,6 +9,9 @@ pub(crate) mod conditioner;
 /// Middleware that compresses packets before sending them.
 pub(crate) mod compression;
 
+/// Middleware that records the packets sent and received to a file.
+pub(crate) mod capture;
+
 pub trait PacketReceiverWrapper<T: PacketReceiver> {
     fn wrap(self, receiver: T) -> impl PacketReceiver;
 }
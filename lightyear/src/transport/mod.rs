@@ -13,6 +13,8 @@ use crate::server::io::transport::ServerTransportEnum;
 use crate::transport::channels::Channels;
 use crate::transport::dummy::DummyIo;
 use crate::transport::local::LocalChannel;
+#[cfg(not(target_family = "wasm"))]
+use crate::transport::replay::ReplayTransport;
 use crate::transport::udp::UdpSocket;
 #[cfg(feature = "websocket")]
 use crate::transport::websocket::client::{WebSocketClientSocket, WebSocketClientSocketBuilder};
@@ -46,6 +48,10 @@ pub(crate) mod webtransport;
 
 pub(crate) mod middleware;
 
+/// The transport replays packets recorded by the capture middleware
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod replay;
+
 pub mod config;
 pub(crate) mod dummy;
 pub(crate) mod error;
@@ -84,12 +90,23 @@ pub(crate) trait Transport {
 pub trait PacketSender: Send + Sync {
     /// Send data on the socket to the remote address
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()>;
+
+    /// Send any packets that were buffered by the sender (for example delayed by the link conditioner).
+    ///
+    /// This is called every frame by the networking systems, even if there is no new data to send.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl PacketSender for BoxedSender {
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
         (**self).send(payload, address)
     }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
 }
 
 /// Receive data from a remote address
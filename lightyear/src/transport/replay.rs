@@ -0,0 +1,211 @@
+//! Transport that replays the packets recorded by the capture middleware.
+//!
+//! The packets that were received by a client during the capture are fed back to the client at the same time
+//! (relative to the start of the capture) as they were originally received. The packets sent by the client
+//! are dropped.
+//!
+//! Replay is client-side only: a netcode server generates a new random challenge key every time it starts,
+//! so it would reject the challenge responses contained in a capture.
+//!
+//! Replaying traffic only reproduces the original session if the client accepts the replayed packets:
+//! the same protocol id and connect token must be used (for example with
+//! [`Authentication::Token`](crate::prelude::client::Authentication::Token)), and the token must not have expired.
+//! The capture contains the packets after decompression, so the replay should be run without compression.
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bevy::utils::{Duration, Instant};
+use tracing::trace;
+
+use crate::client::io::transport::{ClientTransportBuilder, ClientTransportEnum};
+use crate::client::io::{ClientIoEventReceiver, ClientNetworkEventSender};
+use crate::transport::io::IoState;
+use crate::transport::middleware::capture::{read_capture_file, PacketDirection};
+use crate::transport::{
+    BoxedReceiver, BoxedSender, PacketReceiver, PacketSender, Transport, LOCAL_SOCKET,
+};
+
+use super::error::Result;
+
+/// Clock that decides when the captured packets are replayed
+#[derive(Clone, Debug, Default)]
+pub enum ReplayClock {
+    /// Replay the packets at the same pace as they were captured
+    #[default]
+    RealTime,
+    /// Replay the packets according to a clock that is advanced manually.
+    ///
+    /// This is useful to replay a capture deterministically in tests, by advancing the clock by the same
+    /// amount as the app's time.
+    Manual(ManualReplayClock),
+}
+
+/// A clock that only moves forward when [`ManualReplayClock::advance`] is called.
+///
+/// Clones of the clock share the same time.
+#[derive(Clone, Debug, Default)]
+pub struct ManualReplayClock {
+    elapsed_micros: Arc<AtomicU64>,
+}
+
+impl ManualReplayClock {
+    pub fn advance(&self, delta: Duration) {
+        self.elapsed_micros
+            .fetch_add(delta.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.elapsed_micros.load(Ordering::Relaxed))
+    }
+}
+
+pub(crate) struct ReplayBuilder {
+    pub(crate) path: PathBuf,
+    pub(crate) clock: ReplayClock,
+}
+
+impl ReplayBuilder {
+    fn build(self) -> Result<ReplayTransport> {
+        let packets = read_capture_file(&self.path)?
+            .into_iter()
+            .filter(|packet| packet.direction == PacketDirection::Received)
+            .map(|packet| (packet.timestamp, packet.remote_addr, packet.payload))
+            .collect();
+        Ok(ReplayTransport {
+            receiver: ReplayReceiver {
+                packets,
+                clock: self.clock,
+                start: Instant::now(),
+                buffer: vec![],
+            },
+        })
+    }
+}
+
+impl ClientTransportBuilder for ReplayBuilder {
+    fn connect(
+        self,
+    ) -> Result<(
+        ClientTransportEnum,
+        IoState,
+        Option<ClientIoEventReceiver>,
+        Option<ClientNetworkEventSender>,
+    )> {
+        Ok((
+            ClientTransportEnum::Replay(self.build()?),
+            IoState::Connected,
+            None,
+            None,
+        ))
+    }
+}
+
+pub(crate) struct ReplayTransport {
+    receiver: ReplayReceiver,
+}
+
+impl Transport for ReplayTransport {
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn split(self) -> (BoxedSender, BoxedReceiver) {
+        (Box::new(ReplaySender), Box::new(self.receiver))
+    }
+}
+
+struct ReplayReceiver {
+    /// Packets that haven't been replayed yet, along with the time at which they were received
+    packets: VecDeque<(Duration, SocketAddr, Vec<u8>)>,
+    clock: ReplayClock,
+    start: Instant,
+    buffer: Vec<u8>,
+}
+
+impl ReplayReceiver {
+    fn elapsed(&self) -> Duration {
+        match &self.clock {
+            ReplayClock::RealTime => self.start.elapsed(),
+            ReplayClock::Manual(clock) => clock.elapsed(),
+        }
+    }
+}
+
+impl PacketReceiver for ReplayReceiver {
+    fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
+        let elapsed = self.elapsed();
+        if self
+            .packets
+            .front()
+            .map_or(true, |(timestamp, _, _)| *timestamp > elapsed)
+        {
+            return Ok(None);
+        }
+        let (_, address, payload) = self.packets.pop_front().unwrap();
+        self.buffer = payload;
+        Ok(Some((self.buffer.as_mut_slice(), address)))
+    }
+}
+
+struct ReplaySender;
+
+impl PacketSender for ReplaySender {
+    fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
+        trace!(
+            ?address,
+            len = payload.len(),
+            "dropping packet sent during replay"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{SharedIoConfig, TransportConfig};
+    use crate::transport::middleware::capture::CaptureConfig;
+
+    #[test]
+    fn test_replay() {
+        let path = std::env::temp_dir().join("lightyear_test_replay.cap");
+        // record some traffic
+        {
+            let (send, recv) = crossbeam_channel::unbounded();
+            let config = TransportConfig::LocalChannel { send, recv };
+            let mut io = SharedIoConfig::from_transport(config)
+                .with_capture(CaptureConfig::new(&path))
+                .connect()
+                .unwrap();
+            io.sender.send(b"hello", &LOCAL_SOCKET).unwrap();
+            io.sender.send(b"world", &LOCAL_SOCKET).unwrap();
+            io.receiver.recv().unwrap().unwrap();
+            io.receiver.recv().unwrap().unwrap();
+        }
+
+        // replay it
+        let clock = ManualReplayClock::default();
+        let mut io = SharedIoConfig::from_transport(TransportConfig::Replay {
+            path: path.clone(),
+            clock: ReplayClock::Manual(clock.clone()),
+        })
+        .connect()
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // packets sent during the replay are dropped
+        io.sender.send(b"dropped", &LOCAL_SOCKET).unwrap();
+        // the clock has not advanced yet; the packets might have been received at time 0
+        // so advance the clock by a large amount
+        clock.advance(Duration::from_secs(10));
+        let (data, addr) = io.receiver.recv().unwrap().unwrap();
+        assert_eq!(data.as_ref(), b"hello");
+        assert_eq!(addr, LOCAL_SOCKET);
+        let (data, _) = io.receiver.recv().unwrap().unwrap();
+        assert_eq!(data.as_ref(), b"world");
+        assert!(io.receiver.recv().unwrap().is_none());
+    }
+}
@@ -191,10 +191,11 @@ mod tests {
         let server_addr = server_socket.local_addr();
         let (_, server_receiver) = server_socket.split();
 
-        let mut conditioned_server_receiver = LinkConditioner::new(LinkConditionerConfig {
+        let mut conditioned_server_receiver = LinkConditioner::incoming(&LinkConditionerConfig {
             incoming_latency: Duration::from_millis(100),
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
+            ..Default::default()
         })
         .wrap(server_receiver);
 
@@ -1,8 +1,9 @@
 //! Implement lightyear traits for some common bevy types
 use crate::prelude::client::{InterpolationSet, PredictionSet};
+use crate::server::relevance::spatial::SpatialPosition;
 use crate::shared::replication::delta::Diffable;
 use crate::shared::sets::{ClientMarker, InternalReplicationSet, ServerMarker};
-use avian2d::math::Scalar;
+use avian2d::math::{AsF32, Scalar};
 use avian2d::prelude::*;
 use bevy::app::{RunFixedMainLoop, RunFixedMainLoopSystem};
 use bevy::prelude::TransformSystem::TransformPropagate;
@@ -83,6 +84,38 @@ pub mod position {
         res
     }
 
+    /// Cubic Hermite interpolation of the position, using the [`LinearVelocity`] at the start and end
+    /// states as tangents. This avoids overshooting or cutting corners when the entity is turning.
+    ///
+    /// Register it with `add_hermite_interpolation_fn::<LinearVelocity>(position::hermite)`
+    pub fn hermite(
+        start: &Position,
+        start_velocity: &LinearVelocity,
+        end: &Position,
+        end_velocity: &LinearVelocity,
+        t: f32,
+        duration: f32,
+    ) -> Position {
+        let u = Scalar::from(t);
+        let duration = Scalar::from(duration);
+        let u2 = u * u;
+        let u3 = u2 * u;
+        let res = Position::new(
+            start.0 * (2.0 * u3 - 3.0 * u2 + 1.0)
+                + start_velocity.0 * ((u3 - 2.0 * u2 + u) * duration)
+                + end.0 * (3.0 * u2 - 2.0 * u3)
+                + end_velocity.0 * ((u3 - u2) * duration),
+        );
+        trace!(
+            "position hermite: start: {:?} end: {:?} t: {} res: {:?}",
+            start,
+            end,
+            t,
+            res
+        );
+        res
+    }
+
     impl Diffable for Position {
         type Delta = Self;
 
@@ -98,6 +131,12 @@ pub mod position {
             self.0 += delta.0;
         }
     }
+
+    impl SpatialPosition for Position {
+        fn spatial_position(&self) -> bevy::math::Vec3 {
+            self.0.f32().extend(0.0)
+        }
+    }
 }
 
 pub mod rotation {
@@ -110,6 +110,11 @@ impl<R> HistoryBuffer<R> {
         self.buffer.back()
     }
 
+    /// Iterate through the values that are present in the history, from the oldest to the most recent
+    pub fn iter(&self) -> <&HistoryBuffer<R> as IntoIterator>::IntoIter {
+        self.into_iter()
+    }
+
     /// In case of a TickEvent where the client tick is changed, we need to update the ticks in the buffer
     pub(crate) fn update_ticks(&mut self, delta: i16) {
         self.buffer.iter_mut().for_each(|(tick, _)| {
@@ -9,12 +9,28 @@ use lightyear::prelude::client::InterpolationDelay;
 use lightyear::prelude::TickManager;
 #[cfg(all(feature = "2d", not(feature = "3d")))]
 use {
-    avian2d::{math::*, prelude::*},
+    avian2d::{
+        collision::contact_query,
+        math::*,
+        parry::{
+            math::Isometry,
+            query::{cast_shapes, ShapeCastOptions},
+        },
+        prelude::*,
+    },
     bevy::math::Dir2 as Dir,
 };
 #[cfg(all(feature = "3d", not(feature = "2d")))]
 use {
-    avian3d::{math::*, prelude::*},
+    avian3d::{
+        collision::contact_query,
+        math::*,
+        parry::{
+            math::Isometry,
+            query::{cast_shapes, ShapeCastOptions},
+        },
+        prelude::*,
+    },
     bevy::math::Dir3 as Dir,
 };
 
@@ -56,6 +72,8 @@ impl LagCompensationSpatialQuery<'_, '_> {
 
     /// Similar to [`SpatialQuery::cast_ray_predicate`], but does lag compensation by
     /// using the history buffer of the entity.
+    ///
+    /// Returns the closest hit with the rewound colliders.
     pub fn cast_ray_predicate(
         &self,
         interpolation_delay: InterpolationDelay,
@@ -68,7 +86,6 @@ impl LagCompensationSpatialQuery<'_, '_> {
     ) -> Option<RayHitData> {
         // 1): check if the ray hits the aabb envelope
         let tick = self.tick_manager.tick();
-        let tick_duration = self.tick_manager.config.tick_duration;
         // we use interior mutability because the predicate must be a `dyn Fn`
         let exact_hit_data: RefCell<Option<RayHitData>> = RefCell::new(None);
         self.spatial_query.cast_ray_predicate(
@@ -87,27 +104,11 @@ impl LagCompensationSpatialQuery<'_, '_> {
                 };
                 let parent = parent_component.get();
                 info!("Broadphase hit with {child:?}");
-                let (collider, history) = self
-                    .parent_query
-                    .get(parent)
-                    .expect("the parent must have a history");
-                let (interpolation_tick, interpolation_overstep) =
-                    interpolation_delay.tick_and_overstep(tick, tick_duration);
-
-                // find the collider position at that time in history
-                // the start corresponds to tick `interpolation_tick` (we interpolate between `interpolation_tick` and `interpolation_tick + 1`)
-                let (source_idx, (_, (start_position, start_rotation, _))) = history
-                    .into_iter()
-                    .enumerate()
-                    .find(|(_, (history_tick, _))| *history_tick == interpolation_tick)
-                    .unwrap();
-                let (_, (target_position, target_rotation, _)) =
-                    history.into_iter().skip(source_idx + 1).next().unwrap();
-                // we assume that the collider itself doesn't change so we don't need to interpolate it
-                let interpolated_position =
-                    start_position.lerp(**target_position, interpolation_overstep);
-                let interpolated_rotation =
-                    start_rotation.slerp(*target_rotation, interpolation_overstep);
+                let Some((collider, interpolated_position, interpolated_rotation)) =
+                    self.interpolated_collider(parent, &interpolation_delay)
+                else {
+                    return false;
+                };
 
                 #[cfg(all(feature = "2d", not(feature = "3d")))]
                 let dir = direction.as_vec2();
@@ -115,7 +116,7 @@ impl LagCompensationSpatialQuery<'_, '_> {
                 let dir = direction.as_vec3();
 
                 if let Some((distance, normal)) = collider.cast_ray(
-                    interpolated_position,
+                    interpolated_position.0,
                     interpolated_rotation,
                     origin,
                     dir,
@@ -125,24 +126,286 @@ impl LagCompensationSpatialQuery<'_, '_> {
                     if !predicate(parent) {
                         return false;
                     }
+                    let mut exact_hit_data = exact_hit_data.borrow_mut();
+                    if exact_hit_data
+                        .as_ref()
+                        .is_some_and(|hit| hit.distance <= distance)
+                    {
+                        return false;
+                    }
                     info!(
                         ?tick,
-                        ?interpolation_tick,
-                        ?interpolation_overstep,
                         ?interpolated_position,
                         ?parent,
                         "LagCompensation RayHit!"
                     );
-                    *exact_hit_data.borrow_mut() = Some(RayHitData {
+                    *exact_hit_data = Some(RayHitData {
                         entity: parent,
                         distance,
                         normal,
                     });
-                    return true;
                 }
+                // the aabb envelopes are not ordered like the rewound colliders, so we keep
+                // visiting the envelopes to find the closest hit
                 false
             },
         );
         exact_hit_data.into_inner()
     }
+
+    /// Similar to [`SpatialQuery::cast_shape`], but does lag compensation by
+    /// using the history buffer of the entity.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        origin: Vector,
+        shape_rotation: RotationValue,
+        direction: Dir,
+        config: &ShapeCastConfig,
+        filter: &SpatialQueryFilter,
+    ) -> Option<ShapeHitData> {
+        self.cast_shape_predicate(
+            interpolation_delay,
+            shape,
+            origin,
+            shape_rotation,
+            direction,
+            config,
+            &|_| true,
+            filter,
+        )
+    }
+
+    /// Similar to [`SpatialQuery::cast_shape_predicate`], but does lag compensation by
+    /// using the history buffer of the entity.
+    ///
+    /// Returns the hit with the smallest time of impact with the rewound colliders.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape_predicate(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        origin: Vector,
+        shape_rotation: RotationValue,
+        direction: Dir,
+        config: &ShapeCastConfig,
+        predicate: &dyn Fn(Entity) -> bool,
+        filter: &SpatialQueryFilter,
+    ) -> Option<ShapeHitData> {
+        // 1): check if the shape hits the aabb envelope
+        let tick = self.tick_manager.tick();
+        // we use interior mutability because the predicate must be a `dyn Fn`
+        let exact_hit_data: RefCell<Option<ShapeHitData>> = RefCell::new(None);
+        // the origin is often inside an aabb envelope, which must not be ignored
+        let broadphase_config = ShapeCastConfig {
+            max_distance: config.max_distance,
+            target_distance: config.target_distance,
+            compute_contact_on_penetration: config.compute_contact_on_penetration,
+            ignore_origin_penetration: false,
+        };
+        let options = ShapeCastOptions {
+            max_time_of_impact: config.max_distance,
+            target_distance: config.target_distance,
+            stop_at_penetration: !config.ignore_origin_penetration,
+            compute_impact_geometry_on_penetration: config.compute_contact_on_penetration,
+        };
+        let shape_isometry = make_isometry(Position(origin), Rotation::from(shape_rotation));
+        self.spatial_query.cast_shape_predicate(
+            shape,
+            origin,
+            shape_rotation,
+            direction,
+            &broadphase_config,
+            filter,
+            &|child| {
+                // 2) there is a hit! Check if we hit the collider from the history
+                let Ok(parent_component) = self.child_query.get(child) else {
+                    return false;
+                };
+                let parent = parent_component.get();
+                let Some((collider, interpolated_position, interpolated_rotation)) =
+                    self.interpolated_collider(parent, &interpolation_delay)
+                else {
+                    return false;
+                };
+
+                #[cfg(all(feature = "2d", not(feature = "3d")))]
+                let dir = direction.as_vec2();
+                #[cfg(all(feature = "3d", not(feature = "2d")))]
+                let dir = direction.as_vec3();
+
+                // the cast shape moves at unit speed, so the time of impact is the distance travelled
+                let Ok(Some(hit)) = cast_shapes(
+                    &shape_isometry,
+                    &dir.into(),
+                    shape.shape_scaled().as_ref(),
+                    &make_isometry(interpolated_position, interpolated_rotation),
+                    &Vector::ZERO.into(),
+                    collider.shape_scaled().as_ref(),
+                    options,
+                ) else {
+                    return false;
+                };
+                if !predicate(parent) {
+                    return false;
+                }
+                let mut exact_hit_data = exact_hit_data.borrow_mut();
+                if exact_hit_data
+                    .as_ref()
+                    .is_some_and(|data| data.distance <= hit.time_of_impact)
+                {
+                    return false;
+                }
+                info!(
+                    ?tick,
+                    ?interpolated_position,
+                    ?parent,
+                    "LagCompensation ShapeHit!"
+                );
+                *exact_hit_data = Some(ShapeHitData {
+                    entity: parent,
+                    distance: hit.time_of_impact,
+                    point1: hit.witness1.into(),
+                    point2: hit.witness2.into(),
+                    normal1: hit.normal1.into(),
+                    normal2: hit.normal2.into(),
+                });
+                // the aabb envelopes are not ordered like the rewound colliders, so we keep
+                // visiting the envelopes to find the closest hit
+                false
+            },
+        );
+        exact_hit_data.into_inner()
+    }
+
+    /// Similar to [`SpatialQuery::shape_intersections`], but does lag compensation by
+    /// using the history buffer of the entity.
+    ///
+    /// Returns the entities whose collider, rewound to the time that the client saw,
+    /// intersects with the given shape.
+    pub fn shape_intersections(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        shape_position: Vector,
+        shape_rotation: RotationValue,
+        filter: &SpatialQueryFilter,
+    ) -> Vec<Entity> {
+        let mut intersections = vec![];
+        // 1): find the aabb envelopes that intersect with the shape
+        self.spatial_query.shape_intersections_callback(
+            shape,
+            shape_position,
+            shape_rotation,
+            filter,
+            |child| {
+                // 2) check if the shape intersects with the collider from the history
+                let Ok(parent_component) = self.child_query.get(child) else {
+                    return true;
+                };
+                let parent = parent_component.get();
+                let Some((collider, interpolated_position, interpolated_rotation)) =
+                    self.interpolated_collider(parent, &interpolation_delay)
+                else {
+                    return true;
+                };
+                if contact_query::intersection_test(
+                    shape,
+                    Position(shape_position),
+                    Rotation::from(shape_rotation),
+                    collider,
+                    interpolated_position,
+                    interpolated_rotation,
+                )
+                .unwrap_or(false)
+                {
+                    intersections.push(parent);
+                }
+                true
+            },
+        );
+        intersections
+    }
+
+    /// Similar to [`SpatialQuery::point_intersections`], but does lag compensation by
+    /// using the history buffer of the entity.
+    ///
+    /// Returns the entities whose collider, rewound to the time that the client saw,
+    /// contains the given point.
+    pub fn point_intersections(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        point: Vector,
+        filter: &SpatialQueryFilter,
+    ) -> Vec<Entity> {
+        let mut intersections = vec![];
+        // 1): find the aabb envelopes that contain the point
+        self.spatial_query
+            .point_intersections_callback(point, filter, |child| {
+                // 2) check if the point is inside the collider from the history
+                let Ok(parent_component) = self.child_query.get(child) else {
+                    return true;
+                };
+                let parent = parent_component.get();
+                let Some((collider, interpolated_position, interpolated_rotation)) =
+                    self.interpolated_collider(parent, &interpolation_delay)
+                else {
+                    return true;
+                };
+                if collider.contains_point(interpolated_position, interpolated_rotation, point) {
+                    intersections.push(parent);
+                }
+                true
+            });
+        intersections
+    }
+
+    /// Returns the collider of `parent` along with its position and rotation at the time
+    /// that the client with the given [`InterpolationDelay`] was seeing.
+    ///
+    /// Returns None if the entity doesn't have a history yet (for example if it was just spawned),
+    /// or if the history doesn't go back far enough.
+    fn interpolated_collider(
+        &self,
+        parent: Entity,
+        interpolation_delay: &InterpolationDelay,
+    ) -> Option<(&Collider, Position, Rotation)> {
+        let Ok((collider, history)) = self.parent_query.get(parent) else {
+            return None;
+        };
+        let (interpolation_tick, interpolation_overstep) = interpolation_delay.tick_and_overstep(
+            self.tick_manager.tick(),
+            self.tick_manager.config.tick_duration,
+        );
+
+        // find the collider position at that time in history
+        // the start corresponds to tick `interpolation_tick` (we interpolate between `interpolation_tick` and `interpolation_tick + 1`)
+        let mut history = history
+            .into_iter()
+            .skip_while(|(history_tick, _)| *history_tick != interpolation_tick);
+        let (_, (start_position, start_rotation, _)) = history.next()?;
+        let (_, (target_position, target_rotation, _)) = history.next()?;
+        // we assume that the collider itself doesn't change so we don't need to interpolate it
+        let interpolated_position =
+            Position(start_position.lerp(**target_position, interpolation_overstep));
+        let interpolated_rotation = start_rotation.slerp(*target_rotation, interpolation_overstep);
+        trace!(
+            ?interpolation_tick,
+            ?interpolation_overstep,
+            ?interpolated_position,
+            ?parent,
+            "Rewound collider"
+        );
+        Some((collider, interpolated_position, interpolated_rotation))
+    }
+}
+
+/// Converts a position and a rotation to the isometry used by parry
+fn make_isometry(position: Position, rotation: Rotation) -> Isometry<Scalar> {
+    #[cfg(all(feature = "2d", not(feature = "3d")))]
+    return Isometry::new(position.0.into(), rotation.as_radians());
+    #[cfg(all(feature = "3d", not(feature = "2d")))]
+    return Isometry::new(position.0.into(), rotation.to_scaled_axis().into());
 }
@@ -0,0 +1,486 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, Member, Type};
+
+/// Maximum number of fields that can be tracked in the change mask
+const MAX_FIELDS: usize = 64;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FieldKind {
+    /// The field is sent in full when it changes
+    Value,
+    /// The field implements `Diffable`, we send its delta when it changes
+    Nested,
+    /// The field is never sent
+    Skip,
+}
+
+struct DiffField {
+    /// How to access the field in the original type
+    member: Member,
+    ty: Type,
+    kind: FieldKind,
+    /// Index of the field in the change mask, and name of the field in the delta type
+    /// (None for skipped fields)
+    delta: Option<(usize, Ident)>,
+}
+
+struct Variant {
+    ident: Ident,
+    fields: Vec<DiffField>,
+}
+
+pub fn diffable_impl(
+    input: proc_macro::TokenStream,
+    shared_crate_name: TokenStream,
+) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    diffable_impl_inner(&input, &shared_crate_name)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn diffable_impl_inner(
+    input: &DeriveInput,
+    shared_crate_name: &TokenStream,
+) -> syn::Result<TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Diffable cannot be derived for generic types",
+        ));
+    }
+    let name = &input.ident;
+    let vis = &input.vis;
+    let delta_name = format_ident!("{}Delta", name);
+    let diffable = quote! { #shared_crate_name::shared::replication::delta::Diffable };
+    let serde = quote! { #shared_crate_name::_internal::serde };
+
+    // collect the fields, and assign an index in the change mask to each field that is sent
+    let mut num_fields = 0;
+    let (variants, is_enum) = match &input.data {
+        Data::Struct(data) => (
+            vec![Variant {
+                ident: name.clone(),
+                fields: parse_fields(&data.fields, &mut num_fields, None)?,
+            }],
+            false,
+        ),
+        Data::Enum(data) => (
+            data.variants
+                .iter()
+                .map(|variant| {
+                    Ok(Variant {
+                        ident: variant.ident.clone(),
+                        fields: parse_fields(
+                            &variant.fields,
+                            &mut num_fields,
+                            Some(&variant.ident),
+                        )?,
+                    })
+                })
+                .collect::<syn::Result<Vec<_>>>()?,
+            true,
+        ),
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Diffable cannot be derived for unions",
+            ))
+        }
+    };
+    // for enums, one bit of the mask indicates that the variant changed
+    let replace_bit = num_fields;
+    let max_fields = if is_enum { MAX_FIELDS - 1 } else { MAX_FIELDS };
+    if num_fields > max_fields {
+        return Err(syn::Error::new_spanned(
+            input,
+            format!("Diffable can only be derived for types with at most {max_fields} fields"),
+        ));
+    }
+
+    let delta_fields: Vec<(&DiffField, usize, &Ident)> = variants
+        .iter()
+        .flat_map(|variant| variant.fields.iter())
+        .filter_map(|field| field.delta.as_ref().map(|(i, ident)| (field, *i, ident)))
+        .collect();
+    let delta_idents: Vec<&Ident> = delta_fields.iter().map(|(_, _, ident)| *ident).collect();
+    let delta_bits: Vec<usize> = delta_fields.iter().map(|(_, i, _)| *i).collect();
+    let delta_types: Vec<TokenStream> = delta_fields
+        .iter()
+        .map(|(field, _, _)| {
+            let ty = &field.ty;
+            match field.kind {
+                FieldKind::Nested => quote! { <#ty as #diffable>::Delta },
+                _ => quote! { #ty },
+            }
+        })
+        .collect();
+    let max_len = 1 + num_fields + usize::from(is_enum);
+
+    // the replace field only exists for enums
+    let replace_field = is_enum.then(|| quote! { #vis replace: Option<#name>, });
+    let replace_init = is_enum.then(|| quote! { replace, });
+    let replace_serialize_len = is_enum.then(|| {
+        quote! {
+            if self.replace.is_some() {
+                mask |= 1u64 << #replace_bit;
+                len += 1;
+            }
+        }
+    });
+    let replace_serialize = is_enum.then(|| {
+        quote! {
+            if let Some(value) = &self.replace {
+                tuple.serialize_element(value)?;
+            }
+        }
+    });
+    let replace_deserialize = is_enum.then(|| {
+        quote! {
+            let replace = if mask & (1u64 << #replace_bit) != 0 {
+                let value = seq
+                    .next_element()?
+                    .ok_or_else(|| #serde::de::Error::invalid_length(index, &self))?;
+                index += 1;
+                Some(value)
+            } else {
+                None
+            };
+        }
+    });
+
+    let (base_value, diff, apply_diff) = if is_enum {
+        enum_fns(name, &delta_name, &variants, &delta_idents, &diffable)
+    } else {
+        struct_fns(&delta_name, &variants[0].fields, &delta_idents, &diffable)
+    };
+
+    let doc = format!("Delta type generated by `#[derive(Diffable)]` for [`{name}`]");
+    let expecting = format!("a delta for {name}");
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, PartialEq)]
+        #vis struct #delta_name {
+            #replace_field
+            #(#vis #delta_idents: Option<#delta_types>,)*
+        }
+
+        impl #serde::Serialize for #delta_name {
+            fn serialize<S: #serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use #serde::ser::SerializeTuple;
+                // the mask of changed fields is written first, followed only by the changed values
+                let mut mask: u64 = 0;
+                let mut len: usize = 1;
+                #(
+                    if self.#delta_idents.is_some() {
+                        mask |= 1u64 << #delta_bits;
+                        len += 1;
+                    }
+                )*
+                #replace_serialize_len
+                let mut tuple = serializer.serialize_tuple(len)?;
+                tuple.serialize_element(&mask)?;
+                #(
+                    if let Some(value) = &self.#delta_idents {
+                        tuple.serialize_element(value)?;
+                    }
+                )*
+                #replace_serialize
+                tuple.end()
+            }
+        }
+
+        impl<'de> #serde::Deserialize<'de> for #delta_name {
+            fn deserialize<D: #serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct DeltaVisitor;
+
+                impl<'de> #serde::de::Visitor<'de> for DeltaVisitor {
+                    type Value = #delta_name;
+
+                    fn expecting(&self, formatter: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        formatter.write_str(#expecting)
+                    }
+
+                    #[allow(unused_mut, unused_assignments)]
+                    fn visit_seq<A: #serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                        let mask: u64 = seq
+                            .next_element()?
+                            .ok_or_else(|| #serde::de::Error::invalid_length(0, &self))?;
+                        let mut index: usize = 1;
+                        #(
+                            let #delta_idents = if mask & (1u64 << #delta_bits) != 0 {
+                                let value = seq
+                                    .next_element()?
+                                    .ok_or_else(|| #serde::de::Error::invalid_length(index, &self))?;
+                                index += 1;
+                                Some(value)
+                            } else {
+                                None
+                            };
+                        )*
+                        #replace_deserialize
+                        Ok(#delta_name {
+                            #replace_init
+                            #(#delta_idents,)*
+                        })
+                    }
+                }
+
+                deserializer.deserialize_tuple(#max_len, DeltaVisitor)
+            }
+        }
+
+        impl #diffable for #name {
+            type Delta = #delta_name;
+
+            fn base_value() -> Self {
+                #base_value
+            }
+
+            fn diff(&self, new: &Self) -> Self::Delta {
+                #diff
+            }
+
+            fn apply_diff(&mut self, delta: &Self::Delta) {
+                #apply_diff
+            }
+        }
+    })
+}
+
+/// Parse the fields of a struct or of an enum variant
+fn parse_fields(
+    fields: &Fields,
+    num_fields: &mut usize,
+    variant: Option<&Ident>,
+) -> syn::Result<Vec<DiffField>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let mut kind = FieldKind::Value;
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("diffable")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        kind = FieldKind::Skip;
+                        Ok(())
+                    } else if meta.path.is_ident("nested") {
+                        kind = FieldKind::Nested;
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `skip` or `nested`"))
+                    }
+                })?;
+            }
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(i.into()),
+            };
+            let delta = (kind != FieldKind::Skip).then(|| {
+                let index = *num_fields;
+                *num_fields += 1;
+                let ident = match (variant, &field.ident) {
+                    (None, Some(ident)) => ident.clone(),
+                    _ => Ident::new(&format!("field_{index}"), Span::call_site()),
+                };
+                (index, ident)
+            });
+            Ok(DiffField {
+                member,
+                ty: field.ty.clone(),
+                kind,
+                delta,
+            })
+        })
+        .collect()
+}
+
+/// Expression that computes the delta of a single field, given references to the old and new values
+fn field_diff(
+    field: &DiffField,
+    old: &TokenStream,
+    new: &TokenStream,
+    diffable: &TokenStream,
+) -> TokenStream {
+    match field.kind {
+        FieldKind::Nested => quote! {
+            if #old != #new { Some(#diffable::diff(#old, #new)) } else { None }
+        },
+        _ => quote! {
+            if #old != #new { Some(::core::clone::Clone::clone(#new)) } else { None }
+        },
+    }
+}
+
+/// Statement that applies the delta of a single field, given a mutable reference to the field
+fn field_apply(
+    field: &DiffField,
+    ident: &Ident,
+    target: &TokenStream,
+    diffable: &TokenStream,
+) -> TokenStream {
+    match field.kind {
+        FieldKind::Nested => quote! {
+            if let Some(value) = &delta.#ident {
+                #diffable::apply_diff(#target, value);
+            }
+        },
+        _ => quote! {
+            if let Some(value) = &delta.#ident {
+                *#target = ::core::clone::Clone::clone(value);
+            }
+        },
+    }
+}
+
+fn struct_fns(
+    delta_name: &Ident,
+    fields: &[DiffField],
+    delta_idents: &[&Ident],
+    diffable: &TokenStream,
+) -> (TokenStream, TokenStream, TokenStream) {
+    let base_fields = fields.iter().map(|field| {
+        let member = &field.member;
+        let ty = &field.ty;
+        match field.kind {
+            FieldKind::Nested => quote! { #member: <#ty as #diffable>::base_value() },
+            // spanned so that a missing `Default` impl points at the field
+            _ => {
+                quote_spanned! { ty.span()=> #member: <#ty as ::core::default::Default>::default() }
+            }
+        }
+    });
+    let base_value = quote! { Self { #(#base_fields,)* } };
+
+    let diffs = fields.iter().filter_map(|field| {
+        let (_, ident) = field.delta.as_ref()?;
+        let member = &field.member;
+        let value = field_diff(
+            field,
+            &quote! { &self.#member },
+            &quote! { &new.#member },
+            diffable,
+        );
+        Some(quote! { #ident: #value })
+    });
+    let diff = quote! { #delta_name { #(#diffs,)* } };
+
+    let applies = fields.iter().filter_map(|field| {
+        let (_, ident) = field.delta.as_ref()?;
+        let member = &field.member;
+        Some(field_apply(
+            field,
+            ident,
+            &quote! { &mut self.#member },
+            diffable,
+        ))
+    });
+    let apply_diff = quote! { #(#applies)* };
+    (base_value, diff, apply_diff)
+}
+
+fn enum_fns(
+    name: &Ident,
+    delta_name: &Ident,
+    variants: &[Variant],
+    delta_idents: &[&Ident],
+    diffable: &TokenStream,
+) -> (TokenStream, TokenStream, TokenStream) {
+    // spanned so that a missing `Default` impl points at the enum
+    let base_value =
+        quote_spanned! { name.span()=> <#name as ::core::default::Default>::default() };
+
+    let binding = |prefix: &str, field: &DiffField| {
+        let (index, _) = field.delta.as_ref().unwrap();
+        format_ident!("__{}_{}", prefix, index)
+    };
+
+    let diff_arms = variants.iter().map(|variant| {
+        let variant_ident = &variant.ident;
+        let sent_fields: Vec<&DiffField> = variant
+            .fields
+            .iter()
+            .filter(|field| field.delta.is_some())
+            .collect();
+        let members: Vec<&Member> = sent_fields.iter().map(|field| &field.member).collect();
+        let old_bindings: Vec<Ident> = sent_fields
+            .iter()
+            .map(|field| binding("old", field))
+            .collect();
+        let new_bindings: Vec<Ident> = sent_fields
+            .iter()
+            .map(|field| binding("new", field))
+            .collect();
+        let diffs = sent_fields.iter().map(|field| {
+            let (_, ident) = field.delta.as_ref().unwrap();
+            let old = binding("old", field);
+            let new = binding("new", field);
+            let value = field_diff(field, &quote! { #old }, &quote! { #new }, diffable);
+            quote! { #ident: #value }
+        });
+        let variant_idents: Vec<&Ident> = sent_fields
+            .iter()
+            .map(|field| &field.delta.as_ref().unwrap().1)
+            .collect();
+        let other_idents = delta_idents
+            .iter()
+            .filter(|ident| !variant_idents.contains(ident));
+        quote! {
+            (
+                #name::#variant_ident { #(#members: #old_bindings,)* .. },
+                #name::#variant_ident { #(#members: #new_bindings,)* .. },
+            ) => #delta_name {
+                replace: None,
+                #(#diffs,)*
+                #(#other_idents: None,)*
+            }
+        }
+    });
+    let diff = quote! {
+        #[allow(unreachable_patterns)]
+        match (self, new) {
+            #(#diff_arms,)*
+            // the variant changed, we need to send the whole value
+            _ => #delta_name {
+                replace: Some(::core::clone::Clone::clone(new)),
+                #(#delta_idents: None,)*
+            },
+        }
+    };
+
+    let apply_arms = variants.iter().map(|variant| {
+        let variant_ident = &variant.ident;
+        let sent_fields: Vec<&DiffField> = variant
+            .fields
+            .iter()
+            .filter(|field| field.delta.is_some())
+            .collect();
+        let members: Vec<&Member> = sent_fields.iter().map(|field| &field.member).collect();
+        let bindings: Vec<Ident> = sent_fields
+            .iter()
+            .map(|field| binding("field", field))
+            .collect();
+        let applies = sent_fields.iter().map(|field| {
+            let (_, ident) = field.delta.as_ref().unwrap();
+            let target = binding("field", field);
+            field_apply(field, ident, &quote! { #target }, diffable)
+        });
+        quote! {
+            #name::#variant_ident { #(#members: #bindings,)* .. } => {
+                #(#applies)*
+            }
+        }
+    });
+    let apply_diff = quote! {
+        if let Some(value) = &delta.replace {
+            *self = ::core::clone::Clone::clone(value);
+            return;
+        }
+        match self {
+            #(#apply_arms)*
+        }
+    };
+    (base_value, diff, apply_diff)
+}
@@ -9,8 +9,10 @@ use quote::{format_ident, quote};
 use syn::{parse_macro_input, ItemEnum};
 
 use channel::channel_impl;
+use diffable::diffable_impl;
 
 mod channel;
+mod diffable;
 mod shared;
 
 // Channel
@@ -27,3 +29,26 @@ pub fn channel_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     let shared_crate_name = quote! { lightyear };
     channel_impl(input, shared_crate_name)
 }
+
+// Diffable
+/// Derives the Diffable trait for a given struct or enum.
+///
+/// A `{Name}Delta` type is generated, that contains the fields that changed along with a bit mask
+/// of the changed fields. Only the changed fields are serialized. The delta derives `Debug`, `Clone` and `PartialEq`.
+///
+/// The fields must implement `Clone + Debug + PartialEq + Serialize + DeserializeOwned`.
+/// Field attributes:
+/// - `#[diffable(skip)]`: the field is never sent
+/// - `#[diffable(nested)]`: the field implements `Diffable` itself, only its delta is sent when it changes.
+///   The delta of the field must also implement `Debug + Clone + PartialEq`.
+///
+/// `base_value` builds a struct from the `Default` value of each field (or from the `base_value` of nested fields),
+/// so the fields that are not nested must implement `Default`.
+///
+/// For enums, changing the variant sends the whole value, and the enum must implement `Default`
+/// to provide the `base_value`.
+#[proc_macro_derive(Diffable, attributes(diffable))]
+pub fn diffable_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let shared_crate_name = quote! { lightyear };
+    diffable_impl(input, shared_crate_name)
+}
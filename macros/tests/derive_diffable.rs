@@ -0,0 +1,137 @@
+pub mod some_components {
+    use lightyear::prelude::Diffable;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Diffable)]
+    pub struct Stats {
+        pub health: u32,
+        pub mana: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Diffable)]
+    pub struct Player {
+        pub name: String,
+        pub position: (f32, f32),
+        #[diffable(nested)]
+        pub stats: Stats,
+        #[diffable(skip)]
+        pub local_only: u8,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Diffable)]
+    pub struct Score(pub u32, pub u32);
+
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Diffable)]
+    pub enum State {
+        #[default]
+        Idle,
+        Moving {
+            speed: f32,
+            direction: (f32, f32),
+        },
+        Attacking(u64, #[diffable(skip)] u8),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lightyear::prelude::Diffable;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use super::some_components::*;
+
+    /// Serialize the delta, and apply the deserialized delta to `old`
+    fn round_trip<C: Diffable>(old: &C, new: &C) -> (C, usize)
+    where
+        C::Delta: Serialize + DeserializeOwned,
+    {
+        let delta = old.diff(new);
+        let bytes = bincode::serde::encode_to_vec(&delta, bincode::config::standard()).unwrap();
+        let (delta, _): (C::Delta, usize) =
+            bincode::serde::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
+        let mut value = old.clone();
+        value.apply_diff(&delta);
+        (value, bytes.len())
+    }
+
+    #[test]
+    fn test_diffable_derive_struct() {
+        let base = Player::base_value();
+        assert_eq!(base.stats, Stats::default());
+
+        let old = Player {
+            name: "player".to_string(),
+            position: (0.0, 0.0),
+            stats: Stats {
+                health: 100,
+                mana: 50,
+            },
+            local_only: 1,
+        };
+        let mut new = old.clone();
+        new.stats.mana = 20;
+        new.local_only = 2;
+        let (value, _) = round_trip(&old, &new);
+        // skipped fields are not sent
+        assert_eq!(value.local_only, 1);
+        assert_eq!(value.stats, new.stats);
+        assert_eq!(value.name, new.name);
+
+        // no change: only the mask is sent
+        let (value, len) = round_trip(&old, &old);
+        assert_eq!(value, old);
+        assert_eq!(len, 1);
+    }
+
+    #[test]
+    fn test_diffable_derive_tuple_struct() {
+        let old = Score(1, 2);
+        let new = Score(1, 3);
+        let delta = old.diff(&new);
+        assert_eq!(delta.field_0, None);
+        assert_eq!(delta.field_1, Some(3));
+        // the delta implements Debug, Clone and PartialEq
+        assert_eq!(
+            delta.clone(),
+            ScoreDelta {
+                field_0: None,
+                field_1: Some(3),
+            }
+        );
+        let (value, _) = round_trip(&old, &new);
+        assert_eq!(value, new);
+    }
+
+    #[test]
+    fn test_diffable_derive_enum() {
+        assert_eq!(State::base_value(), State::Idle);
+
+        // same variant: only the changed fields are sent
+        let old = State::Moving {
+            speed: 1.0,
+            direction: (1.0, 0.0),
+        };
+        let new = State::Moving {
+            speed: 2.0,
+            direction: (1.0, 0.0),
+        };
+        let delta = old.diff(&new);
+        assert!(delta.replace.is_none());
+        let (value, _) = round_trip(&old, &new);
+        assert_eq!(value, new);
+
+        // different variant: the whole value is sent
+        let new = State::Attacking(3, 1);
+        let delta = old.diff(&new);
+        assert_eq!(delta.replace, Some(new.clone()));
+        let (value, _) = round_trip(&old, &new);
+        assert_eq!(value, new);
+
+        // skipped fields inside a variant are kept
+        let old = State::Attacking(3, 1);
+        let new = State::Attacking(4, 2);
+        let (value, _) = round_trip(&old, &new);
+        assert_eq!(value, State::Attacking(4, 1));
+    }
+}